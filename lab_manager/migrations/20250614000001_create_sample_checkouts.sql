@@ -0,0 +1,28 @@
+-- Sample checkouts: a sample temporarily taken out of its storage location.
+-- The sample keeps its sample_locations row (and position) while checked out.
+
+CREATE TABLE sample_checkouts (
+    id SERIAL PRIMARY KEY,
+    sample_id UUID NOT NULL,
+    barcode VARCHAR(255) NOT NULL,
+    location_id INTEGER NOT NULL REFERENCES storage_locations(id) ON DELETE RESTRICT,
+    position VARCHAR(50),
+    checked_out_by VARCHAR(255) NOT NULL,
+    checked_out_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    due_back_at TIMESTAMPTZ NOT NULL,
+    purpose TEXT,
+    checked_in_by VARCHAR(255),
+    checked_in_at TIMESTAMPTZ,
+    notes TEXT,
+
+    CONSTRAINT sample_checkouts_due_after_checkout CHECK (due_back_at > checked_out_at),
+    CONSTRAINT sample_checkouts_checkin_after_checkout CHECK (checked_in_at IS NULL OR checked_in_at >= checked_out_at)
+);
+
+-- A sample can only have one open checkout at a time
+CREATE UNIQUE INDEX idx_sample_checkouts_open_sample
+    ON sample_checkouts(sample_id) WHERE checked_in_at IS NULL;
+
+CREATE INDEX idx_sample_checkouts_sample_id ON sample_checkouts(sample_id);
+CREATE INDEX idx_sample_checkouts_due_back_open
+    ON sample_checkouts(due_back_at) WHERE checked_in_at IS NULL;
@@ -0,0 +1,53 @@
+-- Storage tables were created with INTEGER sample ids while samples use UUID
+-- primary keys and all storage code, including box-scan reconciliation,
+-- checkouts and evacuation, binds UUIDs. Convert both columns to reference
+-- the real samples.id.
+--
+-- The integer ids never referred to samples, so rows are linked through the
+-- barcode they were stored under. The migration stops if any row cannot be
+-- linked; fix or remove those rows and run it again.
+
+CREATE FUNCTION pg_temp.stored_sample_id(stored_barcode TEXT)
+RETURNS UUID AS $$
+    SELECT id FROM samples WHERE barcode = stored_barcode;
+$$ LANGUAGE sql STABLE;
+
+-- History rows follow the storage record that shares their integer id, or
+-- their own barcode once that record has been removed
+CREATE FUNCTION pg_temp.moved_sample_id(old_sample_id INTEGER, moved_barcode TEXT)
+RETURNS UUID AS $$
+    SELECT COALESCE(
+        (SELECT s.id FROM sample_locations sl JOIN samples s ON s.barcode = sl.barcode
+         WHERE sl.sample_id = old_sample_id),
+        (SELECT id FROM samples WHERE barcode = moved_barcode)
+    );
+$$ LANGUAGE sql STABLE;
+
+DO $$
+DECLARE
+    unlinked_locations INTEGER;
+    unlinked_movements INTEGER;
+BEGIN
+    SELECT COUNT(*) INTO unlinked_locations
+    FROM sample_locations
+    WHERE pg_temp.stored_sample_id(barcode) IS NULL;
+
+    SELECT COUNT(*) INTO unlinked_movements
+    FROM storage_movement_history
+    WHERE pg_temp.moved_sample_id(sample_id, barcode) IS NULL;
+
+    IF unlinked_locations > 0 OR unlinked_movements > 0 THEN
+        RAISE EXCEPTION 'Cannot link storage rows to samples: % sample_locations and % storage_movement_history rows have no sample with their barcode',
+            unlinked_locations, unlinked_movements;
+    END IF;
+END $$;
+
+-- History first, while sample_locations still holds the integer ids
+ALTER TABLE storage_movement_history
+ALTER COLUMN sample_id TYPE UUID USING pg_temp.moved_sample_id(sample_id, barcode);
+
+ALTER TABLE sample_locations
+ALTER COLUMN sample_id TYPE UUID USING pg_temp.stored_sample_id(barcode);
+
+DROP FUNCTION pg_temp.moved_sample_id(INTEGER, TEXT);
+DROP FUNCTION pg_temp.stored_sample_id(TEXT);
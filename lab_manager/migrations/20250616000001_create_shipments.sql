@@ -0,0 +1,69 @@
+-- Shipments of samples between sites and to external providers
+
+-- Samples that have left storage in an outbound shipment
+ALTER TYPE storage_state ADD VALUE IF NOT EXISTS 'intransit';
+
+CREATE TYPE shipment_direction AS ENUM (
+    'outbound',
+    'inbound'
+);
+
+CREATE TYPE shipment_status AS ENUM (
+    'prepared',
+    'shipped',
+    'received',
+    'cancelled'
+);
+
+CREATE TYPE shipping_condition AS ENUM (
+    'dry_ice',
+    'wet_ice',
+    'ambient',
+    'liquid_nitrogen'
+);
+
+CREATE TABLE shipments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    shipment_number VARCHAR(100) NOT NULL,
+    direction shipment_direction NOT NULL,
+    origin VARCHAR(255) NOT NULL,
+    destination VARCHAR(255) NOT NULL,
+    carrier VARCHAR(100),
+    tracking_number VARCHAR(255),
+    shipping_condition shipping_condition NOT NULL,
+    status shipment_status NOT NULL DEFAULT 'prepared',
+    notes TEXT,
+    created_by VARCHAR(255) NOT NULL,
+    shipped_by VARCHAR(255),
+    shipped_at TIMESTAMPTZ,
+    received_by VARCHAR(255),
+    received_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    CONSTRAINT shipments_number_unique UNIQUE (shipment_number)
+);
+
+-- Manifest lines
+CREATE TABLE shipment_items (
+    id SERIAL PRIMARY KEY,
+    shipment_id UUID NOT NULL REFERENCES shipments(id) ON DELETE CASCADE,
+    sample_id UUID,
+    barcode VARCHAR(255) NOT NULL,
+    volume_ul DOUBLE PRECISION,
+    container_type VARCHAR(50),
+    notes TEXT,
+    received BOOLEAN NOT NULL DEFAULT FALSE,
+    received_condition TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    CONSTRAINT shipment_items_barcode_unique UNIQUE (shipment_id, barcode),
+    CONSTRAINT shipment_items_volume_positive CHECK (volume_ul IS NULL OR volume_ul > 0)
+);
+
+CREATE INDEX idx_shipments_status ON shipments(status);
+CREATE INDEX idx_shipments_tracking_number ON shipments(tracking_number);
+CREATE INDEX idx_shipment_items_shipment_id ON shipment_items(shipment_id);
+CREATE INDEX idx_shipment_items_barcode ON shipment_items(barcode);
+
+CREATE TRIGGER update_shipments_updated_at BEFORE UPDATE ON shipments FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,44 @@
+-- Spreadsheet datasets and their rows, with ingestion progress tracking.
+-- Tables may already exist on deployments that created them by hand, so
+-- everything here is idempotent.
+
+CREATE TABLE IF NOT EXISTS spreadsheet_datasets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    filename VARCHAR(255) NOT NULL,
+    original_filename VARCHAR(255) NOT NULL,
+    file_type VARCHAR(20) NOT NULL,
+    file_size BIGINT NOT NULL DEFAULT 0,
+    sheet_name VARCHAR(255),
+    total_rows INTEGER NOT NULL DEFAULT 0,
+    total_columns INTEGER NOT NULL DEFAULT 0,
+    column_headers TEXT[] NOT NULL DEFAULT '{}',
+    upload_status VARCHAR(20) NOT NULL DEFAULT 'processing',
+    error_message TEXT,
+    uploaded_by VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    metadata JSONB NOT NULL DEFAULT '{}'
+);
+
+CREATE TABLE IF NOT EXISTS spreadsheet_records (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    dataset_id UUID NOT NULL REFERENCES spreadsheet_datasets(id) ON DELETE CASCADE,
+    row_number INTEGER NOT NULL,
+    row_data JSONB NOT NULL,
+    search_text TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Ingestion progress, updated after every batch
+ALTER TABLE spreadsheet_datasets
+    ADD COLUMN IF NOT EXISTS processed_rows INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS progress_percent REAL;
+
+CREATE INDEX IF NOT EXISTS idx_spreadsheet_datasets_status ON spreadsheet_datasets(upload_status);
+CREATE INDEX IF NOT EXISTS idx_spreadsheet_datasets_created_at ON spreadsheet_datasets(created_at DESC);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_spreadsheet_records_dataset_row
+    ON spreadsheet_records(dataset_id, row_number);
+CREATE INDEX IF NOT EXISTS idx_spreadsheet_records_row_data
+    ON spreadsheet_records USING GIN (row_data);
+CREATE INDEX IF NOT EXISTS idx_spreadsheet_records_search_text
+    ON spreadsheet_records USING GIN (to_tsvector('english', search_text));
@@ -0,0 +1,6 @@
+-- Typed cell values inferred at upload, stored next to the raw text so
+-- numeric and date columns can be range filtered in SQL. Column types live
+-- in spreadsheet_datasets.metadata -> 'column_types'.
+
+ALTER TABLE spreadsheet_records
+    ADD COLUMN IF NOT EXISTS typed_data JSONB NOT NULL DEFAULT '{}';
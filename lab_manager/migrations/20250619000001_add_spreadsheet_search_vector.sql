@@ -0,0 +1,11 @@
+-- Full-text search over spreadsheet records. The tsvector is generated from
+-- search_text so every insert path keeps it current, and replaces the
+-- expression index on to_tsvector(search_text).
+
+ALTER TABLE spreadsheet_records
+    ADD COLUMN IF NOT EXISTS search_vector tsvector
+    GENERATED ALWAYS AS (to_tsvector('english', COALESCE(search_text, ''))) STORED;
+
+DROP INDEX IF EXISTS idx_spreadsheet_records_search_text;
+CREATE INDEX IF NOT EXISTS idx_spreadsheet_records_search_vector
+    ON spreadsheet_records USING GIN (search_vector);
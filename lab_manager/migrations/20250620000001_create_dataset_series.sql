@@ -0,0 +1,26 @@
+-- Versioned series of spreadsheet datasets. Re-uploads of the same sheet
+-- become numbered versions of one series, and rows are matched across
+-- versions by the series' identity column.
+
+CREATE TABLE IF NOT EXISTS spreadsheet_dataset_series (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL,
+    identity_column VARCHAR(255) NOT NULL,
+    description TEXT,
+    created_by VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT spreadsheet_dataset_series_name_unique UNIQUE (name)
+);
+
+CREATE TABLE IF NOT EXISTS spreadsheet_series_versions (
+    series_id UUID NOT NULL REFERENCES spreadsheet_dataset_series(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    dataset_id UUID NOT NULL REFERENCES spreadsheet_datasets(id) ON DELETE CASCADE,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (series_id, version),
+    CONSTRAINT spreadsheet_series_versions_dataset_unique UNIQUE (dataset_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_spreadsheet_series_versions_added_at
+    ON spreadsheet_series_versions(series_id, added_at);
@@ -0,0 +1,28 @@
+-- Mapping profiles turn spreadsheet dataset rows into samples. Each profile
+-- lists which column feeds which sample field or metadata key, with the
+-- transforms applied on the way.
+
+CREATE TABLE IF NOT EXISTS spreadsheet_mapping_profiles (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    mappings JSONB NOT NULL DEFAULT '[]'::jsonb,
+    created_by VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT spreadsheet_mapping_profiles_name_unique UNIQUE (name)
+);
+
+-- Samples created from dataset rows, one per row at most
+CREATE TABLE IF NOT EXISTS spreadsheet_record_samples (
+    record_id UUID PRIMARY KEY REFERENCES spreadsheet_records(id) ON DELETE CASCADE,
+    sample_id UUID NOT NULL REFERENCES samples(id) ON DELETE CASCADE,
+    dataset_id UUID NOT NULL REFERENCES spreadsheet_datasets(id) ON DELETE CASCADE,
+    profile_id UUID REFERENCES spreadsheet_mapping_profiles(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_spreadsheet_record_samples_dataset
+    ON spreadsheet_record_samples(dataset_id);
+CREATE INDEX IF NOT EXISTS idx_spreadsheet_record_samples_sample
+    ON spreadsheet_record_samples(sample_id);
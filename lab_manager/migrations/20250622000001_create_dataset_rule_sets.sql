@@ -0,0 +1,17 @@
+-- Validation rules for spreadsheet uploads, one rule set per dataset type.
+-- Uploads tagged with a dataset type are checked against its rules and
+-- either rejected or kept with warnings, per `on_violation`.
+
+CREATE TABLE IF NOT EXISTS spreadsheet_rule_sets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL,
+    dataset_type VARCHAR(100) NOT NULL,
+    description TEXT,
+    rules JSONB NOT NULL DEFAULT '[]'::jsonb,
+    on_violation VARCHAR(20) NOT NULL DEFAULT 'reject',
+    created_by VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT spreadsheet_rule_sets_dataset_type_unique UNIQUE (dataset_type),
+    CONSTRAINT spreadsheet_rule_sets_on_violation_check CHECK (on_violation IN ('reject', 'warn'))
+);
@@ -0,0 +1,36 @@
+-- Template versions carry the structured field schema of a template. A
+-- version is never changed once published; a new schema is a new version,
+-- with the field changes from the previous one kept as its changelog.
+
+CREATE TABLE IF NOT EXISTS template_versions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    template_id UUID NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+    version_number INTEGER NOT NULL,
+    fields JSONB NOT NULL DEFAULT '[]'::jsonb,
+    changes JSONB NOT NULL DEFAULT '[]'::jsonb,
+    changelog TEXT,
+    created_by VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CONSTRAINT template_versions_number_unique UNIQUE (template_id, version_number),
+    CONSTRAINT template_versions_number_positive CHECK (version_number > 0)
+);
+
+CREATE OR REPLACE FUNCTION prevent_template_version_update()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'template versions are immutable; publish a new version instead';
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER template_versions_immutable BEFORE UPDATE ON template_versions
+    FOR EACH ROW EXECUTE FUNCTION prevent_template_version_update();
+
+-- The template version a sample was submitted with. Versions that samples
+-- refer to cannot be removed, so neither can their template.
+ALTER TABLE samples
+    ADD COLUMN IF NOT EXISTS template_version_id UUID
+        CONSTRAINT samples_template_version_id_fkey
+        REFERENCES template_versions(id) ON DELETE RESTRICT;
+
+CREATE INDEX IF NOT EXISTS idx_samples_template_version
+    ON samples(template_version_id);
@@ -0,0 +1,98 @@
+-- Reports run on their own connections, logged in as a dedicated role that
+-- may only read report data, and every execution is recorded in query_log.
+--
+-- The application role is not a member of the report role and the report
+-- role is a member of nothing, so a report cannot switch roles. Its password,
+-- if password authentication is used, is set outside migrations:
+--     ALTER ROLE lab_report_reader PASSWORD '...';
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = 'lab_report_reader') THEN
+        CREATE ROLE lab_report_reader;
+    END IF;
+END
+$$;
+
+ALTER ROLE lab_report_reader LOGIN NOSUPERUSER NOCREATEDB NOCREATEROLE;
+
+-- Read-only with a short timeout unless the application raises it for a
+-- transaction, as it does for exports
+ALTER ROLE lab_report_reader SET default_transaction_read_only = on;
+ALTER ROLE lab_report_reader SET statement_timeout = '30s';
+
+GRANT USAGE ON SCHEMA public TO lab_report_reader;
+
+-- Tables without secret columns are readable in full; tables with them are
+-- readable except for those columns. Credentials, sessions, audit and query
+-- logs are not readable at all.
+DO $$
+DECLARE
+    report_table RECORD;
+    readable_columns TEXT;
+    hidden_columns INTEGER;
+BEGIN
+    FOR report_table IN
+        SELECT table_name
+        FROM information_schema.tables
+        WHERE table_schema = 'public'
+        AND table_type = 'BASE TABLE'
+        AND table_name NOT IN (
+            '_sqlx_migrations',
+            'user_sessions',
+            'password_reset_tokens',
+            'email_verification_tokens',
+            'security_audit_log',
+            'query_log'
+        )
+    LOOP
+        SELECT
+            string_agg(quote_ident(column_name), ', ' ORDER BY ordinal_position)
+                FILTER (WHERE NOT hidden),
+            count(*) FILTER (WHERE hidden)
+        INTO readable_columns, hidden_columns
+        FROM (
+            SELECT
+                column_name,
+                ordinal_position,
+                column_name LIKE '%password%'
+                    OR column_name LIKE '%secret%'
+                    OR column_name LIKE '%\_hash' AS hidden
+            FROM information_schema.columns
+            WHERE table_schema = 'public'
+            AND table_name = report_table.table_name
+        ) AS report_columns;
+
+        IF hidden_columns = 0 THEN
+            EXECUTE format(
+                'GRANT SELECT ON TABLE public.%I TO lab_report_reader',
+                report_table.table_name
+            );
+        ELSIF readable_columns IS NOT NULL THEN
+            EXECUTE format(
+                'GRANT SELECT (%s) ON TABLE public.%I TO lab_report_reader',
+                readable_columns,
+                report_table.table_name
+            );
+        END IF;
+    END LOOP;
+END
+$$;
+
+-- Tables created by later migrations are not readable until a migration
+-- grants them explicitly
+
+-- Report executions
+ALTER TABLE query_log
+    ADD COLUMN IF NOT EXISTS user_id UUID,
+    ADD COLUMN IF NOT EXISTS source VARCHAR(20),
+    ADD COLUMN IF NOT EXISTS status VARCHAR(20),
+    ADD COLUMN IF NOT EXISTS row_count INTEGER,
+    ADD COLUMN IF NOT EXISTS error_message TEXT,
+    ADD COLUMN IF NOT EXISTS finished_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_query_log_user_id ON query_log (user_id, created_at DESC);
+
+COMMENT ON ROLE lab_report_reader IS 'Read-only role reports run as';
+COMMENT ON COLUMN query_log.source IS 'Report kind: sql or structured; NULL for RAG queries';
+COMMENT ON COLUMN query_log.status IS 'running, succeeded, failed, cancelled or timed_out';
@@ -0,0 +1,79 @@
+-- Saved reports: structured or SQL report definitions with typed run-time
+-- parameters, owned by a user and shared privately, with a role or with
+-- everyone. Built-in templates are seeded as read-only system reports.
+
+CREATE TYPE report_visibility AS ENUM (
+    'private',
+    'role',
+    'everyone'
+);
+
+CREATE TABLE IF NOT EXISTS saved_reports (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    category VARCHAR(100),
+    -- {"structured": {...}} or {"sql": "..."}
+    query JSONB NOT NULL,
+    parameters JSONB NOT NULL DEFAULT '[]'::jsonb,
+    owner_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    visibility report_visibility NOT NULL DEFAULT 'private',
+    shared_role user_role,
+    is_system BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Stable identifier of a built-in report
+    system_key VARCHAR(100),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    CONSTRAINT saved_reports_system_key_unique UNIQUE (system_key),
+    CONSTRAINT saved_reports_role_shared CHECK (visibility <> 'role' OR shared_role IS NOT NULL),
+    CONSTRAINT saved_reports_owned CHECK (is_system OR owner_id IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_saved_reports_owner ON saved_reports(owner_id);
+CREATE INDEX IF NOT EXISTS idx_saved_reports_visibility ON saved_reports(visibility, shared_role);
+
+INSERT INTO saved_reports (system_key, name, description, category, query, visibility, is_system)
+VALUES
+    (
+        'samples_by_status',
+        'Samples by Status',
+        'Count of samples grouped by status',
+        'Samples',
+        jsonb_build_object('sql', 'SELECT status, COUNT(*) as count FROM samples GROUP BY status ORDER BY count DESC'),
+        'everyone',
+        TRUE
+    ),
+    (
+        'recent_samples',
+        'Recent Samples',
+        'Samples created in the last 30 days',
+        'Samples',
+        jsonb_build_object('sql', 'SELECT name, barcode, location, status, created_at FROM samples WHERE created_at >= NOW() - INTERVAL ''30 days'' ORDER BY created_at DESC'),
+        'everyone',
+        TRUE
+    ),
+    (
+        'templates_usage',
+        'Template Usage',
+        'Number of samples per template',
+        'Templates',
+        jsonb_build_object('sql', 'SELECT t.name as template_name, COUNT(CASE WHEN s.metadata->>''template_name'' = t.name THEN 1 END) as sample_count, t.created_at as template_created FROM templates t LEFT JOIN samples s ON s.metadata->>''template_name'' = t.name GROUP BY t.id, t.name, t.created_at ORDER BY sample_count DESC'),
+        'everyone',
+        TRUE
+    ),
+    (
+        'sample_locations',
+        'Sample Storage Locations',
+        'Samples grouped by storage location',
+        'Storage',
+        jsonb_build_object('sql', 'SELECT location, COUNT(*) as sample_count, status FROM samples GROUP BY location, status ORDER BY location, status'),
+        'everyone',
+        TRUE
+    )
+ON CONFLICT (system_key) DO NOTHING;
+
+CREATE TRIGGER update_saved_reports_updated_at BEFORE UPDATE ON saved_reports FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+COMMENT ON TABLE saved_reports IS 'Saved report definitions with sharing and run-time parameters';
+COMMENT ON COLUMN saved_reports.parameters IS 'Typed parameters (date_range, project, status) substituted when the report runs';
@@ -0,0 +1,61 @@
+-- Scheduled runs of saved reports. Each run writes the report to a CSV or
+-- XLSX file and emails it to the schedule's recipients.
+
+CREATE TYPE report_format AS ENUM (
+    'csv',
+    'xlsx'
+);
+
+CREATE TYPE report_run_status AS ENUM (
+    'running',
+    'succeeded',
+    'failed'
+);
+
+CREATE TABLE IF NOT EXISTS report_schedules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    report_id UUID NOT NULL REFERENCES saved_reports(id) ON DELETE CASCADE,
+    owner_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Five-field cron expression or a shortcut such as @weekly, in UTC
+    cron_expression VARCHAR(100) NOT NULL,
+    -- Parameter values the report runs with
+    parameters JSONB NOT NULL DEFAULT '{}'::jsonb,
+    format report_format NOT NULL DEFAULT 'csv',
+    recipients TEXT[] NOT NULL DEFAULT '{}',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    next_run_at TIMESTAMPTZ,
+    last_run_at TIMESTAMPTZ,
+    last_status report_run_status,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_report_schedules_due
+    ON report_schedules(next_run_at)
+    WHERE enabled;
+CREATE INDEX IF NOT EXISTS idx_report_schedules_owner ON report_schedules(owner_id);
+
+-- Run history, including the stored artifact and who it was sent to
+CREATE TABLE IF NOT EXISTS report_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    schedule_id UUID NOT NULL REFERENCES report_schedules(id) ON DELETE CASCADE,
+    report_id UUID NOT NULL REFERENCES saved_reports(id) ON DELETE CASCADE,
+    status report_run_status NOT NULL DEFAULT 'running',
+    -- Started from the API rather than by the scheduler
+    manual BOOLEAN NOT NULL DEFAULT FALSE,
+    row_count INTEGER,
+    truncated BOOLEAN NOT NULL DEFAULT FALSE,
+    artifact_path TEXT,
+    artifact_size BIGINT,
+    delivered_to TEXT[] NOT NULL DEFAULT '{}',
+    error_message TEXT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_report_runs_schedule ON report_runs(schedule_id, started_at DESC);
+
+CREATE TRIGGER update_report_schedules_updated_at BEFORE UPDATE ON report_schedules FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+COMMENT ON TABLE report_schedules IS 'Cron schedules that run saved reports and deliver them by email';
+COMMENT ON TABLE report_runs IS 'History of scheduled and manual report runs';
@@ -0,0 +1,100 @@
+-- History behind the dashboard trend charts. Triggers record every sample
+-- and sequencing job status change and every change in storage usage, so
+-- the charts don't depend on each code path remembering to log it.
+
+CREATE TABLE IF NOT EXISTS sample_status_history (
+    id BIGSERIAL PRIMARY KEY,
+    sample_id UUID NOT NULL REFERENCES samples(id) ON DELETE CASCADE,
+    status sample_status NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_sample_status_history_sample
+    ON sample_status_history(sample_id, changed_at);
+CREATE INDEX IF NOT EXISTS idx_sample_status_history_changed_at
+    ON sample_status_history(changed_at);
+
+CREATE TABLE IF NOT EXISTS sequencing_job_status_history (
+    id BIGSERIAL PRIMARY KEY,
+    job_id UUID NOT NULL REFERENCES sequencing_jobs(id) ON DELETE CASCADE,
+    status job_status NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_sequencing_job_status_history_job
+    ON sequencing_job_status_history(job_id, changed_at);
+CREATE INDEX IF NOT EXISTS idx_sequencing_job_status_history_changed_at
+    ON sequencing_job_status_history(status, changed_at);
+
+CREATE TABLE IF NOT EXISTS storage_usage_history (
+    id BIGSERIAL PRIMARY KEY,
+    location_id INTEGER NOT NULL REFERENCES storage_locations(id) ON DELETE CASCADE,
+    current_usage INTEGER NOT NULL,
+    capacity INTEGER NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_storage_usage_history_location
+    ON storage_usage_history(location_id, recorded_at);
+
+CREATE OR REPLACE FUNCTION record_sample_status()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND OLD.status = NEW.status THEN
+        RETURN NULL;
+    END IF;
+    INSERT INTO sample_status_history (sample_id, status) VALUES (NEW.id, NEW.status);
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE OR REPLACE FUNCTION record_sequencing_job_status()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND OLD.status = NEW.status THEN
+        RETURN NULL;
+    END IF;
+    INSERT INTO sequencing_job_status_history (job_id, status) VALUES (NEW.id, NEW.status);
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE OR REPLACE FUNCTION record_storage_usage()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE'
+        AND OLD.current_usage = NEW.current_usage
+        AND OLD.capacity = NEW.capacity THEN
+        RETURN NULL;
+    END IF;
+    INSERT INTO storage_usage_history (location_id, current_usage, capacity)
+    VALUES (NEW.id, NEW.current_usage, NEW.capacity);
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER samples_status_history AFTER INSERT OR UPDATE OF status ON samples
+    FOR EACH ROW EXECUTE FUNCTION record_sample_status();
+CREATE TRIGGER sequencing_jobs_status_history AFTER INSERT OR UPDATE OF status ON sequencing_jobs
+    FOR EACH ROW EXECUTE FUNCTION record_sequencing_job_status();
+CREATE TRIGGER storage_locations_usage_history AFTER INSERT OR UPDATE OF current_usage, capacity ON storage_locations
+    FOR EACH ROW EXECUTE FUNCTION record_storage_usage();
+
+-- Existing rows start at their creation; a status reached since then is
+-- dated by the row's last update, the closest record there is
+INSERT INTO sample_status_history (sample_id, status, changed_at)
+SELECT id, 'pending', created_at FROM samples;
+INSERT INTO sample_status_history (sample_id, status, changed_at)
+SELECT id, status, updated_at FROM samples WHERE status <> 'pending';
+
+INSERT INTO sequencing_job_status_history (job_id, status, changed_at)
+SELECT id, 'pending', created_at FROM sequencing_jobs;
+INSERT INTO sequencing_job_status_history (job_id, status, changed_at)
+SELECT id, status, updated_at FROM sequencing_jobs WHERE status <> 'pending';
+
+INSERT INTO storage_usage_history (location_id, current_usage, capacity)
+SELECT id, current_usage, capacity FROM storage_locations;
+
+COMMENT ON TABLE sample_status_history IS 'Every status a sample has had, for dashboard trends';
+COMMENT ON TABLE sequencing_job_status_history IS 'Every status a sequencing job has had, for dashboard trends';
+COMMENT ON TABLE storage_usage_history IS 'Storage location usage after each change, for dashboard trends';
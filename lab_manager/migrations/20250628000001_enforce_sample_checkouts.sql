@@ -0,0 +1,94 @@
+-- An open checkout reserves the sample's storage record and position until
+-- it is checked in. The triggers enforce this in the transaction that
+-- moves, removes, ships or re-stores a sample, so a stale pre-check in
+-- the application cannot let a reserved position be reused.
+
+-- Matches the application's rack position normalization: "a01" = "A1"
+CREATE OR REPLACE FUNCTION normalize_storage_position(value TEXT)
+RETURNS TEXT AS $$
+    SELECT regexp_replace(upper(btrim(value)), '^([^0-9]+)0*([0-9]+)$', '\1\2');
+$$ LANGUAGE sql IMMUTABLE;
+
+-- A checked out sample keeps its location, position and state
+CREATE OR REPLACE FUNCTION guard_checked_out_sample()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        IF NEW.location_id IS NOT DISTINCT FROM OLD.location_id
+            AND NEW.position IS NOT DISTINCT FROM OLD.position
+            AND NEW.storage_state IS NOT DISTINCT FROM OLD.storage_state THEN
+            RETURN NEW;
+        END IF;
+    END IF;
+
+    IF EXISTS (
+        SELECT 1 FROM sample_checkouts
+        WHERE sample_id = OLD.sample_id AND checked_in_at IS NULL
+    ) THEN
+        RAISE EXCEPTION 'Sample % is checked out', OLD.barcode
+            USING ERRCODE = 'check_violation',
+                CONSTRAINT = 'sample_checked_out',
+                DETAIL = OLD.barcode;
+    END IF;
+
+    IF TG_OP = 'DELETE' THEN
+        RETURN OLD;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+-- No other sample may take a position held by an open checkout
+CREATE OR REPLACE FUNCTION guard_reserved_position()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.position IS NOT NULL AND EXISTS (
+        SELECT 1 FROM sample_checkouts
+        WHERE checked_in_at IS NULL
+            AND sample_id <> NEW.sample_id
+            AND location_id = NEW.location_id
+            AND normalize_storage_position(position) = normalize_storage_position(NEW.position)
+    ) THEN
+        RAISE EXCEPTION 'Position % is reserved for a checked out sample', NEW.position
+            USING ERRCODE = 'check_violation',
+                CONSTRAINT = 'position_reserved_by_checkout',
+                DETAIL = NEW.position;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+-- Lock the storage record being checked out so a concurrent move either
+-- finishes first, failing this check, or waits for the checkout
+CREATE OR REPLACE FUNCTION lock_checked_out_sample()
+RETURNS TRIGGER AS $$
+BEGIN
+    PERFORM 1 FROM sample_locations
+    WHERE sample_id = NEW.sample_id
+        AND location_id = NEW.location_id
+        AND position IS NOT DISTINCT FROM NEW.position
+        AND storage_state = 'instorage'
+    FOR UPDATE;
+
+    IF NOT FOUND THEN
+        RAISE EXCEPTION 'Sample % is no longer in its recorded position', NEW.barcode
+            USING ERRCODE = 'check_violation',
+                CONSTRAINT = 'sample_checkout_stale',
+                DETAIL = NEW.barcode;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER sample_locations_checked_out
+    BEFORE UPDATE OF location_id, position, storage_state OR DELETE ON sample_locations
+    FOR EACH ROW EXECUTE FUNCTION guard_checked_out_sample();
+CREATE TRIGGER sample_locations_reserved_position
+    BEFORE INSERT OR UPDATE OF location_id, position ON sample_locations
+    FOR EACH ROW EXECUTE FUNCTION guard_reserved_position();
+CREATE TRIGGER sample_checkouts_lock_sample
+    BEFORE INSERT ON sample_checkouts
+    FOR EACH ROW EXECUTE FUNCTION lock_checked_out_sample();
+
+CREATE INDEX IF NOT EXISTS idx_sample_checkouts_open_location
+    ON sample_checkouts(location_id) WHERE checked_in_at IS NULL;
@@ -0,0 +1,4 @@
+-- Samples shipped out keep their storage record, marked 'intransit', so the
+-- location and position they left stay on record until they are received
+-- back. The sample itself is marked in transit as well.
+ALTER TYPE sample_status ADD VALUE IF NOT EXISTS 'in_transit' AFTER 'in_storage';
@@ -0,0 +1,10 @@
+-- Report access to tables created after the report role. None of them have
+-- secret columns.
+GRANT SELECT ON TABLE
+    saved_reports,
+    report_schedules,
+    report_runs,
+    sample_status_history,
+    sequencing_job_status_history,
+    storage_usage_history
+TO lab_report_reader;
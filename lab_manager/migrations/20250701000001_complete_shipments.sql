@@ -0,0 +1,11 @@
+-- Outbound samples whose receipt the destination has confirmed have left
+-- the lab: the sample is marked shipped and its storage record, kept while
+-- it was in transit, is released.
+ALTER TYPE sample_status ADD VALUE IF NOT EXISTS 'shipped' AFTER 'in_transit';
+ALTER TYPE storage_state ADD VALUE IF NOT EXISTS 'shipped';
+
+-- Shipment number assigned by the sender of an imported manifest; imported
+-- shipments get their own local number
+ALTER TABLE shipments ADD COLUMN external_reference VARCHAR(100);
+
+CREATE INDEX idx_shipments_external_reference ON shipments(external_reference);
@@ -4,10 +4,16 @@ use std::sync::Arc;
 use crate::{
     config::AppConfig,
     models::{spreadsheet::SpreadsheetDataManager, user::UserManager},
-    repositories::{PostgresRepositoryFactory, storage_repository::PostgresStorageRepository},
+    repositories::{
+        PostgresRepositoryFactory, shipment_repository::PostgresShipmentRepository,
+        saved_report_repository::PostgresSavedReportRepository,
+        report_schedule_repository::PostgresReportScheduleRepository,
+        analytics_repository::PostgresAnalyticsRepository,
+        storage_repository::PostgresStorageRepository,
+    },
     sample_submission::SampleSubmissionManager,
     sequencing::SequencingManager,
-    services::{auth_service::AuthService, spreadsheet_service::SpreadsheetService, storage_management_service::StorageManagementService, barcode_service::BarcodeService},
+    services::{auth_service::AuthService, spreadsheet_service::SpreadsheetService, storage_management_service::StorageManagementService, barcode_service::BarcodeService, shipment_service::ShipmentService, report_execution::{connect_report_pool, ReportExecutor}, saved_report_service::SavedReportService, report_schedule_service::ReportScheduleService, analytics_service::AnalyticsService, email_service::{EmailError, EmailService}},
     services::storage_service::{LocalStorageService, StorageService},
 };
 
@@ -96,9 +102,29 @@ pub struct AppComponents {
     pub auth_service: crate::services::auth_service::AuthService,
     pub spreadsheet_service: crate::services::spreadsheet_service::SpreadsheetService,
     pub storage_management_service: Arc<StorageManagementService<PostgresStorageRepository>>,
+    pub shipment_service: Arc<ShipmentService<PostgresShipmentRepository>>,
+    pub report_executor: Arc<ReportExecutor>,
+    pub saved_reports: Arc<SavedReportService<PostgresSavedReportRepository>>,
+    pub report_schedules:
+        Arc<ReportScheduleService<PostgresReportScheduleRepository, PostgresSavedReportRepository>>,
+    pub analytics: Arc<AnalyticsService<PostgresAnalyticsRepository>>,
     pub observability: ObservabilityComponent,
 }
 
+impl AppComponents {
+    /// Storage management service for sample storage operations
+    pub fn storage_management_service(
+        &self,
+    ) -> &Arc<StorageManagementService<PostgresStorageRepository>> {
+        &self.storage_management_service
+    }
+
+    /// Shipment service for sample transfers between sites
+    pub fn shipment_service(&self) -> &Arc<ShipmentService<PostgresShipmentRepository>> {
+        &self.shipment_service
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DatabaseComponent {
     pub pool: PgPool,
@@ -144,6 +170,14 @@ pub struct ComponentBuilder {
     user_manager: Option<UserManager>,
     auth_service: Option<AuthService>,
     spreadsheet_service: Option<SpreadsheetService>,
+    storage_management_service: Option<Arc<StorageManagementService<PostgresStorageRepository>>>,
+    shipment_service: Option<Arc<ShipmentService<PostgresShipmentRepository>>>,
+    report_executor: Option<Arc<ReportExecutor>>,
+    saved_reports: Option<Arc<SavedReportService<PostgresSavedReportRepository>>>,
+    report_schedules: Option<
+        Arc<ReportScheduleService<PostgresReportScheduleRepository, PostgresSavedReportRepository>>,
+    >,
+    analytics: Option<Arc<AnalyticsService<PostgresAnalyticsRepository>>>,
 }
 
 impl ComponentBuilder {
@@ -159,6 +193,12 @@ impl ComponentBuilder {
             user_manager: None,
             auth_service: None,
             spreadsheet_service: None,
+            storage_management_service: None,
+            shipment_service: None,
+            report_executor: None,
+            saved_reports: None,
+            report_schedules: None,
+            analytics: None,
         }
     }
 
@@ -273,11 +313,128 @@ impl ComponentBuilder {
             ))?;
 
         let manager = SpreadsheetDataManager::new(pool.clone());
-        let spreadsheet_service = SpreadsheetService::new(manager);
+        let spreadsheet_service = SpreadsheetService::new(manager)
+            .with_max_workbook_bytes(self.config.storage.max_workbook_bytes);
         self.spreadsheet_service = Some(spreadsheet_service);
         Ok(self)
     }
 
+    /// Build the storage management service
+    pub fn with_storage_management(mut self) -> Result<Self, AssemblyError> {
+        let pool = self
+            .database_pool
+            .as_ref()
+            .ok_or(AssemblyError::MissingDependency(
+                "Database pool required for storage management",
+            ))?;
+
+        let storage_repo = Arc::new(PostgresStorageRepository::new(pool.clone()));
+        let barcode_service = Arc::new(tokio::sync::RwLock::new(
+            BarcodeService::with_default_config(),
+        ));
+        self.storage_management_service = Some(Arc::new(StorageManagementService::new(
+            storage_repo,
+            barcode_service,
+        )));
+        Ok(self)
+    }
+
+    /// Build the shipment service
+    pub fn with_shipments(mut self) -> Result<Self, AssemblyError> {
+        let pool = self
+            .database_pool
+            .as_ref()
+            .ok_or(AssemblyError::MissingDependency(
+                "Database pool required for shipments",
+            ))?;
+
+        let shipment_repo = Arc::new(PostgresShipmentRepository::new(pool.clone()));
+        self.shipment_service = Some(Arc::new(ShipmentService::new(shipment_repo)));
+        Ok(self)
+    }
+
+    /// Build the report executor. Reports run on a pool of their own that
+    /// logs in as the report role; executions are logged through the main pool.
+    pub fn with_report_execution(mut self) -> Result<Self, AssemblyError> {
+        let pool = self
+            .database_pool
+            .as_ref()
+            .ok_or(AssemblyError::MissingDependency(
+                "Database pool required for report execution",
+            ))?;
+        let report_pool = connect_report_pool(&self.config.reports, &self.config.database.url)
+            .map_err(AssemblyError::DatabaseConnection)?;
+
+        self.report_executor = Some(Arc::new(ReportExecutor::new(
+            pool.clone(),
+            report_pool,
+            self.config.reports.clone(),
+        )));
+        Ok(self)
+    }
+
+    /// Build the saved report service
+    pub fn with_saved_reports(mut self) -> Result<Self, AssemblyError> {
+        let pool = self
+            .database_pool
+            .as_ref()
+            .ok_or(AssemblyError::MissingDependency(
+                "Database pool required for saved reports",
+            ))?;
+
+        let report_repo = Arc::new(PostgresSavedReportRepository::new(pool.clone()));
+        self.saved_reports = Some(Arc::new(SavedReportService::new(report_repo)));
+        Ok(self)
+    }
+
+    /// Build the report scheduler. Requires the report executor and saved
+    /// reports; email delivery is only available when SMTP is configured.
+    pub fn with_report_schedules(mut self) -> Result<Self, AssemblyError> {
+        let pool = self
+            .database_pool
+            .as_ref()
+            .ok_or(AssemblyError::MissingDependency(
+                "Database pool required for report schedules",
+            ))?;
+        let executor = self
+            .report_executor
+            .clone()
+            .ok_or(AssemblyError::MissingDependency(
+                "Report executor required for report schedules",
+            ))?;
+        let saved_reports = self
+            .saved_reports
+            .clone()
+            .ok_or(AssemblyError::MissingDependency(
+                "Saved reports required for report schedules",
+            ))?;
+
+        let schedule_repo = Arc::new(PostgresReportScheduleRepository::new(pool.clone()));
+        self.report_schedules = Some(Arc::new(ReportScheduleService::new(
+            schedule_repo,
+            saved_reports,
+            executor,
+            self.config.storage.base_path.join("report_artifacts"),
+            self.config.storage.max_file_size,
+            EmailService::from_config(&self.config.smtp)?,
+        )));
+        Ok(self)
+    }
+
+    /// Build the analytics service behind the dashboard trend charts
+    pub fn with_analytics(mut self) -> Result<Self, AssemblyError> {
+        let pool = self
+            .database_pool
+            .as_ref()
+            .ok_or(AssemblyError::MissingDependency(
+                "Database pool required for analytics",
+            ))?;
+
+        let analytics_repo = Arc::new(PostgresAnalyticsRepository::new(pool.clone()));
+        self.analytics = Some(Arc::new(AnalyticsService::new(analytics_repo)));
+        Ok(self)
+    }
+
     /// Assemble all components
     pub fn build(self) -> Result<AppComponents, AssemblyError> {
         let database_pool = self
@@ -304,6 +461,24 @@ impl ComponentBuilder {
         let spreadsheet_service = self
             .spreadsheet_service
             .ok_or(AssemblyError::MissingComponent("Spreadsheet Service"))?;
+        let storage_management_service = self
+            .storage_management_service
+            .ok_or(AssemblyError::MissingComponent("Storage Management Service"))?;
+        let shipment_service = self
+            .shipment_service
+            .ok_or(AssemblyError::MissingComponent("Shipment Service"))?;
+        let report_executor = self
+            .report_executor
+            .ok_or(AssemblyError::MissingComponent("Report Executor"))?;
+        let saved_reports = self
+            .saved_reports
+            .ok_or(AssemblyError::MissingComponent("Saved Reports"))?;
+        let report_schedules = self
+            .report_schedules
+            .ok_or(AssemblyError::MissingComponent("Report Schedules"))?;
+        let analytics = self
+            .analytics
+            .ok_or(AssemblyError::MissingComponent("Analytics"))?;
 
         // Create observability component
         let observability = ObservabilityComponent {
@@ -330,6 +505,12 @@ impl ComponentBuilder {
             user_manager,
             auth_service,
             spreadsheet_service,
+            storage_management_service,
+            shipment_service,
+            report_executor,
+            saved_reports,
+            report_schedules,
+            analytics,
             observability,
         })
     }
@@ -350,6 +531,12 @@ pub async fn assemble_production_components() -> Result<AppComponents, AssemblyE
         .with_user_management()?
         .with_authentication()?
         .with_spreadsheet()?
+        .with_storage_management()?
+        .with_shipments()?
+        .with_report_execution()?
+        .with_saved_reports()?
+        .with_report_schedules()?
+        .with_analytics()?
         .build()
 }
 
@@ -368,6 +555,12 @@ pub async fn assemble_test_components() -> Result<AppComponents, AssemblyError>
         .with_user_management()?
         .with_authentication()?
         .with_spreadsheet()?
+        .with_storage_management()?
+        .with_shipments()?
+        .with_report_execution()?
+        .with_saved_reports()?
+        .with_report_schedules()?
+        .with_analytics()?
         .build()
 }
 
@@ -389,6 +582,12 @@ impl CustomAssembly {
             .with_user_management()?
             .with_authentication()?
             .with_spreadsheet()?
+            .with_storage_management()?
+            .with_shipments()?
+            .with_report_execution()?
+            .with_saved_reports()?
+            .with_report_schedules()?
+            .with_analytics()?
             .build()?;
 
         Ok(AppComponents {
@@ -401,6 +600,12 @@ impl CustomAssembly {
             user_manager: components.user_manager,
             auth_service: components.auth_service,
             spreadsheet_service: components.spreadsheet_service,
+            storage_management_service: components.storage_management_service,
+            shipment_service: components.shipment_service,
+            report_executor: components.report_executor,
+            saved_reports: components.saved_reports,
+            report_schedules: components.report_schedules,
+            analytics: components.analytics,
             observability: components.observability,
         })
     }
@@ -426,6 +631,8 @@ pub enum AssemblyError {
     Migration(Box<dyn std::error::Error + Send + Sync>),
     #[error("Storage setup error: {0}")]
     StorageSetup(#[from] std::io::Error),
+    #[error("Email setup error: {0}")]
+    EmailSetup(#[from] EmailError),
     #[error("Missing dependency: {0}")]
     MissingDependency(&'static str),
     #[error("Missing component: {0}")]
@@ -0,0 +1,60 @@
+use lab_manager::config::AppConfig;
+use lab_manager::repositories::storage_repository::{
+    PostgresStorageRepository, StorageRepository,
+};
+use sqlx::PgPool;
+use std::env;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("🧪 Lab Manager - Storage Usage Reconciliation");
+    println!("=============================================");
+
+    // Optional location id; all locations are reconciled when omitted
+    let location_id = match env::args().nth(1) {
+        Some(arg) => Some(
+            arg.parse::<i32>()
+                .map_err(|_| format!("Invalid location id: {}", arg))?,
+        ),
+        None => None,
+    };
+
+    let config = AppConfig::from_env().expect("Failed to load configuration");
+    let pool = PgPool::connect(&config.database.url)
+        .await
+        .expect("Failed to connect to database");
+
+    let repo = PostgresStorageRepository::new(pool);
+    let report = repo.recalculate_location_usage(location_id).await?;
+
+    let mut drifted = 0;
+    for row in &report {
+        if row.recorded_usage == row.actual_usage {
+            continue;
+        }
+        drifted += 1;
+
+        let status = if row.corrected {
+            "corrected"
+        } else {
+            "OVER CAPACITY - needs manual review"
+        };
+        println!(
+            "  [{}] {}: recorded {}, actual {} (capacity {}) - {}",
+            row.location_id,
+            row.location_name,
+            row.recorded_usage,
+            row.actual_usage,
+            row.capacity,
+            status
+        );
+    }
+
+    println!(
+        "✅ Checked {} location(s), {} with drifted usage",
+        report.len(),
+        drifted
+    );
+
+    Ok(())
+}
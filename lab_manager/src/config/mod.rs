@@ -11,6 +11,8 @@ pub struct AppConfig {
     pub server: ServerConfig,
     pub rag: RagIntegrationConfig,
     pub shibboleth: ShibbolethConfig,
+    pub reports: ReportConfig,
+    pub smtp: SmtpConfig,
 }
 
 /// Database configuration
@@ -27,6 +29,9 @@ pub struct StorageConfig {
     pub base_path: PathBuf,
     pub max_file_size: u64,
     pub allowed_extensions: Vec<String>,
+    /// Largest xls or OpenDocument workbook accepted; those are decoded in
+    /// memory, while XLSX sheets are streamed
+    pub max_workbook_bytes: u64,
 }
 
 /// Server configuration
@@ -82,6 +87,89 @@ pub struct ShibbolethConfig {
     pub attribute_mappings: std::collections::HashMap<String, String>,
 }
 
+/// Limits on running reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportConfig {
+    /// Read-only database role reports run as
+    pub database_role: String,
+
+    /// Connection string that logs in as the report role. When unset, the
+    /// main database URL is used with `database_role` as the user and no
+    /// password, for trust, peer or certificate authentication.
+    pub database_url: Option<String>,
+
+    /// Reports running longer than this are cancelled by the database
+    pub statement_timeout_ms: u64,
+
+    /// Rows returned by a report at most
+    pub max_rows: i64,
+
+    /// Exports stream their rows, so they get a longer timeout
+    pub export_statement_timeout_ms: u64,
+
+    /// Rows an export returns at most
+    pub max_export_rows: i64,
+
+    /// Reports one user may run at the same time
+    pub max_concurrent_per_user: usize,
+
+    /// How often the scheduler looks for due report schedules; 0 disables it
+    pub schedule_poll_seconds: u64,
+}
+
+/// How the SMTP connection is secured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain SMTP, for local relays and test servers
+    None,
+    #[default]
+    StartTls,
+    /// Implicit TLS, usually on port 465
+    Tls,
+}
+
+/// Outgoing mail server for report delivery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    /// Email delivery is disabled when unset
+    pub host: Option<String>,
+    pub port: u16,
+    pub username: Option<String>,
+    #[serde(skip_serializing)]
+    pub password: Option<String>,
+    pub from_address: String,
+    pub tls: SmtpTls,
+}
+
+impl Default for SmtpConfig {
+    fn default() -> Self {
+        Self {
+            host: None,
+            port: 587,
+            username: None,
+            password: None,
+            from_address: "TracSeq Reports <reports@localhost>".to_string(),
+            tls: SmtpTls::StartTls,
+        }
+    }
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            database_role: "lab_report_reader".to_string(),
+            database_url: None,
+            statement_timeout_ms: 30_000,
+            max_rows: 10_000,
+            export_statement_timeout_ms: 600_000,
+            max_export_rows: 5_000_000,
+            max_concurrent_per_user: 2,
+            schedule_poll_seconds: 60,
+        }
+    }
+}
+
 impl Default for RagIntegrationConfig {
     fn default() -> Self {
         Self {
@@ -165,6 +253,56 @@ impl AppConfig {
         let shibboleth_default_role =
             std::env::var("SHIBBOLETH_DEFAULT_ROLE").unwrap_or_else(|_| "Guest".to_string());
 
+        let report_defaults = ReportConfig::default();
+        let reports = ReportConfig {
+            database_role: std::env::var("REPORT_DATABASE_ROLE")
+                .unwrap_or(report_defaults.database_role),
+            database_url: std::env::var("REPORT_DATABASE_URL")
+                .ok()
+                .filter(|url| !url.is_empty()),
+            statement_timeout_ms: std::env::var("REPORT_STATEMENT_TIMEOUT_MS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(report_defaults.statement_timeout_ms),
+            max_rows: std::env::var("REPORT_MAX_ROWS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(report_defaults.max_rows),
+            export_statement_timeout_ms: std::env::var("REPORT_EXPORT_TIMEOUT_MS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(report_defaults.export_statement_timeout_ms),
+            max_export_rows: std::env::var("REPORT_MAX_EXPORT_ROWS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(report_defaults.max_export_rows),
+            max_concurrent_per_user: std::env::var("REPORT_MAX_CONCURRENT_PER_USER")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(report_defaults.max_concurrent_per_user),
+            schedule_poll_seconds: std::env::var("REPORT_SCHEDULE_POLL_SECONDS")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(report_defaults.schedule_poll_seconds),
+        };
+
+        let smtp_defaults = SmtpConfig::default();
+        let smtp = SmtpConfig {
+            host: std::env::var("SMTP_HOST").ok().filter(|host| !host.is_empty()),
+            port: std::env::var("SMTP_PORT")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(smtp_defaults.port),
+            username: std::env::var("SMTP_USERNAME").ok(),
+            password: std::env::var("SMTP_PASSWORD").ok(),
+            from_address: std::env::var("SMTP_FROM").unwrap_or(smtp_defaults.from_address),
+            tls: match std::env::var("SMTP_TLS").as_deref() {
+                Ok("none") => SmtpTls::None,
+                Ok("tls") => SmtpTls::Tls,
+                _ => smtp_defaults.tls,
+            },
+        };
+
         Ok(Self {
             database: DatabaseConfig {
                 url: database_url,
@@ -175,6 +313,10 @@ impl AppConfig {
                 base_path: PathBuf::from(storage_path),
                 max_file_size: 100 * 1024 * 1024, // 100MB
                 allowed_extensions: vec!["xlsx".to_string(), "xls".to_string(), "csv".to_string()],
+                max_workbook_bytes: std::env::var("MAX_WORKBOOK_BYTES")
+                    .ok()
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(20 * 1024 * 1024), // 20MB
             },
             server: ServerConfig {
                 host,
@@ -193,6 +335,8 @@ impl AppConfig {
                 default_role: shibboleth_default_role,
                 ..ShibbolethConfig::default()
             },
+            reports,
+            smtp,
         })
     }
 
@@ -208,6 +352,7 @@ impl AppConfig {
                 base_path: PathBuf::from("./test_storage"),
                 max_file_size: 10 * 1024 * 1024, // 10MB for tests
                 allowed_extensions: vec!["xlsx".to_string(), "csv".to_string()],
+                max_workbook_bytes: 20 * 1024 * 1024,
             },
             server: ServerConfig {
                 host: "127.0.0.1".to_string(),
@@ -219,6 +364,8 @@ impl AppConfig {
                 enabled: false, // Disabled for tests by default
                 ..ShibbolethConfig::default()
             },
+            reports: ReportConfig::default(),
+            smtp: SmtpConfig::default(),
         }
     }
 }
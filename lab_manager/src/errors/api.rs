@@ -1,4 +1,10 @@
-use crate::errors::{ComponentError, ErrorSeverity};
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::errors::{ComponentError, ErrorResponse, ErrorSeverity};
 
 /// API-specific errors
 #[derive(Debug, thiserror::Error)]
@@ -74,3 +80,29 @@ impl ComponentError for ApiError {
         )
     }
 }
+
+impl ApiError {
+    /// HTTP status code returned for this error
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidRequest(_) | Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::RateLimited | Self::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::DatabaseError(_) | Self::InternalServerError(_) | Self::InternalError => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status_code();
+        (status, Json(ErrorResponse::from_component_error(self))).into_response()
+    }
+}
@@ -1,7 +1,13 @@
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
 use serde::Serialize;
 
 use crate::assembly::AppComponents;
+use crate::models::analytics::{AnalyticsQuery, DashboardAnalytics};
+use crate::services::analytics_service::AnalyticsError;
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -78,3 +84,23 @@ pub async fn get_dashboard_stats(
         completed_sequencing,
     }))
 }
+
+/// Get dashboard trends counted by day, week or month, optionally for one
+/// project or sample type
+pub async fn get_dashboard_analytics(
+    State(state): State<AppComponents>,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<Json<DashboardAnalytics>, (StatusCode, String)> {
+    state
+        .analytics
+        .dashboard_analytics(query, chrono::Utc::now().date_naive())
+        .await
+        .map(Json)
+        .map_err(|e| {
+            let status = match &e {
+                AnalyticsError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                AnalyticsError::InvalidRange(_) => StatusCode::BAD_REQUEST,
+            };
+            (status, e.to_string())
+        })
+}
@@ -4,6 +4,7 @@ pub mod rag_proxy;
 pub mod reports;
 pub mod samples;
 pub mod sequencing;
+pub mod shipments;
 pub mod spreadsheets;
 pub mod storage;
 pub mod templates;
@@ -17,8 +18,8 @@ pub use health::{
 pub use rag_proxy::{process_document, query_submissions};
 pub use reports::{
     create_custom_report, delete_report, get_available_templates, get_report, list_reports,
-    save_report_template, update_report, CreateReportRequest, CustomReport, QueryRequest,
-    ReportTemplate, SaveTemplateRequest,
+    run_saved_report, save_report_template, update_report, QueryRequest, ReportTemplate,
+    RunReportRequest, SaveTemplateRequest,
 };
 pub use samples::{
     create_sample, delete_sample, get_sample, list_samples, update_sample, CreateSampleRequest,
@@ -1,12 +1,41 @@
 use crate::assembly::AppComponents;
+use crate::errors::api::ApiError;
+use crate::middleware::is_admin;
+use crate::models::report::{
+    CreateReportSchedule, CreateSavedReport, DatabaseSchema, ReportFormat, ReportParameter,
+    ReportQuerySpec, ReportRun, ReportSchedule, ReportVisibility, SavedReport, SavedReportQuery,
+};
+use crate::models::user::User;
+use crate::services::email_service::EmailError;
+use crate::services::rag_integration_service::{RagConfig, RagIntegrationService};
+use crate::services::report_assistant::{
+    check_question, draft_formats, draft_from_rag, prepare_draft, ReportAssistantError, ReportDraft,
+};
+use crate::services::report_execution::{
+    ReportExecutionError, ReportRequest, ReportRows, ReportSource, RunningReport,
+};
+use crate::services::report_export::{
+    export_report, file_stem, ReportExport, ReportExportError, ReportExportFormat,
+};
+use crate::services::report_query::{
+    compile_report, compile_report_with_limit, is_safe_query, load_schema, ReportQueryError,
+    DEFAULT_REPORT_LIMIT,
+};
+use crate::services::report_schedule_service::ReportScheduleError;
+use crate::services::report_values::{row_columns, row_to_json};
+use crate::services::saved_report_service::{prepare_report, ReportViewer, SavedReportError};
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use sqlx::{Column, Row, TypeInfo};
 use std::collections::HashMap;
+use tokio_util::io::ReaderStream;
+use tracing::error;
 use uuid::Uuid;
 
 #[derive(Debug, Deserialize)]
@@ -15,57 +44,67 @@ pub struct ReportQuery {
     pub export_format: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct ReportResult {
-    pub columns: Vec<String>,
-    pub rows: Vec<HashMap<String, serde_json::Value>>,
-    pub row_count: usize,
-    pub execution_time_ms: u64,
-    pub query: String,
+/// Lets a client cancel a report it is still waiting for
+#[derive(Debug, Deserialize)]
+pub struct ExecutionParams {
+    pub execution_id: Option<Uuid>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct DatabaseSchema {
-    pub tables: Vec<TableInfo>,
+/// Format of an export, and an ID to cancel it with
+#[derive(Debug, Deserialize)]
+pub struct ReportExportParams {
+    #[serde(default)]
+    pub format: ReportExportFormat,
+    pub execution_id: Option<Uuid>,
+}
+
+/// A question for the report assistant
+#[derive(Debug, Deserialize)]
+pub struct AskReportRequest {
+    pub question: String,
 }
 
+/// A drafted report and the query it runs, for the user to confirm
 #[derive(Debug, Serialize)]
-pub struct TableInfo {
-    pub name: String,
-    pub columns: Vec<ColumnInfo>,
+pub struct ReportDraftPreview {
+    pub draft: ReportDraft,
+    pub query: String,
+    /// Known before running for structured drafts
+    pub columns: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize)]
-pub struct ColumnInfo {
-    pub name: String,
-    pub data_type: String,
-    pub is_nullable: bool,
-    pub is_primary_key: bool,
+pub struct ReportResult {
+    pub execution_id: Uuid,
+    pub columns: Vec<String>,
+    pub rows: Vec<HashMap<String, serde_json::Value>>,
+    pub row_count: usize,
+    pub execution_time_ms: u64,
+    pub query: String,
+    /// More rows matched than the report's limit
+    pub truncated: bool,
 }
 
 #[derive(Debug, Serialize)]
 pub struct ReportTemplate {
+    /// Built-in reports keep a stable key; other templates use their ID
     pub id: String,
+    pub report_id: Uuid,
     pub name: String,
     pub description: String,
+    /// Empty for structured templates
     pub sql: String,
+    pub structured: Option<ReportQuerySpec>,
     pub category: String,
+    pub parameters: Vec<ReportParameter>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateReportRequest {
-    pub name: String,
-    pub description: Option<String>,
-    pub query: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CustomReport {
-    pub id: Uuid,
-    pub name: String,
-    pub description: Option<String>,
-    pub query: String,
-    pub created_at: chrono::DateTime<chrono::Utc>,
+/// Parameter values for a saved report run
+#[derive(Debug, Default, Deserialize)]
+pub struct RunReportRequest {
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    pub limit: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -78,309 +117,822 @@ pub struct QueryRequest {
 pub struct SaveTemplateRequest {
     pub name: String,
     pub description: Option<String>,
+    pub category: Option<String>,
+    /// SQL of the template
     pub template: String,
+    #[serde(default)]
+    pub parameters: Vec<ReportParameter>,
 }
 
-/// Get database schema information
+/// Get the tables and columns reports may read
 pub async fn get_schema(
     State(state): State<AppComponents>,
 ) -> Result<Json<DatabaseSchema>, (StatusCode, String)> {
-    let tables_query = r#"
-        SELECT 
-            t.table_name,
-            c.column_name,
-            c.data_type,
-            c.is_nullable,
-            COALESCE(
-                CASE WHEN tc.constraint_type = 'PRIMARY KEY' THEN true ELSE false END,
-                false
-            ) as is_primary_key
-        FROM information_schema.tables t
-        JOIN information_schema.columns c ON t.table_name = c.table_name
-        LEFT JOIN information_schema.key_column_usage kcu ON c.column_name = kcu.column_name 
-                                                           AND c.table_name = kcu.table_name
-        LEFT JOIN information_schema.table_constraints tc ON kcu.constraint_name = tc.constraint_name
-                                                           AND tc.constraint_type = 'PRIMARY KEY'
-        WHERE t.table_schema = 'public' 
-        AND t.table_type = 'BASE TABLE'
-        ORDER BY t.table_name, c.ordinal_position
-    "#;
-
-    let rows = sqlx::query(tables_query)
-        .fetch_all(&state.database.pool)
+    load_schema(&state.database.pool)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    let mut tables_map: HashMap<String, Vec<ColumnInfo>> = HashMap::new();
-
-    for row in rows {
-        let table_name: String = row.get("table_name");
-        let column_name: String = row.get("column_name");
-        let data_type: String = row.get("data_type");
-        let is_nullable: String = row.get("is_nullable");
-        let is_primary_key: bool = row.get("is_primary_key");
-
-        let column_info = ColumnInfo {
-            name: column_name,
-            data_type,
-            is_nullable: is_nullable == "YES",
-            is_primary_key,
-        };
-
-        tables_map.entry(table_name).or_default().push(column_info);
-    }
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
 
-    let tables = tables_map
-        .into_iter()
-        .map(|(name, columns)| TableInfo { name, columns })
-        .collect();
+/// Run a structured report, checked against the schema and compiled into a
+/// parameterized query
+pub async fn query_report(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Query(params): Query<ExecutionParams>,
+    Json(mut spec): Json<ReportQuerySpec>,
+) -> Result<Json<ReportResult>, (StatusCode, String)> {
+    let executor = &state.report_executor;
+    spec.limit = Some(
+        spec.limit
+            .unwrap_or(DEFAULT_REPORT_LIMIT)
+            .min(executor.max_rows()),
+    );
+
+    let schema = load_schema(&state.database.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let compiled = compile_report(&spec, &schema).map_err(report_query_error)?;
+
+    let report = executor
+        .execute(ReportRequest {
+            execution_id: params.execution_id,
+            user_id: user.map(|Extension(user)| user.id),
+            source: ReportSource::Structured,
+            sql: compiled.sql.clone(),
+            params: compiled.params,
+            limit: compiled.limit,
+        })
+        .await
+        .map_err(report_execution_error)?;
 
-    Ok(Json(DatabaseSchema { tables }))
+    Ok(Json(report_result(report, compiled.columns, compiled.sql)))
 }
 
-/// Execute a SQL query and return results
+/// Execute raw SQL and return results. Limited to administrators; everyone
+/// else writes structured reports.
 pub async fn execute_report(
     State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Query(params): Query<ExecutionParams>,
     Json(query_request): Json<ReportQuery>,
 ) -> Result<Json<ReportResult>, (StatusCode, String)> {
-    // Security: Validate the SQL query
-    if !is_safe_query(&query_request.sql) {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "Only SELECT queries are allowed for reports".to_string(),
-        ));
-    }
+    let user = sql_report_user(user, &query_request.sql)?;
+
+    let executor = &state.report_executor;
+    let report = executor
+        .execute(ReportRequest {
+            execution_id: params.execution_id,
+            user_id: Some(user.id),
+            source: ReportSource::Sql,
+            sql: query_request.sql.clone(),
+            params: Vec::new(),
+            limit: executor.max_rows(),
+        })
+        .await
+        .map_err(report_execution_error)?;
 
-    let start_time = std::time::Instant::now();
+    let columns = row_columns(&report.rows);
+    Ok(Json(report_result(report, columns, query_request.sql)))
+}
 
-    // Execute the query
-    let rows = sqlx::query(&query_request.sql)
-        .fetch_all(&state.database.pool)
+/// Export a structured report as CSV, XLSX or JSON, streamed as it runs
+pub async fn export_query_report(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Query(params): Query<ReportExportParams>,
+    Json(spec): Json<ReportQuerySpec>,
+) -> Result<Response, (StatusCode, String)> {
+    let max_rows = state.report_executor.max_export_rows();
+    let schema = load_schema(&state.database.pool)
         .await
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("SQL Error: {}", e)))?;
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let compiled = compile_report_with_limit(
+        &ReportQuerySpec {
+            limit: Some(spec.limit.unwrap_or(max_rows).min(max_rows)),
+            ..spec
+        },
+        &schema,
+        max_rows,
+    )
+    .map_err(report_query_error)?;
+
+    let request = ReportRequest {
+        execution_id: params.execution_id,
+        user_id: user.map(|Extension(user)| user.id),
+        source: ReportSource::Structured,
+        sql: compiled.sql,
+        params: compiled.params,
+        limit: compiled.limit,
+    };
+    stream_report(
+        &state,
+        request,
+        Some(compiled.columns),
+        params.format,
+        "report",
+    )
+    .await
+}
 
-    let execution_time = start_time.elapsed().as_millis() as u64;
+/// Export the results of raw SQL. Limited to administrators.
+pub async fn export_sql_report(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Query(params): Query<ReportExportParams>,
+    Json(query_request): Json<ReportQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let user = sql_report_user(user, &query_request.sql)?;
+    let request = ReportRequest {
+        execution_id: params.execution_id,
+        user_id: Some(user.id),
+        source: ReportSource::Sql,
+        sql: query_request.sql,
+        params: Vec::new(),
+        limit: state.report_executor.max_export_rows(),
+    };
+    stream_report(&state, request, None, params.format, "report").await
+}
 
-    if rows.is_empty() {
-        return Ok(Json(ReportResult {
-            columns: vec![],
-            rows: vec![],
-            row_count: 0,
-            execution_time_ms: execution_time,
-            query: query_request.sql,
-        }));
+/// Draft a report answering a question in plain language. The RAG system
+/// writes the draft from the report schema; it is checked like any other
+/// report and returned for confirmation rather than run.
+pub async fn ask_report(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Json(request): Json<AskReportRequest>,
+) -> Result<Json<ReportDraftPreview>, (StatusCode, String)> {
+    let allow_sql = user.as_ref().is_some_and(|Extension(user)| is_admin(user));
+    let question = check_question(&request.question).map_err(report_assistant_error)?;
+    if !state.config.rag.enabled {
+        return Err(report_assistant_error(ReportAssistantError::Disabled));
     }
 
-    // Get column names from the first row
-    let columns: Vec<String> = rows[0]
-        .columns()
-        .iter()
-        .map(|col| col.name().to_string())
-        .collect();
-
-    // Convert rows to JSON
-    let mut result_rows = Vec::new();
-    for row in &rows {
-        let mut row_map = HashMap::new();
-        for (i, column) in row.columns().iter().enumerate() {
-            let value = convert_sql_value_to_json(row, i, column.type_info());
-            row_map.insert(column.name().to_string(), value);
-        }
-        result_rows.push(row_map);
-    }
+    let schema = load_schema(&state.database.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let rag_service = RagIntegrationService::new(RagConfig {
+        base_url: state.config.rag.base_url.clone(),
+        timeout_seconds: state.config.rag.timeout_seconds,
+        max_file_size_mb: state.config.rag.max_file_size_mb,
+        supported_formats: state.config.rag.supported_formats.clone(),
+    });
+    let answer = rag_service
+        .draft_report_query(&question, &schema, draft_formats(allow_sql))
+        .await
+        .map_err(|e| report_assistant_error(e.into()))?;
 
-    Ok(Json(ReportResult {
-        columns,
-        rows: result_rows,
-        row_count: rows.len(),
-        execution_time_ms: execution_time,
-        query: query_request.sql,
+    let draft = draft_from_rag(question, answer).map_err(report_assistant_error)?;
+    let prepared = prepare_draft(&draft, &schema, allow_sql, state.report_executor.max_rows())
+        .map_err(report_assistant_error)?;
+
+    Ok(Json(ReportDraftPreview {
+        draft,
+        query: prepared.sql,
+        columns: prepared.columns,
     }))
 }
 
-/// Get predefined report templates
-pub async fn get_report_templates(
-    State(_state): State<AppComponents>,
-) -> Result<Json<Vec<ReportTemplate>>, (StatusCode, String)> {
-    let templates = vec![
-        ReportTemplate {
-            id: "samples_by_status".to_string(),
-            name: "Samples by Status".to_string(),
-            description: "Count of samples grouped by status".to_string(),
-            sql: "SELECT status, COUNT(*) as count FROM samples GROUP BY status ORDER BY count DESC".to_string(),
-            category: "Samples".to_string(),
-        },
-        ReportTemplate {
-            id: "recent_samples".to_string(),
-            name: "Recent Samples".to_string(),
-            description: "Samples created in the last 30 days".to_string(),
-            sql: "SELECT name, barcode, location, status, created_at FROM samples WHERE created_at >= NOW() - INTERVAL '30 days' ORDER BY created_at DESC".to_string(),
-            category: "Samples".to_string(),
-        },
-        ReportTemplate {
-            id: "templates_usage".to_string(),
-            name: "Template Usage".to_string(),
-            description: "Number of samples per template".to_string(),
-            sql: "SELECT t.name as template_name, COUNT(CASE WHEN s.metadata->>'template_name' = t.name THEN 1 END) as sample_count, t.created_at as template_created FROM templates t LEFT JOIN samples s ON s.metadata->>'template_name' = t.name GROUP BY t.id, t.name, t.created_at ORDER BY sample_count DESC".to_string(),
-            category: "Templates".to_string(),
-        },
-        ReportTemplate {
-            id: "sample_locations".to_string(),
-            name: "Sample Storage Locations".to_string(),
-            description: "Samples grouped by storage location".to_string(),
-            sql: "SELECT location, COUNT(*) as sample_count, status FROM samples GROUP BY location, status ORDER BY location, status".to_string(),
-            category: "Storage".to_string(),
-        },
-    ];
+/// Run a confirmed report draft, checked again since it comes back from
+/// the client
+pub async fn run_report_draft(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Query(params): Query<ExecutionParams>,
+    Json(draft): Json<ReportDraft>,
+) -> Result<Json<ReportResult>, (StatusCode, String)> {
+    let (user_id, allow_sql) = match user {
+        Some(Extension(user)) => (Some(user.id), is_admin(&user)),
+        None => (None, false),
+    };
+    let schema = load_schema(&state.database.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok(Json(templates))
-}
+    let executor = &state.report_executor;
+    let prepared = prepare_draft(&draft, &schema, allow_sql, executor.max_rows())
+        .map_err(report_assistant_error)?;
+
+    let report = executor
+        .execute(ReportRequest {
+            execution_id: params.execution_id,
+            user_id,
+            source: prepared.source,
+            sql: prepared.sql.clone(),
+            params: prepared.params,
+            limit: prepared.limit,
+        })
+        .await
+        .map_err(report_execution_error)?;
 
-/// Security function to validate SQL queries
-fn is_safe_query(sql: &str) -> bool {
-    let sql_lower = sql.to_lowercase();
+    let columns = prepared
+        .columns
+        .unwrap_or_else(|| row_columns(&report.rows));
+    Ok(Json(report_result(report, columns, prepared.sql)))
+}
 
-    // Must start with SELECT
-    if !sql_lower.trim().starts_with("select") {
-        return false;
-    }
+/// List the reports the current user is running; administrators see all
+pub async fn list_running_reports(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+) -> Json<Vec<RunningReport>> {
+    let (user_id, admin) = match user {
+        Some(Extension(user)) => (Some(user.id), is_admin(&user)),
+        None => (None, false),
+    };
+    Json(state.report_executor.running(user_id, admin))
+}
 
-    // Forbidden keywords that could modify data
-    let forbidden_keywords = [
-        "insert", "update", "delete", "drop", "create", "alter", "truncate", "grant", "revoke",
-        "exec", "execute", "call", "merge", "replace", "copy", "bulk", "load", "import",
-    ];
+/// Cancel a running report
+pub async fn cancel_report(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(execution_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let (user_id, admin) = match user {
+        Some(Extension(user)) => (Some(user.id), is_admin(&user)),
+        None => (None, false),
+    };
+    state
+        .report_executor
+        .cancel(execution_id, user_id, admin)
+        .await
+        .map_err(report_execution_error)?;
+    Ok(StatusCode::ACCEPTED)
+}
 
-    for keyword in &forbidden_keywords {
-        if sql_lower.contains(keyword) {
-            return false;
-        }
+/// Only administrators run SQL reports, and only single SELECT queries
+fn sql_report_user(user: Option<Extension<User>>, sql: &str) -> Result<User, (StatusCode, String)> {
+    let Some(Extension(user)) = user else {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Authentication is required to run SQL reports".to_string(),
+        ));
+    };
+    if !is_admin(&user) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only administrators can run SQL reports; use /api/reports/query instead".to_string(),
+        ));
     }
 
-    // Additional security checks
-    if sql_lower.contains("--") || sql_lower.contains("/*") || sql_lower.contains("*/") {
-        return false; // No comments allowed
+    // Security: Validate the SQL query
+    if !is_safe_query(sql) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Only single SELECT queries are allowed for reports".to_string(),
+        ));
     }
+    Ok(user)
+}
 
-    if sql_lower.contains(";") && sql_lower.matches(';').count() > 1 {
-        return false; // No multiple statements
-    }
+async fn stream_report(
+    state: &AppComponents,
+    request: ReportRequest,
+    columns: Option<Vec<String>>,
+    format: ReportExportFormat,
+    file_stem: &str,
+) -> Result<Response, (StatusCode, String)> {
+    let spool_dir = state.config.storage.base_path.join("report_exports");
+    let export = export_report(
+        state.report_executor.clone(),
+        request,
+        columns,
+        format,
+        &spool_dir,
+        file_stem,
+    )
+    .await
+    .map_err(report_export_error)?;
+    Ok(export_response(export))
+}
 
-    true
+fn export_response(export: ReportExport) -> Response {
+    let mut response = (
+        StatusCode::OK,
+        [
+            (
+                header::CONTENT_TYPE,
+                export.format.content_type().to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", export.filename),
+            ),
+            (
+                header::HeaderName::from_static("x-report-execution-id"),
+                export.execution_id.to_string(),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(export.body)),
+    )
+        .into_response();
+    if let Some(truncated) = export.truncated {
+        response.headers_mut().insert(
+            header::HeaderName::from_static("x-report-truncated"),
+            header::HeaderValue::from_static(if truncated { "true" } else { "false" }),
+        );
+    }
+    response
 }
 
-/// Convert SQL values to JSON values
-fn convert_sql_value_to_json(
-    row: &sqlx::postgres::PgRow,
-    index: usize,
-    type_info: &sqlx::postgres::PgTypeInfo,
-) -> serde_json::Value {
-    use sqlx::ValueRef;
+fn report_result(report: ReportRows, columns: Vec<String>, query: String) -> ReportResult {
+    ReportResult {
+        execution_id: report.execution_id,
+        columns,
+        rows: report.rows.iter().map(row_to_json).collect(),
+        row_count: report.rows.len(),
+        execution_time_ms: report.execution_time_ms,
+        query,
+        truncated: report.truncated,
+    }
+}
 
-    let value_ref = match row.try_get_raw(index) {
-        Ok(value) => value,
-        Err(_) => return serde_json::Value::Null,
+fn report_query_error(error: ReportQueryError) -> (StatusCode, String) {
+    let status = match &error {
+        ReportQueryError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::BAD_REQUEST,
     };
+    (status, error.to_string())
+}
 
-    if value_ref.is_null() {
-        return serde_json::Value::Null;
+fn report_assistant_error(error: ReportAssistantError) -> (StatusCode, String) {
+    match error {
+        ReportAssistantError::Query(error) => report_query_error(error),
+        error => {
+            let status = match &error {
+                ReportAssistantError::InvalidQuestion(_)
+                | ReportAssistantError::InvalidDraft(_)
+                | ReportAssistantError::UnsafeSql => StatusCode::BAD_REQUEST,
+                ReportAssistantError::SqlNotAllowed => StatusCode::FORBIDDEN,
+                ReportAssistantError::NoDraft => StatusCode::UNPROCESSABLE_ENTITY,
+                ReportAssistantError::Disabled
+                | ReportAssistantError::Rag(ApiError::ServiceUnavailable(_)) => {
+                    StatusCode::SERVICE_UNAVAILABLE
+                }
+                ReportAssistantError::Rag(_) => StatusCode::BAD_GATEWAY,
+                ReportAssistantError::Query(_) => StatusCode::BAD_REQUEST,
+            };
+            (status, error.to_string())
+        }
     }
+}
 
-    match type_info.name() {
-        "BOOL" => serde_json::Value::Bool(row.try_get::<bool, _>(index).unwrap_or(false)),
-        "INT2" | "INT4" => serde_json::Value::Number(serde_json::Number::from(
-            row.try_get::<i32, _>(index).unwrap_or(0),
-        )),
-        "INT8" => serde_json::Value::Number(serde_json::Number::from(
-            row.try_get::<i64, _>(index).unwrap_or(0),
-        )),
-        "TIMESTAMPTZ" | "TIMESTAMP" => {
-            if let Ok(val) = row.try_get::<chrono::DateTime<chrono::Utc>, _>(index) {
-                serde_json::Value::String(val.to_rfc3339())
-            } else {
-                serde_json::Value::Null
-            }
-        }
-        "UUID" => {
-            if let Ok(val) = row.try_get::<uuid::Uuid, _>(index) {
-                serde_json::Value::String(val.to_string())
-            } else {
-                serde_json::Value::Null
-            }
-        }
-        "JSONB" | "JSON" => row
-            .try_get::<serde_json::Value, _>(index)
-            .unwrap_or(serde_json::Value::Null),
-        _ => {
-            // Default to string for everything else
-            if let Ok(val) = row.try_get::<String, _>(index) {
-                serde_json::Value::String(val)
-            } else {
-                serde_json::Value::Null
-            }
+fn report_export_error(error: ReportExportError) -> (StatusCode, String) {
+    match error {
+        ReportExportError::Execution(error) => report_execution_error(error),
+        error => {
+            error!("Report export failed: {}", error);
+            (StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
         }
     }
 }
 
-/// List all reports (stub implementation)
+fn report_execution_error(error: ReportExecutionError) -> (StatusCode, String) {
+    let status = match &error {
+        ReportExecutionError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        ReportExecutionError::QueryFailed(_) => StatusCode::BAD_REQUEST,
+        ReportExecutionError::Cancelled | ReportExecutionError::AlreadyRunning(_) => {
+            StatusCode::CONFLICT
+        }
+        ReportExecutionError::TimedOut(_) => StatusCode::REQUEST_TIMEOUT,
+        ReportExecutionError::TooManyReports(_) => StatusCode::TOO_MANY_REQUESTS,
+        ReportExecutionError::NotRunning(_) => StatusCode::NOT_FOUND,
+    };
+    (status, error.to_string())
+}
+
+/// Get report templates: system reports and reports shared with everyone
+pub async fn get_report_templates(
+    State(state): State<AppComponents>,
+) -> Result<Json<Vec<ReportTemplate>>, (StatusCode, String)> {
+    let reports = state
+        .saved_reports
+        .list_templates()
+        .await
+        .map_err(saved_report_error)?;
+
+    Ok(Json(reports.into_iter().map(report_template).collect()))
+}
+
+/// List saved reports the current user owns or that are shared with them
 pub async fn list_reports(
-    State(_state): State<AppComponents>,
-) -> Result<Json<Vec<CustomReport>>, (StatusCode, String)> {
-    Ok(Json(Vec::new()))
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+) -> Result<Json<Vec<SavedReport>>, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    state
+        .saved_reports
+        .list_reports(&viewer)
+        .await
+        .map(Json)
+        .map_err(saved_report_error)
 }
 
-/// Create a custom report (stub implementation)
+/// Save a report owned by the current user
 pub async fn create_custom_report(
-    State(_state): State<AppComponents>,
-    Json(_request): Json<CreateReportRequest>,
-) -> Result<Json<CustomReport>, (StatusCode, String)> {
-    Err((
-        StatusCode::NOT_IMPLEMENTED,
-        "Create custom report not yet implemented".to_string(),
-    ))
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Json(request): Json<CreateSavedReport>,
+) -> Result<(StatusCode, Json<SavedReport>), (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    let schema = load_schema(&state.database.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    state
+        .saved_reports
+        .create_report(&viewer, request, &schema, Utc::now().date_naive())
+        .await
+        .map(|report| (StatusCode::CREATED, Json(report)))
+        .map_err(saved_report_error)
 }
 
-/// Get a report by ID (stub implementation)
+/// Get a saved report by ID
 pub async fn get_report(
-    State(_state): State<AppComponents>,
-    Path(_report_id): Path<Uuid>,
-) -> Result<Json<CustomReport>, (StatusCode, String)> {
-    Err((StatusCode::NOT_FOUND, "Report not found".to_string()))
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(report_id): Path<Uuid>,
+) -> Result<Json<SavedReport>, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    state
+        .saved_reports
+        .get_report(report_id, &viewer)
+        .await
+        .map(Json)
+        .map_err(saved_report_error)
 }
 
-/// Update a report (stub implementation)
+/// Replace a saved report's definition
 pub async fn update_report(
-    State(_state): State<AppComponents>,
-    Path(_report_id): Path<Uuid>,
-    Json(_request): Json<CreateReportRequest>,
-) -> Result<Json<CustomReport>, (StatusCode, String)> {
-    Err((
-        StatusCode::NOT_IMPLEMENTED,
-        "Update report not yet implemented".to_string(),
-    ))
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(report_id): Path<Uuid>,
+    Json(request): Json<CreateSavedReport>,
+) -> Result<Json<SavedReport>, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    let schema = load_schema(&state.database.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    state
+        .saved_reports
+        .update_report(
+            report_id,
+            &viewer,
+            request,
+            &schema,
+            Utc::now().date_naive(),
+        )
+        .await
+        .map(Json)
+        .map_err(saved_report_error)
 }
 
-/// Delete a report (stub implementation)
+/// Delete a saved report
 pub async fn delete_report(
-    State(_state): State<AppComponents>,
-    Path(_report_id): Path<Uuid>,
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(report_id): Path<Uuid>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    Err((
-        StatusCode::NOT_IMPLEMENTED,
-        "Delete report not yet implemented".to_string(),
-    ))
+    let viewer = report_viewer(user)?;
+    state
+        .saved_reports
+        .delete_report(report_id, &viewer)
+        .await
+        .map_err(saved_report_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Run a saved report with the given parameter values
+pub async fn run_saved_report(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(report_id): Path<Uuid>,
+    Query(params): Query<ExecutionParams>,
+    Json(request): Json<RunReportRequest>,
+) -> Result<Json<ReportResult>, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    let saved = state
+        .saved_reports
+        .get_report(report_id, &viewer)
+        .await
+        .map_err(saved_report_error)?;
+    let schema = load_schema(&state.database.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let executor = &state.report_executor;
+    let prepared = prepare_report(
+        &saved.query,
+        &saved.parameters,
+        &request.parameters,
+        &schema,
+        request.limit,
+        executor.max_rows(),
+        Utc::now().date_naive(),
+    )
+    .map_err(saved_report_error)?;
+
+    let report = executor
+        .execute(ReportRequest {
+            execution_id: params.execution_id,
+            user_id: Some(viewer.user_id),
+            source: prepared.source,
+            sql: prepared.sql.clone(),
+            params: prepared.params,
+            limit: prepared.limit,
+        })
+        .await
+        .map_err(report_execution_error)?;
+
+    let columns = prepared
+        .columns
+        .unwrap_or_else(|| row_columns(&report.rows));
+    Ok(Json(report_result(report, columns, prepared.sql)))
+}
+
+/// Export a saved report with the given parameter values
+pub async fn export_saved_report(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(report_id): Path<Uuid>,
+    Query(params): Query<ReportExportParams>,
+    Json(request): Json<RunReportRequest>,
+) -> Result<Response, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    let saved = state
+        .saved_reports
+        .get_report(report_id, &viewer)
+        .await
+        .map_err(saved_report_error)?;
+    let schema = load_schema(&state.database.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let max_rows = state.report_executor.max_export_rows();
+    let prepared = prepare_report(
+        &saved.query,
+        &saved.parameters,
+        &request.parameters,
+        &schema,
+        Some(request.limit.unwrap_or(max_rows)),
+        max_rows,
+        Utc::now().date_naive(),
+    )
+    .map_err(saved_report_error)?;
+
+    let report = ReportRequest {
+        execution_id: params.execution_id,
+        user_id: Some(viewer.user_id),
+        source: prepared.source,
+        sql: prepared.sql,
+        params: prepared.params,
+        limit: prepared.limit,
+    };
+    stream_report(
+        &state,
+        report,
+        prepared.columns,
+        params.format,
+        &file_stem(&saved.name),
+    )
+    .await
 }
 
 /// Get available templates (alias to existing function)
 pub use get_report_templates as get_available_templates;
 
-/// Save report template (stub implementation)
+/// Save a SQL report template shared with everyone. Limited to
+/// administrators.
 pub async fn save_report_template(
-    State(_state): State<AppComponents>,
-    Json(_request): Json<SaveTemplateRequest>,
-) -> Result<Json<ReportTemplate>, (StatusCode, String)> {
-    Err((
-        StatusCode::NOT_IMPLEMENTED,
-        "Save report template not yet implemented".to_string(),
-    ))
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Json(request): Json<SaveTemplateRequest>,
+) -> Result<(StatusCode, Json<ReportTemplate>), (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    let schema = load_schema(&state.database.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let report = CreateSavedReport {
+        name: request.name,
+        description: request.description,
+        category: request.category,
+        query: SavedReportQuery::Sql(request.template),
+        parameters: request.parameters,
+        visibility: ReportVisibility::Everyone,
+        shared_role: None,
+    };
+    state
+        .saved_reports
+        .create_report(&viewer, report, &schema, Utc::now().date_naive())
+        .await
+        .map(|report| (StatusCode::CREATED, Json(report_template(report))))
+        .map_err(saved_report_error)
+}
+
+/// List report schedules the current user owns; every schedule for
+/// administrators
+pub async fn list_report_schedules(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+) -> Result<Json<Vec<ReportSchedule>>, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    state
+        .report_schedules
+        .list_schedules(&viewer)
+        .await
+        .map(Json)
+        .map_err(report_schedule_error)
+}
+
+/// Schedule a saved report to run and be emailed on a cron schedule
+pub async fn create_report_schedule(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Json(request): Json<CreateReportSchedule>,
+) -> Result<(StatusCode, Json<ReportSchedule>), (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    state
+        .report_schedules
+        .create_schedule(&viewer, request, Utc::now())
+        .await
+        .map(|schedule| (StatusCode::CREATED, Json(schedule)))
+        .map_err(report_schedule_error)
+}
+
+/// Get a report schedule
+pub async fn get_report_schedule(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<Json<ReportSchedule>, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    state
+        .report_schedules
+        .get_schedule(schedule_id, &viewer)
+        .await
+        .map(Json)
+        .map_err(report_schedule_error)
+}
+
+/// Replace a report schedule's definition
+pub async fn update_report_schedule(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(schedule_id): Path<Uuid>,
+    Json(request): Json<CreateReportSchedule>,
+) -> Result<Json<ReportSchedule>, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    state
+        .report_schedules
+        .update_schedule(schedule_id, &viewer, request, Utc::now())
+        .await
+        .map(Json)
+        .map_err(report_schedule_error)
+}
+
+/// Delete a report schedule and its run history
+pub async fn delete_report_schedule(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    state
+        .report_schedules
+        .delete_schedule(schedule_id, &viewer)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(report_schedule_error)
+}
+
+/// Run a schedule now and deliver it as a scheduled run would
+pub async fn run_report_schedule(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<Json<ReportRun>, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    state
+        .report_schedules
+        .run_now(schedule_id, &viewer)
+        .await
+        .map(Json)
+        .map_err(report_schedule_error)
+}
+
+/// Run history of a schedule, newest first
+pub async fn list_report_runs(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(schedule_id): Path<Uuid>,
+) -> Result<Json<Vec<ReportRun>>, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    state
+        .report_schedules
+        .list_runs(schedule_id, &viewer)
+        .await
+        .map(Json)
+        .map_err(report_schedule_error)
+}
+
+/// Download the file a run produced
+pub async fn download_report_run(
+    State(state): State<AppComponents>,
+    user: Option<Extension<User>>,
+    Path(run_id): Path<Uuid>,
+) -> Result<Response, (StatusCode, String)> {
+    let viewer = report_viewer(user)?;
+    let (filename, content) = state
+        .report_schedules
+        .get_artifact(run_id, &viewer)
+        .await
+        .map_err(report_schedule_error)?;
+
+    let format = if filename.ends_with(".xlsx") {
+        ReportFormat::Xlsx
+    } else {
+        ReportFormat::Csv
+    };
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        content,
+    )
+        .into_response())
+}
+
+fn report_template(report: SavedReport) -> ReportTemplate {
+    let (sql, structured) = match report.query {
+        SavedReportQuery::Sql(sql) => (sql, None),
+        SavedReportQuery::Structured(spec) => (String::new(), Some(spec)),
+    };
+    ReportTemplate {
+        id: report.system_key.unwrap_or_else(|| report.id.to_string()),
+        report_id: report.id,
+        name: report.name,
+        description: report.description.unwrap_or_default(),
+        sql,
+        structured,
+        category: report.category.unwrap_or_else(|| "Other".to_string()),
+        parameters: report.parameters,
+    }
+}
+
+fn report_viewer(user: Option<Extension<User>>) -> Result<ReportViewer, (StatusCode, String)> {
+    let Some(Extension(user)) = user else {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            "Authentication is required for saved reports".to_string(),
+        ));
+    };
+    Ok(ReportViewer {
+        user_id: user.id,
+        is_admin: is_admin(&user),
+        role: user.role,
+    })
+}
+
+fn saved_report_error(error: SavedReportError) -> (StatusCode, String) {
+    let status = match &error {
+        SavedReportError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        SavedReportError::Query(ReportQueryError::DatabaseError(_)) => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+        SavedReportError::ReportNotFound(_) => StatusCode::NOT_FOUND,
+        SavedReportError::Forbidden(_) | SavedReportError::ReadOnly(_) => StatusCode::FORBIDDEN,
+        SavedReportError::InvalidReport(_)
+        | SavedReportError::InvalidParameter(_)
+        | SavedReportError::Query(_) => StatusCode::BAD_REQUEST,
+    };
+    (status, error.to_string())
+}
+
+fn report_schedule_error(error: ReportScheduleError) -> (StatusCode, String) {
+    match error {
+        ReportScheduleError::Report(error) => saved_report_error(error),
+        ReportScheduleError::Execution(error) => report_execution_error(error),
+        error => {
+            let status = match &error {
+                ReportScheduleError::ScheduleNotFound(_) | ReportScheduleError::RunNotFound(_) => {
+                    StatusCode::NOT_FOUND
+                }
+                ReportScheduleError::InvalidSchedule(_)
+                | ReportScheduleError::EmailDisabled
+                | ReportScheduleError::Email(EmailError::InvalidAddress(_)) => {
+                    StatusCode::BAD_REQUEST
+                }
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            (status, error.to_string())
+        }
+    }
 }
@@ -123,6 +123,9 @@ pub struct BatchCreateSamplesRequest {
     pub samples: Vec<CreateSample>,
     pub storage_location_id: Option<i32>,
     pub template_name: Option<String>,
+    /// Template version the samples were submitted with, for samples that
+    /// do not name one themselves
+    pub template_version_id: Option<Uuid>,
     pub stored_by: Option<String>,
 }
 
@@ -178,11 +181,16 @@ pub async fn create_samples_batch(
             continue;
         }
 
+        let mut sample_data = sample_data.clone();
+        if sample_data.template_version_id.is_none() {
+            sample_data.template_version_id = batch_request.template_version_id;
+        }
+
         // Create the sample first
         match state
             .sample_processing
             .manager
-            .create_sample((*sample_data).clone())
+            .create_sample(sample_data.clone())
             .await
         {
             Ok(sample) => {
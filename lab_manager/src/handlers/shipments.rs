@@ -0,0 +1,206 @@
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json},
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    assembly::AppComponents,
+    errors::api::ApiError,
+    models::shipment::{CreateShipment, ReceivedItem, Shipment, ShipmentStatus, ShipmentWithItems},
+    services::shipment_service::{export_manifest_csv, ShipmentError},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ListShipmentsParams {
+    pub status: Option<ShipmentStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DispatchShipmentRequest {
+    pub shipped_by: String,
+    pub tracking_number: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReceiveShipmentRequest {
+    pub received_by: String,
+    /// Receipt details per line; leave empty when every line arrived
+    #[serde(default)]
+    pub items: Vec<ReceivedItem>,
+    /// Store received samples in this location (inbound shipments only)
+    pub store_in_location: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportManifestParams {
+    pub imported_by: Option<String>,
+}
+
+/// Create a shipment with its manifest
+pub async fn create_shipment(
+    State(app): State<AppComponents>,
+    Json(request): Json<CreateShipment>,
+) -> Result<Json<ShipmentWithItems>, ApiError> {
+    let shipment = app
+        .shipment_service()
+        .create_shipment(request)
+        .await
+        .map_err(shipment_error)?;
+
+    Ok(Json(shipment))
+}
+
+/// List shipments, optionally filtered by status
+pub async fn list_shipments(
+    State(app): State<AppComponents>,
+    Query(params): Query<ListShipmentsParams>,
+) -> Result<Json<Vec<Shipment>>, ApiError> {
+    let shipments = app
+        .shipment_service()
+        .list_shipments(params.status)
+        .await
+        .map_err(shipment_error)?;
+
+    Ok(Json(shipments))
+}
+
+/// Get a shipment with its manifest
+pub async fn get_shipment(
+    State(app): State<AppComponents>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<ShipmentWithItems>, ApiError> {
+    let shipment = app
+        .shipment_service()
+        .get_shipment(id)
+        .await
+        .map_err(shipment_error)?;
+
+    Ok(Json(shipment))
+}
+
+/// Mark a shipment as shipped, releasing outbound samples from storage
+pub async fn dispatch_shipment(
+    State(app): State<AppComponents>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<DispatchShipmentRequest>,
+) -> Result<Json<Shipment>, ApiError> {
+    let shipment = app
+        .shipment_service()
+        .dispatch_shipment(id, &request.shipped_by, request.tracking_number)
+        .await
+        .map_err(shipment_error)?;
+
+    Ok(Json(shipment))
+}
+
+/// Mark a shipment as received
+pub async fn receive_shipment(
+    State(app): State<AppComponents>,
+    Path(id): Path<Uuid>,
+    Json(request): Json<ReceiveShipmentRequest>,
+) -> Result<Json<Shipment>, ApiError> {
+    let shipment = app
+        .shipment_service()
+        .receive_shipment(
+            id,
+            &request.received_by,
+            request.items,
+            request.store_in_location,
+        )
+        .await
+        .map_err(shipment_error)?;
+
+    Ok(Json(shipment))
+}
+
+/// Cancel a shipment that has not shipped yet
+pub async fn cancel_shipment(
+    State(app): State<AppComponents>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Shipment>, ApiError> {
+    let shipment = app
+        .shipment_service()
+        .cancel_shipment(id)
+        .await
+        .map_err(shipment_error)?;
+
+    Ok(Json(shipment))
+}
+
+/// Download the shipment manifest as CSV
+pub async fn export_manifest(
+    State(app): State<AppComponents>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let shipment = app
+        .shipment_service()
+        .get_shipment(id)
+        .await
+        .map_err(shipment_error)?;
+    let csv = export_manifest_csv(&shipment).map_err(shipment_error)?;
+    let filename = format!(
+        "attachment; filename=\"{}_manifest.csv\"",
+        shipment.shipment.shipment_number
+    );
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, filename),
+        ],
+        csv,
+    ))
+}
+
+/// Register an inbound shipment from a sender's manifest CSV
+pub async fn import_manifest(
+    State(app): State<AppComponents>,
+    Query(params): Query<ImportManifestParams>,
+    mut multipart: Multipart,
+) -> Result<Json<ShipmentWithItems>, ApiError> {
+    let mut manifest = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("Failed to read multipart field: {}", e)))?
+    {
+        if field.name() == Some("file") {
+            let bytes = field
+                .bytes()
+                .await
+                .map_err(|e| ApiError::BadRequest(format!("Failed to read manifest: {}", e)))?;
+            manifest = Some(bytes);
+        }
+    }
+
+    let manifest =
+        manifest.ok_or_else(|| ApiError::BadRequest("No manifest file provided".to_string()))?;
+    let imported_by = params.imported_by.unwrap_or_else(|| "system".to_string());
+
+    let shipment = app
+        .shipment_service()
+        .import_manifest(&manifest, &imported_by)
+        .await
+        .map_err(shipment_error)?;
+
+    Ok(Json(shipment))
+}
+
+fn shipment_error(error: ShipmentError) -> ApiError {
+    match error {
+        ShipmentError::ShipmentNotFound(_) => ApiError::NotFound,
+        ShipmentError::DuplicateShipmentNumber(_)
+        | ShipmentError::SampleCheckedOut(_)
+        | ShipmentError::SampleAlreadyStored(_)
+        | ShipmentError::InvalidStatusTransition { .. } => ApiError::Conflict(error.to_string()),
+        ShipmentError::InvalidManifest(_)
+        | ShipmentError::UnknownSamples(_)
+        | ShipmentError::CsvError(_) => ApiError::BadRequest(error.to_string()),
+        ShipmentError::DatabaseError(_) => ApiError::InternalServerError(error.to_string()),
+    }
+}
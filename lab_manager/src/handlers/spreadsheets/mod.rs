@@ -1,24 +1,68 @@
 use axum::{
-    extract::{Multipart, Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    body::Body,
+    extract::{multipart::Field, Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
 use crate::{
     assembly::AppComponents,
-    models::spreadsheet::{SpreadsheetDataset, SpreadsheetSearchQuery, SpreadsheetSearchResult},
-    services::Service,
+    models::spreadsheet::{
+        AggregateQuery, CreateDatasetRuleSet, CreateDatasetSeries, CreateSampleMappingProfile,
+        DatasetRuleSet, DatasetSeries, DatasetSeriesWithVersions, LinkedSample, RangeBound,
+        RangeFilter, RankedSearchQuery, RankedSearchResult, SampleMappingProfile, SeriesVersion,
+        SpreadsheetDataset, SpreadsheetSearchQuery, SpreadsheetSearchResult, UploadProgress,
+        ViolationAction,
+    },
+    services::{
+        csv_dialect::CsvDialectOverride,
+        dataset_series::{DatasetDiff, SeriesError, DEFAULT_DIFF_LIMIT, MAX_DIFF_LIMIT},
+        dataset_validation::{QualityReport, RuleSetError},
+        sample_mapping::{
+            MappingError, MappingPreview, SampleConversion, DEFAULT_PREVIEW_LIMIT,
+            MAX_PREVIEW_LIMIT,
+        },
+        spreadsheet_aggregate::{AggregateError, TabularResult},
+        spreadsheet_export::{ExportError, ExportFormat, ExportOptions, SpreadsheetExport},
+        spreadsheet_ingest::IngestOptions,
+        spreadsheet_search::build_tsquery,
+        spreadsheet_service::SpreadsheetService,
+        Service,
+    },
 };
 
+/// Largest spreadsheet accepted for upload
+pub const MAX_SPREADSHEET_UPLOAD_BYTES: usize = 512 * 1024 * 1024;
+
+/// Uploads above this size are ingested in the background unless the
+/// request says otherwise
+pub const BACKGROUND_UPLOAD_THRESHOLD_BYTES: u64 = 10 * 1024 * 1024;
+
 /// Upload request parameters
 #[derive(Debug, Deserialize)]
 pub struct UploadParams {
     pub sheet_name: Option<String>,
     pub uploaded_by: Option<String>,
+    /// Ingest in the background and return immediately; defaults to true for
+    /// large files
+    pub background: Option<bool>,
+    /// CSV dialect overrides; anything not given is detected from the file
+    pub encoding: Option<String>,
+    pub delimiter: Option<String>,
+    pub quote: Option<String>,
+    pub header_row: Option<usize>,
+    /// Add the uploaded dataset to this series as its next version
+    pub series_id: Option<Uuid>,
+    /// Check the upload against the rule set of this dataset type
+    pub dataset_type: Option<String>,
+    /// Override the rule set's action on violations
+    pub on_violation: Option<ViolationAction>,
 }
 
 /// Upload response
@@ -40,6 +84,89 @@ pub struct SearchParams {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
     // Column filters as query parameters like: ?filter_Sample_ID=LAB001&filter_Department=Oncology
+    // Typed filters: ?min_Volume=10&max_Collected=2024-06-30&in_Sample_Type=DNA,RNA
+}
+
+/// Ranked search parameters
+#[derive(Debug, Deserialize)]
+pub struct RankedSearchParams {
+    /// Words, "quoted phrases", prefix*, -excluded and OR
+    pub q: String,
+    /// Comma-separated dataset IDs; searches every dataset when omitted
+    pub dataset_ids: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Export parameters
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    /// `csv` (default) or `xlsx`
+    pub format: Option<ExportFormat>,
+    /// Prepend dataset ID, source file and source row columns
+    pub provenance: Option<bool>,
+}
+
+impl ExportParams {
+    fn options(&self) -> ExportOptions {
+        ExportOptions {
+            format: self.format.unwrap_or_default(),
+            include_provenance: self.provenance.unwrap_or(false),
+        }
+    }
+}
+
+/// Selects one version of a series; the latest when neither is given
+#[derive(Debug, Deserialize)]
+pub struct SeriesVersionParams {
+    pub version: Option<i32>,
+    /// Latest version added at or before this time
+    pub as_of: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Versions to compare
+#[derive(Debug, Deserialize)]
+pub struct SeriesDiffParams {
+    pub from: i32,
+    pub to: i32,
+    /// Most changed rows to return
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddSeriesVersionRequest {
+    pub dataset_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SamplePreviewRequest {
+    pub profile_id: Uuid,
+    pub limit: Option<usize>,
+    /// Return only rows that cannot be converted
+    #[serde(default)]
+    pub only_invalid: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConvertSamplesRequest {
+    pub profile_id: Uuid,
+    /// Convert the valid rows and leave the invalid ones behind instead of
+    /// rejecting the whole dataset
+    #[serde(default)]
+    pub skip_invalid: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ValidateDatasetRequest {
+    pub rule_set_id: Uuid,
+}
+
+/// Search results from one version of a series
+#[derive(Debug, Serialize)]
+pub struct SeriesSearchResult {
+    pub version: SeriesVersion,
+    #[serde(flatten)]
+    pub result: SpreadsheetSearchResult,
 }
 
 /// List datasets parameters
@@ -84,16 +211,48 @@ pub async fn upload_spreadsheet(
     let service = &components.spreadsheet_service;
     info!("Received spreadsheet upload request");
 
+    let csv_dialect = match CsvDialectOverride::from_params(
+        params.encoding.as_deref(),
+        params.delimiter.as_deref(),
+        params.quote.as_deref(),
+        params.header_row,
+    ) {
+        Ok(csv_dialect) => csv_dialect,
+        Err(e) => {
+            warn!("Invalid CSV dialect override: {}", e);
+            return Ok(Json(UploadResponse {
+                success: false,
+                dataset: None,
+                message: e.to_string(),
+            }));
+        }
+    };
+
+    let validation = match params.dataset_type.as_deref() {
+        Some(dataset_type) => match service
+            .upload_validation(dataset_type, params.on_violation)
+            .await
+        {
+            Ok(validation) => Some(validation),
+            Err(e @ RuleSetError::UnknownDatasetType(_)) => {
+                warn!("Upload names an unknown dataset type: {}", e);
+                return Ok(Json(UploadResponse {
+                    success: false,
+                    dataset: None,
+                    message: e.to_string(),
+                }));
+            }
+            Err(e) => return Err(rule_set_error_status(e)),
+        },
+        None => None,
+    };
+
     while let Some(field) = multipart.next_field().await.map_err(|e| {
         error!("Failed to read multipart field: {}", e);
         StatusCode::BAD_REQUEST
     })? {
         if field.name() == Some("file") {
             let filename = field.file_name().unwrap_or("unknown").to_string();
-            let file_data = field.bytes().await.map_err(|e| {
-                error!("Failed to read file data: {}", e);
-                StatusCode::BAD_REQUEST
-            })?;
 
             // Detect file type from filename
             let file_type = match service.detect_file_type(&filename) {
@@ -124,19 +283,56 @@ pub async fn upload_spreadsheet(
             // Generate unique filename for storage
             let stored_filename = format!("{}_{}", Uuid::new_v4(), filename);
 
+            // Spool the upload to disk so large files are never held in memory
+            let spool_dir = components
+                .config
+                .storage
+                .base_path
+                .join("spreadsheet_uploads");
+            let spool_path = spool_dir.join(&stored_filename);
+            let file_size = spool_field(field, &spool_dir, &spool_path)
+                .await
+                .map_err(|e| {
+                    error!("Failed to spool upload {}: {}", filename, e);
+                    StatusCode::BAD_REQUEST
+                })?;
+
+            let background = params
+                .background
+                .unwrap_or(file_size > BACKGROUND_UPLOAD_THRESHOLD_BYTES);
+
             // Process the upload
-            match service
-                .process_upload(
-                    stored_filename,
+            let result = service
+                .process_upload_file(
                     filename.clone(),
-                    file_data.to_vec(),
+                    spool_path,
                     file_type,
-                    params.sheet_name,
+                    IngestOptions {
+                        sheet_name: params.sheet_name,
+                        csv_dialect,
+                        validation,
+                        ..Default::default()
+                    },
                     params.uploaded_by,
+                    background,
                 )
-                .await
-            {
-                Ok(dataset) => {
+                .await;
+
+            match (result, params.series_id) {
+                (Ok(dataset), Some(series_id)) => {
+                    return Ok(Json(
+                        add_upload_to_series(service, series_id, dataset, &filename).await,
+                    ));
+                }
+                (Ok(dataset), None) if background => {
+                    info!("Queued background ingestion for file: {}", filename);
+                    return Ok(Json(UploadResponse {
+                        success: true,
+                        dataset: Some(dataset),
+                        message: "File uploaded; processing in the background".to_string(),
+                    }));
+                }
+                (Ok(dataset), None) => {
                     info!("Successfully processed upload for file: {}", filename);
                     return Ok(Json(UploadResponse {
                         success: true,
@@ -144,7 +340,7 @@ pub async fn upload_spreadsheet(
                         message: "File uploaded and processed successfully".to_string(),
                     }));
                 }
-                Err(e) => {
+                (Err(e), _) => {
                     error!("Failed to process upload for file {}: {}", filename, e);
                     return Ok(Json(UploadResponse {
                         success: false,
@@ -164,6 +360,83 @@ pub async fn upload_spreadsheet(
     }))
 }
 
+/// Add a processed upload to a series as its next version
+async fn add_upload_to_series(
+    service: &SpreadsheetService,
+    series_id: Uuid,
+    dataset: SpreadsheetDataset,
+    filename: &str,
+) -> UploadResponse {
+    match service.add_dataset_to_series(series_id, dataset.id).await {
+        Ok(version) => {
+            info!(
+                "Added upload {} to series {} as version {}",
+                filename, series_id, version.version
+            );
+            UploadResponse {
+                success: true,
+                dataset: Some(dataset),
+                message: format!("File uploaded as version {} of the series", version.version),
+            }
+        }
+        Err(e) => {
+            warn!(
+                "Failed to add upload {} to series {}: {}",
+                filename, series_id, e
+            );
+            UploadResponse {
+                success: false,
+                dataset: Some(dataset),
+                message: format!("File uploaded but not added to the series: {}", e),
+            }
+        }
+    }
+}
+
+/// Write a multipart field to disk chunk by chunk, returning its size
+async fn spool_field(
+    mut field: Field<'_>,
+    spool_dir: &std::path::Path,
+    spool_path: &std::path::Path,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    tokio::fs::create_dir_all(spool_dir).await?;
+    let mut file = tokio::fs::File::create(spool_path).await?;
+    let mut written = 0u64;
+
+    let result = async {
+        while let Some(chunk) = field.chunk().await? {
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        file.flush().await?;
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(written)
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(spool_path).await;
+    }
+    result
+}
+
+/// Ingestion progress of a dataset
+pub async fn get_upload_progress(
+    State(components): State<AppComponents>,
+    Path(dataset_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<UploadProgress>>, StatusCode> {
+    match components.spreadsheet_service.get_dataset(dataset_id).await {
+        Ok(dataset) => Ok(Json(ApiResponse::success(
+            UploadProgress::from(&dataset),
+            "Upload progress retrieved",
+        ))),
+        Err(sqlx::Error::RowNotFound) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to get progress for dataset {}: {}", dataset_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 /// Search spreadsheet data
 pub async fn search_data(
     State(components): State<AppComponents>,
@@ -173,16 +446,63 @@ pub async fn search_data(
     let service = &components.spreadsheet_service;
     info!("Received data search request");
 
-    // Extract column filters from query parameters
+    let query = build_search_query(params, raw_params)?;
+
+    match service.search_data(query).await {
+        Ok(result) => {
+            info!(
+                "Search completed successfully, found {} records",
+                result.records.len()
+            );
+            Ok(Json(ApiResponse::success(
+                result,
+                "Search completed successfully",
+            )))
+        }
+        Err(e) => {
+            error!("Search failed: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Search query from the search parameters plus the column filters given
+/// as `filter_`, `min_`, `max_` and `in_` prefixed query parameters
+fn build_search_query(
+    params: SearchParams,
+    raw_params: HashMap<String, String>,
+) -> Result<SpreadsheetSearchQuery, StatusCode> {
     let mut column_filters = HashMap::new();
+    let mut range_filters: HashMap<String, RangeFilter> = HashMap::new();
+    let mut in_filters = HashMap::new();
     for (key, value) in raw_params {
-        if key.starts_with("filter_") {
-            let column_name = key.trim_start_matches("filter_");
+        if let Some(column_name) = key.strip_prefix("filter_") {
             column_filters.insert(column_name.to_string(), value);
+        } else if let Some(column_name) = key.strip_prefix("min_") {
+            let bound = RangeBound::parse(&value, false).ok_or_else(|| {
+                warn!("Invalid range bound for {}: {}", column_name, value);
+                StatusCode::BAD_REQUEST
+            })?;
+            range_filters
+                .entry(column_name.to_string())
+                .or_default()
+                .min = Some(bound);
+        } else if let Some(column_name) = key.strip_prefix("max_") {
+            let bound = RangeBound::parse(&value, true).ok_or_else(|| {
+                warn!("Invalid range bound for {}: {}", column_name, value);
+                StatusCode::BAD_REQUEST
+            })?;
+            range_filters
+                .entry(column_name.to_string())
+                .or_default()
+                .max = Some(bound);
+        } else if let Some(column_name) = key.strip_prefix("in_") {
+            let values = value.split(',').map(|v| v.trim().to_string()).collect();
+            in_filters.insert(column_name.to_string(), values);
         }
     }
 
-    let query = SpreadsheetSearchQuery {
+    Ok(SpreadsheetSearchQuery {
         search_term: params.search_term,
         dataset_id: params.dataset_id,
         pool_filter: params.pool_filter,
@@ -193,15 +513,51 @@ pub async fn search_data(
         } else {
             Some(column_filters)
         },
+        range_filters: (!range_filters.is_empty()).then_some(range_filters),
+        in_filters: (!in_filters.is_empty()).then_some(in_filters),
         limit: params.limit,
         offset: params.offset,
+    })
+}
+
+/// Ranked full-text search across datasets, with matching cells
+/// highlighted and facet counts per dataset and column
+pub async fn ranked_search(
+    State(components): State<AppComponents>,
+    Query(params): Query<RankedSearchParams>,
+) -> Result<Json<ApiResponse<RankedSearchResult>>, StatusCode> {
+    let Some(tsquery) = build_tsquery(&params.q) else {
+        warn!("Search query has no searchable terms: {}", params.q);
+        return Err(StatusCode::BAD_REQUEST);
     };
 
-    match service.search_data(query).await {
+    let dataset_ids = params
+        .dataset_ids
+        .as_deref()
+        .map(|ids| {
+            ids.split(',')
+                .map(|id| id.trim().parse::<Uuid>())
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .map_err(|e| {
+            warn!("Invalid dataset ID in search: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+
+    let query = RankedSearchQuery {
+        tsquery,
+        dataset_ids,
+        limit: params.limit,
+        offset: params.offset,
+    };
+
+    match components.spreadsheet_service.ranked_search(query).await {
         Ok(result) => {
             info!(
-                "Search completed successfully, found {} records",
-                result.records.len()
+                "Ranked search matched {} records across {} datasets",
+                result.total_count,
+                result.dataset_facets.len()
             );
             Ok(Json(ApiResponse::success(
                 result,
@@ -209,12 +565,623 @@ pub async fn search_data(
             )))
         }
         Err(e) => {
-            error!("Search failed: {}", e);
+            error!("Ranked search failed: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Group, aggregate, pivot or join records across datasets
+pub async fn aggregate_records(
+    State(components): State<AppComponents>,
+    Json(query): Json<AggregateQuery>,
+) -> Result<Json<ApiResponse<TabularResult>>, StatusCode> {
+    match components.spreadsheet_service.aggregate(query).await {
+        Ok(table) => {
+            info!(
+                "Aggregation returned {} rows in {} ms",
+                table.row_count, table.execution_time_ms
+            );
+            Ok(Json(ApiResponse::success(
+                table,
+                "Aggregation completed successfully",
+            )))
+        }
+        Err(AggregateError::DatasetNotFound) => Err(StatusCode::NOT_FOUND),
+        Err(e @ AggregateError::ValidationError(_))
+        | Err(e @ AggregateError::TooManyPivotValues { .. }) => {
+            warn!("Invalid aggregation request: {}", e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+        Err(AggregateError::DatabaseError(e)) => {
+            error!("Aggregation failed: {}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
         }
     }
 }
 
+/// Export every record matching a search as CSV or XLSX. Takes the same
+/// filters as the search endpoint; limit and offset are ignored.
+pub async fn export_search(
+    State(components): State<AppComponents>,
+    Query(params): Query<SearchParams>,
+    Query(export_params): Query<ExportParams>,
+    Query(raw_params): Query<HashMap<String, String>>,
+) -> Result<Response, StatusCode> {
+    let query = build_search_query(params, raw_params)?;
+    info!("Received search export request");
+
+    match components
+        .spreadsheet_service
+        .export_search(
+            query,
+            export_params.options(),
+            &export_spool_dir(&components),
+        )
+        .await
+    {
+        Ok(export) => Ok(export_response(export)),
+        Err(e) => Err(export_error_status(e)),
+    }
+}
+
+/// Export a whole dataset as CSV or XLSX
+pub async fn export_dataset(
+    State(components): State<AppComponents>,
+    Path(dataset_id): Path<Uuid>,
+    Query(export_params): Query<ExportParams>,
+) -> Result<Response, StatusCode> {
+    info!("Received export request for dataset: {}", dataset_id);
+
+    match components
+        .spreadsheet_service
+        .export_dataset(
+            dataset_id,
+            export_params.options(),
+            &export_spool_dir(&components),
+        )
+        .await
+    {
+        Ok(export) => Ok(export_response(export)),
+        Err(e) => Err(export_error_status(e)),
+    }
+}
+
+fn export_spool_dir(components: &AppComponents) -> std::path::PathBuf {
+    components
+        .config
+        .storage
+        .base_path
+        .join("spreadsheet_exports")
+}
+
+fn export_response(export: SpreadsheetExport) -> Response {
+    (
+        StatusCode::OK,
+        [
+            (
+                header::CONTENT_TYPE,
+                export.format.content_type().to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", export.filename),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(export.body)),
+    )
+        .into_response()
+}
+
+fn export_error_status(error: ExportError) -> StatusCode {
+    match error {
+        ExportError::DatasetNotFound => StatusCode::NOT_FOUND,
+        ExportError::TooManyRows { .. } => {
+            warn!("Export rejected: {}", error);
+            StatusCode::BAD_REQUEST
+        }
+        e => {
+            error!("Export failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Create a dataset series
+pub async fn create_series(
+    State(components): State<AppComponents>,
+    Json(request): Json<CreateDatasetSeries>,
+) -> Result<Json<ApiResponse<DatasetSeries>>, StatusCode> {
+    info!(
+        "Received request to create dataset series: {}",
+        request.name
+    );
+
+    match components.spreadsheet_service.create_series(request).await {
+        Ok(series) => Ok(Json(ApiResponse::success(
+            series,
+            "Dataset series created successfully",
+        ))),
+        Err(e) => Err(series_error_status(e)),
+    }
+}
+
+/// List dataset series
+pub async fn list_series(
+    State(components): State<AppComponents>,
+) -> Result<Json<ApiResponse<Vec<DatasetSeries>>>, StatusCode> {
+    match components.spreadsheet_service.list_series().await {
+        Ok(series) => Ok(Json(ApiResponse::success(
+            series,
+            "Dataset series retrieved successfully",
+        ))),
+        Err(e) => {
+            error!("Failed to list dataset series: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Get a dataset series with its versions
+pub async fn get_series(
+    State(components): State<AppComponents>,
+    Path(series_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<DatasetSeriesWithVersions>>, StatusCode> {
+    match components
+        .spreadsheet_service
+        .get_series_with_versions(series_id)
+        .await
+    {
+        Ok(series) => Ok(Json(ApiResponse::success(
+            series,
+            "Dataset series retrieved successfully",
+        ))),
+        Err(e) => Err(series_error_status(e)),
+    }
+}
+
+/// Add an existing dataset to a series as its next version
+pub async fn add_series_version(
+    State(components): State<AppComponents>,
+    Path(series_id): Path<Uuid>,
+    Json(request): Json<AddSeriesVersionRequest>,
+) -> Result<Json<ApiResponse<SeriesVersion>>, StatusCode> {
+    info!(
+        "Received request to add dataset {} to series {}",
+        request.dataset_id, series_id
+    );
+
+    match components
+        .spreadsheet_service
+        .add_dataset_to_series(series_id, request.dataset_id)
+        .await
+    {
+        Ok(version) => Ok(Json(ApiResponse::success(
+            version,
+            "Dataset added to series successfully",
+        ))),
+        Err(e) => Err(series_error_status(e)),
+    }
+}
+
+/// Added, removed and changed rows between two versions of a series
+pub async fn diff_series_versions(
+    State(components): State<AppComponents>,
+    Path(series_id): Path<Uuid>,
+    Query(params): Query<SeriesDiffParams>,
+) -> Result<Json<ApiResponse<DatasetDiff>>, StatusCode> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_DIFF_LIMIT)
+        .min(MAX_DIFF_LIMIT);
+
+    match components
+        .spreadsheet_service
+        .diff_versions(series_id, params.from, params.to, limit)
+        .await
+    {
+        Ok(diff) => {
+            info!(
+                "Diffed series {} versions {}..{}: {} added, {} removed, {} changed",
+                series_id,
+                params.from,
+                params.to,
+                diff.summary.added,
+                diff.summary.removed,
+                diff.summary.changed
+            );
+            Ok(Json(ApiResponse::success(
+                diff,
+                "Versions compared successfully",
+            )))
+        }
+        Err(e) => Err(series_error_status(e)),
+    }
+}
+
+/// Search one version of a series, chosen by number or as of a point in
+/// time; accepts the same filters as the dataset search
+pub async fn search_series(
+    State(components): State<AppComponents>,
+    Path(series_id): Path<Uuid>,
+    Query(params): Query<SearchParams>,
+    Query(version_params): Query<SeriesVersionParams>,
+    Query(raw_params): Query<HashMap<String, String>>,
+) -> Result<Json<ApiResponse<SeriesSearchResult>>, StatusCode> {
+    let service = &components.spreadsheet_service;
+
+    let version = service
+        .resolve_version(series_id, version_params.version, version_params.as_of)
+        .await
+        .map_err(series_error_status)?;
+
+    let mut query = build_search_query(params, raw_params)?;
+    query.dataset_id = Some(version.dataset_id);
+
+    match service.search_data(query).await {
+        Ok(result) => Ok(Json(ApiResponse::success(
+            SeriesSearchResult { version, result },
+            "Search completed successfully",
+        ))),
+        Err(e) => {
+            error!("Series search failed: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn series_error_status(error: SeriesError) -> StatusCode {
+    match error {
+        SeriesError::SeriesNotFound
+        | SeriesError::DatasetNotFound
+        | SeriesError::VersionNotFound => StatusCode::NOT_FOUND,
+        SeriesError::DuplicateSeriesName(_)
+        | SeriesError::DatasetAlreadyInSeries
+        | SeriesError::VersionNotReady(_)
+        | SeriesError::DuplicateIdentities { .. } => {
+            warn!("Dataset series conflict: {}", error);
+            StatusCode::CONFLICT
+        }
+        SeriesError::MissingIdentityColumn(_) | SeriesError::ValidationError(_) => {
+            warn!("Invalid dataset series request: {}", error);
+            StatusCode::BAD_REQUEST
+        }
+        SeriesError::DatabaseError(e) => {
+            error!("Dataset series database error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Create a sample mapping profile
+pub async fn create_mapping_profile(
+    State(components): State<AppComponents>,
+    Json(request): Json<CreateSampleMappingProfile>,
+) -> Result<Json<ApiResponse<SampleMappingProfile>>, StatusCode> {
+    info!(
+        "Received request to create sample mapping profile: {}",
+        request.name
+    );
+
+    match components
+        .spreadsheet_service
+        .create_mapping_profile(request)
+        .await
+    {
+        Ok(profile) => Ok(Json(ApiResponse::success(
+            profile,
+            "Mapping profile created successfully",
+        ))),
+        Err(e) => Err(mapping_error_status(e)),
+    }
+}
+
+/// List sample mapping profiles
+pub async fn list_mapping_profiles(
+    State(components): State<AppComponents>,
+) -> Result<Json<ApiResponse<Vec<SampleMappingProfile>>>, StatusCode> {
+    match components.spreadsheet_service.list_mapping_profiles().await {
+        Ok(profiles) => Ok(Json(ApiResponse::success(
+            profiles,
+            "Mapping profiles retrieved successfully",
+        ))),
+        Err(e) => {
+            error!("Failed to list mapping profiles: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Get a sample mapping profile
+pub async fn get_mapping_profile(
+    State(components): State<AppComponents>,
+    Path(profile_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<SampleMappingProfile>>, StatusCode> {
+    match components
+        .spreadsheet_service
+        .get_mapping_profile(profile_id)
+        .await
+    {
+        Ok(profile) => Ok(Json(ApiResponse::success(
+            profile,
+            "Mapping profile retrieved successfully",
+        ))),
+        Err(e) => Err(mapping_error_status(e)),
+    }
+}
+
+/// Delete a sample mapping profile. Samples already created keep their
+/// link to the source row.
+pub async fn delete_mapping_profile(
+    State(components): State<AppComponents>,
+    Path(profile_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<u64>>, StatusCode> {
+    info!("Received request to delete mapping profile: {}", profile_id);
+
+    match components
+        .spreadsheet_service
+        .delete_mapping_profile(profile_id)
+        .await
+    {
+        Ok(0) => {
+            warn!("Mapping profile not found for deletion: {}", profile_id);
+            Err(StatusCode::NOT_FOUND)
+        }
+        Ok(rows_affected) => Ok(Json(ApiResponse::success(
+            rows_affected,
+            "Mapping profile deleted successfully",
+        ))),
+        Err(e) => {
+            error!("Failed to delete mapping profile {}: {}", profile_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Dry run of converting a dataset's rows into samples
+pub async fn preview_dataset_samples(
+    State(components): State<AppComponents>,
+    Path(dataset_id): Path<Uuid>,
+    Json(request): Json<SamplePreviewRequest>,
+) -> Result<Json<ApiResponse<MappingPreview>>, StatusCode> {
+    let limit = request
+        .limit
+        .unwrap_or(DEFAULT_PREVIEW_LIMIT)
+        .min(MAX_PREVIEW_LIMIT);
+
+    match components
+        .spreadsheet_service
+        .preview_sample_mapping(dataset_id, request.profile_id, limit, request.only_invalid)
+        .await
+    {
+        Ok(preview) => Ok(Json(ApiResponse::success(
+            preview,
+            "Sample mapping preview generated successfully",
+        ))),
+        Err(e) => Err(mapping_error_status(e)),
+    }
+}
+
+/// Create samples from a dataset's rows
+pub async fn convert_dataset_samples(
+    State(components): State<AppComponents>,
+    Path(dataset_id): Path<Uuid>,
+    Json(request): Json<ConvertSamplesRequest>,
+) -> Result<Json<ApiResponse<SampleConversion>>, StatusCode> {
+    info!(
+        "Received request to create samples from dataset {} with profile {}",
+        dataset_id, request.profile_id
+    );
+
+    match components
+        .spreadsheet_service
+        .convert_dataset_to_samples(dataset_id, request.profile_id, request.skip_invalid)
+        .await
+    {
+        Ok(conversion) => {
+            info!(
+                "Created {} samples from dataset {}",
+                conversion.created, dataset_id
+            );
+            Ok(Json(ApiResponse::success(
+                conversion,
+                "Samples created successfully",
+            )))
+        }
+        Err(e @ MappingError::InvalidRows { .. }) => {
+            warn!("Refusing to convert dataset {}: {}", dataset_id, e);
+            Ok(Json(ApiResponse::error(&e.to_string())))
+        }
+        Err(e) => Err(mapping_error_status(e)),
+    }
+}
+
+/// List samples created from a dataset's rows
+pub async fn list_dataset_samples(
+    State(components): State<AppComponents>,
+    Path(dataset_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<Vec<LinkedSample>>>, StatusCode> {
+    match components
+        .spreadsheet_service
+        .list_dataset_samples(dataset_id)
+        .await
+    {
+        Ok(samples) => Ok(Json(ApiResponse::success(
+            samples,
+            "Dataset samples retrieved successfully",
+        ))),
+        Err(e) => {
+            error!("Failed to list samples of dataset {}: {}", dataset_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn mapping_error_status(error: MappingError) -> StatusCode {
+    match error {
+        MappingError::ProfileNotFound | MappingError::DatasetNotFound => StatusCode::NOT_FOUND,
+        MappingError::DuplicateProfileName(_)
+        | MappingError::InvalidRows { .. }
+        | MappingError::Conflict => {
+            warn!("Sample mapping conflict: {}", error);
+            StatusCode::CONFLICT
+        }
+        MappingError::InvalidProfile(_) | MappingError::MissingColumns(_) => {
+            warn!("Invalid sample mapping request: {}", error);
+            StatusCode::BAD_REQUEST
+        }
+        MappingError::DatabaseError(e) => {
+            error!("Sample mapping database error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Create the rule set uploads of a dataset type are checked against
+pub async fn create_rule_set(
+    State(components): State<AppComponents>,
+    Json(request): Json<CreateDatasetRuleSet>,
+) -> Result<Json<ApiResponse<DatasetRuleSet>>, StatusCode> {
+    info!(
+        "Received request to create rule set for dataset type: {}",
+        request.dataset_type
+    );
+
+    match components
+        .spreadsheet_service
+        .create_rule_set(request)
+        .await
+    {
+        Ok(rule_set) => Ok(Json(ApiResponse::success(
+            rule_set,
+            "Rule set created successfully",
+        ))),
+        Err(e) => Err(rule_set_error_status(e)),
+    }
+}
+
+/// List dataset rule sets
+pub async fn list_rule_sets(
+    State(components): State<AppComponents>,
+) -> Result<Json<ApiResponse<Vec<DatasetRuleSet>>>, StatusCode> {
+    match components.spreadsheet_service.list_rule_sets().await {
+        Ok(rule_sets) => Ok(Json(ApiResponse::success(
+            rule_sets,
+            "Rule sets retrieved successfully",
+        ))),
+        Err(e) => {
+            error!("Failed to list rule sets: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Get a dataset rule set
+pub async fn get_rule_set(
+    State(components): State<AppComponents>,
+    Path(rule_set_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<DatasetRuleSet>>, StatusCode> {
+    match components
+        .spreadsheet_service
+        .get_rule_set(rule_set_id)
+        .await
+    {
+        Ok(rule_set) => Ok(Json(ApiResponse::success(
+            rule_set,
+            "Rule set retrieved successfully",
+        ))),
+        Err(e) => Err(rule_set_error_status(e)),
+    }
+}
+
+/// Delete a dataset rule set. Quality reports already stored are kept.
+pub async fn delete_rule_set(
+    State(components): State<AppComponents>,
+    Path(rule_set_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<u64>>, StatusCode> {
+    info!("Received request to delete rule set: {}", rule_set_id);
+
+    match components
+        .spreadsheet_service
+        .delete_rule_set(rule_set_id)
+        .await
+    {
+        Ok(0) => {
+            warn!("Rule set not found for deletion: {}", rule_set_id);
+            Err(StatusCode::NOT_FOUND)
+        }
+        Ok(rows_affected) => Ok(Json(ApiResponse::success(
+            rows_affected,
+            "Rule set deleted successfully",
+        ))),
+        Err(e) => {
+            error!("Failed to delete rule set {}: {}", rule_set_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Check an existing dataset against a rule set
+pub async fn validate_dataset(
+    State(components): State<AppComponents>,
+    Path(dataset_id): Path<Uuid>,
+    Json(request): Json<ValidateDatasetRequest>,
+) -> Result<Json<ApiResponse<QualityReport>>, StatusCode> {
+    info!(
+        "Received request to validate dataset {} with rule set {}",
+        dataset_id, request.rule_set_id
+    );
+
+    match components
+        .spreadsheet_service
+        .validate_dataset(dataset_id, request.rule_set_id)
+        .await
+    {
+        Ok(report) => Ok(Json(ApiResponse::success(
+            report,
+            "Dataset validated successfully",
+        ))),
+        Err(e) => Err(rule_set_error_status(e)),
+    }
+}
+
+/// Get the last quality report of a dataset
+pub async fn get_quality_report(
+    State(components): State<AppComponents>,
+    Path(dataset_id): Path<Uuid>,
+) -> Result<Json<ApiResponse<QualityReport>>, StatusCode> {
+    match components
+        .spreadsheet_service
+        .get_quality_report(dataset_id)
+        .await
+    {
+        Ok(Some(report)) => Ok(Json(ApiResponse::success(
+            report,
+            "Quality report retrieved successfully",
+        ))),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => Err(rule_set_error_status(e)),
+    }
+}
+
+fn rule_set_error_status(error: RuleSetError) -> StatusCode {
+    match error {
+        RuleSetError::RuleSetNotFound | RuleSetError::DatasetNotFound => StatusCode::NOT_FOUND,
+        RuleSetError::DuplicateDatasetType(_) => {
+            warn!("Rule set conflict: {}", error);
+            StatusCode::CONFLICT
+        }
+        RuleSetError::UnknownDatasetType(_) | RuleSetError::InvalidRuleSet(_) => {
+            warn!("Invalid rule set request: {}", error);
+            StatusCode::BAD_REQUEST
+        }
+        RuleSetError::DatabaseError(e) => {
+            error!("Rule set database error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
 /// Get dataset by ID
 pub async fn get_dataset(
     State(components): State<AppComponents>,
@@ -432,20 +1399,20 @@ pub async fn get_sheet_names(
                 None => {
                     warn!("Unsupported file type for file: {}", filename);
                     return Ok(Json(ApiResponse::error(
-                        "Unsupported file type. Only Excel files (xlsx, xls) are supported for sheet name detection.",
+                        "Unsupported file type. Only workbooks (xlsx, xls, ods) are supported for sheet name detection.",
                     )));
                 }
             };
 
-            // Only Excel files have multiple sheets
-            if file_type.to_lowercase() == "csv" {
+            // Only workbooks have multiple sheets
+            if matches!(file_type.as_str(), "csv" | "tsv" | "jsonl") {
                 return Ok(Json(ApiResponse::success(
                     vec!["Sheet1".to_string()],
-                    "CSV files have only one sheet",
+                    "Text files have only one sheet",
                 )));
             }
 
-            match service.get_excel_sheet_names(&file_data) {
+            match service.get_excel_sheet_names(&file_data, &file_type) {
                 Ok(sheet_names) => {
                     info!(
                         "Successfully retrieved {} sheet names from file: {}",
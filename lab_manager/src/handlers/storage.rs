@@ -1,7 +1,7 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::Json,
+    extract::{Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json},
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -9,7 +9,15 @@ use uuid::Uuid;
 use crate::{
     assembly::AppComponents,
     errors::api::ApiError,
-    models::storage::{StorageLocation, TemperatureZone, ContainerType},
+    models::storage::{
+        ContainerType, SampleCheckout, StorageLocation, TemperatureZone, TimeOutOfStorage,
+    },
+    repositories::storage_repository::{LocationUsageReconciliation, UpdateStorageLocation},
+    services::{
+        evacuation_planner::EvacuationPlan,
+        storage_management_service::{CheckInResult, EvacuationResult, StorageManagementError},
+        storage_reconciliation::{parse_box_scan_csv, BoxScanReconciliation},
+    },
 };
 
 /// Storage location information for API responses
@@ -99,6 +107,18 @@ pub struct StorageLocationUpdate {
     pub location_path: Option<String>,
 }
 
+impl From<UpdateStorageLocationRequest> for UpdateStorageLocation {
+    fn from(request: UpdateStorageLocationRequest) -> Self {
+        Self {
+            name: request.name,
+            description: request.description,
+            capacity: request.capacity,
+            is_active: request.is_active,
+            location_path: request.location_path,
+        }
+    }
+}
+
 impl From<UpdateStorageLocationRequest> for StorageLocationUpdate {
     fn from(request: UpdateStorageLocationRequest) -> Self {
         Self {
@@ -109,4 +129,264 @@ impl From<UpdateStorageLocationRequest> for StorageLocationUpdate {
             location_path: request.location_path,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Query parameters for box-scan reconciliation
+#[derive(Debug, Deserialize)]
+pub struct ReconcileScanParams {
+    #[serde(default)]
+    pub apply_corrections: bool,
+    pub reconciled_by: Option<String>,
+}
+
+/// Reconcile a flatbed rack scan (CSV of position to tube ID) against a storage location
+pub async fn reconcile_location_scan(
+    State(app): State<AppComponents>,
+    Path(location_id): Path<i32>,
+    Query(params): Query<ReconcileScanParams>,
+    mut multipart: Multipart,
+) -> Result<Json<BoxScanReconciliation>, ApiError> {
+    let mut scan_data = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("Failed to read multipart field: {}", e)))?
+    {
+        if field.name() == Some("file") {
+            let bytes = field
+                .bytes()
+                .await
+                .map_err(|e| ApiError::BadRequest(format!("Failed to read scan file: {}", e)))?;
+            scan_data = Some(bytes);
+        }
+    }
+
+    let scan_data =
+        scan_data.ok_or_else(|| ApiError::BadRequest("No scan file provided".to_string()))?;
+    let entries = parse_box_scan_csv(&scan_data).map_err(storage_error)?;
+    let reconciled_by = params
+        .reconciled_by
+        .unwrap_or_else(|| "system".to_string());
+
+    let result = app
+        .storage_management_service()
+        .reconcile_location_scan(location_id, &entries, params.apply_corrections, &reconciled_by)
+        .await
+        .map_err(storage_error)?;
+
+    Ok(Json(result))
+}
+
+/// Request structure for checking a sample out of storage
+#[derive(Debug, Deserialize)]
+pub struct CheckoutSampleRequest {
+    pub barcode: String,
+    pub checked_out_by: String,
+    pub due_back_at: chrono::DateTime<chrono::Utc>,
+    pub purpose: Option<String>,
+}
+
+/// Request structure for checking a sample back in
+#[derive(Debug, Deserialize)]
+pub struct CheckInSampleRequest {
+    pub barcode: String,
+    pub checked_in_by: String,
+    pub notes: Option<String>,
+}
+
+/// Query parameters for listing checkouts
+#[derive(Debug, Deserialize)]
+pub struct ListCheckoutsParams {
+    #[serde(default)]
+    pub overdue_only: bool,
+}
+
+/// Open checkout with its overdue flag
+#[derive(Debug, Serialize)]
+pub struct CheckoutInfo {
+    #[serde(flatten)]
+    pub checkout: SampleCheckout,
+    pub is_overdue: bool,
+}
+
+/// Check a sample out of storage, keeping its position reserved
+pub async fn checkout_sample(
+    State(app): State<AppComponents>,
+    Json(request): Json<CheckoutSampleRequest>,
+) -> Result<Json<SampleCheckout>, ApiError> {
+    let checkout = app
+        .storage_management_service()
+        .checkout_sample(
+            &request.barcode,
+            &request.checked_out_by,
+            request.due_back_at,
+            request.purpose,
+        )
+        .await
+        .map_err(storage_error)?;
+
+    Ok(Json(checkout))
+}
+
+/// Check a sample back into its reserved position
+pub async fn check_in_sample(
+    State(app): State<AppComponents>,
+    Json(request): Json<CheckInSampleRequest>,
+) -> Result<Json<CheckInResult>, ApiError> {
+    let result = app
+        .storage_management_service()
+        .check_in_sample(&request.barcode, &request.checked_in_by, request.notes)
+        .await
+        .map_err(storage_error)?;
+
+    Ok(Json(result))
+}
+
+/// List open checkouts, flagging overdue ones
+pub async fn list_checkouts(
+    State(app): State<AppComponents>,
+    Query(params): Query<ListCheckoutsParams>,
+) -> Result<Json<Vec<CheckoutInfo>>, ApiError> {
+    let now = chrono::Utc::now();
+    let checkouts = app
+        .storage_management_service()
+        .list_checkouts(params.overdue_only)
+        .await
+        .map_err(storage_error)?
+        .into_iter()
+        .map(|checkout| CheckoutInfo {
+            is_overdue: checkout.is_overdue(now),
+            checkout,
+        })
+        .collect();
+
+    Ok(Json(checkouts))
+}
+
+/// Get the cumulative time a sample has spent out of storage
+pub async fn get_time_out_of_storage(
+    State(app): State<AppComponents>,
+    Path(barcode): Path<String>,
+) -> Result<Json<TimeOutOfStorage>, ApiError> {
+    let summary = app
+        .storage_management_service()
+        .get_time_out_of_storage(&barcode)
+        .await
+        .map_err(storage_error)?;
+
+    Ok(Json(summary))
+}
+
+/// Request structure for planning a freezer evacuation
+#[derive(Debug, Default, Deserialize)]
+pub struct EvacuationPlanRequest {
+    pub target_location_ids: Option<Vec<i32>>,
+}
+
+/// Request structure for executing a reviewed evacuation plan
+#[derive(Debug, Deserialize)]
+pub struct ExecuteEvacuationRequest {
+    pub plan: EvacuationPlan,
+    pub moved_by: String,
+    pub reason: String,
+    #[serde(default)]
+    pub deactivate_source: bool,
+}
+
+/// Plan the relocation of every sample in a storage location
+pub async fn plan_evacuation(
+    State(app): State<AppComponents>,
+    Path(location_id): Path<i32>,
+    Json(request): Json<EvacuationPlanRequest>,
+) -> Result<Json<EvacuationPlan>, ApiError> {
+    let plan = app
+        .storage_management_service()
+        .plan_freezer_evacuation(location_id, request.target_location_ids)
+        .await
+        .map_err(storage_error)?;
+
+    Ok(Json(plan))
+}
+
+/// Render an evacuation plan as a printable CSV pick list
+pub async fn evacuation_pick_list(Json(plan): Json<EvacuationPlan>) -> impl IntoResponse {
+    let filename = format!(
+        "attachment; filename=\"evacuation_{}_pick_list.csv\"",
+        plan.source_location_id
+    );
+
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, filename),
+        ],
+        plan.pick_list_csv(),
+    )
+}
+
+/// Execute a reviewed evacuation plan as one batch of moves
+pub async fn execute_evacuation(
+    State(app): State<AppComponents>,
+    Json(request): Json<ExecuteEvacuationRequest>,
+) -> Result<Json<EvacuationResult>, ApiError> {
+    let result = app
+        .storage_management_service()
+        .execute_freezer_evacuation(
+            &request.plan,
+            &request.moved_by,
+            &request.reason,
+            request.deactivate_source,
+        )
+        .await
+        .map_err(storage_error)?;
+
+    Ok(Json(result))
+}
+
+/// Query parameters for usage reconciliation
+#[derive(Debug, Deserialize)]
+pub struct ReconcileUsageParams {
+    pub location_id: Option<i32>,
+}
+
+/// Recompute location usage from stored samples, correcting drift
+pub async fn reconcile_capacity_usage(
+    State(app): State<AppComponents>,
+    Query(params): Query<ReconcileUsageParams>,
+) -> Result<Json<Vec<LocationUsageReconciliation>>, ApiError> {
+    let report = app
+        .storage_management_service()
+        .reconcile_location_usage(params.location_id)
+        .await
+        .map_err(storage_error)?;
+
+    Ok(Json(report))
+}
+
+fn storage_error(error: StorageManagementError) -> ApiError {
+    match error {
+        StorageManagementError::LocationNotFound(_)
+        | StorageManagementError::SampleNotFound(_)
+        | StorageManagementError::BarcodeNotFound(_) => ApiError::NotFound,
+        StorageManagementError::SampleAlreadyCheckedOut(_)
+        | StorageManagementError::SampleNotCheckedOut(_)
+        | StorageManagementError::PositionReserved(_)
+        | StorageManagementError::SampleNotInStorage(_)
+        | StorageManagementError::EvacuationPlanStale(_) => ApiError::Conflict(error.to_string()),
+        StorageManagementError::InvalidScanFile(_)
+        | StorageManagementError::InvalidDueBackTime(_)
+        | StorageManagementError::LocationInactive(_)
+        | StorageManagementError::InsufficientCapacity { .. }
+        | StorageManagementError::IncompatibleTemperature { .. }
+        | StorageManagementError::InvalidStateTransition { .. } => {
+            ApiError::BadRequest(error.to_string())
+        }
+        StorageManagementError::DatabaseError(_)
+        | StorageManagementError::BarcodeGenerationError(_)
+        | StorageManagementError::EvacuationFailed(_) => {
+            ApiError::InternalServerError(error.to_string())
+        }
+    }
+}
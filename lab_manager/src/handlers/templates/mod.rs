@@ -1,16 +1,26 @@
 use axum::{
-    extract::{Multipart, Path, State},
-    http::StatusCode,
+    extract::{Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use serde::Deserialize;
 use serde_json::json;
 use uuid::Uuid;
 
 use crate::{
     assembly::AppComponents,
-    models::template::{CreateTemplate, ParsedTemplateResponse, TemplateResponse, UpdateTemplate},
+    models::template::{
+        ChangelogEntry, CreateTemplate, CreateTemplateVersion, ParsedTemplateResponse,
+        TemplateResponse, TemplateVersion, UpdateTemplate,
+    },
     repositories::{Repository, RepositoryFactory},
-    services::template_service::TemplateService,
+    sample_submission::Sample,
+    services::{
+        template_service::{TemplateService, TemplateVersionError, TemplateVersionService},
+        template_submission::SubmissionOutcome,
+        template_workbook::{build_submission_workbook, SubmissionWorkbook, DEFAULT_WORKBOOK_ROWS},
+    },
 };
 
 // Re-export types for handlers/mod.rs
@@ -247,7 +257,15 @@ pub async fn delete_template(
     template_service
         .delete_template(template_id)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(
+            |e| match e.as_database_error().and_then(|db| db.constraint()) {
+                Some("samples_template_version_id_fkey") => (
+                    StatusCode::CONFLICT,
+                    "Samples were submitted with a version of this template".to_string(),
+                ),
+                _ => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            },
+        )?;
 
     // Delete the physical file from storage
     let file_path = std::path::Path::new(&template.file_path);
@@ -284,3 +302,230 @@ pub async fn create_template(
         metadata: template.metadata,
     }))
 }
+
+/// Publish a new version of a template's field schema
+pub async fn publish_template_version(
+    State(state): State<AppComponents>,
+    Path(template_id): Path<Uuid>,
+    Json(request): Json<CreateTemplateVersion>,
+) -> Result<Json<TemplateVersion>, (StatusCode, String)> {
+    let version_repo = state.repositories.factory.template_version_repository();
+    let version_service = TemplateVersionService::new(version_repo);
+
+    version_service
+        .publish_version(template_id, request)
+        .await
+        .map(Json)
+        .map_err(version_error)
+}
+
+/// List the versions of a template, newest first
+pub async fn list_template_versions(
+    State(state): State<AppComponents>,
+    Path(template_id): Path<Uuid>,
+) -> Result<Json<Vec<TemplateVersion>>, (StatusCode, String)> {
+    let version_repo = state.repositories.factory.template_version_repository();
+    let version_service = TemplateVersionService::new(version_repo);
+
+    version_service
+        .list_versions(template_id)
+        .await
+        .map(Json)
+        .map_err(version_error)
+}
+
+/// Get the current version of a template
+pub async fn get_latest_template_version(
+    State(state): State<AppComponents>,
+    Path(template_id): Path<Uuid>,
+) -> Result<Json<TemplateVersion>, (StatusCode, String)> {
+    let version_repo = state.repositories.factory.template_version_repository();
+    let version_service = TemplateVersionService::new(version_repo);
+
+    version_service
+        .latest_version(template_id)
+        .await
+        .map(Json)
+        .map_err(version_error)
+}
+
+/// Get one version of a template by number
+pub async fn get_template_version(
+    State(state): State<AppComponents>,
+    Path((template_id, version_number)): Path<(Uuid, i32)>,
+) -> Result<Json<TemplateVersion>, (StatusCode, String)> {
+    let version_repo = state.repositories.factory.template_version_repository();
+    let version_service = TemplateVersionService::new(version_repo);
+
+    version_service
+        .get_version(template_id, version_number)
+        .await
+        .map(Json)
+        .map_err(version_error)
+}
+
+/// List the samples submitted with a version of a template
+pub async fn list_template_version_samples(
+    State(state): State<AppComponents>,
+    Path((template_id, version_number)): Path<(Uuid, i32)>,
+) -> Result<Json<Vec<Sample>>, (StatusCode, String)> {
+    let version_repo = state.repositories.factory.template_version_repository();
+    let version_service = TemplateVersionService::new(version_repo);
+
+    version_service
+        .list_version_samples(template_id, version_number)
+        .await
+        .map(Json)
+        .map_err(version_error)
+}
+
+/// Get the changelog of a template
+pub async fn get_template_changelog(
+    State(state): State<AppComponents>,
+    Path(template_id): Path<Uuid>,
+) -> Result<Json<Vec<ChangelogEntry>>, (StatusCode, String)> {
+    let version_repo = state.repositories.factory.template_version_repository();
+    let version_service = TemplateVersionService::new(version_repo);
+
+    version_service
+        .changelog(template_id)
+        .await
+        .map(Json)
+        .map_err(version_error)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WorkbookParams {
+    /// Version to generate the workbook from; the latest when missing
+    pub version: Option<i32>,
+    /// Data rows covered by the dropdowns and type checks
+    pub rows: Option<u32>,
+}
+
+/// Download a blank submission workbook for a version of a template
+pub async fn download_template_workbook(
+    State(state): State<AppComponents>,
+    Path(template_id): Path<Uuid>,
+    Query(params): Query<WorkbookParams>,
+) -> Result<Response, (StatusCode, String)> {
+    let template_repo = state.repositories.factory.template_repository();
+    let template_service = TemplateService::new(template_repo);
+    let version_repo = state.repositories.factory.template_version_repository();
+    let version_service = TemplateVersionService::new(version_repo);
+
+    let template = template_service
+        .get_template(template_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "Template not found".to_string()))?;
+    let version = match params.version {
+        Some(version_number) => {
+            version_service
+                .get_version(template_id, version_number)
+                .await
+        }
+        None => version_service.latest_version(template_id).await,
+    }
+    .map_err(version_error)?;
+
+    let workbook = build_submission_workbook(
+        &template,
+        &version,
+        params.rows.unwrap_or(DEFAULT_WORKBOOK_ROWS),
+    )
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(workbook_response(StatusCode::OK, workbook))
+}
+
+/// Upload a filled submission workbook. Samples are created when every row
+/// passes; otherwise the workbook comes back annotated with what to fix.
+pub async fn submit_template_workbook(
+    State(state): State<AppComponents>,
+    Path(template_id): Path<Uuid>,
+    mut multipart: Multipart,
+) -> Result<Response, (StatusCode, String)> {
+    let mut file_content = None;
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Failed to process multipart form: {}", e),
+        )
+    })? {
+        if field.name() == Some("file") {
+            let bytes = field.bytes().await.map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to read file: {}", e),
+                )
+            })?;
+            file_content = Some(bytes.to_vec());
+        }
+    }
+    let file_content = file_content.ok_or((StatusCode::BAD_REQUEST, "Missing file".to_string()))?;
+
+    let template_repo = state.repositories.factory.template_repository();
+    let template_service = TemplateService::new(template_repo);
+    let version_repo = state.repositories.factory.template_version_repository();
+    let version_service = TemplateVersionService::new(version_repo);
+
+    let template = template_service
+        .get_template(template_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "Template not found".to_string()))?;
+
+    match version_service
+        .submit_workbook(&template, file_content)
+        .await
+        .map_err(version_error)?
+    {
+        SubmissionOutcome::Accepted(receipt) => {
+            Ok((StatusCode::CREATED, Json(receipt)).into_response())
+        }
+        SubmissionOutcome::Rejected { issues, workbook } => {
+            let mut response = workbook_response(StatusCode::UNPROCESSABLE_ENTITY, workbook);
+            response.headers_mut().insert(
+                "x-submission-problems",
+                header::HeaderValue::from(issues.len()),
+            );
+            Ok(response)
+        }
+    }
+}
+
+fn workbook_response(status: StatusCode, workbook: SubmissionWorkbook) -> Response {
+    (
+        status,
+        [
+            (
+                header::CONTENT_TYPE,
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet".to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", workbook.filename),
+            ),
+        ],
+        workbook.bytes,
+    )
+        .into_response()
+}
+
+fn version_error(error: TemplateVersionError) -> (StatusCode, String) {
+    let status = match &error {
+        TemplateVersionError::TemplateNotFound | TemplateVersionError::VersionNotFound => {
+            StatusCode::NOT_FOUND
+        }
+        TemplateVersionError::InvalidFields(_)
+        | TemplateVersionError::InvalidWorkbook(_)
+        | TemplateVersionError::WrongTemplate => StatusCode::BAD_REQUEST,
+        TemplateVersionError::Unchanged(_)
+        | TemplateVersionError::Conflict
+        | TemplateVersionError::SamplesConflict => StatusCode::CONFLICT,
+        TemplateVersionError::DatabaseError(_) | TemplateVersionError::Workbook(_) => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+    (status, error.to_string())
+}
@@ -16,6 +16,7 @@ pub mod validation;
 
 use std::net::SocketAddr;
 use std::process;
+use std::time::Duration;
 
 use assembly::assemble_production_components;
 use middleware::validation::initialize_validation_regexes;
@@ -49,6 +50,15 @@ async fn main() {
         }
     };
 
+    // Start running scheduled reports
+    let poll_seconds = components.config.reports.schedule_poll_seconds;
+    if poll_seconds > 0 {
+        components
+            .report_schedules
+            .clone()
+            .spawn_scheduler(Duration::from_secs(poll_seconds));
+    }
+
     // Create the application router
     let app = create_app_router().with_state(components);
 
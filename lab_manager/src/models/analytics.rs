@@ -0,0 +1,76 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Width of the buckets dashboard trends are counted in. Weeks start on
+/// Monday; all buckets are in UTC.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalyticsInterval {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+/// Dashboard trend request. `project` and `sample_type` match the sample
+/// metadata keys of the same name.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AnalyticsQuery {
+    #[serde(default)]
+    pub interval: AnalyticsInterval,
+    /// First day counted; defaults to a range that suits the interval
+    pub from: Option<NaiveDate>,
+    /// Last day counted; defaults to today
+    pub to: Option<NaiveDate>,
+    pub project: Option<String>,
+    pub sample_type: Option<String>,
+}
+
+/// Samples a trend counts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnalyticsFilter {
+    pub project: Option<String>,
+    pub sample_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DashboardAnalytics {
+    pub interval: AnalyticsInterval,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub project: Option<String>,
+    pub sample_type: Option<String>,
+    pub buckets: Vec<AnalyticsBucket>,
+}
+
+/// Trends for one bucket. Status counts and storage usage are as of the
+/// end of the bucket; the rest count what happened during it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalyticsBucket {
+    pub start: NaiveDate,
+    pub samples_received: i64,
+    pub samples_by_status: BTreeMap<String, i64>,
+    /// Jobs that went into progress
+    pub sequencing_started: i64,
+    pub sequencing_completed: i64,
+    /// Storage is shared by every project, so it is never filtered
+    pub storage_used: i64,
+    pub storage_capacity: i64,
+    /// Percentage of capacity in use
+    pub storage_utilization: Option<f64>,
+    pub turnaround: TurnaroundStats,
+}
+
+/// Hours from receiving a sample to completing it, for samples completed in
+/// a bucket
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnaroundStats {
+    pub completed: i64,
+    pub p50_hours: Option<f64>,
+    pub p90_hours: Option<f64>,
+    pub p95_hours: Option<f64>,
+}
@@ -1,3 +1,6 @@
+pub mod analytics;
+pub mod report;
+pub mod shipment;
 pub mod spreadsheet;
 pub mod storage;
 pub mod template;
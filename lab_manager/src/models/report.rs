@@ -0,0 +1,401 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::models::user::UserRole;
+
+/// Tables and columns reports may read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseSchema {
+    pub tables: Vec<TableInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableInfo {
+    pub name: String,
+    pub columns: Vec<ColumnInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub data_type: String,
+    /// Postgres type name, used to cast filter values to the column's type
+    #[serde(skip_serializing, default)]
+    pub udt_name: String,
+    pub is_nullable: bool,
+    pub is_primary_key: bool,
+}
+
+impl DatabaseSchema {
+    pub fn table(&self, name: &str) -> Option<&TableInfo> {
+        self.tables.iter().find(|table| table.name == name)
+    }
+}
+
+impl TableInfo {
+    pub fn column(&self, name: &str) -> Option<&ColumnInfo> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+}
+
+impl ColumnInfo {
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self.udt_name.as_str(),
+            "int2" | "int4" | "int8" | "float4" | "float8" | "numeric"
+        )
+    }
+}
+
+/// A report written as a structured query rather than SQL. Fields are named
+/// `entity.field`; a bare field name refers to the main entity.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReportQuerySpec {
+    /// Table the report reads
+    pub entity: String,
+    #[serde(default)]
+    pub joins: Vec<ReportJoin>,
+    /// Columns to return. Grouped reports return their group-by fields
+    /// when this is empty.
+    #[serde(default)]
+    pub fields: Vec<FieldRef>,
+    #[serde(default)]
+    pub filters: Vec<ReportFilter>,
+    #[serde(default)]
+    pub group_by: Vec<FieldRef>,
+    #[serde(default)]
+    pub aggregates: Vec<ReportAggregate>,
+    #[serde(default)]
+    pub sort: Vec<ReportSort>,
+    pub limit: Option<i64>,
+}
+
+impl ReportQuerySpec {
+    pub fn is_grouped(&self) -> bool {
+        !self.group_by.is_empty() || !self.aggregates.is_empty()
+    }
+}
+
+/// A column of one of the report's entities
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "FieldRefInput")]
+pub struct FieldRef {
+    /// Defaults to the report's main entity
+    pub entity: Option<String>,
+    pub field: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FieldRefInput {
+    Name(String),
+    Ref {
+        entity: Option<String>,
+        field: String,
+    },
+}
+
+impl From<FieldRefInput> for FieldRef {
+    fn from(input: FieldRefInput) -> Self {
+        match input {
+            FieldRefInput::Name(name) => match name.split_once('.') {
+                Some((entity, field)) => Self {
+                    entity: Some(entity.to_string()),
+                    field: field.to_string(),
+                },
+                None => Self {
+                    entity: None,
+                    field: name,
+                },
+            },
+            FieldRefInput::Ref { entity, field } => Self { entity, field },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportJoinKind {
+    #[default]
+    Inner,
+    Left,
+}
+
+/// Join another entity on a pair of fields with equal values
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportJoin {
+    pub entity: String,
+    /// Field of an entity already in the report
+    pub left: FieldRef,
+    /// Field of the joined entity
+    pub right: FieldRef,
+    #[serde(default)]
+    pub kind: ReportJoinKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterOperator {
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    /// Case-insensitive substring match
+    Contains,
+    /// Case-insensitive prefix match
+    StartsWith,
+    In,
+    NotIn,
+    /// Inclusive; the value is a two-element array
+    Between,
+    IsNull,
+    IsNotNull,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportFilter {
+    pub field: FieldRef,
+    pub op: FilterOperator,
+    /// Unused by `is_null` and `is_not_null`
+    #[serde(default)]
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportAggregateFunction {
+    Count,
+    CountDistinct,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl ReportAggregateFunction {
+    pub fn name(self) -> &'static str {
+        match self {
+            ReportAggregateFunction::Count => "count",
+            ReportAggregateFunction::CountDistinct => "count_distinct",
+            ReportAggregateFunction::Sum => "sum",
+            ReportAggregateFunction::Avg => "avg",
+            ReportAggregateFunction::Min => "min",
+            ReportAggregateFunction::Max => "max",
+        }
+    }
+}
+
+/// Count counts rows, or non-null values when given a field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportAggregate {
+    pub function: ReportAggregateFunction,
+    pub field: Option<FieldRef>,
+    /// Output column name
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Order by an output column: a selected field or an aggregate's name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportSort {
+    pub field: String,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+/// Who besides its owner can see and run a saved report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "report_visibility", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ReportVisibility {
+    #[default]
+    Private,
+    /// Users with the report's shared role
+    Role,
+    Everyone,
+}
+
+/// The query of a saved report. Only administrators write SQL reports.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SavedReportQuery {
+    /// Filters take a parameter's value when their value is
+    /// `{"param": "<name>"}`
+    Structured(ReportQuerySpec),
+    /// Parameters are referenced as `{{name}}`, or `{{name.start}}` and
+    /// `{{name.end}}` for date ranges, and bound as text. A range's end is
+    /// the last moment of its end date.
+    Sql(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportParameterKind {
+    /// `{"start": "2025-01-01", "end": "2025-01-31"}`, both inclusive, or
+    /// `{"last_days": 30}`
+    DateRange,
+    /// A project name
+    Project,
+    /// A status, or a list of statuses
+    Status,
+}
+
+/// A value supplied when a saved report runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportParameter {
+    pub name: String,
+    pub label: Option<String>,
+    pub kind: ReportParameterKind,
+    #[serde(default)]
+    pub required: bool,
+    /// Used when a run supplies no value
+    #[serde(default)]
+    pub default: Option<serde_json::Value>,
+}
+
+/// A stored report definition
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SavedReport {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub category: Option<String>,
+    #[sqlx(json)]
+    pub query: SavedReportQuery,
+    #[sqlx(json)]
+    pub parameters: Vec<ReportParameter>,
+    /// Unset for system reports
+    pub owner_id: Option<Uuid>,
+    pub visibility: ReportVisibility,
+    pub shared_role: Option<UserRole>,
+    /// Built-in reports, which cannot be changed
+    pub is_system: bool,
+    pub system_key: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Definition of a new saved report; updates replace the whole definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSavedReport {
+    pub name: String,
+    pub description: Option<String>,
+    pub category: Option<String>,
+    pub query: SavedReportQuery,
+    #[serde(default)]
+    pub parameters: Vec<ReportParameter>,
+    #[serde(default)]
+    pub visibility: ReportVisibility,
+    /// Required when the report is shared with a role
+    pub shared_role: Option<UserRole>,
+}
+
+/// File format of a report artifact
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "report_format", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Csv,
+    Xlsx,
+}
+
+impl ReportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "text/csv; charset=utf-8",
+            ReportFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "csv",
+            ReportFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "report_run_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ReportRunStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// A saved report run on a cron schedule and emailed to its recipients
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ReportSchedule {
+    pub id: Uuid,
+    pub report_id: Uuid,
+    pub owner_id: Uuid,
+    /// Five-field cron expression or a shortcut such as `@weekly`, in UTC
+    pub cron_expression: String,
+    #[sqlx(json)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    pub format: ReportFormat,
+    pub recipients: Vec<String>,
+    pub enabled: bool,
+    /// Unset while the schedule is disabled
+    pub next_run_at: Option<DateTime<Utc>>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_status: Option<ReportRunStatus>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Definition of a new schedule; updates replace the whole definition
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateReportSchedule {
+    pub report_id: Uuid,
+    pub cron_expression: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub format: ReportFormat,
+    /// Email addresses the report is sent to; the artifact is only stored
+    /// when empty
+    #[serde(default)]
+    pub recipients: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// One run of a schedule
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ReportRun {
+    pub id: Uuid,
+    pub schedule_id: Uuid,
+    pub report_id: Uuid,
+    pub status: ReportRunStatus,
+    /// Started from the API rather than by the scheduler
+    pub manual: bool,
+    pub row_count: Option<i32>,
+    pub truncated: bool,
+    #[serde(skip_serializing)]
+    pub artifact_path: Option<String>,
+    pub artifact_size: Option<i64>,
+    pub delivered_to: Vec<String>,
+    pub error_message: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
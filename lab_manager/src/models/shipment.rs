@@ -0,0 +1,137 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Whether a shipment leaves this lab or arrives at it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "shipment_direction", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ShipmentDirection {
+    Outbound,
+    Inbound,
+}
+
+/// Shipment lifecycle status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "shipment_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ShipmentStatus {
+    Prepared,
+    Shipped,
+    Received,
+    Cancelled,
+}
+
+impl ShipmentStatus {
+    pub fn can_transition_to(&self, new_status: ShipmentStatus) -> bool {
+        use ShipmentStatus::*;
+        matches!(
+            (self, new_status),
+            (Prepared, Shipped) | (Shipped, Received) | (Prepared, Cancelled)
+        )
+    }
+}
+
+/// Temperature conditions a shipment travels under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "shipping_condition", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ShippingCondition {
+    DryIce,
+    WetIce,
+    Ambient,
+    LiquidNitrogen,
+}
+
+impl ShippingCondition {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ShippingCondition::DryIce => "Dry ice",
+            ShippingCondition::WetIce => "Wet ice",
+            ShippingCondition::Ambient => "Ambient",
+            ShippingCondition::LiquidNitrogen => "Liquid nitrogen dry shipper",
+        }
+    }
+}
+
+/// Shipment header
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Shipment {
+    pub id: Uuid,
+    pub shipment_number: String,
+    /// Shipment number assigned by the sender of an imported manifest
+    pub external_reference: Option<String>,
+    pub direction: ShipmentDirection,
+    pub origin: String,
+    pub destination: String,
+    pub carrier: Option<String>,
+    pub tracking_number: Option<String>,
+    pub shipping_condition: ShippingCondition,
+    pub status: ShipmentStatus,
+    pub notes: Option<String>,
+    pub created_by: String,
+    pub shipped_by: Option<String>,
+    pub shipped_at: Option<DateTime<Utc>>,
+    pub received_by: Option<String>,
+    pub received_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Manifest line of a shipment
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ShipmentItem {
+    pub id: i32,
+    pub shipment_id: Uuid,
+    pub sample_id: Option<Uuid>,
+    pub barcode: String,
+    pub volume_ul: Option<f64>,
+    pub container_type: Option<String>,
+    pub notes: Option<String>,
+    pub received: bool,
+    pub received_condition: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Shipment together with its manifest
+#[derive(Debug, Clone, Serialize)]
+pub struct ShipmentWithItems {
+    #[serde(flatten)]
+    pub shipment: Shipment,
+    pub items: Vec<ShipmentItem>,
+}
+
+/// Data for creating a shipment
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateShipment {
+    pub shipment_number: Option<String>,
+    pub external_reference: Option<String>,
+    pub direction: ShipmentDirection,
+    pub origin: String,
+    pub destination: String,
+    pub carrier: Option<String>,
+    pub tracking_number: Option<String>,
+    pub shipping_condition: ShippingCondition,
+    pub notes: Option<String>,
+    pub created_by: String,
+    pub items: Vec<CreateShipmentItem>,
+}
+
+/// Data for a manifest line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CreateShipmentItem {
+    pub sample_id: Option<Uuid>,
+    pub barcode: String,
+    pub volume_ul: Option<f64>,
+    pub container_type: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// Receipt details for a single manifest line
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReceivedItem {
+    pub barcode: String,
+    pub condition: Option<String>,
+    pub position: Option<String>,
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
 use std::collections::HashMap;
@@ -21,22 +21,54 @@ pub struct SpreadsheetDataset {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub metadata: serde_json::Value,
+    pub processed_rows: i32,
+    pub progress_percent: Option<f32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "varchar", rename_all = "snake_case")]
 pub enum UploadStatus {
+    Queued,
     Processing,
     Completed,
     Failed,
 }
 
+/// Ingestion progress of a dataset, for polling background uploads
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadProgress {
+    pub dataset_id: Uuid,
+    pub status: UploadStatus,
+    pub processed_rows: i32,
+    pub total_rows: Option<i32>,
+    pub progress_percent: Option<f32>,
+    pub error_message: Option<String>,
+}
+
+impl From<&SpreadsheetDataset> for UploadProgress {
+    fn from(dataset: &SpreadsheetDataset) -> Self {
+        Self {
+            dataset_id: dataset.id,
+            status: dataset.upload_status.clone(),
+            processed_rows: dataset.processed_rows,
+            // CSV row counts are only known once the whole file has been read
+            total_rows: (dataset.total_rows > 0
+                || dataset.upload_status == UploadStatus::Completed)
+                .then_some(dataset.total_rows),
+            progress_percent: dataset.progress_percent,
+            error_message: dataset.error_message.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
 pub struct SpreadsheetRecord {
     pub id: Uuid,
     pub dataset_id: Uuid,
     pub row_number: i32,
     pub row_data: serde_json::Value,
+    /// Values of typed columns, keyed by header
+    pub typed_data: serde_json::Value,
     pub search_text: Option<String>,
     pub created_at: DateTime<Utc>,
 }
@@ -47,6 +79,8 @@ pub struct SpreadsheetSearchRecord {
     pub dataset_id: Uuid,
     pub row_number: i32,
     pub row_data: serde_json::Value,
+    /// Values of typed columns, keyed by header
+    pub typed_data: serde_json::Value,
     pub search_text: Option<String>,
     pub created_at: DateTime<Utc>,
     pub original_filename: String,
@@ -54,6 +88,14 @@ pub struct SpreadsheetSearchRecord {
     pub dataset_filename: String,
 }
 
+/// Original column order of a dataset
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DatasetColumns {
+    pub id: Uuid,
+    pub original_filename: String,
+    pub column_headers: Vec<String>,
+}
+
 #[derive(Debug, Deserialize, Clone, Serialize)]
 pub struct CreateSpreadsheetDataset {
     pub filename: String,
@@ -73,7 +115,7 @@ pub struct CreateSpreadsheetRecord {
     pub row_data: serde_json::Value,
 }
 
-#[derive(Debug, Deserialize, Clone, Serialize)]
+#[derive(Debug, Deserialize, Clone, Default, Serialize)]
 pub struct SpreadsheetSearchQuery {
     pub search_term: Option<String>,
     pub dataset_id: Option<Uuid>,
@@ -81,10 +123,436 @@ pub struct SpreadsheetSearchQuery {
     pub pool_filter: Option<String>,
     pub sample_filter: Option<String>,
     pub project_filter: Option<String>,
+    /// Inclusive bounds on typed numeric or date columns, keyed by column
+    #[serde(default)]
+    pub range_filters: Option<HashMap<String, RangeFilter>>,
+    /// Exact matches against any of the listed values, keyed by column
+    #[serde(default)]
+    pub in_filters: Option<HashMap<String, Vec<String>>>,
     pub limit: Option<i64>,
     pub offset: Option<i64>,
 }
 
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, Serialize)]
+pub struct RangeFilter {
+    pub min: Option<RangeBound>,
+    pub max: Option<RangeBound>,
+}
+
+/// A range filter bound. Numbers match numeric columns and timestamps match
+/// date columns; rows where the column has no typed value never match.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum RangeBound {
+    Number(f64),
+    Date(NaiveDateTime),
+}
+
+impl RangeBound {
+    /// Parse a bound from a query string value. Date-only upper bounds cover
+    /// the whole day.
+    pub fn parse(raw: &str, upper: bool) -> Option<Self> {
+        let raw = raw.trim();
+        if let Ok(number) = raw.parse::<f64>() {
+            return number.is_finite().then_some(RangeBound::Number(number));
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+            let time = if upper {
+                NaiveTime::from_hms_opt(23, 59, 59)?
+            } else {
+                NaiveTime::MIN
+            };
+            return Some(RangeBound::Date(date.and_time(time)));
+        }
+        if let Ok(timestamp) = DateTime::parse_from_rfc3339(raw) {
+            return Some(RangeBound::Date(timestamp.naive_utc()));
+        }
+        ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(raw, format).ok())
+            .map(RangeBound::Date)
+    }
+}
+
+/// Cross-dataset aggregation over spreadsheet records. With no grouping,
+/// aggregates or pivot, the selected columns are returned row by row.
+#[derive(Debug, Deserialize, Clone, Default, Serialize)]
+pub struct AggregateQuery {
+    /// Narrows the records read, as for a search
+    #[serde(default)]
+    pub filter: SpreadsheetSearchQuery,
+    /// Datasets to read; all datasets when empty
+    #[serde(default)]
+    pub dataset_ids: Vec<Uuid>,
+    pub join: Option<JoinSpec>,
+    #[serde(default)]
+    pub group_by: Vec<ColumnRef>,
+    #[serde(default)]
+    pub aggregates: Vec<AggregateSpec>,
+    pub pivot: Option<PivotSpec>,
+    /// Columns returned when nothing is aggregated
+    #[serde(default)]
+    pub columns: Vec<ColumnRef>,
+    pub limit: Option<i64>,
+}
+
+impl AggregateQuery {
+    /// Whether rows are grouped rather than returned one per record
+    pub fn is_grouped(&self) -> bool {
+        !self.group_by.is_empty() || !self.aggregates.is_empty() || self.pivot.is_some()
+    }
+}
+
+/// Which dataset of a join a column is read from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatasetSide {
+    #[default]
+    Left,
+    Right,
+}
+
+impl DatasetSide {
+    fn alias(self) -> &'static str {
+        match self {
+            DatasetSide::Left => "sr",
+            DatasetSide::Right => "jr",
+        }
+    }
+}
+
+/// A dataset column. A bare column name refers to the left dataset.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "ColumnRefInput")]
+pub struct ColumnRef {
+    pub column: String,
+    pub side: DatasetSide,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColumnRefInput {
+    Name(String),
+    Ref {
+        column: String,
+        #[serde(default)]
+        side: DatasetSide,
+    },
+}
+
+impl From<ColumnRefInput> for ColumnRef {
+    fn from(input: ColumnRefInput) -> Self {
+        match input {
+            ColumnRefInput::Name(column) => Self {
+                column,
+                side: DatasetSide::Left,
+            },
+            ColumnRefInput::Ref { column, side } => Self { column, side },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Mean,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    pub fn name(self) -> &'static str {
+        match self {
+            AggregateFunction::Count => "count",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Mean => "mean",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+        }
+    }
+
+    fn sql_name(self) -> &'static str {
+        match self {
+            AggregateFunction::Mean => "avg",
+            other => other.name(),
+        }
+    }
+}
+
+/// Count counts records, or non-empty cells when given a column. The other
+/// functions read the typed numeric values of a column.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AggregateSpec {
+    pub function: AggregateFunction,
+    pub column: Option<ColumnRef>,
+    /// Output column name
+    pub alias: Option<String>,
+}
+
+/// Spread the aggregates across one column per value of `column`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PivotSpec {
+    pub column: ColumnRef,
+    /// Values to turn into columns; every value present when omitted
+    pub values: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JoinKind {
+    #[default]
+    Inner,
+    Left,
+}
+
+/// Match records of the queried datasets to records of another dataset
+/// with the same key value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JoinSpec {
+    pub dataset_id: Uuid,
+    pub left_key: String,
+    /// Defaults to `left_key`
+    pub right_key: Option<String>,
+    #[serde(default)]
+    pub kind: JoinKind,
+}
+
+impl JoinSpec {
+    pub fn right_key(&self) -> &str {
+        self.right_key.as_deref().unwrap_or(&self.left_key)
+    }
+}
+
+/// Values bound to the filters of a search, in placeholder order
+enum SearchBind {
+    Uuid(Uuid),
+    UuidList(Vec<Uuid>),
+    Text(String),
+    Number(f64),
+    Timestamp(NaiveDateTime),
+    TextList(Vec<String>),
+}
+
+/// WHERE conditions of a record search over `spreadsheet_records sr` joined
+/// to `spreadsheet_datasets sd`, with their bind values numbered from `$1`
+struct SearchFilter {
+    conditions: Vec<String>,
+    binds: Vec<SearchBind>,
+}
+
+impl SearchFilter {
+    fn new(query: &SpreadsheetSearchQuery) -> Self {
+        let mut filter = Self {
+            conditions: Vec::new(),
+            binds: Vec::new(),
+        };
+
+        if let Some(dataset_id) = query.dataset_id {
+            let p = filter.push(SearchBind::Uuid(dataset_id));
+            filter.conditions.push(format!("sr.dataset_id = ${}", p));
+        }
+
+        if let Some(search_term) = &query.search_term {
+            let p = filter.push(SearchBind::Text(search_term.clone()));
+            filter.conditions.push(format!(
+                "sr.search_vector @@ plainto_tsquery('english', ${})",
+                p
+            ));
+        }
+
+        // Pool/sample/project filters with smart column detection
+        if let Some(pool_filter) = &query.pool_filter {
+            let p = filter.push(SearchBind::Text(format!("%{}%", pool_filter)));
+            filter.conditions.push(format!(
+                "(sr.row_data ->> 'Pool' ILIKE ${0} OR sr.row_data ->> 'Pool_ID' ILIKE ${0} OR sr.row_data ->> 'PoolID' ILIKE ${0})",
+                p
+            ));
+        }
+
+        if let Some(sample_filter) = &query.sample_filter {
+            let p = filter.push(SearchBind::Text(format!("%{}%", sample_filter)));
+            filter.conditions.push(format!(
+                "(sr.row_data ->> 'Sample' ILIKE ${0} OR sr.row_data ->> 'Sample_ID' ILIKE ${0} OR sr.row_data ->> 'SampleID' ILIKE ${0} OR sr.row_data ->> 'Sample_Name' ILIKE ${0})",
+                p
+            ));
+        }
+
+        if let Some(project_filter) = &query.project_filter {
+            let p = filter.push(SearchBind::Text(format!("%{}%", project_filter)));
+            filter.conditions.push(format!(
+                "(sr.row_data ->> 'Project' ILIKE ${0} OR sr.row_data ->> 'Project_ID' ILIKE ${0} OR sr.row_data ->> 'ProjectID' ILIKE ${0} OR sr.row_data ->> 'Project_Name' ILIKE ${0})",
+                p
+            ));
+        }
+
+        // Column names are bound rather than spliced into the SQL
+        if let Some(filters) = &query.column_filters {
+            for (column, value) in filters {
+                let column_param = filter.push(SearchBind::Text(column.clone()));
+                let value_param = filter.push(SearchBind::Text(format!("%{}%", value)));
+                filter.conditions.push(format!(
+                    "sr.row_data ->> ${} ILIKE ${}",
+                    column_param, value_param
+                ));
+            }
+        }
+
+        if let Some(ranges) = &query.range_filters {
+            for (column, range) in ranges {
+                let bounds = [(">=", range.min), ("<=", range.max)];
+                for (operator, bound) in bounds {
+                    let Some(bound) = bound else { continue };
+                    let column_param = filter.push(SearchBind::Text(column.clone()));
+                    let typed_value = match bound {
+                        RangeBound::Number(_) => format!(
+                            "CASE WHEN jsonb_typeof(sr.typed_data -> ${0}) = 'number' THEN (sr.typed_data ->> ${0})::float8 END",
+                            column_param
+                        ),
+                        RangeBound::Date(_) => format!(
+                            "CASE WHEN sd.metadata -> 'column_types' -> ${0} ->> 'type' IN ('date', 'date_time') THEN (sr.typed_data ->> ${0})::timestamp END",
+                            column_param
+                        ),
+                    };
+                    let bound_param = filter.push(match bound {
+                        RangeBound::Number(value) => SearchBind::Number(value),
+                        RangeBound::Date(value) => SearchBind::Timestamp(value),
+                    });
+                    filter
+                        .conditions
+                        .push(format!("({}) {} ${}", typed_value, operator, bound_param));
+                }
+            }
+        }
+
+        if let Some(in_filters) = &query.in_filters {
+            for (column, values) in in_filters {
+                let column_param = filter.push(SearchBind::Text(column.clone()));
+                let values_param = filter.push(SearchBind::TextList(values.clone()));
+                filter.conditions.push(format!(
+                    "sr.row_data ->> ${} = ANY(${})",
+                    column_param, values_param
+                ));
+            }
+        }
+
+        filter
+    }
+
+    /// Add a bind value, returning its placeholder number
+    fn push(&mut self, bind: SearchBind) -> usize {
+        self.binds.push(bind);
+        self.binds.len()
+    }
+
+    /// Placeholder number of the next value bound after the filters
+    fn next_param(&self) -> usize {
+        self.binds.len() + 1
+    }
+
+    fn where_clause(&self) -> String {
+        if self.conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", self.conditions.join(" AND "))
+        }
+    }
+
+    fn bind<'q, O>(
+        &'q self,
+        mut query: sqlx::query::QueryAs<'q, sqlx::Postgres, O, sqlx::postgres::PgArguments>,
+    ) -> sqlx::query::QueryAs<'q, sqlx::Postgres, O, sqlx::postgres::PgArguments> {
+        for bind in &self.binds {
+            query = match bind {
+                SearchBind::Uuid(value) => query.bind(value),
+                SearchBind::UuidList(values) => query.bind(values),
+                SearchBind::Text(value) => query.bind(value),
+                SearchBind::Number(value) => query.bind(value),
+                SearchBind::Timestamp(value) => query.bind(value),
+                SearchBind::TextList(values) => query.bind(values),
+            };
+        }
+        query
+    }
+}
+
+/// FROM and WHERE clauses of an aggregation: the filtered records as `sr`,
+/// their dataset as `sd` and any joined records as `jr`
+fn aggregate_source(query: &AggregateQuery, filter: &mut SearchFilter) -> String {
+    if !query.dataset_ids.is_empty() {
+        let p = filter.push(SearchBind::UuidList(query.dataset_ids.clone()));
+        filter
+            .conditions
+            .push(format!("sr.dataset_id = ANY(${})", p));
+    }
+
+    let join = match &query.join {
+        Some(join) => {
+            let kind = match join.kind {
+                JoinKind::Inner => "JOIN",
+                JoinKind::Left => "LEFT JOIN",
+            };
+            let dataset = filter.push(SearchBind::Uuid(join.dataset_id));
+            let right_key = filter.push(SearchBind::Text(join.right_key().to_string()));
+            let left_key = filter.push(SearchBind::Text(join.left_key.clone()));
+            format!(
+                "{} spreadsheet_records jr ON jr.dataset_id = ${} AND jr.row_data ->> ${} = sr.row_data ->> ${}",
+                kind, dataset, right_key, left_key
+            )
+        }
+        None => String::new(),
+    };
+
+    format!(
+        "FROM spreadsheet_records sr JOIN spreadsheet_datasets sd ON sr.dataset_id = sd.id {} {}",
+        join,
+        filter.where_clause()
+    )
+}
+
+fn column_text(filter: &mut SearchFilter, column: &ColumnRef) -> String {
+    let p = filter.push(SearchBind::Text(column.column.clone()));
+    format!("{}.row_data ->> ${}", column.side.alias(), p)
+}
+
+/// Cell text with empty cells read as NULL, for grouping and pivoting
+fn column_value(filter: &mut SearchFilter, column: &ColumnRef) -> String {
+    format!("NULLIF({}, '')", column_text(filter, column))
+}
+
+fn column_number(filter: &mut SearchFilter, column: &ColumnRef) -> String {
+    let p = filter.push(SearchBind::Text(column.column.clone()));
+    format!(
+        "CASE WHEN jsonb_typeof({0}.typed_data -> ${1}) = 'number' THEN ({0}.typed_data ->> ${1})::float8 END",
+        column.side.alias(),
+        p
+    )
+}
+
+fn aggregate_expr(
+    filter: &mut SearchFilter,
+    spec: &AggregateSpec,
+    condition: Option<&str>,
+) -> String {
+    let filter_clause = condition
+        .map(|condition| format!(" FILTER (WHERE {})", condition))
+        .unwrap_or_default();
+    match (spec.function, &spec.column) {
+        (AggregateFunction::Count, None) => format!("count(*){}", filter_clause),
+        (AggregateFunction::Count, Some(column)) => {
+            format!("count({}){}", column_value(filter, column), filter_clause)
+        }
+        (function, Some(column)) => format!(
+            "{}({}){}",
+            function.sql_name(),
+            column_number(filter, column),
+            filter_clause
+        ),
+        (_, None) => "NULL::float8".to_string(),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SpreadsheetSearchResult {
     pub records: Vec<SpreadsheetSearchRecord>,
@@ -93,6 +561,292 @@ pub struct SpreadsheetSearchResult {
     pub available_filters: Option<AvailableFilters>,
 }
 
+/// Ranked full-text search across datasets
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct RankedSearchQuery {
+    /// Postgres `tsquery` expression to match
+    pub tsquery: String,
+    /// Restrict to these datasets; all datasets when unset
+    pub dataset_ids: Option<Vec<Uuid>>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RankedSearchRecord {
+    pub id: Uuid,
+    pub dataset_id: Uuid,
+    pub row_number: i32,
+    pub row_data: serde_json::Value,
+    pub original_filename: String,
+    pub rank: f32,
+    /// Cells that matched, with the matching terms highlighted
+    #[sqlx(json)]
+    pub matches: Vec<CellMatch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CellMatch {
+    pub column: String,
+    pub snippet: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DatasetFacet {
+    pub dataset_id: Uuid,
+    pub original_filename: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ColumnFacet {
+    pub column_name: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RankedSearchResult {
+    pub records: Vec<RankedSearchRecord>,
+    pub total_count: i64,
+    pub dataset_facets: Vec<DatasetFacet>,
+    pub column_facets: Vec<ColumnFacet>,
+}
+
+/// Datasets that are successive uploads of the same sheet
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DatasetSeries {
+    pub id: Uuid,
+    pub name: String,
+    /// Column whose value identifies a row across versions
+    pub identity_column: String,
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct CreateDatasetSeries {
+    pub name: String,
+    pub identity_column: String,
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SeriesVersion {
+    pub series_id: Uuid,
+    pub version: i32,
+    pub dataset_id: Uuid,
+    pub original_filename: String,
+    pub upload_status: UploadStatus,
+    pub total_rows: i32,
+    pub added_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatasetSeriesWithVersions {
+    #[serde(flatten)]
+    pub series: DatasetSeries,
+    pub versions: Vec<SeriesVersion>,
+}
+
+/// A row identity present in either of two versions whose data differs
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct VersionRowPair {
+    pub key: String,
+    pub from_row_number: Option<i32>,
+    pub from_row: Option<serde_json::Value>,
+    pub to_row_number: Option<i32>,
+    pub to_row: Option<serde_json::Value>,
+}
+
+/// Row counts behind a version diff
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct VersionMatchCounts {
+    /// Identities present in both versions
+    pub matched: i64,
+    /// Rows in either version with no identity value
+    pub unkeyed: i64,
+}
+
+/// Where a mapped column value goes on the sample
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MappingTarget {
+    Name,
+    Barcode,
+    Location,
+    /// A key in the sample metadata
+    Metadata(String),
+}
+
+/// Step applied to a cell value before it is stored on the sample
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ValueTransform {
+    Trim,
+    Uppercase,
+    Lowercase,
+    Prefix {
+        value: String,
+    },
+    Replace {
+        from: String,
+        to: String,
+    },
+    /// Parse as a number
+    Number,
+    /// Parse a quantity and express it in `to`. A unit written in the cell
+    /// wins over `from`, which is assumed when the cell has none.
+    ConvertUnit {
+        from: String,
+        to: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldMapping {
+    pub column: String,
+    pub target: MappingTarget,
+    #[serde(default)]
+    pub transforms: Vec<ValueTransform>,
+    /// Used when the cell is missing or empty
+    pub default: Option<String>,
+}
+
+/// A check applied to the rows of an uploaded dataset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DatasetRule {
+    /// The column must exist and every cell in it must be filled
+    RequiredColumn { column: String },
+    AllowedValues {
+        column: String,
+        values: Vec<String>,
+        #[serde(default)]
+        ignore_case: bool,
+    },
+    /// Filled cells must match the whole pattern
+    Pattern { column: String, pattern: String },
+    /// Filled cells must not repeat
+    Unique { column: String },
+    /// Filled cells must be numbers within the bounds
+    NumericRange {
+        column: String,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+}
+
+impl DatasetRule {
+    pub fn column(&self) -> &str {
+        match self {
+            DatasetRule::RequiredColumn { column }
+            | DatasetRule::AllowedValues { column, .. }
+            | DatasetRule::Pattern { column, .. }
+            | DatasetRule::Unique { column }
+            | DatasetRule::NumericRange { column, .. } => column,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleSeverity {
+    #[default]
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetRuleSpec {
+    #[serde(flatten)]
+    pub rule: DatasetRule,
+    #[serde(default)]
+    pub severity: RuleSeverity,
+}
+
+/// What happens to an upload whose rows break error-level rules
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "varchar", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ViolationAction {
+    #[default]
+    Reject,
+    /// Keep the dataset and report the errors as warnings
+    Warn,
+}
+
+/// Validation rules for uploads of one dataset type
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct DatasetRuleSet {
+    pub id: Uuid,
+    pub name: String,
+    pub dataset_type: String,
+    pub description: Option<String>,
+    #[sqlx(json)]
+    pub rules: Vec<DatasetRuleSpec>,
+    pub on_violation: ViolationAction,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct CreateDatasetRuleSet {
+    pub name: String,
+    pub dataset_type: String,
+    pub description: Option<String>,
+    pub rules: Vec<DatasetRuleSpec>,
+    #[serde(default)]
+    pub on_violation: ViolationAction,
+    pub created_by: Option<String>,
+}
+
+/// Reusable binding of dataset columns to sample fields
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SampleMappingProfile {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    #[sqlx(json)]
+    pub mappings: Vec<FieldMapping>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct CreateSampleMappingProfile {
+    pub name: String,
+    pub description: Option<String>,
+    pub mappings: Vec<FieldMapping>,
+    pub created_by: Option<String>,
+}
+
+/// A sample created from a dataset row
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LinkedSample {
+    pub record_id: Uuid,
+    pub row_number: i32,
+    pub sample_id: Uuid,
+    pub name: String,
+    pub barcode: String,
+    pub profile_id: Option<Uuid>,
+    pub linked_at: DateTime<Utc>,
+}
+
+/// A sample to create from a dataset row
+#[derive(Debug, Clone)]
+pub struct RecordSample {
+    pub record_id: Uuid,
+    pub name: String,
+    pub barcode: String,
+    pub location: String,
+    pub metadata: serde_json::Value,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AvailableFilters {
     pub pools: Vec<String>,
@@ -142,314 +896,1064 @@ pub struct SpreadsheetDataManager {
     pool: PgPool,
 }
 
-impl SpreadsheetDataManager {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+impl SpreadsheetDataManager {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create_dataset(
+        &self,
+        dataset: CreateSpreadsheetDataset,
+    ) -> Result<SpreadsheetDataset, sqlx::Error> {
+        sqlx::query_as::<_, SpreadsheetDataset>(
+            r#"
+            INSERT INTO spreadsheet_datasets (filename, original_filename, file_type, file_size, sheet_name, column_headers, uploaded_by, metadata)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, filename, original_filename, file_type, file_size, sheet_name, total_rows, total_columns, column_headers, upload_status, error_message, uploaded_by, created_at, updated_at, metadata, processed_rows, progress_percent
+            "#,
+        )
+        .bind(&dataset.filename)
+        .bind(&dataset.original_filename)
+        .bind(&dataset.file_type)
+        .bind(dataset.file_size)
+        .bind(dataset.sheet_name.as_deref())
+        .bind(&dataset.column_headers)
+        .bind(dataset.uploaded_by.as_deref())
+        .bind(dataset.metadata.unwrap_or(serde_json::json!({})))
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn update_dataset_status(
+        &self,
+        dataset_id: Uuid,
+        status: UploadStatus,
+        error_message: Option<String>,
+        total_rows: Option<i32>,
+        total_columns: Option<i32>,
+    ) -> Result<SpreadsheetDataset, sqlx::Error> {
+        sqlx::query_as::<_, SpreadsheetDataset>(
+            r#"
+            UPDATE spreadsheet_datasets 
+            SET upload_status = $2, error_message = $3, total_rows = COALESCE($4, total_rows), total_columns = COALESCE($5, total_columns), updated_at = NOW()
+            WHERE id = $1
+            RETURNING id, filename, original_filename, file_type, file_size, sheet_name, total_rows, total_columns, column_headers, upload_status, error_message, uploaded_by, created_at, updated_at, metadata, processed_rows, progress_percent
+            "#,
+        )
+        .bind(dataset_id)
+        .bind(status)
+        .bind(error_message)
+        .bind(total_rows)
+        .bind(total_columns)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn create_record(
+        &self,
+        record: CreateSpreadsheetRecord,
+    ) -> Result<SpreadsheetRecord, sqlx::Error> {
+        // Generate search text from row data
+        let search_text = self.generate_search_text(&record.row_data);
+
+        sqlx::query_as::<_, SpreadsheetRecord>(
+            r#"
+            INSERT INTO spreadsheet_records (dataset_id, row_number, row_data, search_text)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, dataset_id, row_number, row_data, typed_data, search_text, created_at
+            "#,
+        )
+        .bind(record.dataset_id)
+        .bind(record.row_number)
+        .bind(&record.row_data)
+        .bind(&search_text)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn bulk_create_records(
+        &self,
+        records: Vec<CreateSpreadsheetRecord>,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut affected_rows = 0u64;
+
+        for record in records {
+            let search_text = self.generate_search_text(&record.row_data);
+
+            let result = sqlx::query(
+                r#"
+                INSERT INTO spreadsheet_records (dataset_id, row_number, row_data, search_text)
+                VALUES ($1, $2, $3, $4)
+                "#,
+            )
+            .bind(record.dataset_id)
+            .bind(record.row_number)
+            .bind(&record.row_data)
+            .bind(&search_text)
+            .execute(&mut *tx)
+            .await?;
+
+            affected_rows += result.rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(affected_rows)
+    }
+
+    /// Insert a batch of consecutive rows with a single multi-row statement.
+    /// `first_row_number` is the 1-based row number of `rows[0]`, and
+    /// `typed_rows` holds each row's typed values in the same order.
+    pub async fn insert_record_batch(
+        &self,
+        dataset_id: Uuid,
+        first_row_number: i32,
+        rows: Vec<serde_json::Value>,
+        typed_rows: Vec<serde_json::Value>,
+    ) -> Result<u64, sqlx::Error> {
+        let row_numbers: Vec<i32> = (first_row_number..).take(rows.len()).collect();
+        let search_texts: Vec<String> = rows
+            .iter()
+            .map(|row| self.generate_search_text(row))
+            .collect();
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO spreadsheet_records (dataset_id, row_number, row_data, typed_data, search_text)
+            SELECT $1, r.row_number, r.row_data, r.typed_data, r.search_text
+            FROM UNNEST($2::int4[], $3::jsonb[], $4::jsonb[], $5::text[])
+                AS r(row_number, row_data, typed_data, search_text)
+            "#,
+        )
+        .bind(dataset_id)
+        .bind(&row_numbers)
+        .bind(&rows)
+        .bind(&typed_rows)
+        .bind(&search_texts)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Mark a dataset as being ingested and record its headers
+    pub async fn start_ingest(
+        &self,
+        dataset_id: Uuid,
+        column_headers: &[String],
+        total_rows: Option<i32>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE spreadsheet_datasets
+            SET upload_status = $2, column_headers = $3, total_columns = $4,
+                total_rows = COALESCE($5, total_rows), processed_rows = 0, progress_percent = 0,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(dataset_id)
+        .bind(UploadStatus::Processing)
+        .bind(column_headers)
+        .bind(column_headers.len() as i32)
+        .bind(total_rows)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Merge keys into a dataset's metadata, replacing any with the same name
+    pub async fn merge_dataset_metadata(
+        &self,
+        dataset_id: Uuid,
+        metadata: serde_json::Value,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE spreadsheet_datasets
+            SET metadata = metadata || $2, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(dataset_id)
+        .bind(metadata)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record ingestion progress after a batch
+    pub async fn update_ingest_progress(
+        &self,
+        dataset_id: Uuid,
+        processed_rows: i32,
+        progress_percent: Option<f32>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE spreadsheet_datasets
+            SET processed_rows = $2, progress_percent = COALESCE($3, progress_percent), updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(dataset_id)
+        .bind(processed_rows)
+        .bind(progress_percent)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove rows left behind by a failed ingestion
+    pub async fn delete_dataset_records(&self, dataset_id: Uuid) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM spreadsheet_records WHERE dataset_id = $1")
+            .bind(dataset_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn search_records(
+        &self,
+        query: SpreadsheetSearchQuery,
+    ) -> Result<SpreadsheetSearchResult, sqlx::Error> {
+        let limit = query.limit.unwrap_or(50).min(1000); // Cap at 1000 results
+        let offset = query.offset.unwrap_or(0);
+
+        let filter = SearchFilter::new(&query);
+        let where_clause = filter.where_clause();
+        let next_param = filter.next_param();
+
+        // Build the main query with dataset information joined
+        let records_query = format!(
+            r#"
+            SELECT sr.id, sr.dataset_id, sr.row_number, sr.row_data, sr.typed_data, sr.search_text, sr.created_at,
+                   sd.original_filename, sd.file_type, sd.filename as dataset_filename
+            FROM spreadsheet_records sr
+            JOIN spreadsheet_datasets sd ON sr.dataset_id = sd.id
+            {}
+            ORDER BY sr.dataset_id, sr.row_number
+            LIMIT ${} OFFSET ${}
+            "#,
+            where_clause,
+            next_param,
+            next_param + 1
+        );
+
+        let total_count = self.count_search_records(&query).await?;
+
+        let records = filter
+            .bind(sqlx::query_as::<_, SpreadsheetSearchRecord>(&records_query))
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await?;
+
+        // Get dataset info if we have a specific dataset_id
+        let dataset_info = if let Some(dataset_id) = query.dataset_id {
+            self.get_dataset(dataset_id).await.ok()
+        } else {
+            None
+        };
+
+        // Get available filters if requested
+        let available_filters = if query.dataset_id.is_some() {
+            self.get_available_filters(query.dataset_id).await.ok()
+        } else {
+            None
+        };
+
+        Ok(SpreadsheetSearchResult {
+            records,
+            total_count,
+            dataset_info,
+            available_filters,
+        })
+    }
+
+    pub async fn count_search_records(
+        &self,
+        query: &SpreadsheetSearchQuery,
+    ) -> Result<i64, sqlx::Error> {
+        let filter = SearchFilter::new(query);
+        let count_query = format!(
+            r#"
+            SELECT COUNT(*) as total
+            FROM spreadsheet_records sr
+            JOIN spreadsheet_datasets sd ON sr.dataset_id = sd.id
+            {}
+            "#,
+            filter.where_clause()
+        );
+
+        let (total,) = filter
+            .bind(sqlx::query_as::<_, (i64,)>(&count_query))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(total)
+    }
+
+    /// Records matching a search that sort after `after`, in
+    /// (dataset, row) order. Ignores the query's limit and offset so large
+    /// results can be read page by page without rescanning skipped rows.
+    pub async fn search_records_after(
+        &self,
+        query: &SpreadsheetSearchQuery,
+        after: Option<(Uuid, i32)>,
+        limit: i64,
+    ) -> Result<Vec<SpreadsheetSearchRecord>, sqlx::Error> {
+        let filter = SearchFilter::new(query);
+        let p = filter.next_param();
+        let mut conditions = filter.conditions.clone();
+        if after.is_some() {
+            conditions.push(format!(
+                "(sr.dataset_id, sr.row_number) > (${}, ${})",
+                p,
+                p + 1
+            ));
+        }
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let limit_param = if after.is_some() { p + 2 } else { p };
+
+        let records_query = format!(
+            r#"
+            SELECT sr.id, sr.dataset_id, sr.row_number, sr.row_data, sr.typed_data, sr.search_text, sr.created_at,
+                   sd.original_filename, sd.file_type, sd.filename as dataset_filename
+            FROM spreadsheet_records sr
+            JOIN spreadsheet_datasets sd ON sr.dataset_id = sd.id
+            {}
+            ORDER BY sr.dataset_id, sr.row_number
+            LIMIT ${}
+            "#,
+            where_clause, limit_param
+        );
+
+        let mut records_query_builder =
+            filter.bind(sqlx::query_as::<_, SpreadsheetSearchRecord>(&records_query));
+        if let Some((dataset_id, row_number)) = after {
+            records_query_builder = records_query_builder.bind(dataset_id).bind(row_number);
+        }
+        records_query_builder
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Column headers of every dataset with records matching a search, in
+    /// the order their records are returned
+    pub async fn search_dataset_columns(
+        &self,
+        query: &SpreadsheetSearchQuery,
+    ) -> Result<Vec<DatasetColumns>, sqlx::Error> {
+        let filter = SearchFilter::new(query);
+        let columns_query = format!(
+            r#"
+            SELECT id, original_filename, column_headers
+            FROM spreadsheet_datasets
+            WHERE id IN (
+                SELECT DISTINCT sr.dataset_id
+                FROM spreadsheet_records sr
+                JOIN spreadsheet_datasets sd ON sr.dataset_id = sd.id
+                {}
+            )
+            ORDER BY id
+            "#,
+            filter.where_clause()
+        );
+
+        filter
+            .bind(sqlx::query_as::<_, DatasetColumns>(&columns_query))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Distinct values of a column across the records an aggregation reads,
+    /// in sort order with blanks last
+    pub async fn aggregate_pivot_values(
+        &self,
+        query: &AggregateQuery,
+        column: &ColumnRef,
+        limit: i64,
+    ) -> Result<Vec<Option<String>>, sqlx::Error> {
+        let mut filter = SearchFilter::new(&query.filter);
+        let source = aggregate_source(query, &mut filter);
+        let value = column_value(&mut filter, column);
+        let values_query = format!(
+            "SELECT DISTINCT {} AS value {} ORDER BY 1 NULLS LAST LIMIT ${}",
+            value,
+            source,
+            filter.next_param()
+        );
+
+        let values: Vec<(Option<String>,)> = filter
+            .bind(sqlx::query_as(&values_query))
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(values.into_iter().map(|(value,)| value).collect())
+    }
+
+    /// Run an aggregation. Each row comes back as its cells in output column
+    /// order: the group columns, then the aggregates (repeated for each
+    /// pivot value when pivoting), or the selected columns when nothing is
+    /// aggregated.
+    pub async fn aggregate_records(
+        &self,
+        query: &AggregateQuery,
+        pivot_values: &[Option<String>],
+        limit: i64,
+    ) -> Result<Vec<Vec<serde_json::Value>>, sqlx::Error> {
+        let mut filter = SearchFilter::new(&query.filter);
+        let source = aggregate_source(query, &mut filter);
+
+        // Output cells are aliased c0, c1, ... and sort keys k0, k1, ...
+        let mut select = Vec::new();
+        let mut sort_keys = Vec::new();
+        let mut group_clause = String::new();
+        if query.is_grouped() {
+            for column in &query.group_by {
+                select.push(column_value(&mut filter, column));
+            }
+            match &query.pivot {
+                Some(pivot) => {
+                    for value in pivot_values {
+                        let text = column_value(&mut filter, &pivot.column);
+                        let condition = match value {
+                            Some(value) => {
+                                let p = filter.push(SearchBind::Text(value.clone()));
+                                format!("{} = ${}", text, p)
+                            }
+                            None => format!("{} IS NULL", text),
+                        };
+                        for spec in &query.aggregates {
+                            select.push(aggregate_expr(&mut filter, spec, Some(&condition)));
+                        }
+                    }
+                }
+                None => {
+                    for spec in &query.aggregates {
+                        select.push(aggregate_expr(&mut filter, spec, None));
+                    }
+                }
+            }
+            if !query.group_by.is_empty() {
+                let positions: Vec<String> =
+                    (1..=query.group_by.len()).map(|i| i.to_string()).collect();
+                group_clause = format!("GROUP BY {}", positions.join(", "));
+            }
+        } else {
+            for column in &query.columns {
+                select.push(column_text(&mut filter, column));
+            }
+            sort_keys.push("sr.dataset_id");
+            sort_keys.push("sr.row_number");
+            if query.join.is_some() {
+                sort_keys.push("jr.row_number");
+            }
+        }
+
+        if select.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let cells = select.len();
+        let mut columns: Vec<String> = select
+            .into_iter()
+            .enumerate()
+            .map(|(i, expr)| format!("{} AS c{}", expr, i))
+            .collect();
+        columns.extend(
+            sort_keys
+                .iter()
+                .enumerate()
+                .map(|(i, key)| format!("{} AS k{}", key, i)),
+        );
+        let order_by: Vec<String> = if query.is_grouped() {
+            (0..query.group_by.len())
+                .map(|i| format!("c{} NULLS LAST", i))
+                .collect()
+        } else {
+            (0..sort_keys.len())
+                .map(|i| format!("k{} NULLS FIRST", i))
+                .collect()
+        };
+        let order_clause = if order_by.is_empty() {
+            String::new()
+        } else {
+            format!("ORDER BY {}", order_by.join(", "))
+        };
+        let aggregate_query = format!(
+            "SELECT to_jsonb(t) FROM (SELECT {} {} {}) t {} LIMIT ${}",
+            columns.join(", "),
+            source,
+            group_clause,
+            order_clause,
+            filter.next_param()
+        );
+
+        let rows: Vec<(serde_json::Value,)> = filter
+            .bind(sqlx::query_as(&aggregate_query))
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(mut row,)| {
+                (0..cells)
+                    .map(|i| {
+                        row.get_mut(format!("c{}", i))
+                            .map(serde_json::Value::take)
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Rank records matching a `tsquery` and report which cells matched.
+    /// Facet counts cover every match, not just the returned page.
+    pub async fn ranked_search(
+        &self,
+        query: RankedSearchQuery,
+    ) -> Result<RankedSearchResult, sqlx::Error> {
+        let limit = query.limit.unwrap_or(50).clamp(1, 1000);
+        let offset = query.offset.unwrap_or(0).max(0);
+
+        // Cell snippets are only worked out for the page being returned.
+        // Keys starting with an underscore are derived, not uploaded cells.
+        let records = sqlx::query_as::<_, RankedSearchRecord>(
+            r#"
+            WITH q AS (SELECT to_tsquery('english', $1) AS query),
+            page AS (
+                SELECT sr.id, ts_rank_cd(sr.search_vector, q.query) AS rank
+                FROM spreadsheet_records sr, q
+                WHERE sr.search_vector @@ q.query
+                AND ($2::uuid[] IS NULL OR sr.dataset_id = ANY($2))
+                ORDER BY rank DESC, sr.dataset_id, sr.row_number
+                LIMIT $3 OFFSET $4
+            )
+            SELECT sr.id, sr.dataset_id, sr.row_number, sr.row_data, sd.original_filename,
+                   page.rank,
+                   COALESCE((
+                       SELECT jsonb_agg(jsonb_build_object(
+                           'column', cell.key,
+                           'snippet', ts_headline('english', cell.value, q.query,
+                               'StartSel=<mark>, StopSel=</mark>, MaxWords=20, MinWords=5')
+                       ) ORDER BY cell.key)
+                       FROM jsonb_each_text(sr.row_data) cell
+                       WHERE cell.key NOT LIKE '\_%'
+                       AND to_tsvector('english', cell.value) @@ q.query
+                   ), '[]'::jsonb) AS matches
+            FROM page
+            JOIN spreadsheet_records sr ON sr.id = page.id
+            JOIN spreadsheet_datasets sd ON sd.id = sr.dataset_id
+            CROSS JOIN q
+            ORDER BY page.rank DESC, sr.dataset_id, sr.row_number
+            "#,
+        )
+        .bind(&query.tsquery)
+        .bind(&query.dataset_ids)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let dataset_facets = sqlx::query_as::<_, DatasetFacet>(
+            r#"
+            SELECT sr.dataset_id, sd.original_filename, COUNT(*) AS count
+            FROM spreadsheet_records sr
+            JOIN spreadsheet_datasets sd ON sd.id = sr.dataset_id
+            WHERE sr.search_vector @@ to_tsquery('english', $1)
+            AND ($2::uuid[] IS NULL OR sr.dataset_id = ANY($2))
+            GROUP BY sr.dataset_id, sd.original_filename
+            ORDER BY count DESC, sd.original_filename
+            "#,
+        )
+        .bind(&query.tsquery)
+        .bind(&query.dataset_ids)
+        .fetch_all(&self.pool)
+        .await?;
+        let total_count = dataset_facets.iter().map(|facet| facet.count).sum();
+
+        let column_facets = sqlx::query_as::<_, ColumnFacet>(
+            r#"
+            WITH q AS (SELECT to_tsquery('english', $1) AS query)
+            SELECT cell.key AS column_name, COUNT(*) AS count
+            FROM spreadsheet_records sr
+            CROSS JOIN q
+            CROSS JOIN LATERAL jsonb_each_text(sr.row_data) cell
+            WHERE sr.search_vector @@ q.query
+            AND ($2::uuid[] IS NULL OR sr.dataset_id = ANY($2))
+            AND cell.key NOT LIKE '\_%'
+            AND to_tsvector('english', cell.value) @@ q.query
+            GROUP BY cell.key
+            ORDER BY count DESC, cell.key
+            LIMIT 50
+            "#,
+        )
+        .bind(&query.tsquery)
+        .bind(&query.dataset_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(RankedSearchResult {
+            records,
+            total_count,
+            dataset_facets,
+            column_facets,
+        })
+    }
+
+    pub async fn create_series(
+        &self,
+        series: CreateDatasetSeries,
+    ) -> Result<DatasetSeries, sqlx::Error> {
+        sqlx::query_as::<_, DatasetSeries>(
+            r#"
+            INSERT INTO spreadsheet_dataset_series (name, identity_column, description, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, name, identity_column, description, created_by, created_at, updated_at
+            "#,
+        )
+        .bind(&series.name)
+        .bind(&series.identity_column)
+        .bind(series.description.as_deref())
+        .bind(series.created_by.as_deref())
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn get_series(&self, series_id: Uuid) -> Result<DatasetSeries, sqlx::Error> {
+        sqlx::query_as::<_, DatasetSeries>(
+            r#"
+            SELECT id, name, identity_column, description, created_by, created_at, updated_at
+            FROM spreadsheet_dataset_series
+            WHERE id = $1
+            "#,
+        )
+        .bind(series_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn list_series(&self) -> Result<Vec<DatasetSeries>, sqlx::Error> {
+        sqlx::query_as::<_, DatasetSeries>(
+            r#"
+            SELECT id, name, identity_column, description, created_by, created_at, updated_at
+            FROM spreadsheet_dataset_series
+            ORDER BY name
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn list_series_versions(
+        &self,
+        series_id: Uuid,
+    ) -> Result<Vec<SeriesVersion>, sqlx::Error> {
+        sqlx::query_as::<_, SeriesVersion>(
+            r#"
+            SELECT v.series_id, v.version, v.dataset_id, d.original_filename, d.upload_status,
+                   d.total_rows, v.added_at
+            FROM spreadsheet_series_versions v
+            JOIN spreadsheet_datasets d ON d.id = v.dataset_id
+            WHERE v.series_id = $1
+            ORDER BY v.version
+            "#,
+        )
+        .bind(series_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Version `version` of a series, or the latest version added at or
+    /// before `as_of` when no version number is given
+    pub async fn find_series_version(
+        &self,
+        series_id: Uuid,
+        version: Option<i32>,
+        as_of: Option<DateTime<Utc>>,
+    ) -> Result<Option<SeriesVersion>, sqlx::Error> {
+        sqlx::query_as::<_, SeriesVersion>(
+            r#"
+            SELECT v.series_id, v.version, v.dataset_id, d.original_filename, d.upload_status,
+                   d.total_rows, v.added_at
+            FROM spreadsheet_series_versions v
+            JOIN spreadsheet_datasets d ON d.id = v.dataset_id
+            WHERE v.series_id = $1
+            AND ($2::int4 IS NULL OR v.version = $2)
+            AND ($3::timestamptz IS NULL OR v.added_at <= $3)
+            ORDER BY v.version DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(series_id)
+        .bind(version)
+        .bind(as_of)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Append a dataset to a series as its next version. The series row is
+    /// locked so concurrent uploads get distinct version numbers.
+    pub async fn add_series_version(
+        &self,
+        series_id: Uuid,
+        dataset_id: Uuid,
+    ) -> Result<SeriesVersion, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("SELECT id FROM spreadsheet_dataset_series WHERE id = $1 FOR UPDATE")
+            .bind(series_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        let version = sqlx::query_as::<_, SeriesVersion>(
+            r#"
+            WITH inserted AS (
+                INSERT INTO spreadsheet_series_versions (series_id, version, dataset_id)
+                SELECT $1, COALESCE(MAX(version), 0) + 1, $2
+                FROM spreadsheet_series_versions
+                WHERE series_id = $1
+                RETURNING series_id, version, dataset_id, added_at
+            )
+            SELECT i.series_id, i.version, i.dataset_id, d.original_filename, d.upload_status,
+                   d.total_rows, i.added_at
+            FROM inserted i
+            JOIN spreadsheet_datasets d ON d.id = i.dataset_id
+            "#,
+        )
+        .bind(series_id)
+        .bind(dataset_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE spreadsheet_dataset_series SET updated_at = NOW() WHERE id = $1")
+            .bind(series_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(version)
+    }
+
+    /// Identity values that occur more than once in a dataset
+    pub async fn duplicate_identities(
+        &self,
+        dataset_id: Uuid,
+        identity_column: &str,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT row_data ->> $2
+            FROM spreadsheet_records
+            WHERE dataset_id = $1 AND COALESCE(row_data ->> $2, '') <> ''
+            GROUP BY row_data ->> $2
+            HAVING COUNT(*) > 1
+            ORDER BY 1
+            LIMIT 10
+            "#,
+        )
+        .bind(dataset_id)
+        .bind(identity_column)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Rows of two datasets matched on `identity_column`, where the row is
+    /// missing from one side or its data differs. Rows without an identity
+    /// value are left out.
+    pub async fn differing_rows(
+        &self,
+        from_dataset_id: Uuid,
+        to_dataset_id: Uuid,
+        identity_column: &str,
+    ) -> Result<Vec<VersionRowPair>, sqlx::Error> {
+        sqlx::query_as::<_, VersionRowPair>(
+            r#"
+            WITH a AS (
+                SELECT row_data ->> $3 AS key, row_number, row_data
+                FROM spreadsheet_records
+                WHERE dataset_id = $1 AND COALESCE(row_data ->> $3, '') <> ''
+            ),
+            b AS (
+                SELECT row_data ->> $3 AS key, row_number, row_data
+                FROM spreadsheet_records
+                WHERE dataset_id = $2 AND COALESCE(row_data ->> $3, '') <> ''
+            )
+            SELECT COALESCE(a.key, b.key) AS key,
+                   a.row_number AS from_row_number, a.row_data AS from_row,
+                   b.row_number AS to_row_number, b.row_data AS to_row
+            FROM a
+            FULL OUTER JOIN b ON a.key = b.key
+            WHERE a.row_data IS DISTINCT FROM b.row_data
+            ORDER BY COALESCE(b.row_number, a.row_number), key
+            "#,
+        )
+        .bind(from_dataset_id)
+        .bind(to_dataset_id)
+        .bind(identity_column)
+        .fetch_all(&self.pool)
+        .await
     }
 
-    pub async fn create_dataset(
+    pub async fn version_match_counts(
         &self,
-        dataset: CreateSpreadsheetDataset,
-    ) -> Result<SpreadsheetDataset, sqlx::Error> {
-        sqlx::query_as::<_, SpreadsheetDataset>(
+        from_dataset_id: Uuid,
+        to_dataset_id: Uuid,
+        identity_column: &str,
+    ) -> Result<VersionMatchCounts, sqlx::Error> {
+        sqlx::query_as::<_, VersionMatchCounts>(
             r#"
-            INSERT INTO spreadsheet_datasets (filename, original_filename, file_type, file_size, sheet_name, column_headers, uploaded_by, metadata)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-            RETURNING id, filename, original_filename, file_type, file_size, sheet_name, total_rows, total_columns, column_headers, upload_status, error_message, uploaded_by, created_at, updated_at, metadata
+            SELECT
+                (SELECT COUNT(*)
+                 FROM spreadsheet_records a
+                 JOIN spreadsheet_records b
+                   ON b.dataset_id = $2 AND b.row_data ->> $3 = a.row_data ->> $3
+                 WHERE a.dataset_id = $1 AND COALESCE(a.row_data ->> $3, '') <> '') AS matched,
+                (SELECT COUNT(*)
+                 FROM spreadsheet_records
+                 WHERE dataset_id IN ($1, $2) AND COALESCE(row_data ->> $3, '') = '') AS unkeyed
             "#,
         )
-        .bind(&dataset.filename)
-        .bind(&dataset.original_filename)
-        .bind(&dataset.file_type)
-        .bind(dataset.file_size)
-        .bind(dataset.sheet_name.as_deref())
-        .bind(&dataset.column_headers)
-        .bind(dataset.uploaded_by.as_deref())
-        .bind(dataset.metadata.unwrap_or(serde_json::json!({})))
+        .bind(from_dataset_id)
+        .bind(to_dataset_id)
+        .bind(identity_column)
         .fetch_one(&self.pool)
         .await
     }
 
-    pub async fn update_dataset_status(
+    pub async fn create_rule_set(
         &self,
-        dataset_id: Uuid,
-        status: UploadStatus,
-        error_message: Option<String>,
-        total_rows: Option<i32>,
-        total_columns: Option<i32>,
-    ) -> Result<SpreadsheetDataset, sqlx::Error> {
-        sqlx::query_as::<_, SpreadsheetDataset>(
+        rule_set: CreateDatasetRuleSet,
+    ) -> Result<DatasetRuleSet, sqlx::Error> {
+        sqlx::query_as::<_, DatasetRuleSet>(
             r#"
-            UPDATE spreadsheet_datasets 
-            SET upload_status = $2, error_message = $3, total_rows = COALESCE($4, total_rows), total_columns = COALESCE($5, total_columns), updated_at = NOW()
+            INSERT INTO spreadsheet_rule_sets (name, dataset_type, description, rules, on_violation, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, name, dataset_type, description, rules, on_violation, created_by, created_at, updated_at
+            "#,
+        )
+        .bind(&rule_set.name)
+        .bind(&rule_set.dataset_type)
+        .bind(rule_set.description.as_deref())
+        .bind(sqlx::types::Json(&rule_set.rules))
+        .bind(rule_set.on_violation)
+        .bind(rule_set.created_by.as_deref())
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn get_rule_set(&self, rule_set_id: Uuid) -> Result<DatasetRuleSet, sqlx::Error> {
+        sqlx::query_as::<_, DatasetRuleSet>(
+            r#"
+            SELECT id, name, dataset_type, description, rules, on_violation, created_by, created_at, updated_at
+            FROM spreadsheet_rule_sets
             WHERE id = $1
-            RETURNING id, filename, original_filename, file_type, file_size, sheet_name, total_rows, total_columns, column_headers, upload_status, error_message, uploaded_by, created_at, updated_at, metadata
             "#,
         )
-        .bind(dataset_id)
-        .bind(status)
-        .bind(error_message)
-        .bind(total_rows)
-        .bind(total_columns)
+        .bind(rule_set_id)
         .fetch_one(&self.pool)
         .await
     }
 
-    pub async fn create_record(
+    pub async fn find_rule_set_for_type(
         &self,
-        record: CreateSpreadsheetRecord,
-    ) -> Result<SpreadsheetRecord, sqlx::Error> {
-        // Generate search text from row data
-        let search_text = self.generate_search_text(&record.row_data);
+        dataset_type: &str,
+    ) -> Result<Option<DatasetRuleSet>, sqlx::Error> {
+        sqlx::query_as::<_, DatasetRuleSet>(
+            r#"
+            SELECT id, name, dataset_type, description, rules, on_violation, created_by, created_at, updated_at
+            FROM spreadsheet_rule_sets
+            WHERE dataset_type = $1
+            "#,
+        )
+        .bind(dataset_type)
+        .fetch_optional(&self.pool)
+        .await
+    }
 
-        sqlx::query_as::<_, SpreadsheetRecord>(
+    pub async fn list_rule_sets(&self) -> Result<Vec<DatasetRuleSet>, sqlx::Error> {
+        sqlx::query_as::<_, DatasetRuleSet>(
             r#"
-            INSERT INTO spreadsheet_records (dataset_id, row_number, row_data, search_text)
+            SELECT id, name, dataset_type, description, rules, on_violation, created_by, created_at, updated_at
+            FROM spreadsheet_rule_sets
+            ORDER BY dataset_type
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn delete_rule_set(&self, rule_set_id: Uuid) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM spreadsheet_rule_sets WHERE id = $1")
+            .bind(rule_set_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn create_mapping_profile(
+        &self,
+        profile: CreateSampleMappingProfile,
+    ) -> Result<SampleMappingProfile, sqlx::Error> {
+        sqlx::query_as::<_, SampleMappingProfile>(
+            r#"
+            INSERT INTO spreadsheet_mapping_profiles (name, description, mappings, created_by)
             VALUES ($1, $2, $3, $4)
-            RETURNING id, dataset_id, row_number, row_data, search_text, created_at
+            RETURNING id, name, description, mappings, created_by, created_at, updated_at
             "#,
         )
-        .bind(record.dataset_id)
-        .bind(record.row_number)
-        .bind(&record.row_data)
-        .bind(&search_text)
+        .bind(&profile.name)
+        .bind(profile.description.as_deref())
+        .bind(sqlx::types::Json(&profile.mappings))
+        .bind(profile.created_by.as_deref())
         .fetch_one(&self.pool)
         .await
     }
 
-    pub async fn bulk_create_records(
+    pub async fn get_mapping_profile(
         &self,
-        records: Vec<CreateSpreadsheetRecord>,
+        profile_id: Uuid,
+    ) -> Result<SampleMappingProfile, sqlx::Error> {
+        sqlx::query_as::<_, SampleMappingProfile>(
+            r#"
+            SELECT id, name, description, mappings, created_by, created_at, updated_at
+            FROM spreadsheet_mapping_profiles
+            WHERE id = $1
+            "#,
+        )
+        .bind(profile_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn list_mapping_profiles(&self) -> Result<Vec<SampleMappingProfile>, sqlx::Error> {
+        sqlx::query_as::<_, SampleMappingProfile>(
+            r#"
+            SELECT id, name, description, mappings, created_by, created_at, updated_at
+            FROM spreadsheet_mapping_profiles
+            ORDER BY name
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn delete_mapping_profile(&self, profile_id: Uuid) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM spreadsheet_mapping_profiles WHERE id = $1")
+            .bind(profile_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Records of a dataset that already have a sample
+    pub async fn linked_record_ids(&self, dataset_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+        sqlx::query_scalar("SELECT record_id FROM spreadsheet_record_samples WHERE dataset_id = $1")
+            .bind(dataset_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Which of `barcodes` are already taken by samples
+    pub async fn existing_sample_barcodes(
+        &self,
+        barcodes: &[String],
+    ) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT barcode FROM samples WHERE barcode = ANY($1)")
+            .bind(barcodes)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Create samples for dataset rows and link each to its row, all or
+    /// nothing
+    pub async fn create_record_samples(
+        &self,
+        dataset_id: Uuid,
+        profile_id: Uuid,
+        samples: &[RecordSample],
     ) -> Result<u64, sqlx::Error> {
         let mut tx = self.pool.begin().await?;
-        let mut affected_rows = 0u64;
+        let mut created = 0;
 
-        for record in records {
-            let search_text = self.generate_search_text(&record.row_data);
+        for chunk in samples.chunks(1000) {
+            let record_ids: Vec<Uuid> = chunk.iter().map(|s| s.record_id).collect();
+            let names: Vec<&str> = chunk.iter().map(|s| s.name.as_str()).collect();
+            let barcodes: Vec<&str> = chunk.iter().map(|s| s.barcode.as_str()).collect();
+            let locations: Vec<&str> = chunk.iter().map(|s| s.location.as_str()).collect();
+            let metadata: Vec<&serde_json::Value> = chunk.iter().map(|s| &s.metadata).collect();
 
+            // Barcodes are unique, so they pair each new sample with its row
             let result = sqlx::query(
                 r#"
-                INSERT INTO spreadsheet_records (dataset_id, row_number, row_data, search_text)
-                VALUES ($1, $2, $3, $4)
+                WITH input AS (
+                    SELECT *
+                    FROM UNNEST($1::uuid[], $2::text[], $3::text[], $4::text[], $5::jsonb[])
+                        AS t(record_id, name, barcode, location, metadata)
+                ),
+                inserted AS (
+                    INSERT INTO samples (name, barcode, location, status, metadata)
+                    SELECT name, barcode, location, 'pending', metadata FROM input
+                    RETURNING id, barcode
+                )
+                INSERT INTO spreadsheet_record_samples (record_id, sample_id, dataset_id, profile_id)
+                SELECT input.record_id, inserted.id, $6, $7
+                FROM input
+                JOIN inserted ON inserted.barcode = input.barcode
                 "#,
             )
-            .bind(record.dataset_id)
-            .bind(record.row_number)
-            .bind(&record.row_data)
-            .bind(&search_text)
+            .bind(&record_ids)
+            .bind(&names)
+            .bind(&barcodes)
+            .bind(&locations)
+            .bind(&metadata)
+            .bind(dataset_id)
+            .bind(profile_id)
             .execute(&mut *tx)
             .await?;
-
-            affected_rows += result.rows_affected();
+            created += result.rows_affected();
         }
 
         tx.commit().await?;
-        Ok(affected_rows)
+        Ok(created)
     }
 
-    #[allow(unused_assignments)]
-    pub async fn search_records(
+    pub async fn list_linked_samples(
         &self,
-        query: SpreadsheetSearchQuery,
-    ) -> Result<SpreadsheetSearchResult, sqlx::Error> {
-        let limit = query.limit.unwrap_or(50).min(1000); // Cap at 1000 results
-        let offset = query.offset.unwrap_or(0);
-
-        // Build the WHERE clause dynamically
-        let mut where_conditions = Vec::new();
-        let mut param_count = 1;
-
-        if query.dataset_id.is_some() {
-            where_conditions.push(format!("sr.dataset_id = ${}", param_count));
-            param_count += 1;
-        }
-
-        if query.search_term.is_some() {
-            where_conditions.push(format!(
-                "to_tsvector('english', sr.search_text) @@ plainto_tsquery('english', ${})",
-                param_count
-            ));
-            param_count += 1;
-        }
-
-        // Add pool/sample/project filters with smart column detection
-        if query.pool_filter.is_some() {
-            where_conditions.push(format!(
-                "(sr.row_data ->> 'Pool' ILIKE ${} OR sr.row_data ->> 'Pool_ID' ILIKE ${} OR sr.row_data ->> 'PoolID' ILIKE ${})",
-                param_count, param_count, param_count
-            ));
-            param_count += 1;
-        }
-
-        if query.sample_filter.is_some() {
-            where_conditions.push(format!(
-                "(sr.row_data ->> 'Sample' ILIKE ${} OR sr.row_data ->> 'Sample_ID' ILIKE ${} OR sr.row_data ->> 'SampleID' ILIKE ${} OR sr.row_data ->> 'Sample_Name' ILIKE ${})",
-                param_count, param_count, param_count, param_count
-            ));
-            param_count += 1;
-        }
-
-        if let Some(_project_filter) = &query.project_filter {
-            where_conditions.push(format!(
-                "(sr.row_data ->> 'Project' ILIKE ${} OR sr.row_data ->> 'Project_ID' ILIKE ${} OR sr.row_data ->> 'ProjectID' ILIKE ${} OR sr.row_data ->> 'Project_Name' ILIKE ${})",
-                param_count, param_count, param_count, param_count
-            ));
-            param_count += 1;
-        }
-
-        // Add column filters
-        if let Some(filters) = &query.column_filters {
-            for (column, _value) in filters {
-                where_conditions.push(format!(
-                    "sr.row_data ->> '{}' ILIKE ${}",
-                    column, param_count
-                ));
-                param_count += 1;
-            }
-        }
-
-        let where_clause = if where_conditions.is_empty() {
-            String::new()
-        } else {
-            format!("WHERE {}", where_conditions.join(" AND "))
-        };
-
-        // Build the main query with dataset information joined
-        let records_query = format!(
-            r#"
-            SELECT sr.id, sr.dataset_id, sr.row_number, sr.row_data, sr.search_text, sr.created_at,
-                   sd.original_filename, sd.file_type, sd.filename as dataset_filename
-            FROM spreadsheet_records sr
-            JOIN spreadsheet_datasets sd ON sr.dataset_id = sd.id
-            {}
-            ORDER BY sr.dataset_id, sr.row_number
-            LIMIT ${} OFFSET ${}
-            "#,
-            where_clause,
-            param_count,
-            param_count + 1
-        );
-
-        // Build the count query
-        let count_query = format!(
+        dataset_id: Uuid,
+    ) -> Result<Vec<LinkedSample>, sqlx::Error> {
+        sqlx::query_as::<_, LinkedSample>(
             r#"
-            SELECT COUNT(*) as total
-            FROM spreadsheet_records sr
-            JOIN spreadsheet_datasets sd ON sr.dataset_id = sd.id
-            {}
+            SELECT l.record_id, r.row_number, l.sample_id, s.name, s.barcode, l.profile_id,
+                   l.created_at AS linked_at
+            FROM spreadsheet_record_samples l
+            JOIN spreadsheet_records r ON r.id = l.record_id
+            JOIN samples s ON s.id = l.sample_id
+            WHERE l.dataset_id = $1
+            ORDER BY r.row_number
             "#,
-            where_clause
-        );
-
-        // Execute count query
-        let mut count_query_builder = sqlx::query_scalar::<_, i64>(&count_query);
-        param_count = 1;
-
-        // Prepare filter patterns outside the binding logic to handle lifetimes
-        let pool_filter_pattern = query.pool_filter.as_ref().map(|f| format!("%{}%", f));
-        let sample_filter_pattern = query.sample_filter.as_ref().map(|f| format!("%{}%", f));
-        let project_filter_pattern = query.project_filter.as_ref().map(|f| format!("%{}%", f));
-
-        if let Some(dataset_id) = query.dataset_id {
-            count_query_builder = count_query_builder.bind(dataset_id);
-            param_count += 1;
-        }
-
-        if let Some(search_term) = &query.search_term {
-            count_query_builder = count_query_builder.bind(search_term);
-            param_count += 1;
-        }
-
-        if let Some(ref filter_pattern) = pool_filter_pattern {
-            count_query_builder = count_query_builder.bind(filter_pattern);
-            param_count += 1;
-        }
-
-        if let Some(ref filter_pattern) = sample_filter_pattern {
-            count_query_builder = count_query_builder.bind(filter_pattern);
-            param_count += 1;
-        }
-
-        if let Some(ref filter_pattern) = project_filter_pattern {
-            count_query_builder = count_query_builder.bind(filter_pattern);
-            param_count += 1;
-        }
-
-        if let Some(filters) = &query.column_filters {
-            for (_, value) in filters {
-                count_query_builder = count_query_builder.bind(format!("%{}%", value));
-            }
-        }
-
-        let total_count = count_query_builder.fetch_one(&self.pool).await?;
-
-        // Execute records query
-        let mut records_query_builder =
-            sqlx::query_as::<_, SpreadsheetSearchRecord>(&records_query);
-        param_count = 1;
-
-        if let Some(dataset_id) = query.dataset_id {
-            records_query_builder = records_query_builder.bind(dataset_id);
-            param_count += 1;
-        }
-
-        if let Some(search_term) = &query.search_term {
-            records_query_builder = records_query_builder.bind(search_term);
-            param_count += 1;
-        }
-
-        if let Some(ref filter_pattern) = pool_filter_pattern {
-            records_query_builder = records_query_builder.bind(filter_pattern);
-            param_count += 1;
-        }
-
-        if let Some(ref filter_pattern) = sample_filter_pattern {
-            records_query_builder = records_query_builder.bind(filter_pattern);
-            param_count += 1;
-        }
-
-        if let Some(ref filter_pattern) = project_filter_pattern {
-            records_query_builder = records_query_builder.bind(filter_pattern);
-            param_count += 1;
-        }
-
-        if let Some(filters) = &query.column_filters {
-            for (_, value) in filters {
-                records_query_builder = records_query_builder.bind(format!("%{}%", value));
-                param_count += 1;
-            }
-        }
-
-        records_query_builder = records_query_builder.bind(limit).bind(offset);
-
-        let records = records_query_builder.fetch_all(&self.pool).await?;
-
-        // Get dataset info if we have a specific dataset_id
-        let dataset_info = if let Some(dataset_id) = query.dataset_id {
-            self.get_dataset(dataset_id).await.ok()
-        } else {
-            None
-        };
-
-        // Get available filters if requested
-        let available_filters = if query.dataset_id.is_some() {
-            self.get_available_filters(query.dataset_id).await.ok()
-        } else {
-            None
-        };
-
-        Ok(SpreadsheetSearchResult {
-            records,
-            total_count,
-            dataset_info,
-            available_filters,
-        })
+        )
+        .bind(dataset_id)
+        .fetch_all(&self.pool)
+        .await
     }
 
     pub async fn get_dataset(&self, dataset_id: Uuid) -> Result<SpreadsheetDataset, sqlx::Error> {
         sqlx::query_as::<_, SpreadsheetDataset>(
             r#"
-            SELECT id, filename, original_filename, file_type, file_size, sheet_name, total_rows, total_columns, column_headers, upload_status, error_message, uploaded_by, created_at, updated_at, metadata
+            SELECT id, filename, original_filename, file_type, file_size, sheet_name, total_rows, total_columns, column_headers, upload_status, error_message, uploaded_by, created_at, updated_at, metadata, processed_rows, progress_percent
             FROM spreadsheet_datasets
             WHERE id = $1
             "#,
@@ -469,7 +1973,7 @@ impl SpreadsheetDataManager {
 
         sqlx::query_as::<_, SpreadsheetDataset>(
             r#"
-            SELECT id, filename, original_filename, file_type, file_size, sheet_name, total_rows, total_columns, column_headers, upload_status, error_message, uploaded_by, created_at, updated_at, metadata
+            SELECT id, filename, original_filename, file_type, file_size, sheet_name, total_rows, total_columns, column_headers, upload_status, error_message, uploaded_by, created_at, updated_at, metadata, processed_rows, progress_percent
             FROM spreadsheet_datasets
             ORDER BY created_at DESC
             LIMIT $1 OFFSET $2
@@ -675,6 +2179,35 @@ impl SpreadsheetDataManager {
             }
         }
 
+        // Prefer the type inferred at upload, which range filters work on.
+        // Datasets uploaded before typing fall back to guessing from values.
+        let stored_type: Option<String> = sqlx::query_scalar(
+            "SELECT metadata -> 'column_types' -> $2 ->> 'type' FROM spreadsheet_datasets WHERE id = $1",
+        )
+        .bind(dataset_id)
+        .bind(column_name)
+        .fetch_optional(&self.pool)
+        .await?
+        .flatten();
+
+        if let Some(data_type) = stored_type {
+            let suggested_filter_type = match data_type.as_str() {
+                "integer" | "decimal" => FilterType::Numeric,
+                "date" | "date_time" => FilterType::Date,
+                "boolean" | "categorical" => FilterType::Dropdown,
+                _ if unique_values.len() <= 10 => FilterType::Dropdown,
+                _ => FilterType::Text,
+            };
+
+            return Ok(ColumnAnalysis {
+                column_name: column_name.to_string(),
+                unique_values: unique_values.into_iter().take(50).collect(),
+                value_count: total_count as usize,
+                data_type,
+                suggested_filter_type,
+            });
+        }
+
         // Calculate data type and filter type before moving unique_values
         let data_type = self.detect_data_type(&unique_values);
         let suggested_filter_type = if unique_values.len() <= 10 {
@@ -745,3 +2278,32 @@ impl SpreadsheetDataManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_bound_parsing() {
+        assert_eq!(
+            RangeBound::parse("12.5", false),
+            Some(RangeBound::Number(12.5))
+        );
+
+        let day = NaiveDate::from_ymd_opt(2024, 6, 30).unwrap();
+        assert_eq!(
+            RangeBound::parse("2024-06-30", false),
+            Some(RangeBound::Date(day.and_hms_opt(0, 0, 0).unwrap()))
+        );
+        assert_eq!(
+            RangeBound::parse("2024-06-30", true),
+            Some(RangeBound::Date(day.and_hms_opt(23, 59, 59).unwrap()))
+        );
+        assert_eq!(
+            RangeBound::parse("2024-06-30T10:00:00+02:00", false),
+            Some(RangeBound::Date(day.and_hms_opt(8, 0, 0).unwrap()))
+        );
+        assert_eq!(RangeBound::parse("soon", false), None);
+        assert_eq!(RangeBound::parse("NaN", false), None);
+    }
+}
@@ -67,6 +67,8 @@ pub enum StorageState {
     InSequencing, // Sample moved to sequencing
     Completed,    // Sample processing completed
     Discarded,    // Sample discarded or destroyed
+    InTransit,    // Sample shipped out and not yet received
+    Shipped,      // Sample received by the destination of its shipment
 }
 
 impl StorageState {
@@ -77,6 +79,9 @@ impl StorageState {
             (Validated, InStorage) => true,
             (InStorage, InSequencing) => true,
             (InSequencing, Completed) => true,
+            (InStorage, InTransit) => true,
+            (InTransit, InStorage) => true,
+            (InTransit, Shipped) => true,
             (_, Discarded) => true, // Can discard from any state
             _ => false,
         }
@@ -156,6 +161,50 @@ pub struct StorageMovementHistory {
     pub notes: Option<String>,
 }
 
+/// Temporary checkout of a sample from its storage location
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SampleCheckout {
+    pub id: i32,
+    pub sample_id: uuid::Uuid,
+    pub barcode: String,
+    pub location_id: i32,
+    pub position: Option<String>, // Position reserved while the sample is out
+    pub checked_out_by: String,
+    pub checked_out_at: DateTime<Utc>,
+    pub due_back_at: DateTime<Utc>,
+    pub purpose: Option<String>,
+    pub checked_in_by: Option<String>,
+    pub checked_in_at: Option<DateTime<Utc>>,
+    pub notes: Option<String>,
+}
+
+impl SampleCheckout {
+    pub fn is_open(&self) -> bool {
+        self.checked_in_at.is_none()
+    }
+
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        self.is_open() && now > self.due_back_at
+    }
+
+    /// Time spent out of storage, up to `now` for checkouts that are still open
+    pub fn time_out_seconds(&self, now: DateTime<Utc>) -> i64 {
+        let end = self.checked_in_at.unwrap_or(now);
+        (end - self.checked_out_at).num_seconds().max(0)
+    }
+}
+
+/// Cumulative time a sample has spent out of its temperature-controlled storage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeOutOfStorage {
+    pub sample_id: uuid::Uuid,
+    pub barcode: String,
+    pub checkout_count: i32,
+    pub total_seconds_out: i64,
+    pub currently_checked_out: bool,
+    pub open_checkout: Option<SampleCheckout>,
+}
+
 /// Storage capacity statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageCapacityStats {
@@ -227,3 +276,65 @@ pub struct StorageRequirement {
     pub special_conditions: Vec<String>,
     pub max_storage_duration_days: Option<i32>,
 }
+
+/// Normalize a rack position so that "a01", "A1" and " A01 " compare equal
+pub fn normalize_position(position: &str) -> String {
+    let trimmed = position.trim().to_uppercase();
+    let split = trimmed
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (row, column) = trimmed.split_at(split);
+
+    if !row.is_empty() && !column.is_empty() && column.chars().all(|c| c.is_ascii_digit()) {
+        let column = column.trim_start_matches('0');
+        format!("{}{}", row, if column.is_empty() { "0" } else { column })
+    } else {
+        trimmed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn checkout(out_for_minutes: i64, due_in_minutes: i64, checked_in: bool) -> SampleCheckout {
+        let now = Utc::now();
+        let checked_out_at = now - Duration::minutes(out_for_minutes);
+        SampleCheckout {
+            id: 1,
+            sample_id: uuid::Uuid::new_v4(),
+            barcode: "LAB-DNA-001".to_string(),
+            location_id: 1,
+            position: Some("A1".to_string()),
+            checked_out_by: "tech".to_string(),
+            checked_out_at,
+            due_back_at: checked_out_at + Duration::minutes(due_in_minutes),
+            purpose: None,
+            checked_in_by: checked_in.then(|| "tech".to_string()),
+            checked_in_at: checked_in.then_some(now),
+            notes: None,
+        }
+    }
+
+    #[test]
+    fn test_checkout_overdue() {
+        let now = Utc::now();
+        assert!(checkout(90, 60, false).is_overdue(now));
+        assert!(!checkout(30, 60, false).is_overdue(now));
+        assert!(!checkout(90, 60, true).is_overdue(now));
+    }
+
+    #[test]
+    fn test_checkout_time_out() {
+        let item = checkout(45, 60, true);
+        assert_eq!(item.time_out_seconds(Utc::now()), 45 * 60);
+    }
+
+    #[test]
+    fn test_normalize_position() {
+        assert_eq!(normalize_position(" a01 "), "A1");
+        assert_eq!(normalize_position("H12"), "H12");
+        assert_eq!(normalize_position("Slot 3"), "SLOT 3");
+    }
+}
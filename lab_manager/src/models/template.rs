@@ -60,3 +60,113 @@ pub struct ParsedTemplateResponse {
     pub template: TemplateResponse,
     pub data: SpreadsheetData,
 }
+
+/// Kind of value a template field holds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    #[default]
+    Text,
+    Integer,
+    Number,
+    Boolean,
+    /// Calendar date written as YYYY-MM-DD
+    Date,
+}
+
+impl FieldType {
+    /// Whether a filled cell holds a value of this type
+    pub fn accepts(&self, value: &str) -> bool {
+        let value = value.trim();
+        match self {
+            FieldType::Text => true,
+            FieldType::Integer => value.parse::<i64>().is_ok(),
+            FieldType::Number => value.parse::<f64>().is_ok_and(f64::is_finite),
+            FieldType::Boolean => matches!(
+                value.to_lowercase().as_str(),
+                "true" | "false" | "yes" | "no" | "1" | "0"
+            ),
+            FieldType::Date => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok(),
+        }
+    }
+}
+
+/// One field a template asks submitters to fill in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateField {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub field_type: FieldType,
+    #[serde(default)]
+    pub required: bool,
+    /// When not empty, the only values the field may take
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_values: Vec<String>,
+    pub unit: Option<String>,
+    pub help_text: Option<String>,
+}
+
+/// How a field differs from the previous version of its template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum FieldChange {
+    Added {
+        field: String,
+    },
+    Removed {
+        field: String,
+    },
+    /// Names the attributes that changed, such as `type` or `unit`
+    Modified {
+        field: String,
+        attributes: Vec<String>,
+    },
+}
+
+/// A published, immutable field schema of a template
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TemplateVersion {
+    pub id: Uuid,
+    pub template_id: Uuid,
+    pub version_number: i32,
+    #[sqlx(json)]
+    pub fields: Vec<TemplateField>,
+    /// Field changes from the previous version
+    #[sqlx(json)]
+    pub changes: Vec<FieldChange>,
+    pub changelog: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTemplateVersion {
+    pub fields: Vec<TemplateField>,
+    /// What changed and why, in the author's words
+    pub changelog: Option<String>,
+    pub created_by: Option<String>,
+}
+
+/// A version as listed in a template's changelog, without its fields
+#[derive(Debug, Serialize)]
+pub struct ChangelogEntry {
+    pub version_id: Uuid,
+    pub version_number: i32,
+    pub changes: Vec<FieldChange>,
+    pub changelog: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<TemplateVersion> for ChangelogEntry {
+    fn from(version: TemplateVersion) -> Self {
+        Self {
+            version_id: version.id,
+            version_number: version.version_number,
+            changes: version.changes,
+            changelog: version.changelog,
+            created_by: version.created_by,
+            created_at: version.created_at,
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::models::analytics::AnalyticsFilter;
+
+/// A bucket of a dashboard trend, from `start` up to but excluding `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeBucket {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// Turnaround of the samples completed in a bucket
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TurnaroundRow {
+    pub bucket_start: DateTime<Utc>,
+    pub completed: i64,
+    pub p50_hours: Option<f64>,
+    pub p90_hours: Option<f64>,
+    pub p95_hours: Option<f64>,
+}
+
+/// Analytics repository trait for dashboard trend queries. Each query
+/// returns rows keyed by bucket start.
+#[async_trait]
+pub trait AnalyticsRepository: Send + Sync {
+    async fn samples_received(
+        &self,
+        buckets: &[TimeBucket],
+        filter: &AnalyticsFilter,
+    ) -> Result<Vec<(DateTime<Utc>, i64)>, sqlx::Error>;
+    /// Samples in each status at the end of each bucket
+    async fn samples_by_status(
+        &self,
+        buckets: &[TimeBucket],
+        filter: &AnalyticsFilter,
+    ) -> Result<Vec<(DateTime<Utc>, String, i64)>, sqlx::Error>;
+    /// Jobs started and completed in each bucket. Jobs match the filter when
+    /// one of their samples does.
+    async fn sequencing_throughput(
+        &self,
+        buckets: &[TimeBucket],
+        filter: &AnalyticsFilter,
+    ) -> Result<Vec<(DateTime<Utc>, i64, i64)>, sqlx::Error>;
+    /// Used and total capacity at the end of each bucket
+    async fn storage_utilization(
+        &self,
+        buckets: &[TimeBucket],
+    ) -> Result<Vec<(DateTime<Utc>, i64, i64)>, sqlx::Error>;
+    async fn sample_turnaround(
+        &self,
+        buckets: &[TimeBucket],
+        filter: &AnalyticsFilter,
+    ) -> Result<Vec<TurnaroundRow>, sqlx::Error>;
+}
+
+/// PostgreSQL implementation of analytics repository
+#[derive(Debug)]
+pub struct PostgresAnalyticsRepository {
+    pool: PgPool,
+}
+
+impl PostgresAnalyticsRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// Samples matching the filter bound as $3 (project) and $4 (sample type)
+const SAMPLE_FILTER: &str = r#"
+    ($3::text IS NULL OR s.metadata->>'project' = $3)
+    AND ($4::text IS NULL OR s.metadata->>'sample_type' = $4)
+"#;
+
+fn bucket_bounds(buckets: &[TimeBucket]) -> (Vec<DateTime<Utc>>, Vec<DateTime<Utc>>) {
+    buckets
+        .iter()
+        .map(|bucket| (bucket.start, bucket.end))
+        .unzip()
+}
+
+#[async_trait]
+impl AnalyticsRepository for PostgresAnalyticsRepository {
+    async fn samples_received(
+        &self,
+        buckets: &[TimeBucket],
+        filter: &AnalyticsFilter,
+    ) -> Result<Vec<(DateTime<Utc>, i64)>, sqlx::Error> {
+        let (starts, ends) = bucket_bounds(buckets);
+        sqlx::query_as::<_, (DateTime<Utc>, i64)>(&format!(
+            r#"
+            SELECT b.bucket_start, COUNT(s.id)
+            FROM unnest($1::timestamptz[], $2::timestamptz[]) AS b(bucket_start, bucket_end)
+            LEFT JOIN samples s
+                ON s.created_at >= b.bucket_start
+                AND s.created_at < b.bucket_end
+                AND {}
+            GROUP BY b.bucket_start
+            ORDER BY b.bucket_start
+            "#,
+            SAMPLE_FILTER
+        ))
+        .bind(starts)
+        .bind(ends)
+        .bind(&filter.project)
+        .bind(&filter.sample_type)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn samples_by_status(
+        &self,
+        buckets: &[TimeBucket],
+        filter: &AnalyticsFilter,
+    ) -> Result<Vec<(DateTime<Utc>, String, i64)>, sqlx::Error> {
+        let (starts, ends) = bucket_bounds(buckets);
+        // Each status a sample had lasts until its next one
+        sqlx::query_as::<_, (DateTime<Utc>, String, i64)>(&format!(
+            r#"
+            WITH spans AS (
+                SELECT
+                    h.status,
+                    h.changed_at,
+                    LEAD(h.changed_at) OVER (PARTITION BY h.sample_id ORDER BY h.changed_at, h.id)
+                        AS next_changed_at
+                FROM sample_status_history h
+                JOIN samples s ON s.id = h.sample_id
+                WHERE {}
+            )
+            SELECT b.bucket_start, sp.status::text, COUNT(*)
+            FROM unnest($1::timestamptz[], $2::timestamptz[]) AS b(bucket_start, bucket_end)
+            JOIN spans sp
+                ON sp.changed_at < b.bucket_end
+                AND (sp.next_changed_at IS NULL OR sp.next_changed_at >= b.bucket_end)
+            GROUP BY b.bucket_start, sp.status
+            ORDER BY b.bucket_start, sp.status
+            "#,
+            SAMPLE_FILTER
+        ))
+        .bind(starts)
+        .bind(ends)
+        .bind(&filter.project)
+        .bind(&filter.sample_type)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn sequencing_throughput(
+        &self,
+        buckets: &[TimeBucket],
+        filter: &AnalyticsFilter,
+    ) -> Result<Vec<(DateTime<Utc>, i64, i64)>, sqlx::Error> {
+        let (starts, ends) = bucket_bounds(buckets);
+        // Jobs list their samples in metadata.sample_ids
+        sqlx::query_as::<_, (DateTime<Utc>, i64, i64)>(&format!(
+            r#"
+            WITH jobs AS (
+                SELECT
+                    MIN(h.changed_at) FILTER (WHERE h.status = 'in_progress') AS started_at,
+                    MIN(h.changed_at) FILTER (WHERE h.status = 'completed') AS completed_at
+                FROM sequencing_job_status_history h
+                JOIN sequencing_jobs j ON j.id = h.job_id
+                WHERE ($3::text IS NULL AND $4::text IS NULL)
+                    OR EXISTS (
+                        SELECT 1
+                        FROM samples s
+                        WHERE s.id::text IN (
+                            SELECT jsonb_array_elements_text(
+                                CASE jsonb_typeof(j.metadata->'sample_ids')
+                                    WHEN 'array' THEN j.metadata->'sample_ids'
+                                    ELSE '[]'::jsonb
+                                END
+                            )
+                        )
+                        AND {}
+                    )
+                GROUP BY h.job_id
+            )
+            SELECT
+                b.bucket_start,
+                COUNT(*) FILTER (
+                    WHERE j.started_at >= b.bucket_start AND j.started_at < b.bucket_end
+                ),
+                COUNT(*) FILTER (
+                    WHERE j.completed_at >= b.bucket_start AND j.completed_at < b.bucket_end
+                )
+            FROM unnest($1::timestamptz[], $2::timestamptz[]) AS b(bucket_start, bucket_end)
+            LEFT JOIN jobs j
+                ON (j.started_at >= b.bucket_start AND j.started_at < b.bucket_end)
+                OR (j.completed_at >= b.bucket_start AND j.completed_at < b.bucket_end)
+            GROUP BY b.bucket_start
+            ORDER BY b.bucket_start
+            "#,
+            SAMPLE_FILTER
+        ))
+        .bind(starts)
+        .bind(ends)
+        .bind(&filter.project)
+        .bind(&filter.sample_type)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn storage_utilization(
+        &self,
+        buckets: &[TimeBucket],
+    ) -> Result<Vec<(DateTime<Utc>, i64, i64)>, sqlx::Error> {
+        let (starts, ends) = bucket_bounds(buckets);
+        sqlx::query_as::<_, (DateTime<Utc>, i64, i64)>(
+            r#"
+            SELECT
+                b.bucket_start,
+                COALESCE(SUM(u.current_usage), 0)::BIGINT,
+                COALESCE(SUM(u.capacity), 0)::BIGINT
+            FROM unnest($1::timestamptz[], $2::timestamptz[]) AS b(bucket_start, bucket_end)
+            LEFT JOIN LATERAL (
+                SELECT DISTINCT ON (h.location_id) h.current_usage, h.capacity
+                FROM storage_usage_history h
+                WHERE h.recorded_at < b.bucket_end
+                ORDER BY h.location_id, h.recorded_at DESC, h.id DESC
+            ) u ON TRUE
+            GROUP BY b.bucket_start
+            ORDER BY b.bucket_start
+            "#,
+        )
+        .bind(starts)
+        .bind(ends)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn sample_turnaround(
+        &self,
+        buckets: &[TimeBucket],
+        filter: &AnalyticsFilter,
+    ) -> Result<Vec<TurnaroundRow>, sqlx::Error> {
+        let (starts, ends) = bucket_bounds(buckets);
+        sqlx::query_as::<_, TurnaroundRow>(&format!(
+            r#"
+            WITH completed AS (
+                SELECT
+                    MIN(h.changed_at) AS completed_at,
+                    EXTRACT(EPOCH FROM MIN(h.changed_at) - s.created_at)::float8 / 3600 AS hours
+                FROM sample_status_history h
+                JOIN samples s ON s.id = h.sample_id
+                WHERE h.status = 'completed' AND {}
+                GROUP BY s.id, s.created_at
+            )
+            SELECT
+                b.bucket_start,
+                COUNT(c.hours) AS completed,
+                percentile_cont(0.5) WITHIN GROUP (ORDER BY c.hours) AS p50_hours,
+                percentile_cont(0.9) WITHIN GROUP (ORDER BY c.hours) AS p90_hours,
+                percentile_cont(0.95) WITHIN GROUP (ORDER BY c.hours) AS p95_hours
+            FROM unnest($1::timestamptz[], $2::timestamptz[]) AS b(bucket_start, bucket_end)
+            LEFT JOIN completed c
+                ON c.completed_at >= b.bucket_start
+                AND c.completed_at < b.bucket_end
+            GROUP BY b.bucket_start
+            ORDER BY b.bucket_start
+            "#,
+            SAMPLE_FILTER
+        ))
+        .bind(starts)
+        .bind(ends)
+        .bind(&filter.project)
+        .bind(&filter.sample_type)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -32,8 +32,11 @@ pub trait RepositoryFactory {
         Error = sqlx::Error,
     >;
 
+    type TemplateVersionRepo: template_version_repository::TemplateVersionRepository;
+
     fn template_repository(&self) -> Self::TemplateRepo;
     fn sample_repository(&self) -> Self::SampleRepo;
+    fn template_version_repository(&self) -> Self::TemplateVersionRepo;
 }
 
 /// PostgreSQL repository factory
@@ -51,6 +54,7 @@ impl PostgresRepositoryFactory {
 impl RepositoryFactory for PostgresRepositoryFactory {
     type TemplateRepo = PostgresTemplateRepository;
     type SampleRepo = PostgresSampleRepository;
+    type TemplateVersionRepo = template_version_repository::PostgresTemplateVersionRepository;
 
     fn template_repository(&self) -> Self::TemplateRepo {
         PostgresTemplateRepository::new(self.pool.clone())
@@ -59,6 +63,10 @@ impl RepositoryFactory for PostgresRepositoryFactory {
     fn sample_repository(&self) -> Self::SampleRepo {
         PostgresSampleRepository::new(self.pool.clone())
     }
+
+    fn template_version_repository(&self) -> Self::TemplateVersionRepo {
+        template_version_repository::PostgresTemplateVersionRepository::new(self.pool.clone())
+    }
 }
 
 /// Concrete PostgreSQL template repository
@@ -230,8 +238,8 @@ impl
     ) -> Result<crate::sample_submission::Sample, Self::Error> {
         sqlx::query_as::<_, crate::sample_submission::Sample>(
             r#"
-            INSERT INTO samples (name, barcode, location, status, metadata)
-            VALUES ($1, $2, $3, 'pending', $4)
+            INSERT INTO samples (name, barcode, location, status, metadata, template_version_id)
+            VALUES ($1, $2, $3, 'pending', $4, $5)
             RETURNING *
             "#,
         )
@@ -239,6 +247,7 @@ impl
         .bind(&data.barcode)
         .bind(&data.location)
         .bind(data.metadata.unwrap_or(serde_json::json!({})))
+        .bind(data.template_version_id)
         .fetch_one(&self.pool)
         .await
     }
@@ -331,3 +340,18 @@ impl InMemoryRepositoryFactory {
 
 // Export storage repository module
 pub mod storage_repository;
+
+// Export shipment repository module
+pub mod shipment_repository;
+
+// Export template version repository module
+pub mod template_version_repository;
+
+// Export saved report repository module
+pub mod saved_report_repository;
+
+// Export report schedule repository module
+pub mod report_schedule_repository;
+
+// Export analytics repository module
+pub mod analytics_repository;
@@ -0,0 +1,322 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::report::{
+    CreateReportSchedule, DatabaseSchema, ReportRun, ReportRunStatus, ReportSchedule,
+};
+use crate::models::user::UserRole;
+use crate::services::report_query::load_schema;
+
+/// Outcome of a report run, recorded when it finishes
+#[derive(Debug, Clone, Default)]
+pub struct FinishedRun {
+    pub row_count: Option<i32>,
+    pub truncated: bool,
+    pub artifact_path: Option<String>,
+    pub artifact_size: Option<i64>,
+    pub delivered_to: Vec<String>,
+    pub error_message: Option<String>,
+}
+
+/// Report schedule repository trait for database operations
+#[async_trait]
+pub trait ReportScheduleRepository: Send + Sync {
+    async fn create_schedule(
+        &self,
+        owner_id: Uuid,
+        schedule: &CreateReportSchedule,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> Result<ReportSchedule, sqlx::Error>;
+    async fn get_schedule(&self, id: Uuid) -> Result<Option<ReportSchedule>, sqlx::Error>;
+    /// Schedules a user owns. With `all` set, every schedule.
+    async fn list_schedules(
+        &self,
+        owner_id: Uuid,
+        all: bool,
+    ) -> Result<Vec<ReportSchedule>, sqlx::Error>;
+    async fn update_schedule(
+        &self,
+        id: Uuid,
+        schedule: &CreateReportSchedule,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<ReportSchedule>, sqlx::Error>;
+    async fn delete_schedule(&self, id: Uuid) -> Result<bool, sqlx::Error>;
+    /// Enabled schedules whose next run is at or before `now`, oldest first
+    async fn list_due_schedules(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ReportSchedule>, sqlx::Error>;
+    /// Move a due schedule to its next run. Returns false when another
+    /// instance claimed it first.
+    async fn claim_schedule(
+        &self,
+        id: Uuid,
+        due_at: DateTime<Utc>,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> Result<bool, sqlx::Error>;
+    async fn start_run(
+        &self,
+        schedule: &ReportSchedule,
+        manual: bool,
+    ) -> Result<ReportRun, sqlx::Error>;
+    /// Record the outcome of a run on the run and its schedule
+    async fn finish_run(
+        &self,
+        run_id: Uuid,
+        status: ReportRunStatus,
+        outcome: &FinishedRun,
+    ) -> Result<ReportRun, sqlx::Error>;
+    async fn list_runs(&self, schedule_id: Uuid, limit: i64)
+        -> Result<Vec<ReportRun>, sqlx::Error>;
+    async fn get_run(&self, id: Uuid) -> Result<Option<ReportRun>, sqlx::Error>;
+    /// Email address and role of a schedule's owner
+    async fn get_owner(&self, user_id: Uuid) -> Result<Option<(String, UserRole)>, sqlx::Error>;
+    async fn load_schema(&self) -> Result<DatabaseSchema, sqlx::Error>;
+}
+
+/// PostgreSQL implementation of report schedule repository
+#[derive(Debug)]
+pub struct PostgresReportScheduleRepository {
+    pool: PgPool,
+}
+
+impl PostgresReportScheduleRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ReportScheduleRepository for PostgresReportScheduleRepository {
+    async fn create_schedule(
+        &self,
+        owner_id: Uuid,
+        schedule: &CreateReportSchedule,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> Result<ReportSchedule, sqlx::Error> {
+        sqlx::query_as::<_, ReportSchedule>(
+            r#"
+            INSERT INTO report_schedules (report_id, owner_id, cron_expression, parameters, format, recipients, enabled, next_run_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING *
+            "#,
+        )
+        .bind(schedule.report_id)
+        .bind(owner_id)
+        .bind(&schedule.cron_expression)
+        .bind(sqlx::types::Json(&schedule.parameters))
+        .bind(schedule.format)
+        .bind(&schedule.recipients)
+        .bind(schedule.enabled)
+        .bind(next_run_at)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn get_schedule(&self, id: Uuid) -> Result<Option<ReportSchedule>, sqlx::Error> {
+        sqlx::query_as::<_, ReportSchedule>("SELECT * FROM report_schedules WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn list_schedules(
+        &self,
+        owner_id: Uuid,
+        all: bool,
+    ) -> Result<Vec<ReportSchedule>, sqlx::Error> {
+        sqlx::query_as::<_, ReportSchedule>(
+            "SELECT * FROM report_schedules WHERE $2 OR owner_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(owner_id)
+        .bind(all)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn update_schedule(
+        &self,
+        id: Uuid,
+        schedule: &CreateReportSchedule,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<ReportSchedule>, sqlx::Error> {
+        sqlx::query_as::<_, ReportSchedule>(
+            r#"
+            UPDATE report_schedules
+            SET report_id = $2,
+                cron_expression = $3,
+                parameters = $4,
+                format = $5,
+                recipients = $6,
+                enabled = $7,
+                next_run_at = $8
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(schedule.report_id)
+        .bind(&schedule.cron_expression)
+        .bind(sqlx::types::Json(&schedule.parameters))
+        .bind(schedule.format)
+        .bind(&schedule.recipients)
+        .bind(schedule.enabled)
+        .bind(next_run_at)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn delete_schedule(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM report_schedules WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_due_schedules(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<ReportSchedule>, sqlx::Error> {
+        sqlx::query_as::<_, ReportSchedule>(
+            r#"
+            SELECT * FROM report_schedules
+            WHERE enabled AND next_run_at <= $1
+            ORDER BY next_run_at
+            LIMIT $2
+            "#,
+        )
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn claim_schedule(
+        &self,
+        id: Uuid,
+        due_at: DateTime<Utc>,
+        next_run_at: Option<DateTime<Utc>>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE report_schedules
+            SET next_run_at = $3
+            WHERE id = $1 AND enabled AND next_run_at = $2
+            "#,
+        )
+        .bind(id)
+        .bind(due_at)
+        .bind(next_run_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn start_run(
+        &self,
+        schedule: &ReportSchedule,
+        manual: bool,
+    ) -> Result<ReportRun, sqlx::Error> {
+        sqlx::query_as::<_, ReportRun>(
+            r#"
+            INSERT INTO report_runs (schedule_id, report_id, manual)
+            VALUES ($1, $2, $3)
+            RETURNING *
+            "#,
+        )
+        .bind(schedule.id)
+        .bind(schedule.report_id)
+        .bind(manual)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn finish_run(
+        &self,
+        run_id: Uuid,
+        status: ReportRunStatus,
+        outcome: &FinishedRun,
+    ) -> Result<ReportRun, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let run = sqlx::query_as::<_, ReportRun>(
+            r#"
+            UPDATE report_runs
+            SET status = $2,
+                row_count = $3,
+                truncated = $4,
+                artifact_path = $5,
+                artifact_size = $6,
+                delivered_to = $7,
+                error_message = $8,
+                finished_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(run_id)
+        .bind(status)
+        .bind(outcome.row_count)
+        .bind(outcome.truncated)
+        .bind(&outcome.artifact_path)
+        .bind(outcome.artifact_size)
+        .bind(&outcome.delivered_to)
+        .bind(&outcome.error_message)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query("UPDATE report_schedules SET last_run_at = $2, last_status = $3 WHERE id = $1")
+            .bind(run.schedule_id)
+            .bind(run.started_at)
+            .bind(status)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(run)
+    }
+
+    async fn list_runs(
+        &self,
+        schedule_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<ReportRun>, sqlx::Error> {
+        sqlx::query_as::<_, ReportRun>(
+            r#"
+            SELECT * FROM report_runs
+            WHERE schedule_id = $1
+            ORDER BY started_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(schedule_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn get_run(&self, id: Uuid) -> Result<Option<ReportRun>, sqlx::Error> {
+        sqlx::query_as::<_, ReportRun>("SELECT * FROM report_runs WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn get_owner(&self, user_id: Uuid) -> Result<Option<(String, UserRole)>, sqlx::Error> {
+        sqlx::query_as::<_, (String, UserRole)>(
+            "SELECT email, role FROM users WHERE id = $1 AND status = 'active'",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn load_schema(&self) -> Result<DatabaseSchema, sqlx::Error> {
+        load_schema(&self.pool).await
+    }
+}
@@ -0,0 +1,157 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::report::{CreateSavedReport, SavedReport};
+use crate::models::user::UserRole;
+
+/// Saved report repository trait for database operations
+#[async_trait]
+pub trait SavedReportRepository: Send + Sync {
+    async fn create_report(
+        &self,
+        owner_id: Uuid,
+        report: &CreateSavedReport,
+    ) -> Result<SavedReport, sqlx::Error>;
+    async fn get_report(&self, id: Uuid) -> Result<Option<SavedReport>, sqlx::Error>;
+    /// Reports a user owns or that are shared with them, system reports
+    /// first. With `all` set, every report.
+    async fn list_visible_reports(
+        &self,
+        user_id: Uuid,
+        role: &UserRole,
+        all: bool,
+    ) -> Result<Vec<SavedReport>, sqlx::Error>;
+    /// Reports shared with everyone, system reports first
+    async fn list_public_reports(&self) -> Result<Vec<SavedReport>, sqlx::Error>;
+    /// Replace a report's definition. Returns `None` for missing and system
+    /// reports.
+    async fn update_report(
+        &self,
+        id: Uuid,
+        report: &CreateSavedReport,
+    ) -> Result<Option<SavedReport>, sqlx::Error>;
+    /// Delete a report. Returns false for missing and system reports.
+    async fn delete_report(&self, id: Uuid) -> Result<bool, sqlx::Error>;
+}
+
+/// PostgreSQL implementation of saved report repository
+#[derive(Debug)]
+pub struct PostgresSavedReportRepository {
+    pool: PgPool,
+}
+
+impl PostgresSavedReportRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SavedReportRepository for PostgresSavedReportRepository {
+    async fn create_report(
+        &self,
+        owner_id: Uuid,
+        report: &CreateSavedReport,
+    ) -> Result<SavedReport, sqlx::Error> {
+        sqlx::query_as::<_, SavedReport>(
+            r#"
+            INSERT INTO saved_reports (name, description, category, query, parameters, owner_id, visibility, shared_role)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING *
+            "#,
+        )
+        .bind(&report.name)
+        .bind(&report.description)
+        .bind(&report.category)
+        .bind(sqlx::types::Json(&report.query))
+        .bind(sqlx::types::Json(&report.parameters))
+        .bind(owner_id)
+        .bind(report.visibility)
+        .bind(&report.shared_role)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn get_report(&self, id: Uuid) -> Result<Option<SavedReport>, sqlx::Error> {
+        sqlx::query_as::<_, SavedReport>("SELECT * FROM saved_reports WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn list_visible_reports(
+        &self,
+        user_id: Uuid,
+        role: &UserRole,
+        all: bool,
+    ) -> Result<Vec<SavedReport>, sqlx::Error> {
+        sqlx::query_as::<_, SavedReport>(
+            r#"
+            SELECT * FROM saved_reports
+            WHERE $3
+                OR is_system
+                OR owner_id = $1
+                OR visibility = 'everyone'
+                OR (visibility = 'role' AND shared_role = $2)
+            ORDER BY is_system DESC, category NULLS LAST, name
+            "#,
+        )
+        .bind(user_id)
+        .bind(role)
+        .bind(all)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn list_public_reports(&self) -> Result<Vec<SavedReport>, sqlx::Error> {
+        sqlx::query_as::<_, SavedReport>(
+            r#"
+            SELECT * FROM saved_reports
+            WHERE is_system OR visibility = 'everyone'
+            ORDER BY is_system DESC, category NULLS LAST, name
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn update_report(
+        &self,
+        id: Uuid,
+        report: &CreateSavedReport,
+    ) -> Result<Option<SavedReport>, sqlx::Error> {
+        sqlx::query_as::<_, SavedReport>(
+            r#"
+            UPDATE saved_reports
+            SET name = $2,
+                description = $3,
+                category = $4,
+                query = $5,
+                parameters = $6,
+                visibility = $7,
+                shared_role = $8
+            WHERE id = $1 AND NOT is_system
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(&report.name)
+        .bind(&report.description)
+        .bind(&report.category)
+        .bind(sqlx::types::Json(&report.query))
+        .bind(sqlx::types::Json(&report.parameters))
+        .bind(report.visibility)
+        .bind(&report.shared_role)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn delete_report(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM saved_reports WHERE id = $1 AND NOT is_system")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
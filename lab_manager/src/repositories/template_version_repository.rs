@@ -0,0 +1,198 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::template::{FieldChange, TemplateField, TemplateVersion};
+use crate::sample_submission::{CreateSample, Sample};
+
+/// Template version repository trait for database operations
+#[async_trait]
+pub trait TemplateVersionRepository: Send + Sync {
+    /// Insert a version under the given number. Fails with a unique violation
+    /// when another version took the number first.
+    async fn create_version(
+        &self,
+        template_id: Uuid,
+        version_number: i32,
+        fields: &[TemplateField],
+        changes: &[FieldChange],
+        changelog: Option<String>,
+        created_by: Option<String>,
+    ) -> Result<TemplateVersion, sqlx::Error>;
+    async fn latest_version(
+        &self,
+        template_id: Uuid,
+    ) -> Result<Option<TemplateVersion>, sqlx::Error>;
+    async fn get_version(
+        &self,
+        template_id: Uuid,
+        version_number: i32,
+    ) -> Result<Option<TemplateVersion>, sqlx::Error>;
+    async fn find_version(&self, version_id: Uuid) -> Result<Option<TemplateVersion>, sqlx::Error>;
+    /// Versions of a template, newest first
+    async fn list_versions(&self, template_id: Uuid) -> Result<Vec<TemplateVersion>, sqlx::Error>;
+    /// Samples submitted with a version
+    async fn list_version_samples(&self, version_id: Uuid) -> Result<Vec<Sample>, sqlx::Error>;
+    /// Which of `barcodes` are already taken by samples
+    async fn existing_barcodes(&self, barcodes: &[String]) -> Result<Vec<String>, sqlx::Error>;
+    /// Insert samples submitted with a version, all or none. Fails with a
+    /// unique violation when a barcode is taken first.
+    async fn create_samples(
+        &self,
+        version_id: Uuid,
+        samples: &[CreateSample],
+    ) -> Result<Vec<Sample>, sqlx::Error>;
+}
+
+/// PostgreSQL implementation of template version repository
+#[derive(Debug)]
+pub struct PostgresTemplateVersionRepository {
+    pool: PgPool,
+}
+
+impl PostgresTemplateVersionRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl TemplateVersionRepository for PostgresTemplateVersionRepository {
+    async fn create_version(
+        &self,
+        template_id: Uuid,
+        version_number: i32,
+        fields: &[TemplateField],
+        changes: &[FieldChange],
+        changelog: Option<String>,
+        created_by: Option<String>,
+    ) -> Result<TemplateVersion, sqlx::Error> {
+        sqlx::query_as::<_, TemplateVersion>(
+            r#"
+            INSERT INTO template_versions (template_id, version_number, fields, changes, changelog, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+        )
+        .bind(template_id)
+        .bind(version_number)
+        .bind(sqlx::types::Json(fields))
+        .bind(sqlx::types::Json(changes))
+        .bind(changelog)
+        .bind(created_by)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn latest_version(
+        &self,
+        template_id: Uuid,
+    ) -> Result<Option<TemplateVersion>, sqlx::Error> {
+        sqlx::query_as::<_, TemplateVersion>(
+            r#"
+            SELECT * FROM template_versions
+            WHERE template_id = $1
+            ORDER BY version_number DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(template_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn get_version(
+        &self,
+        template_id: Uuid,
+        version_number: i32,
+    ) -> Result<Option<TemplateVersion>, sqlx::Error> {
+        sqlx::query_as::<_, TemplateVersion>(
+            "SELECT * FROM template_versions WHERE template_id = $1 AND version_number = $2",
+        )
+        .bind(template_id)
+        .bind(version_number)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn find_version(&self, version_id: Uuid) -> Result<Option<TemplateVersion>, sqlx::Error> {
+        sqlx::query_as::<_, TemplateVersion>("SELECT * FROM template_versions WHERE id = $1")
+            .bind(version_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn list_versions(&self, template_id: Uuid) -> Result<Vec<TemplateVersion>, sqlx::Error> {
+        sqlx::query_as::<_, TemplateVersion>(
+            r#"
+            SELECT * FROM template_versions
+            WHERE template_id = $1
+            ORDER BY version_number DESC
+            "#,
+        )
+        .bind(template_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn list_version_samples(&self, version_id: Uuid) -> Result<Vec<Sample>, sqlx::Error> {
+        sqlx::query_as::<_, Sample>(
+            r#"
+            SELECT * FROM samples
+            WHERE template_version_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(version_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn existing_barcodes(&self, barcodes: &[String]) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT barcode FROM samples WHERE barcode = ANY($1)")
+            .bind(barcodes)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn create_samples(
+        &self,
+        version_id: Uuid,
+        samples: &[CreateSample],
+    ) -> Result<Vec<Sample>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut created = Vec::with_capacity(samples.len());
+
+        for chunk in samples.chunks(1000) {
+            let names: Vec<&str> = chunk.iter().map(|s| s.name.as_str()).collect();
+            let barcodes: Vec<&str> = chunk.iter().map(|s| s.barcode.as_str()).collect();
+            let locations: Vec<&str> = chunk.iter().map(|s| s.location.as_str()).collect();
+            let metadata: Vec<serde_json::Value> = chunk
+                .iter()
+                .map(|s| s.metadata.clone().unwrap_or_else(|| serde_json::json!({})))
+                .collect();
+
+            let inserted = sqlx::query_as::<_, Sample>(
+                r#"
+                INSERT INTO samples (name, barcode, location, status, metadata, template_version_id)
+                SELECT name, barcode, location, 'pending', metadata, $5
+                FROM UNNEST($1::text[], $2::text[], $3::text[], $4::jsonb[])
+                    WITH ORDINALITY AS t(name, barcode, location, metadata, position)
+                ORDER BY position
+                RETURNING id, name, barcode, location, status, created_at, updated_at, metadata, template_version_id
+                "#,
+            )
+            .bind(&names)
+            .bind(&barcodes)
+            .bind(&locations)
+            .bind(&metadata)
+            .bind(version_id)
+            .fetch_all(&mut *tx)
+            .await?;
+            created.extend(inserted);
+        }
+
+        tx.commit().await?;
+        Ok(created)
+    }
+}
@@ -1,4 +1,5 @@
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{delete, get, post, put},
     Router,
 };
@@ -7,8 +8,8 @@ use tower_http::cors::CorsLayer;
 use crate::{
     assembly::AppComponents,
     handlers::{
-        dashboard, health, rag_proxy, reports, samples, sequencing, spreadsheets, storage,
-        templates, users,
+        dashboard, health, rag_proxy, reports, samples, sequencing, shipments, spreadsheets,
+        storage, templates, users,
     },
 };
 
@@ -17,6 +18,10 @@ pub fn health_routes() -> Router<AppComponents> {
     Router::new()
         .route("/health", get(health::health_check))
         .route("/api/dashboard/stats", get(dashboard::get_dashboard_stats))
+        .route(
+            "/api/dashboard/analytics",
+            get(dashboard::get_dashboard_analytics),
+        )
 }
 
 /// Template management routes
@@ -28,6 +33,38 @@ pub fn template_routes() -> Router<AppComponents> {
         .route("/api/templates/:id", put(templates::update_template))
         .route("/api/templates/:id/data", get(templates::get_template_data))
         .route("/api/templates/:id", delete(templates::delete_template))
+        .route(
+            "/api/templates/:id/versions",
+            post(templates::publish_template_version),
+        )
+        .route(
+            "/api/templates/:id/versions",
+            get(templates::list_template_versions),
+        )
+        .route(
+            "/api/templates/:id/versions/latest",
+            get(templates::get_latest_template_version),
+        )
+        .route(
+            "/api/templates/:id/versions/:version",
+            get(templates::get_template_version),
+        )
+        .route(
+            "/api/templates/:id/versions/:version/samples",
+            get(templates::list_template_version_samples),
+        )
+        .route(
+            "/api/templates/:id/changelog",
+            get(templates::get_template_changelog),
+        )
+        .route(
+            "/api/templates/:id/workbook",
+            get(templates::download_template_workbook),
+        )
+        .route(
+            "/api/templates/:id/submissions",
+            post(templates::submit_template_workbook),
+        )
 }
 
 /// RAG proxy routes - forward requests to RAG API Bridge on port 3002
@@ -111,14 +148,116 @@ pub fn storage_routes() -> Router<AppComponents> {
             get(storage::scan_sample_barcode),
         )
         .route("/api/storage/capacity", get(storage::get_capacity_overview))
+        .route(
+            "/api/storage/capacity/reconcile",
+            post(storage::reconcile_capacity_usage),
+        )
+        .route(
+            "/api/storage/locations/:id/scan-reconciliation",
+            post(storage::reconcile_location_scan),
+        )
+        .route("/api/storage/checkout", post(storage::checkout_sample))
+        .route("/api/storage/checkin", post(storage::check_in_sample))
+        .route("/api/storage/checkouts", get(storage::list_checkouts))
+        .route(
+            "/api/storage/samples/:barcode/time-out",
+            get(storage::get_time_out_of_storage),
+        )
+        .route(
+            "/api/storage/locations/:id/evacuation-plan",
+            post(storage::plan_evacuation),
+        )
+        .route(
+            "/api/storage/evacuation/pick-list",
+            post(storage::evacuation_pick_list),
+        )
+        .route(
+            "/api/storage/evacuation/execute",
+            post(storage::execute_evacuation),
+        )
+}
+
+/// Shipment routes for sample transfers between sites
+pub fn shipment_routes() -> Router<AppComponents> {
+    Router::new()
+        .route("/api/shipments", get(shipments::list_shipments))
+        .route("/api/shipments", post(shipments::create_shipment))
+        .route("/api/shipments/import", post(shipments::import_manifest))
+        .route("/api/shipments/:id", get(shipments::get_shipment))
+        .route("/api/shipments/:id/dispatch", post(shipments::dispatch_shipment))
+        .route("/api/shipments/:id/receive", post(shipments::receive_shipment))
+        .route("/api/shipments/:id/cancel", post(shipments::cancel_shipment))
+        .route("/api/shipments/:id/manifest", get(shipments::export_manifest))
 }
 
 /// Reports and analytics routes
 pub fn reports_routes() -> Router<AppComponents> {
     Router::new()
+        .route("/api/reports/query", post(reports::query_report))
         .route("/api/reports/execute", post(reports::execute_report))
+        .route("/api/reports/ask", post(reports::ask_report))
+        .route("/api/reports/ask/run", post(reports::run_report_draft))
+        .route(
+            "/api/reports/query/export",
+            post(reports::export_query_report),
+        )
+        .route(
+            "/api/reports/execute/export",
+            post(reports::export_sql_report),
+        )
+        .route(
+            "/api/reports/executions",
+            get(reports::list_running_reports),
+        )
+        .route(
+            "/api/reports/executions/:id/cancel",
+            post(reports::cancel_report),
+        )
         .route("/api/reports/templates", get(reports::get_report_templates))
+        .route("/api/reports/templates", post(reports::save_report_template))
         .route("/api/reports/schema", get(reports::get_schema))
+        .route("/api/reports/saved", get(reports::list_reports))
+        .route("/api/reports/saved", post(reports::create_custom_report))
+        .route("/api/reports/saved/:id", get(reports::get_report))
+        .route("/api/reports/saved/:id", put(reports::update_report))
+        .route("/api/reports/saved/:id", delete(reports::delete_report))
+        .route("/api/reports/saved/:id/run", post(reports::run_saved_report))
+        .route(
+            "/api/reports/saved/:id/export",
+            post(reports::export_saved_report),
+        )
+        .route(
+            "/api/reports/schedules",
+            get(reports::list_report_schedules),
+        )
+        .route(
+            "/api/reports/schedules",
+            post(reports::create_report_schedule),
+        )
+        .route(
+            "/api/reports/schedules/:id",
+            get(reports::get_report_schedule),
+        )
+        .route(
+            "/api/reports/schedules/:id",
+            put(reports::update_report_schedule),
+        )
+        .route(
+            "/api/reports/schedules/:id",
+            delete(reports::delete_report_schedule),
+        )
+        .route(
+            "/api/reports/schedules/:id/run",
+            post(reports::run_report_schedule),
+        )
+        .route(
+            "/api/reports/schedules/:id/runs",
+            get(reports::list_report_runs),
+        )
+        .route(
+            "/api/reports/runs/:id/artifact",
+            get(reports::download_report_run),
+        )
 }
 
 /// Spreadsheet processing routes
@@ -128,7 +267,9 @@ pub fn spreadsheet_routes() -> Router<AppComponents> {
     let router = Router::new()
         .route(
             "/api/spreadsheets/upload",
-            post(spreadsheets::upload_spreadsheet),
+            post(spreadsheets::upload_spreadsheet).layer(DefaultBodyLimit::max(
+                spreadsheets::MAX_SPREADSHEET_UPLOAD_BYTES,
+            )),
         )
         .route(
             "/api/spreadsheets/upload-multiple",
@@ -139,6 +280,18 @@ pub fn spreadsheet_routes() -> Router<AppComponents> {
             post(spreadsheets::get_sheet_names),
         )
         .route("/api/spreadsheets/search", get(spreadsheets::search_data))
+        .route(
+            "/api/spreadsheets/search/ranked",
+            get(spreadsheets::ranked_search),
+        )
+        .route(
+            "/api/spreadsheets/search/export",
+            get(spreadsheets::export_search),
+        )
+        .route(
+            "/api/spreadsheets/aggregate",
+            post(spreadsheets::aggregate_records),
+        )
         .route(
             "/api/spreadsheets/datasets",
             get(spreadsheets::list_datasets),
@@ -151,6 +304,26 @@ pub fn spreadsheet_routes() -> Router<AppComponents> {
             "/api/spreadsheets/datasets/:id",
             delete(spreadsheets::delete_dataset),
         )
+        .route(
+            "/api/spreadsheets/datasets/:id/export",
+            get(spreadsheets::export_dataset),
+        )
+        .route(
+            "/api/spreadsheets/datasets/:id/samples",
+            get(spreadsheets::list_dataset_samples),
+        )
+        .route(
+            "/api/spreadsheets/datasets/:id/samples",
+            post(spreadsheets::convert_dataset_samples),
+        )
+        .route(
+            "/api/spreadsheets/datasets/:id/samples/preview",
+            post(spreadsheets::preview_dataset_samples),
+        )
+        .route(
+            "/api/spreadsheets/datasets/:id/progress",
+            get(spreadsheets::get_upload_progress),
+        )
         .route(
             "/api/spreadsheets/datasets/:id/analyze",
             get(spreadsheets::analyze_dataset),
@@ -159,6 +332,67 @@ pub fn spreadsheet_routes() -> Router<AppComponents> {
             "/api/spreadsheets/datasets/:id/columns/:column_name/analyze",
             get(spreadsheets::analyze_column),
         )
+        .route(
+            "/api/spreadsheets/series",
+            post(spreadsheets::create_series),
+        )
+        .route("/api/spreadsheets/series", get(spreadsheets::list_series))
+        .route(
+            "/api/spreadsheets/series/:id",
+            get(spreadsheets::get_series),
+        )
+        .route(
+            "/api/spreadsheets/series/:id/versions",
+            post(spreadsheets::add_series_version),
+        )
+        .route(
+            "/api/spreadsheets/series/:id/diff",
+            get(spreadsheets::diff_series_versions),
+        )
+        .route(
+            "/api/spreadsheets/series/:id/search",
+            get(spreadsheets::search_series),
+        )
+        .route(
+            "/api/spreadsheets/mapping-profiles",
+            post(spreadsheets::create_mapping_profile),
+        )
+        .route(
+            "/api/spreadsheets/mapping-profiles",
+            get(spreadsheets::list_mapping_profiles),
+        )
+        .route(
+            "/api/spreadsheets/mapping-profiles/:id",
+            get(spreadsheets::get_mapping_profile),
+        )
+        .route(
+            "/api/spreadsheets/mapping-profiles/:id",
+            delete(spreadsheets::delete_mapping_profile),
+        )
+        .route(
+            "/api/spreadsheets/rule-sets",
+            post(spreadsheets::create_rule_set),
+        )
+        .route(
+            "/api/spreadsheets/rule-sets",
+            get(spreadsheets::list_rule_sets),
+        )
+        .route(
+            "/api/spreadsheets/rule-sets/:id",
+            get(spreadsheets::get_rule_set),
+        )
+        .route(
+            "/api/spreadsheets/rule-sets/:id",
+            delete(spreadsheets::delete_rule_set),
+        )
+        .route(
+            "/api/spreadsheets/datasets/:id/validate",
+            post(spreadsheets::validate_dataset),
+        )
+        .route(
+            "/api/spreadsheets/datasets/:id/quality-report",
+            get(spreadsheets::get_quality_report),
+        )
         .route(
             "/api/spreadsheets/filters",
             get(spreadsheets::get_available_filters),
@@ -221,6 +455,7 @@ pub fn create_authenticated_routes() -> Router<AppComponents> {
         .merge(sample_routes())
         .merge(sequencing_routes())
         .merge(storage_routes())
+        .merge(shipment_routes())
         .merge(reports_routes())
         .merge(spreadsheet_routes())
         .merge(user_routes())
@@ -242,6 +477,10 @@ pub fn create_app_router() -> Router<AppComponents> {
         .route("/auth/reset-password", post(users::reset_password))
         // Dashboard routes (require authentication)
         .route("/dashboard/stats", get(dashboard::get_dashboard_stats))
+        .route(
+            "/dashboard/analytics",
+            get(dashboard::get_dashboard_analytics),
+        )
         // Sample management routes
         .route("/samples", get(samples::list_samples))
         .route("/samples", post(samples::create_sample))
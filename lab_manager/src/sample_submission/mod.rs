@@ -13,6 +13,8 @@ pub struct Sample {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub metadata: serde_json::Value,
+    /// Template version the sample was submitted with
+    pub template_version_id: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, PartialEq)]
@@ -21,6 +23,8 @@ pub enum SampleStatus {
     Pending,
     Validated,
     InStorage,
+    InTransit,
+    Shipped,
     InSequencing,
     Completed,
 }
@@ -31,6 +35,8 @@ pub struct CreateSample {
     pub barcode: String,
     pub location: String,
     pub metadata: Option<serde_json::Value>,
+    #[serde(default)]
+    pub template_version_id: Option<Uuid>,
 }
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
@@ -55,15 +61,16 @@ impl SampleSubmissionManager {
     pub async fn create_sample(&self, sample: CreateSample) -> Result<Sample, sqlx::Error> {
         sqlx::query_as::<_, Sample>(
             r#"
-            INSERT INTO samples (name, barcode, location, status, metadata)
-            VALUES ($1, $2, $3, 'pending', $4)
-            RETURNING id, name, barcode, location, status, created_at, updated_at, metadata
+            INSERT INTO samples (name, barcode, location, status, metadata, template_version_id)
+            VALUES ($1, $2, $3, 'pending', $4, $5)
+            RETURNING id, name, barcode, location, status, created_at, updated_at, metadata, template_version_id
             "#,
         )
         .bind(&sample.name)
         .bind(&sample.barcode)
         .bind(&sample.location)
         .bind(sample.metadata.unwrap_or(serde_json::json!({})))
+        .bind(sample.template_version_id)
         .fetch_one(&self.pool)
         .await
     }
@@ -74,7 +81,7 @@ impl SampleSubmissionManager {
             UPDATE samples
             SET status = 'validated', updated_at = NOW()
             WHERE id = $1
-            RETURNING id, name, barcode, location, status, created_at, updated_at, metadata
+            RETURNING id, name, barcode, location, status, created_at, updated_at, metadata, template_version_id
             "#,
         )
         .bind(sample_id)
@@ -85,7 +92,7 @@ impl SampleSubmissionManager {
     pub async fn get_sample(&self, sample_id: Uuid) -> Result<Sample, sqlx::Error> {
         sqlx::query_as::<_, Sample>(
             r#"
-            SELECT id, name, barcode, location, status, created_at, updated_at, metadata
+            SELECT id, name, barcode, location, status, created_at, updated_at, metadata, template_version_id
             FROM samples
             WHERE id = $1
             "#,
@@ -98,7 +105,7 @@ impl SampleSubmissionManager {
     pub async fn list_samples(&self) -> Result<Vec<Sample>, sqlx::Error> {
         sqlx::query_as::<_, Sample>(
             r#"
-            SELECT id, name, barcode, location, status, created_at, updated_at, metadata
+            SELECT id, name, barcode, location, status, created_at, updated_at, metadata, template_version_id
             FROM samples
             ORDER BY created_at DESC
             "#,
@@ -147,7 +154,7 @@ impl SampleSubmissionManager {
             UPDATE samples 
             SET {}, updated_at = NOW()
             WHERE id = ${}
-            RETURNING id, name, barcode, location, status, created_at, updated_at, metadata
+            RETURNING id, name, barcode, location, status, created_at, updated_at, metadata, template_version_id
             "#,
             query_parts.join(", "),
             param_count
@@ -0,0 +1,382 @@
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, NaiveTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::models::analytics::{
+    AnalyticsBucket, AnalyticsFilter, AnalyticsInterval, AnalyticsQuery, DashboardAnalytics,
+    TurnaroundStats,
+};
+use crate::repositories::analytics_repository::{AnalyticsRepository, TimeBucket};
+
+/// Most buckets a trend request may cover
+pub const MAX_BUCKETS: usize = 400;
+
+/// Analytics service for dashboard trend charts
+#[derive(Debug)]
+pub struct AnalyticsService<R: AnalyticsRepository> {
+    analytics_repo: Arc<R>,
+}
+
+impl<R: AnalyticsRepository> AnalyticsService<R> {
+    pub fn new(analytics_repo: Arc<R>) -> Self {
+        Self { analytics_repo }
+    }
+
+    /// Count every dashboard trend in buckets of the requested interval.
+    /// The range starts at the beginning of the bucket `from` falls in.
+    pub async fn dashboard_analytics(
+        &self,
+        query: AnalyticsQuery,
+        today: NaiveDate,
+    ) -> Result<DashboardAnalytics, AnalyticsError> {
+        let to = query.to.unwrap_or(today);
+        let from = query
+            .from
+            .unwrap_or_else(|| default_from(query.interval, to));
+        let starts = bucket_starts(query.interval, from, to)?;
+        let buckets = time_buckets(query.interval, &starts);
+        let filter = AnalyticsFilter {
+            project: non_empty(query.project),
+            sample_type: non_empty(query.sample_type),
+        };
+
+        let repo = &self.analytics_repo;
+        let (received, statuses, sequencing, storage, turnaround) = tokio::try_join!(
+            repo.samples_received(&buckets, &filter),
+            repo.samples_by_status(&buckets, &filter),
+            repo.sequencing_throughput(&buckets, &filter),
+            repo.storage_utilization(&buckets),
+            repo.sample_turnaround(&buckets, &filter),
+        )?;
+
+        let mut points: Vec<AnalyticsBucket> = starts
+            .iter()
+            .map(|start| AnalyticsBucket {
+                start: *start,
+                ..AnalyticsBucket::default()
+            })
+            .collect();
+        let index: HashMap<_, _> = buckets
+            .iter()
+            .enumerate()
+            .map(|(index, bucket)| (bucket.start, index))
+            .collect();
+
+        for (start, count) in received {
+            if let Some(point) = bucket_at(&mut points, &index, start) {
+                point.samples_received = count;
+            }
+        }
+        for (start, status, count) in statuses {
+            if let Some(point) = bucket_at(&mut points, &index, start) {
+                point.samples_by_status.insert(status, count);
+            }
+        }
+        for (start, started, completed) in sequencing {
+            if let Some(point) = bucket_at(&mut points, &index, start) {
+                point.sequencing_started = started;
+                point.sequencing_completed = completed;
+            }
+        }
+        for (start, used, capacity) in storage {
+            if let Some(point) = bucket_at(&mut points, &index, start) {
+                point.storage_used = used;
+                point.storage_capacity = capacity;
+                point.storage_utilization = utilization(used, capacity);
+            }
+        }
+        for row in turnaround {
+            if let Some(point) = bucket_at(&mut points, &index, row.bucket_start) {
+                point.turnaround = TurnaroundStats {
+                    completed: row.completed,
+                    p50_hours: row.p50_hours,
+                    p90_hours: row.p90_hours,
+                    p95_hours: row.p95_hours,
+                };
+            }
+        }
+
+        Ok(DashboardAnalytics {
+            interval: query.interval,
+            from: starts.first().copied().unwrap_or(from),
+            to,
+            project: filter.project,
+            sample_type: filter.sample_type,
+            buckets: points,
+        })
+    }
+}
+
+fn bucket_at<'a>(
+    points: &'a mut [AnalyticsBucket],
+    index: &HashMap<DateTime<Utc>, usize>,
+    start: DateTime<Utc>,
+) -> Option<&'a mut AnalyticsBucket> {
+    index.get(&start).and_then(|index| points.get_mut(*index))
+}
+
+/// Start of the bucket a day falls in
+pub fn bucket_start(interval: AnalyticsInterval, day: NaiveDate) -> NaiveDate {
+    match interval {
+        AnalyticsInterval::Day => day,
+        AnalyticsInterval::Week => day - Days::new(day.weekday().num_days_from_monday() as u64),
+        AnalyticsInterval::Month => day.with_day(1).unwrap_or(day),
+    }
+}
+
+/// Starts of the buckets covering `from` to `to`
+pub fn bucket_starts(
+    interval: AnalyticsInterval,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<NaiveDate>, AnalyticsError> {
+    if from > to {
+        return Err(AnalyticsError::InvalidRange(format!(
+            "from ({}) is after to ({})",
+            from, to
+        )));
+    }
+
+    let mut starts = Vec::new();
+    let mut start = bucket_start(interval, from);
+    while start <= to {
+        if starts.len() == MAX_BUCKETS {
+            return Err(AnalyticsError::InvalidRange(format!(
+                "at most {} buckets can be requested; use a longer interval",
+                MAX_BUCKETS
+            )));
+        }
+        starts.push(start);
+        start = next_start(interval, start);
+    }
+    Ok(starts)
+}
+
+fn next_start(interval: AnalyticsInterval, start: NaiveDate) -> NaiveDate {
+    match interval {
+        AnalyticsInterval::Day => start + Days::new(1),
+        AnalyticsInterval::Week => start + Days::new(7),
+        AnalyticsInterval::Month => start + Months::new(1),
+    }
+}
+
+/// Buckets in UTC
+fn time_buckets(interval: AnalyticsInterval, starts: &[NaiveDate]) -> Vec<TimeBucket> {
+    starts
+        .iter()
+        .map(|start| TimeBucket {
+            start: start.and_time(NaiveTime::MIN).and_utc(),
+            end: next_start(interval, *start)
+                .and_time(NaiveTime::MIN)
+                .and_utc(),
+        })
+        .collect()
+}
+
+/// 30 days, 12 weeks or 12 months up to `to`
+fn default_from(interval: AnalyticsInterval, to: NaiveDate) -> NaiveDate {
+    let from = match interval {
+        AnalyticsInterval::Day => to.checked_sub_days(Days::new(29)),
+        AnalyticsInterval::Week => to.checked_sub_days(Days::new(7 * 11)),
+        AnalyticsInterval::Month => to.checked_sub_months(Months::new(11)),
+    };
+    from.unwrap_or(to)
+}
+
+fn utilization(used: i64, capacity: i64) -> Option<f64> {
+    (capacity > 0).then(|| (used as f64 / capacity as f64 * 1000.0).round() / 10.0)
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Analytics service errors
+#[derive(Debug, thiserror::Error)]
+pub enum AnalyticsError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Invalid range: {0}")]
+    InvalidRange(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::analytics_repository::TurnaroundRow;
+    use async_trait::async_trait;
+
+    fn date(value: &str) -> NaiveDate {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_bucket_starts_align_to_the_interval() {
+        assert_eq!(
+            bucket_starts(
+                AnalyticsInterval::Week,
+                date("2025-06-04"),
+                date("2025-06-16")
+            )
+            .unwrap(),
+            vec![date("2025-06-02"), date("2025-06-09"), date("2025-06-16")]
+        );
+        assert_eq!(
+            bucket_starts(
+                AnalyticsInterval::Month,
+                date("2025-01-31"),
+                date("2025-03-01")
+            )
+            .unwrap(),
+            vec![date("2025-01-01"), date("2025-02-01"), date("2025-03-01")]
+        );
+        assert_eq!(
+            bucket_starts(
+                AnalyticsInterval::Day,
+                date("2025-06-30"),
+                date("2025-06-30")
+            )
+            .unwrap(),
+            vec![date("2025-06-30")]
+        );
+
+        assert!(matches!(
+            bucket_starts(
+                AnalyticsInterval::Day,
+                date("2025-07-01"),
+                date("2025-06-30")
+            ),
+            Err(AnalyticsError::InvalidRange(_))
+        ));
+        assert!(matches!(
+            bucket_starts(
+                AnalyticsInterval::Day,
+                date("2020-01-01"),
+                date("2025-06-30")
+            ),
+            Err(AnalyticsError::InvalidRange(_))
+        ));
+        assert_eq!(
+            bucket_starts(
+                AnalyticsInterval::Month,
+                date("2020-01-01"),
+                date("2025-06-30")
+            )
+            .unwrap()
+            .len(),
+            66
+        );
+    }
+
+    #[test]
+    fn test_default_ranges() {
+        let today = date("2025-06-30");
+        assert_eq!(
+            default_from(AnalyticsInterval::Day, today),
+            date("2025-06-01")
+        );
+        assert_eq!(
+            default_from(AnalyticsInterval::Week, today),
+            date("2025-04-14")
+        );
+        assert_eq!(
+            default_from(AnalyticsInterval::Month, today),
+            date("2024-07-30")
+        );
+    }
+
+    /// Rows for the first bucket only
+    struct FirstBucket;
+
+    #[async_trait]
+    impl AnalyticsRepository for FirstBucket {
+        async fn samples_received(
+            &self,
+            buckets: &[TimeBucket],
+            filter: &AnalyticsFilter,
+        ) -> Result<Vec<(DateTime<Utc>, i64)>, sqlx::Error> {
+            assert_eq!(filter.project.as_deref(), Some("X"));
+            assert_eq!(filter.sample_type, None);
+            Ok(vec![(buckets[0].start, 4)])
+        }
+
+        async fn samples_by_status(
+            &self,
+            buckets: &[TimeBucket],
+            _filter: &AnalyticsFilter,
+        ) -> Result<Vec<(DateTime<Utc>, String, i64)>, sqlx::Error> {
+            Ok(vec![
+                (buckets[0].start, "pending".to_string(), 3),
+                (buckets[0].start, "completed".to_string(), 1),
+            ])
+        }
+
+        async fn sequencing_throughput(
+            &self,
+            buckets: &[TimeBucket],
+            _filter: &AnalyticsFilter,
+        ) -> Result<Vec<(DateTime<Utc>, i64, i64)>, sqlx::Error> {
+            Ok(vec![(buckets[0].start, 2, 1)])
+        }
+
+        async fn storage_utilization(
+            &self,
+            buckets: &[TimeBucket],
+        ) -> Result<Vec<(DateTime<Utc>, i64, i64)>, sqlx::Error> {
+            Ok(buckets.iter().map(|bucket| (bucket.start, 1, 3)).collect())
+        }
+
+        async fn sample_turnaround(
+            &self,
+            buckets: &[TimeBucket],
+            _filter: &AnalyticsFilter,
+        ) -> Result<Vec<TurnaroundRow>, sqlx::Error> {
+            Ok(vec![TurnaroundRow {
+                bucket_start: buckets[0].start,
+                completed: 1,
+                p50_hours: Some(36.0),
+                p90_hours: Some(36.0),
+                p95_hours: Some(36.0),
+            }])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rows_fill_their_buckets() {
+        let service = AnalyticsService::new(Arc::new(FirstBucket));
+        let analytics = service
+            .dashboard_analytics(
+                AnalyticsQuery {
+                    interval: AnalyticsInterval::Week,
+                    from: Some(date("2025-06-04")),
+                    to: None,
+                    project: Some(" X ".to_string()),
+                    sample_type: Some(String::new()),
+                },
+                date("2025-06-10"),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(analytics.from, date("2025-06-02"));
+        assert_eq!(analytics.project.as_deref(), Some("X"));
+        assert_eq!(analytics.buckets.len(), 2);
+
+        let first = &analytics.buckets[0];
+        assert_eq!(first.samples_received, 4);
+        assert_eq!(first.samples_by_status["pending"], 3);
+        assert_eq!(first.sequencing_started, 2);
+        assert_eq!(first.storage_utilization, Some(33.3));
+        assert_eq!(first.turnaround.p50_hours, Some(36.0));
+
+        let second = &analytics.buckets[1];
+        assert_eq!(second.start, date("2025-06-09"));
+        assert_eq!(second.samples_received, 0);
+        assert!(second.samples_by_status.is_empty());
+        assert_eq!(second.storage_used, 1);
+        assert_eq!(second.turnaround, TurnaroundStats::default());
+    }
+}
@@ -0,0 +1,344 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Rows inspected before a dataset's column types are fixed
+pub const INFERENCE_SAMPLE_ROWS: usize = 1000;
+
+/// Text columns with at most this many distinct values are categorical
+const CATEGORICAL_MAX_DISTINCT: usize = 20;
+
+/// Tried in order, so month-first wins when a sample is ambiguous
+const DATE_FORMATS: [&str; 6] = [
+    "%Y-%m-%d", "%d.%m.%Y", "%m/%d/%Y", "%d/%m/%Y", "%Y/%m/%d", "%d-%b-%Y",
+];
+
+const DATE_TIME_FORMATS: [&str; 9] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M",
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+    "%d/%m/%Y %H:%M",
+];
+
+/// Placeholders instruments and LIMS exports write for missing values
+const MISSING_VALUES: [&str; 7] = ["n/a", "na", "#n/a", "null", "none", "-", "--"];
+
+const TRUE_VALUES: [&str; 5] = ["true", "yes", "y", "t", "pass"];
+const FALSE_VALUES: [&str; 5] = ["false", "no", "n", "f", "fail"];
+
+/// Inferred type of a spreadsheet column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnType {
+    Integer,
+    Decimal,
+    Date,
+    DateTime,
+    Boolean,
+    Categorical,
+    Text,
+    /// No values in the sample
+    Empty,
+}
+
+impl ColumnType {
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, ColumnType::Integer | ColumnType::Decimal)
+    }
+
+    pub fn is_temporal(&self) -> bool {
+        matches!(self, ColumnType::Date | ColumnType::DateTime)
+    }
+}
+
+/// A column's type and, for dates and decimals, the format it was read with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnSchema {
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+    /// chrono format string for dates, decimal separator for decimals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+}
+
+impl ColumnSchema {
+    fn new(column_type: ColumnType) -> Self {
+        Self {
+            column_type,
+            format: None,
+        }
+    }
+
+    fn with_format(column_type: ColumnType, format: &str) -> Self {
+        Self {
+            column_type,
+            format: Some(format.to_string()),
+        }
+    }
+
+    /// Typed JSON value for a raw cell, or `None` when the column is untyped
+    /// or the cell does not conform. Dates are normalised to ISO 8601 so
+    /// they can be cast to `timestamp` in SQL.
+    pub fn typed_value(&self, raw: &str) -> Option<serde_json::Value> {
+        let raw = raw.trim();
+        if is_missing(raw) {
+            return None;
+        }
+
+        match self.column_type {
+            // Integer columns may still hold the odd decimal further down
+            ColumnType::Integer => raw
+                .parse::<i64>()
+                .ok()
+                .map(serde_json::Value::from)
+                .or_else(|| parse_decimal(raw, ".").and_then(json_number)),
+            ColumnType::Decimal => {
+                parse_decimal(raw, self.format.as_deref().unwrap_or(".")).and_then(json_number)
+            }
+            ColumnType::Date => {
+                let format = self.format.as_deref()?;
+                NaiveDate::parse_from_str(raw, format)
+                    .ok()
+                    .map(|date| date.format("%Y-%m-%d").to_string().into())
+            }
+            ColumnType::DateTime => {
+                let format = self.format.as_deref()?;
+                NaiveDateTime::parse_from_str(raw, format)
+                    .ok()
+                    .map(|value| value.format("%Y-%m-%dT%H:%M:%S").to_string().into())
+            }
+            ColumnType::Boolean => parse_bool(raw).map(serde_json::Value::Bool),
+            ColumnType::Categorical | ColumnType::Text | ColumnType::Empty => None,
+        }
+    }
+}
+
+/// Infer each column's type from sample rows
+pub fn infer_column_types(column_count: usize, rows: &[Vec<String>]) -> Vec<ColumnSchema> {
+    (0..column_count)
+        .map(|col| {
+            let values: Vec<&str> = rows
+                .iter()
+                .filter_map(|row| row.get(col))
+                .map(|value| value.trim())
+                .filter(|value| !is_missing(value))
+                .collect();
+            infer_column(&values)
+        })
+        .collect()
+}
+
+/// Infer a column's type from its non-missing values
+pub fn infer_column(values: &[&str]) -> ColumnSchema {
+    if values.is_empty() {
+        return ColumnSchema::new(ColumnType::Empty);
+    }
+
+    if values.iter().all(|v| v.parse::<i64>().is_ok()) {
+        return ColumnSchema::new(ColumnType::Integer);
+    }
+    if values.iter().all(|v| parse_decimal(v, ".").is_some()) {
+        return ColumnSchema::with_format(ColumnType::Decimal, ".");
+    }
+    if is_decimal_comma_column(values) {
+        return ColumnSchema::with_format(ColumnType::Decimal, ",");
+    }
+    if let Some(format) = DATE_FORMATS.iter().find(|format| {
+        values
+            .iter()
+            .all(|v| NaiveDate::parse_from_str(v, format).is_ok())
+    }) {
+        return ColumnSchema::with_format(ColumnType::Date, format);
+    }
+    if let Some(format) = DATE_TIME_FORMATS.iter().find(|format| {
+        values
+            .iter()
+            .all(|v| NaiveDateTime::parse_from_str(v, format).is_ok())
+    }) {
+        return ColumnSchema::with_format(ColumnType::DateTime, format);
+    }
+    if values.iter().all(|v| parse_bool(v).is_some()) {
+        return ColumnSchema::new(ColumnType::Boolean);
+    }
+
+    let distinct: HashSet<&str> = values.iter().copied().collect();
+    if distinct.len() <= CATEGORICAL_MAX_DISTINCT && distinct.len() * 2 <= values.len() {
+        ColumnSchema::new(ColumnType::Categorical)
+    } else {
+        ColumnSchema::new(ColumnType::Text)
+    }
+}
+
+/// Typed values of a row, keyed by header. Untyped and empty cells are left
+/// out; the raw text stays in `row_data`.
+pub fn typed_row(
+    headers: &[String],
+    schemas: &[ColumnSchema],
+    fields: &[String],
+) -> serde_json::Value {
+    let typed: serde_json::Map<String, serde_json::Value> = headers
+        .iter()
+        .zip(schemas)
+        .zip(fields)
+        .filter_map(|((header, schema), raw)| {
+            schema.typed_value(raw).map(|value| (header.clone(), value))
+        })
+        .collect();
+    serde_json::Value::Object(typed)
+}
+
+/// Column types keyed by header, as stored in the dataset metadata
+pub fn column_types_json(headers: &[String], schemas: &[ColumnSchema]) -> serde_json::Value {
+    let types: serde_json::Map<String, serde_json::Value> = headers
+        .iter()
+        .zip(schemas)
+        .map(|(header, schema)| (header.clone(), serde_json::json!(schema)))
+        .collect();
+    serde_json::Value::Object(types)
+}
+
+/// Parse a plain decimal number. Rejects `inf`, `NaN` and thousands separators.
+fn parse_decimal(raw: &str, separator: &str) -> Option<f64> {
+    let normalised = if separator == "." {
+        raw.to_string()
+    } else {
+        raw.replace(separator, ".")
+    };
+    let plain = normalised
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'));
+    if !plain || !normalised.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    normalised.parse::<f64>().ok().filter(|v| v.is_finite())
+}
+
+/// `12,5`-style decimals. A comma followed by exactly three digits in every
+/// value could equally be a thousands separator, so that is left as text.
+fn is_decimal_comma_column(values: &[&str]) -> bool {
+    let mut unambiguous = false;
+    for value in values {
+        let Some((whole, fraction)) = value.split_once(',') else {
+            if value.parse::<i64>().is_err() {
+                return false;
+            }
+            continue;
+        };
+        if whole.trim_start_matches(['-', '+']).is_empty()
+            || !whole
+                .trim_start_matches(['-', '+'])
+                .chars()
+                .all(|c| c.is_ascii_digit())
+            || fraction.is_empty()
+            || !fraction.chars().all(|c| c.is_ascii_digit())
+        {
+            return false;
+        }
+        unambiguous |= fraction.len() != 3;
+    }
+    unambiguous
+}
+
+fn is_missing(raw: &str) -> bool {
+    raw.is_empty() || MISSING_VALUES.contains(&raw.to_lowercase().as_str())
+}
+
+fn parse_bool(raw: &str) -> Option<bool> {
+    let lower = raw.to_lowercase();
+    if TRUE_VALUES.contains(&lower.as_str()) {
+        Some(true)
+    } else if FALSE_VALUES.contains(&lower.as_str()) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn json_number(value: f64) -> Option<serde_json::Value> {
+    serde_json::Number::from_f64(value).map(serde_json::Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer(values: &[&str]) -> ColumnSchema {
+        infer_column(values)
+    }
+
+    #[test]
+    fn test_infers_numeric_columns() {
+        assert_eq!(infer(&["1", "-20", "300"]).column_type, ColumnType::Integer);
+        let with_gaps = infer_column_types(1, &[vec!["4".to_string()], vec!["N/A".to_string()]]);
+        assert_eq!(with_gaps[0].column_type, ColumnType::Integer);
+        assert_eq!(infer(&["1.5", "2", "3e2"]).column_type, ColumnType::Decimal);
+
+        let european = infer(&["12,5", "8", "0,25"]);
+        assert_eq!(european.column_type, ColumnType::Decimal);
+        assert_eq!(european.typed_value("12,5"), Some(serde_json::json!(12.5)));
+
+        // Could be thousands separators
+        assert_ne!(infer(&["1,234", "5,678"]).column_type, ColumnType::Decimal);
+        assert_ne!(infer(&["inf", "NaN"]).column_type, ColumnType::Decimal);
+    }
+
+    #[test]
+    fn test_infers_date_formats() {
+        let iso = infer(&["2024-01-31", "2024-02-01"]);
+        assert_eq!(iso.column_type, ColumnType::Date);
+        assert_eq!(iso.typed_value("2024-02-01"), Some("2024-02-01".into()));
+
+        // 31/01 rules out month-first
+        let day_first = infer(&["31/01/2024", "01/02/2024"]);
+        assert_eq!(day_first.format.as_deref(), Some("%d/%m/%Y"));
+        assert_eq!(
+            day_first.typed_value("01/02/2024"),
+            Some("2024-02-01".into())
+        );
+
+        let timestamps = infer(&["01.02.2024 13:45", "02.02.2024 08:00"]);
+        assert_eq!(timestamps.column_type, ColumnType::DateTime);
+        assert_eq!(
+            timestamps.typed_value("01.02.2024 13:45"),
+            Some("2024-02-01T13:45:00".into())
+        );
+    }
+
+    #[test]
+    fn test_infers_boolean_categorical_and_text() {
+        let qc = infer(&["Yes", "no", "YES"]);
+        assert_eq!(qc.column_type, ColumnType::Boolean);
+        assert_eq!(qc.typed_value("no"), Some(false.into()));
+
+        let sample_types = ["DNA", "RNA", "DNA", "Plasma", "RNA", "DNA"];
+        assert_eq!(infer(&sample_types).column_type, ColumnType::Categorical);
+        assert_eq!(infer(&["S1", "S2", "S3"]).column_type, ColumnType::Text);
+        assert_eq!(infer(&[]).column_type, ColumnType::Empty);
+    }
+
+    #[test]
+    fn test_typed_row_skips_untyped_and_nonconforming_cells() {
+        let headers = vec![
+            "Sample".to_string(),
+            "Volume".to_string(),
+            "Count".to_string(),
+        ];
+        let rows = vec![
+            vec!["S1".to_string(), "1.5".to_string(), "3".to_string()],
+            vec!["S2".to_string(), "2.0".to_string(), "4".to_string()],
+        ];
+        let schemas = infer_column_types(headers.len(), &rows);
+
+        let typed = typed_row(
+            &headers,
+            &schemas,
+            &["S3".to_string(), "n/a".to_string(), "4.5".to_string()],
+        );
+        assert_eq!(typed, serde_json::json!({"Count": 4.5}));
+    }
+}
@@ -0,0 +1,584 @@
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+
+/// Bytes inspected when detecting encoding and dialect
+pub const SAMPLE_BYTES: usize = 64 * 1024;
+
+/// Lines inspected when detecting the delimiter and header row
+const SAMPLE_LINES: usize = 200;
+
+/// Delimiters tried during detection, in order of preference on a tie
+const CANDIDATE_DELIMITERS: [char; 4] = [',', '\t', ';', '|'];
+
+/// Text encodings understood by the CSV reader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextEncoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    /// Windows-1252, also used for ISO-8859-1 files as browsers do
+    #[serde(rename = "windows-1252")]
+    Windows1252,
+}
+
+impl TextEncoding {
+    /// Parse an encoding label such as `utf-8`, `UTF-16LE` or `latin1`
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Some(TextEncoding::Utf8),
+            "utf-16" | "utf-16le" | "utf16le" => Some(TextEncoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(TextEncoding::Utf16Be),
+            "windows-1252" | "cp1252" | "latin1" | "latin-1" | "iso-8859-1" => {
+                Some(TextEncoding::Windows1252)
+            }
+            _ => None,
+        }
+    }
+
+    fn bom(&self) -> &'static [u8] {
+        match self {
+            TextEncoding::Utf8 => &[0xEF, 0xBB, 0xBF],
+            TextEncoding::Utf16Le => &[0xFF, 0xFE],
+            TextEncoding::Utf16Be => &[0xFE, 0xFF],
+            TextEncoding::Windows1252 => &[],
+        }
+    }
+}
+
+/// How a delimited text file is laid out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CsvDialect {
+    pub encoding: TextEncoding,
+    pub has_bom: bool,
+    pub delimiter: char,
+    /// Quote character, or `None` when fields are never quoted
+    pub quote: Option<char>,
+    /// Lines of preamble before the header row
+    pub header_row: usize,
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        Self {
+            encoding: TextEncoding::Utf8,
+            has_bom: false,
+            delimiter: ',',
+            quote: Some('"'),
+            header_row: 0,
+        }
+    }
+}
+
+/// Dialect settings supplied by the uploader; anything left unset is detected
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CsvDialectOverride {
+    pub encoding: Option<TextEncoding>,
+    pub delimiter: Option<char>,
+    /// `Some(None)` disables quoting
+    pub quote: Option<Option<char>>,
+    pub header_row: Option<usize>,
+}
+
+impl CsvDialectOverride {
+    /// Build an override from upload parameters. Delimiters may be given as
+    /// the character itself or as `tab`, `comma`, `semicolon` or `pipe`;
+    /// `quote=none` turns quoting off.
+    pub fn from_params(
+        encoding: Option<&str>,
+        delimiter: Option<&str>,
+        quote: Option<&str>,
+        header_row: Option<usize>,
+    ) -> Result<Self, DialectError> {
+        let encoding = encoding
+            .map(|label| {
+                TextEncoding::from_label(label)
+                    .ok_or_else(|| DialectError::UnknownEncoding(label.to_string()))
+            })
+            .transpose()?;
+
+        let delimiter = delimiter
+            .map(|value| match value.to_lowercase().as_str() {
+                "tab" | "\\t" => Ok('\t'),
+                "comma" => Ok(','),
+                "semicolon" => Ok(';'),
+                "pipe" => Ok('|'),
+                _ => single_ascii_char(value)
+                    .ok_or_else(|| DialectError::InvalidDelimiter(value.to_string())),
+            })
+            .transpose()?;
+
+        let quote = quote
+            .map(|value| match value.to_lowercase().as_str() {
+                "none" | "" => Ok(None),
+                _ => single_ascii_char(value)
+                    .map(Some)
+                    .ok_or_else(|| DialectError::InvalidQuote(value.to_string())),
+            })
+            .transpose()?;
+
+        Ok(Self {
+            encoding,
+            delimiter,
+            quote,
+            header_row,
+        })
+    }
+
+    /// Names of the settings that were overridden, for the dataset metadata
+    pub fn overridden_fields(&self) -> Vec<&'static str> {
+        [
+            ("encoding", self.encoding.is_some()),
+            ("delimiter", self.delimiter.is_some()),
+            ("quote", self.quote.is_some()),
+            ("header_row", self.header_row.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect()
+    }
+}
+
+fn single_ascii_char(value: &str) -> Option<char> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii() => Some(c),
+        _ => None,
+    }
+}
+
+/// Detect the dialect of a file from its first bytes, applying any override.
+/// `complete` says whether `sample` is the whole file; if not, its last line
+/// is treated as truncated.
+pub fn detect_dialect(sample: &[u8], complete: bool, overrides: &CsvDialectOverride) -> CsvDialect {
+    let (detected_encoding, has_bom) = detect_encoding(sample);
+    let encoding = overrides.encoding.unwrap_or(detected_encoding);
+    let has_bom = has_bom && encoding == detected_encoding;
+
+    let body = if has_bom {
+        &sample[encoding.bom().len()..]
+    } else {
+        sample
+    };
+    let text = decode_all(body, encoding);
+
+    let mut lines: Vec<&str> = text.lines().collect();
+    if !complete && lines.len() > 1 {
+        lines.pop();
+    }
+    lines.truncate(SAMPLE_LINES);
+
+    let quote_for_counting = overrides.quote.unwrap_or(Some('"'));
+    let (delimiter, header_row) = match overrides.delimiter {
+        Some(delimiter) => (
+            delimiter,
+            table_start(&lines, delimiter, quote_for_counting).map_or(0, |(start, _)| start),
+        ),
+        None => detect_delimiter(&lines, quote_for_counting),
+    };
+
+    let quote = overrides
+        .quote
+        .unwrap_or_else(|| detect_quote(&lines[header_row.min(lines.len())..], delimiter));
+
+    CsvDialect {
+        encoding,
+        has_bom,
+        delimiter,
+        quote,
+        header_row: overrides.header_row.unwrap_or(header_row),
+    }
+}
+
+/// Detect the encoding from a byte order mark, falling back to heuristics
+pub fn detect_encoding(sample: &[u8]) -> (TextEncoding, bool) {
+    for encoding in [
+        TextEncoding::Utf8,
+        TextEncoding::Utf16Le,
+        TextEncoding::Utf16Be,
+    ] {
+        if sample.starts_with(encoding.bom()) {
+            return (encoding, true);
+        }
+    }
+
+    // UTF-16 text from Excel is mostly ASCII, so every other byte is zero
+    let pairs = sample.len() / 2;
+    if pairs >= 2 {
+        let even_zeros = sample.iter().step_by(2).filter(|b| **b == 0).count();
+        let odd_zeros = sample
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count();
+        if odd_zeros * 10 >= pairs * 3 && even_zeros * 10 < pairs {
+            return (TextEncoding::Utf16Le, false);
+        }
+        if even_zeros * 10 >= pairs * 3 && odd_zeros * 10 < pairs {
+            return (TextEncoding::Utf16Be, false);
+        }
+    }
+
+    match std::str::from_utf8(sample) {
+        Ok(_) => (TextEncoding::Utf8, false),
+        // A multi-byte character cut off at the end of the sample is fine
+        Err(e) if e.error_len().is_none() => (TextEncoding::Utf8, false),
+        Err(_) => (TextEncoding::Windows1252, false),
+    }
+}
+
+/// Pick the delimiter that splits the most lines into the same number of
+/// fields, and the line where that table starts
+fn detect_delimiter(lines: &[&str], quote: Option<char>) -> (char, usize) {
+    // `max_by_key` keeps the last maximum, so walk the candidates backwards
+    // to let the earlier delimiter win a tie
+    let best = CANDIDATE_DELIMITERS
+        .iter()
+        .rev()
+        .filter_map(|&delimiter| {
+            table_start(lines, delimiter, quote).map(|(start, score)| (delimiter, start, score))
+        })
+        .max_by_key(|(_, _, score)| *score);
+
+    best.map_or((',', 0), |(delimiter, start, _)| (delimiter, start))
+}
+
+/// First line of the table for `delimiter` and how many lines agree with its
+/// field count. `None` when the delimiter never splits a line.
+fn table_start(lines: &[&str], delimiter: char, quote: Option<char>) -> Option<(usize, usize)> {
+    let counts: Vec<usize> = lines
+        .iter()
+        .map(|line| count_fields(line, delimiter, quote))
+        .collect();
+
+    let mut frequency = std::collections::HashMap::new();
+    for count in counts.iter().filter(|count| **count > 1) {
+        *frequency.entry(*count).or_insert(0usize) += 1;
+    }
+    // Prefer the widest layout when two field counts are equally common
+    let (&fields, &score) = frequency
+        .iter()
+        .max_by_key(|(fields, score)| (**score, **fields))?;
+
+    let start = counts.iter().position(|count| *count == fields)?;
+    Some((start, score))
+}
+
+fn count_fields(line: &str, delimiter: char, quote: Option<char>) -> usize {
+    if line.trim().is_empty() {
+        return 0;
+    }
+
+    let mut in_quotes = false;
+    let mut fields = 1;
+    for c in line.chars() {
+        if Some(c) == quote {
+            in_quotes = !in_quotes;
+        } else if c == delimiter && !in_quotes {
+            fields += 1;
+        }
+    }
+    fields
+}
+
+/// Double quotes unless the table only ever wraps fields in single quotes
+fn detect_quote(lines: &[&str], delimiter: char) -> Option<char> {
+    let quoted_fields = |quote: char| -> usize {
+        lines
+            .iter()
+            .flat_map(|line| line.split(delimiter))
+            .map(str::trim)
+            .filter(|field| field.len() >= 2 && field.starts_with(quote) && field.ends_with(quote))
+            .count()
+    };
+
+    if quoted_fields('"') == 0 && quoted_fields('\'') > 0 {
+        Some('\'')
+    } else {
+        Some('"')
+    }
+}
+
+fn decode_all(bytes: &[u8], encoding: TextEncoding) -> String {
+    let mut decoder = Decoder::new(encoding);
+    let mut text = String::new();
+    decoder.decode(bytes, true, &mut text);
+    text
+}
+
+/// Incremental decoder that keeps partial characters between chunks
+struct Decoder {
+    encoding: TextEncoding,
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    fn new(encoding: TextEncoding) -> Self {
+        Self {
+            encoding,
+            pending: Vec::new(),
+        }
+    }
+
+    fn decode(&mut self, input: &[u8], last: bool, out: &mut String) {
+        self.pending.extend_from_slice(input);
+
+        let consumed = match self.encoding {
+            TextEncoding::Utf8 => {
+                // Hold back a character split across chunks
+                let end = if last {
+                    self.pending.len()
+                } else {
+                    self.pending.len() - incomplete_utf8_tail(&self.pending)
+                };
+                out.push_str(&String::from_utf8_lossy(&self.pending[..end]));
+                end
+            }
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+                let mut units: Vec<u16> = self
+                    .pending
+                    .chunks_exact(2)
+                    .map(|pair| match self.encoding {
+                        TextEncoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                        _ => u16::from_be_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+                // Keep a high surrogate until its partner arrives
+                if !last && units.last().is_some_and(|u| (0xD800..0xDC00).contains(u)) {
+                    units.pop();
+                }
+                out.extend(
+                    char::decode_utf16(units.iter().copied())
+                        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)),
+                );
+                let consumed = units.len() * 2;
+                if last && consumed < self.pending.len() {
+                    out.push(char::REPLACEMENT_CHARACTER);
+                    self.pending.len()
+                } else {
+                    consumed
+                }
+            }
+            TextEncoding::Windows1252 => {
+                out.extend(self.pending.iter().map(|b| windows_1252_char(*b)));
+                self.pending.len()
+            }
+        };
+
+        self.pending.drain(..consumed);
+    }
+}
+
+/// Length of a multi-byte character cut off at the end of `bytes`
+fn incomplete_utf8_tail(bytes: &[u8]) -> usize {
+    for back in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - back];
+        if byte & 0xC0 != 0x80 {
+            let width = match byte {
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                0xF0..=0xF7 => 4,
+                _ => 1,
+            };
+            return if width > back { back } else { 0 };
+        }
+    }
+    0
+}
+
+fn windows_1252_char(byte: u8) -> char {
+    const HIGH: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž',
+        '\u{8F}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}',
+        'ž', 'Ÿ',
+    ];
+    match byte {
+        0x80..=0x9F => HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+/// Reader that transcodes its input to UTF-8 on the fly, dropping any BOM
+pub struct DecodingReader<R: Read> {
+    inner: R,
+    decoder: Decoder,
+    skip_bom: usize,
+    raw: Vec<u8>,
+    out: String,
+    out_pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> DecodingReader<R> {
+    pub fn new(inner: R, dialect: &CsvDialect) -> Self {
+        Self {
+            inner,
+            decoder: Decoder::new(dialect.encoding),
+            skip_bom: if dialect.has_bom {
+                dialect.encoding.bom().len()
+            } else {
+                0
+            },
+            raw: vec![0; 8 * 1024],
+            out: String::new(),
+            out_pos: 0,
+            eof: false,
+        }
+    }
+
+    fn refill(&mut self) -> std::io::Result<()> {
+        self.out.clear();
+        self.out_pos = 0;
+
+        while self.out.is_empty() && !self.eof {
+            let read = self.inner.read(&mut self.raw)?;
+            let mut chunk = &self.raw[..read];
+            if read == 0 {
+                self.eof = true;
+            } else if self.skip_bom > 0 {
+                let skipped = self.skip_bom.min(chunk.len());
+                chunk = &chunk[skipped..];
+                self.skip_bom -= skipped;
+            }
+            self.decoder.decode(chunk, self.eof, &mut self.out);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.out_pos >= self.out.len() {
+            self.refill()?;
+        }
+
+        let available = &self.out.as_bytes()[self.out_pos..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.out_pos += len;
+        Ok(len)
+    }
+}
+
+/// Dialect errors for user-supplied overrides
+#[derive(Debug, thiserror::Error)]
+pub enum DialectError {
+    #[error("Unknown encoding '{0}'")]
+    UnknownEncoding(String),
+
+    #[error("Delimiter must be a single ASCII character, got '{0}'")]
+    InvalidDelimiter(String),
+
+    #[error("Quote must be a single ASCII character or 'none', got '{0}'")]
+    InvalidQuote(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(sample: &[u8]) -> CsvDialect {
+        detect_dialect(sample, true, &CsvDialectOverride::default())
+    }
+
+    fn decode(sample: &[u8]) -> String {
+        let dialect = detect(sample);
+        let mut text = String::new();
+        DecodingReader::new(sample, &dialect)
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn test_detects_semicolon_and_tab_delimiters() {
+        assert_eq!(
+            detect(b"Probe;Menge;Wert\nA;1,5;2\nB;2,5;3\n").delimiter,
+            ';'
+        );
+        assert_eq!(detect(b"id\tname\n1\tfoo, bar\n2\tbaz\n").delimiter, '\t');
+        assert_eq!(detect(b"a|b|c\n1|2|3\n").delimiter, '|');
+    }
+
+    #[test]
+    fn test_quoted_delimiters_do_not_count() {
+        let dialect = detect(b"name,notes\n\"Smith; J\",\"a; b; c\"\n\"Doe; K\",x\n");
+        assert_eq!(dialect.delimiter, ',');
+        assert_eq!(dialect.quote, Some('"'));
+    }
+
+    #[test]
+    fn test_skips_instrument_preamble() {
+        let sample = b"Instrument: NanoDrop One\nRun date: 2024-05-01\n\nSample,Conc,A260/280\nS1,12.5,1.9\nS2,8.1,2.0\n";
+        let dialect = detect(sample);
+        assert_eq!(dialect.delimiter, ',');
+        assert_eq!(dialect.header_row, 3);
+    }
+
+    #[test]
+    fn test_detects_encodings() {
+        assert_eq!(
+            detect_encoding(b"\xEF\xBB\xBFa,b"),
+            (TextEncoding::Utf8, true)
+        );
+        assert_eq!(
+            detect_encoding(b"caf\xE9,b\n"),
+            (TextEncoding::Windows1252, false)
+        );
+
+        let utf16: Vec<u8> = "a,b\n1,2\n"
+            .encode_utf16()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        assert_eq!(detect_encoding(&utf16), (TextEncoding::Utf16Le, false));
+    }
+
+    #[test]
+    fn test_decoding_reader_transcodes_to_utf8() {
+        assert_eq!(decode(b"caf\xE9;\x80\n"), "café;€\n");
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend("Größe,µl\n".encode_utf16().flat_map(|u| u.to_le_bytes()));
+        assert_eq!(decode(&utf16), "Größe,µl\n");
+    }
+
+    #[test]
+    fn test_decoding_reader_keeps_characters_split_across_reads() {
+        // A reader that hands out one byte at a time splits every character
+        struct OneByte<'a>(&'a [u8]);
+        impl Read for OneByte<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let Some((first, rest)) = self.0.split_first() else {
+                    return Ok(0);
+                };
+                buf[0] = *first;
+                self.0 = rest;
+                Ok(1)
+            }
+        }
+
+        let input = "µl,😀\n".as_bytes();
+        let mut text = String::new();
+        DecodingReader::new(OneByte(input), &CsvDialect::default())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "µl,😀\n");
+    }
+
+    #[test]
+    fn test_overrides_win_over_detection() {
+        let overrides =
+            CsvDialectOverride::from_params(Some("latin1"), Some("tab"), Some("none"), Some(2))
+                .unwrap();
+        let dialect = detect_dialect(b"a,b\n1,2\n", true, &overrides);
+
+        assert_eq!(dialect.encoding, TextEncoding::Windows1252);
+        assert_eq!(dialect.delimiter, '\t');
+        assert_eq!(dialect.quote, None);
+        assert_eq!(dialect.header_row, 2);
+        assert!(CsvDialectOverride::from_params(None, Some(";;"), None, None).is_err());
+    }
+}
@@ -0,0 +1,376 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::spreadsheet::{VersionMatchCounts, VersionRowPair};
+
+/// Changed rows returned by a diff unless the caller asks for more
+pub const DEFAULT_DIFF_LIMIT: usize = 1000;
+
+/// Upper bound on changed rows returned by a diff
+pub const MAX_DIFF_LIMIT: usize = 10_000;
+
+/// Row and cell changes between two versions of a series
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatasetDiff {
+    pub series_id: Uuid,
+    pub identity_column: String,
+    pub from_version: i32,
+    pub to_version: i32,
+    pub summary: DiffSummary,
+    pub rows: Vec<RowDiff>,
+    /// More rows changed than were returned
+    pub truncated: bool,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    /// Rows in either version with an empty identity column, which cannot
+    /// be matched and are not compared
+    pub unkeyed_rows: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowChange {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct RowDiff {
+    pub key: String,
+    pub change: RowChange,
+    pub from_row_number: Option<i32>,
+    pub to_row_number: Option<i32>,
+    pub cells: Vec<CellChange>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CellChange {
+    pub column: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Summarise differing row pairs into a diff, keeping at most `limit` rows
+pub fn summarize_diff(
+    pairs: Vec<VersionRowPair>,
+    counts: &VersionMatchCounts,
+    limit: usize,
+) -> (DiffSummary, Vec<RowDiff>, bool) {
+    let mut summary = DiffSummary {
+        unkeyed_rows: counts.unkeyed as usize,
+        ..Default::default()
+    };
+    let mut rows = Vec::new();
+    let mut truncated = false;
+
+    for pair in pairs {
+        let Some(row) = diff_row(pair) else {
+            continue;
+        };
+        match row.change {
+            RowChange::Added => summary.added += 1,
+            RowChange::Removed => summary.removed += 1,
+            RowChange::Changed => summary.changed += 1,
+        }
+        if rows.len() < limit {
+            rows.push(row);
+        } else {
+            truncated = true;
+        }
+    }
+
+    summary.unchanged = (counts.matched as usize).saturating_sub(summary.changed);
+    (summary, rows, truncated)
+}
+
+/// Cell-level diff of one row identity. `None` when the only differences
+/// are in derived `_`-prefixed keys.
+pub fn diff_row(pair: VersionRowPair) -> Option<RowDiff> {
+    let change = match (&pair.from_row, &pair.to_row) {
+        (None, Some(_)) => RowChange::Added,
+        (Some(_), None) => RowChange::Removed,
+        (Some(_), Some(_)) => RowChange::Changed,
+        (None, None) => return None,
+    };
+
+    let empty = serde_json::Map::new();
+    let old = pair
+        .from_row
+        .as_ref()
+        .and_then(|row| row.as_object())
+        .unwrap_or(&empty);
+    let new = pair
+        .to_row
+        .as_ref()
+        .and_then(|row| row.as_object())
+        .unwrap_or(&empty);
+
+    // Keep the column order of the newer row, then any dropped columns
+    let columns = new
+        .keys()
+        .chain(old.keys().filter(|column| !new.contains_key(*column)))
+        .filter(|column| !column.starts_with('_'));
+
+    let cells: Vec<CellChange> = columns
+        .filter_map(|column| {
+            let old_value = old.get(column).map(cell_text);
+            let new_value = new.get(column).map(cell_text);
+            (old_value != new_value).then(|| CellChange {
+                column: column.clone(),
+                old: old_value,
+                new: new_value,
+            })
+        })
+        .collect();
+
+    if change == RowChange::Changed && cells.is_empty() {
+        return None;
+    }
+
+    Some(RowDiff {
+        key: pair.key,
+        change,
+        from_row_number: pair.from_row_number,
+        to_row_number: pair.to_row_number,
+        cells,
+    })
+}
+
+fn cell_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Dataset series errors
+#[derive(Debug, thiserror::Error)]
+pub enum SeriesError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Dataset series not found")]
+    SeriesNotFound,
+
+    #[error("Dataset not found")]
+    DatasetNotFound,
+
+    #[error("Version not found")]
+    VersionNotFound,
+
+    #[error("A dataset series named '{0}' already exists")]
+    DuplicateSeriesName(String),
+
+    #[error("Dataset already belongs to a series")]
+    DatasetAlreadyInSeries,
+
+    #[error("Dataset has no '{0}' column to identify rows by")]
+    MissingIdentityColumn(String),
+
+    #[error("Version {0} has not finished processing")]
+    VersionNotReady(i32),
+
+    #[error("Version {version} has duplicate identity values: {keys:?}")]
+    DuplicateIdentities { version: i32, keys: Vec<String> },
+
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pair(from: Option<serde_json::Value>, to: Option<serde_json::Value>) -> VersionRowPair {
+        VersionRowPair {
+            key: "S1".to_string(),
+            from_row_number: from.as_ref().map(|_| 1),
+            from_row: from,
+            to_row_number: to.as_ref().map(|_| 2),
+            to_row: to,
+        }
+    }
+
+    #[test]
+    fn test_changed_row_lists_only_changed_cells() {
+        let row = diff_row(pair(
+            Some(json!({"Sample": "S1", "Status": "Pending", "Freezer": "F1"})),
+            Some(json!({"Sample": "S1", "Status": "Sequenced", "Lane": "3"})),
+        ))
+        .unwrap();
+
+        assert_eq!(row.change, RowChange::Changed);
+        let cells: Vec<_> = row
+            .cells
+            .iter()
+            .map(|c| (c.column.as_str(), c.old.as_deref(), c.new.as_deref()))
+            .collect();
+        assert_eq!(
+            cells,
+            [
+                ("Lane", None, Some("3")),
+                ("Status", Some("Pending"), Some("Sequenced")),
+                ("Freezer", Some("F1"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_derived_keys_do_not_count_as_changes() {
+        let row = diff_row(pair(
+            Some(json!({"Sample": "S1", "_search_enhanced": "a"})),
+            Some(json!({"Sample": "S1", "_search_enhanced": "b"})),
+        ));
+        assert_eq!(row, None);
+    }
+
+    #[test]
+    fn test_summary_counts_and_truncation() {
+        let pairs = vec![
+            pair(None, Some(json!({"Sample": "S1"}))),
+            pair(Some(json!({"Sample": "S1"})), None),
+            pair(
+                Some(json!({"Sample": "S1", "QC": "fail"})),
+                Some(json!({"Sample": "S1", "QC": "pass"})),
+            ),
+        ];
+        let counts = VersionMatchCounts {
+            matched: 5,
+            unkeyed: 2,
+        };
+
+        let (summary, rows, truncated) = summarize_diff(pairs, &counts, 2);
+        assert_eq!(
+            summary,
+            DiffSummary {
+                added: 1,
+                removed: 1,
+                changed: 1,
+                unchanged: 4,
+                unkeyed_rows: 2,
+            }
+        );
+        assert_eq!(rows.len(), 2);
+        assert!(truncated);
+    }
+
+    // Needs a migrated Postgres database:
+    // TEST_DATABASE_URL=... cargo test -- --ignored dataset_series
+    #[tokio::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn test_series_diff_and_as_of() {
+        use crate::models::spreadsheet::{
+            CreateDatasetSeries, SpreadsheetSearchQuery, UploadStatus,
+        };
+        use crate::services::spreadsheet_ingest::tests::{ingest_test_csv, setup_test_manager};
+        use crate::services::spreadsheet_service::SpreadsheetService;
+
+        let (manager, pool) = setup_test_manager().await;
+        let service = SpreadsheetService::new(manager.clone());
+        let series = service
+            .create_series(CreateDatasetSeries {
+                name: format!("Tracking sheet {}", Uuid::new_v4()),
+                identity_column: "Sample".to_string(),
+                description: None,
+                created_by: None,
+            })
+            .await
+            .unwrap();
+
+        let mut dataset_ids = Vec::new();
+        for csv in [
+            &b"Sample,Status,Lane\nS1,Pending,1\nS2,Pending,1\nS3,Pending,2\n,orphan,\n"[..],
+            &b"Sample,Status,Lane\nS1,Pending,1\nS2,Sequenced,1\nS4,Pending,3\n"[..],
+        ] {
+            let (dataset, _) = ingest_test_csv(&manager, "series_test.csv", csv, 10).await;
+            manager
+                .update_dataset_status(dataset.id, UploadStatus::Completed, None, None, None)
+                .await
+                .unwrap();
+            service
+                .add_dataset_to_series(series.id, dataset.id)
+                .await
+                .unwrap();
+            dataset_ids.push(dataset.id);
+        }
+
+        assert!(matches!(
+            service
+                .add_dataset_to_series(series.id, dataset_ids[0])
+                .await,
+            Err(SeriesError::DatasetAlreadyInSeries)
+        ));
+
+        let diff = service.diff_versions(series.id, 1, 2, 10).await.unwrap();
+        assert_eq!(
+            (
+                diff.summary.added,
+                diff.summary.removed,
+                diff.summary.changed
+            ),
+            (1, 1, 1)
+        );
+        assert_eq!(diff.summary.unchanged, 1);
+        assert_eq!(diff.summary.unkeyed_rows, 1);
+        let changed = diff
+            .rows
+            .iter()
+            .find(|row| row.change == RowChange::Changed)
+            .unwrap();
+        assert_eq!(changed.key, "S2");
+        assert_eq!(changed.cells.len(), 1);
+        assert_eq!(changed.cells[0].column, "Status");
+        assert_eq!(changed.cells[0].new.as_deref(), Some("Sequenced"));
+
+        // Before the second upload was added, the series reads as version 1
+        let first_added = service
+            .resolve_version(series.id, Some(1), None)
+            .await
+            .unwrap()
+            .added_at;
+        let as_of = service
+            .resolve_version(series.id, None, Some(first_added))
+            .await
+            .unwrap();
+        assert_eq!(as_of.version, 1);
+        let latest = service
+            .resolve_version(series.id, None, None)
+            .await
+            .unwrap();
+        assert_eq!(latest.version, 2);
+
+        let result = manager
+            .search_records(SpreadsheetSearchQuery {
+                search_term: None,
+                dataset_id: Some(as_of.dataset_id),
+                column_filters: None,
+                pool_filter: None,
+                sample_filter: None,
+                project_filter: None,
+                range_filters: None,
+                in_filters: None,
+                limit: None,
+                offset: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(result.total_count, 4);
+
+        sqlx::query("DELETE FROM spreadsheet_dataset_series WHERE id = $1")
+            .bind(series.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        for dataset_id in dataset_ids {
+            manager.delete_dataset(dataset_id).await.unwrap();
+        }
+    }
+}
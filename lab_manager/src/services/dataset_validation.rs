@@ -0,0 +1,441 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use crate::models::spreadsheet::{
+    DatasetRule, DatasetRuleSet, DatasetRuleSpec, RuleSeverity, ViolationAction,
+};
+use crate::validation::{dataset_rules::build_row_chain, ValidationResult};
+
+/// Violations listed in a quality report; the counts cover all of them
+pub const MAX_REPORTED_VIOLATIONS: usize = 1000;
+
+/// Rule set an upload is checked against, and what to do when it fails
+#[derive(Debug, Clone)]
+pub struct UploadValidation {
+    pub rule_set: DatasetRuleSet,
+    pub action: ViolationAction,
+}
+
+/// Outcome of checking a dataset against a rule set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QualityReport {
+    pub rule_set_id: Uuid,
+    pub rule_set_name: String,
+    pub dataset_type: String,
+    pub action: ViolationAction,
+    /// False when error-level violations caused the upload to be rejected
+    pub accepted: bool,
+    pub rows_checked: usize,
+    pub rows_with_errors: usize,
+    pub error_count: usize,
+    pub warning_count: usize,
+    pub rules: Vec<RuleSummary>,
+    pub violations: Vec<Violation>,
+    /// More violations were found than are listed
+    pub truncated: bool,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Violation counts of one rule on one column
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleSummary {
+    pub code: String,
+    pub column: Option<String>,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Violation {
+    /// Missing for problems with the sheet as a whole, such as a missing column
+    pub row_number: Option<i32>,
+    pub column: Option<String>,
+    pub code: String,
+    pub severity: RuleSeverity,
+    pub message: String,
+    pub value: Option<String>,
+}
+
+/// Check a rule set can be built before it is stored
+pub fn validate_rule_set(rules: &[DatasetRuleSpec]) -> Result<(), String> {
+    if rules.is_empty() {
+        return Err("A rule set needs at least one rule".to_string());
+    }
+    if rules
+        .iter()
+        .any(|spec| spec.rule.column().trim().is_empty())
+    {
+        return Err("Every rule needs a column".to_string());
+    }
+    build_row_chain(rules).map(|_| ())
+}
+
+/// Rules whose column is in the sheet, and violations for those whose
+/// column is not
+pub fn split_rules_by_headers(
+    rules: &[DatasetRuleSpec],
+    headers: &[String],
+) -> (Vec<DatasetRuleSpec>, Vec<Violation>) {
+    let mut present = Vec::new();
+    let mut missing = Vec::new();
+    for spec in rules {
+        let column = spec.rule.column();
+        if headers.iter().any(|header| header == column) {
+            present.push(spec.clone());
+            continue;
+        }
+        let code = match spec.rule {
+            DatasetRule::RequiredColumn { .. } => "MISSING_REQUIRED_COLUMN",
+            _ => "MISSING_COLUMN",
+        };
+        missing.push(Violation {
+            row_number: None,
+            column: Some(column.to_string()),
+            code: code.to_string(),
+            severity: spec.severity,
+            message: format!("Column '{}' is not in the sheet", column),
+            value: None,
+        });
+    }
+    (present, missing)
+}
+
+/// Accumulates row results into a quality report
+pub struct QualityReportBuilder {
+    report: QualityReport,
+    rules: BTreeMap<(String, Option<String>), RuleSummary>,
+}
+
+impl QualityReportBuilder {
+    pub fn new(rule_set: &DatasetRuleSet, action: ViolationAction) -> Self {
+        Self {
+            report: QualityReport {
+                rule_set_id: rule_set.id,
+                rule_set_name: rule_set.name.clone(),
+                dataset_type: rule_set.dataset_type.clone(),
+                action,
+                accepted: true,
+                rows_checked: 0,
+                rows_with_errors: 0,
+                error_count: 0,
+                warning_count: 0,
+                rules: Vec::new(),
+                violations: Vec::new(),
+                truncated: false,
+                checked_at: Utc::now(),
+            },
+            rules: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_violation(&mut self, violation: Violation) {
+        let summary = self
+            .rules
+            .entry((violation.code.clone(), violation.column.clone()))
+            .or_insert_with(|| RuleSummary {
+                code: violation.code.clone(),
+                column: violation.column.clone(),
+                errors: 0,
+                warnings: 0,
+            });
+        match violation.severity {
+            RuleSeverity::Error => {
+                summary.errors += 1;
+                self.report.error_count += 1;
+            }
+            RuleSeverity::Warning => {
+                summary.warnings += 1;
+                self.report.warning_count += 1;
+            }
+        }
+
+        if self.report.violations.len() < MAX_REPORTED_VIOLATIONS {
+            self.report.violations.push(violation);
+        } else {
+            self.report.truncated = true;
+        }
+    }
+
+    /// Record the chain's result for one row
+    pub fn add_row(
+        &mut self,
+        row_number: i32,
+        cells: &serde_json::Value,
+        result: ValidationResult,
+    ) {
+        self.report.rows_checked += 1;
+        if !result.errors.is_empty() {
+            self.report.rows_with_errors += 1;
+        }
+
+        let value = |field: &Option<String>| {
+            field
+                .as_ref()
+                .and_then(|column| cells.get(column))
+                .and_then(|value| value.as_str())
+                .map(str::to_string)
+        };
+        for error in result.errors {
+            self.add_violation(Violation {
+                row_number: Some(row_number),
+                value: value(&error.field),
+                column: error.field,
+                code: error.code,
+                severity: RuleSeverity::Error,
+                message: error.message,
+            });
+        }
+        for warning in result.warnings {
+            self.add_violation(Violation {
+                row_number: Some(row_number),
+                value: value(&warning.field),
+                column: warning.field,
+                code: warning.code,
+                severity: RuleSeverity::Warning,
+                message: warning.message,
+            });
+        }
+    }
+
+    pub fn finish(mut self) -> QualityReport {
+        self.report.accepted =
+            self.report.error_count == 0 || self.report.action == ViolationAction::Warn;
+        self.report.rules = self.rules.into_values().collect();
+        self.report
+    }
+}
+
+/// Dataset validation errors
+#[derive(Debug, thiserror::Error)]
+pub enum RuleSetError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Rule set not found")]
+    RuleSetNotFound,
+
+    #[error("Dataset not found")]
+    DatasetNotFound,
+
+    #[error("No validation rules are defined for dataset type '{0}'")]
+    UnknownDatasetType(String),
+
+    #[error("Dataset type '{0}' already has a rule set")]
+    DuplicateDatasetType(String),
+
+    #[error("Invalid rule set: {0}")]
+    InvalidRuleSet(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::dataset_rules::SheetRow;
+    use serde_json::json;
+
+    fn rule_set(rules: serde_json::Value) -> DatasetRuleSet {
+        DatasetRuleSet {
+            id: Uuid::new_v4(),
+            name: "Sequencing manifest".to_string(),
+            dataset_type: "manifest".to_string(),
+            description: None,
+            rules: serde_json::from_value(rules).unwrap(),
+            on_violation: ViolationAction::Reject,
+            created_by: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn check(rule_set: &DatasetRuleSet, rows: &[serde_json::Value]) -> QualityReport {
+        let chain = build_row_chain(&rule_set.rules).unwrap();
+        let mut builder = QualityReportBuilder::new(rule_set, rule_set.on_violation);
+        for (i, cells) in rows.iter().enumerate() {
+            let row = SheetRow {
+                row_number: i as i32 + 1,
+                cells: cells.clone(),
+            };
+            builder.add_row(row.row_number, cells, chain.validate(&row));
+        }
+        builder.finish()
+    }
+
+    #[test]
+    fn test_rules_report_row_and_column_of_each_violation() {
+        let rules = rule_set(json!([
+            {"type": "required_column", "column": "Sample"},
+            {"type": "unique", "column": "Sample"},
+            {"type": "allowed_values", "column": "Type", "values": ["DNA", "RNA"], "ignore_case": true},
+            {"type": "pattern", "column": "Well", "pattern": "[A-H](0?[1-9]|1[0-2])"},
+            {"type": "numeric_range", "column": "Conc", "min": 0, "max": 100, "severity": "warning"}
+        ]));
+        let report = check(
+            &rules,
+            &[
+                json!({"Sample": "S1", "Type": "dna", "Well": "A1", "Conc": "12"}),
+                json!({"Sample": "S1", "Type": "Protein", "Well": "A13", "Conc": "250"}),
+                json!({"Sample": "", "Type": "RNA", "Well": "B02", "Conc": "n/a"}),
+            ],
+        );
+
+        assert!(!report.accepted);
+        assert_eq!((report.error_count, report.warning_count), (4, 2));
+        assert_eq!(report.rows_with_errors, 2);
+
+        let located: Vec<_> = report
+            .violations
+            .iter()
+            .map(|v| (v.row_number, v.column.as_deref(), v.code.as_str()))
+            .collect();
+        assert_eq!(
+            located,
+            [
+                (Some(2), Some("Sample"), "DUPLICATE_VALUE"),
+                (Some(2), Some("Type"), "VALUE_NOT_ALLOWED"),
+                (Some(2), Some("Well"), "PATTERN_MISMATCH"),
+                (Some(2), Some("Conc"), "OUT_OF_RANGE"),
+                (Some(3), Some("Sample"), "REQUIRED_VALUE"),
+                (Some(3), Some("Conc"), "NOT_A_NUMBER"),
+            ]
+        );
+        assert_eq!(report.violations[1].value.as_deref(), Some("Protein"));
+    }
+
+    #[test]
+    fn test_warn_action_accepts_errors() {
+        let mut rules = rule_set(json!([{"type": "required_column", "column": "Sample"}]));
+        rules.on_violation = ViolationAction::Warn;
+        let report = check(&rules, &[json!({"Sample": ""})]);
+
+        assert!(report.accepted);
+        assert_eq!(report.error_count, 1);
+    }
+
+    #[test]
+    fn test_missing_columns_and_bad_rules() {
+        let rules = rule_set(json!([
+            {"type": "required_column", "column": "Sample"},
+            {"type": "unique", "column": "Barcode", "severity": "warning"}
+        ]));
+        let (present, missing) = split_rules_by_headers(&rules.rules, &["Barcode".to_string()]);
+        assert_eq!(present.len(), 1);
+        assert_eq!(missing[0].code, "MISSING_REQUIRED_COLUMN");
+        assert_eq!(missing[0].row_number, None);
+
+        let bad_pattern: Vec<DatasetRuleSpec> =
+            serde_json::from_value(json!([{"type": "pattern", "column": "Well", "pattern": "("}]))
+                .unwrap();
+        assert!(validate_rule_set(&bad_pattern).is_err());
+        assert!(validate_rule_set(&[]).is_err());
+    }
+
+    // Needs a migrated Postgres database:
+    // TEST_DATABASE_URL=... cargo test -- --ignored dataset_validation
+    #[tokio::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn test_upload_rule_set_rejects_or_warns() {
+        use crate::models::spreadsheet::{CreateDatasetRuleSet, UploadStatus, ViolationAction};
+        use crate::services::spreadsheet_ingest::tests::{create_test_dataset, setup_test_manager};
+        use crate::services::spreadsheet_ingest::{row_to_json, IngestOptions, IngestSource};
+        use crate::services::spreadsheet_service::SpreadsheetService;
+        use std::sync::Arc;
+
+        let (manager, _pool) = setup_test_manager().await;
+        let service = SpreadsheetService::new(manager.clone());
+
+        let dataset_type = format!("manifest_{}", Uuid::new_v4());
+        let rule_set = service
+            .create_rule_set(CreateDatasetRuleSet {
+                name: "Manifest rules".to_string(),
+                dataset_type: dataset_type.clone(),
+                description: None,
+                rules: serde_json::from_value(json!([
+                    {"type": "required_column", "column": "Sample"},
+                    {"type": "unique", "column": "Sample"},
+                    {"type": "numeric_range", "column": "Conc", "min": 0, "severity": "warning"},
+                    {"type": "required_column", "column": "Project"}
+                ]))
+                .unwrap(),
+                on_violation: ViolationAction::Reject,
+                created_by: None,
+            })
+            .await
+            .unwrap();
+
+        let csv = &b"Sample,Conc\nS1,12\nS1,-3\n,4\n"[..];
+        let upload = |action| {
+            let (service, manager) = (&service, &manager);
+            let dataset_type = &dataset_type;
+            async move {
+                let dataset = create_test_dataset(manager, "manifest.csv", Vec::new()).await;
+                let options = IngestOptions {
+                    validation: Some(
+                        service
+                            .upload_validation(dataset_type, action)
+                            .await
+                            .unwrap(),
+                    ),
+                    ..Default::default()
+                };
+                let result = service
+                    .ingest_dataset(
+                        dataset.id,
+                        IngestSource::Bytes(Arc::from(csv)),
+                        "csv",
+                        options,
+                        row_to_json,
+                    )
+                    .await;
+                (dataset.id, result.map_err(|e| e.to_string()))
+            }
+        };
+
+        let (rejected, result) = upload(None).await;
+        assert!(result.unwrap_err().contains("Manifest rules"));
+        let dataset = service.get_dataset(rejected).await.unwrap();
+        assert_eq!(dataset.upload_status, UploadStatus::Failed);
+
+        let report = service.get_quality_report(rejected).await.unwrap().unwrap();
+        assert!(!report.accepted);
+        assert_eq!((report.error_count, report.warning_count), (3, 1));
+        let located: Vec<_> = report
+            .violations
+            .iter()
+            .map(|v| (v.row_number, v.code.as_str()))
+            .collect();
+        assert_eq!(
+            located,
+            [
+                (None, "MISSING_REQUIRED_COLUMN"),
+                (Some(2), "DUPLICATE_VALUE"),
+                (Some(2), "OUT_OF_RANGE"),
+                (Some(3), "REQUIRED_VALUE"),
+            ]
+        );
+
+        let (accepted, result) = upload(Some(ViolationAction::Warn)).await;
+        let dataset = result.unwrap();
+        assert_eq!(dataset.upload_status, UploadStatus::Completed);
+        assert_eq!(dataset.total_rows, 3);
+        assert!(
+            service
+                .get_quality_report(accepted)
+                .await
+                .unwrap()
+                .unwrap()
+                .accepted
+        );
+
+        // Re-checking keeps the rows whatever the rule set's action
+        let report = service
+            .validate_dataset(accepted, rule_set.id)
+            .await
+            .unwrap();
+        assert!(!report.accepted);
+        assert_eq!(report.rows_checked, 3);
+
+        service.delete_rule_set(rule_set.id).await.unwrap();
+    }
+}
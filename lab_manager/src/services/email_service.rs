@@ -0,0 +1,196 @@
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::{SmtpConfig, SmtpTls};
+
+/// A file sent with an email
+#[derive(Debug, Clone)]
+pub struct EmailAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// Sends email through the configured SMTP server
+#[derive(Clone)]
+pub struct EmailService {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl std::fmt::Debug for EmailService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailService")
+            .field("from", &self.from.to_string())
+            .finish()
+    }
+}
+
+impl EmailService {
+    /// Connect settings for the SMTP server, or `None` when no server is
+    /// configured
+    pub fn from_config(config: &SmtpConfig) -> Result<Option<Self>, EmailError> {
+        let Some(host) = config.host.as_deref() else {
+            return Ok(None);
+        };
+
+        let builder = match config.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+        };
+        let builder = match (&config.username, &config.password) {
+            (Some(username), Some(password)) => {
+                builder.credentials(Credentials::new(username.clone(), password.clone()))
+            }
+            _ => builder,
+        };
+
+        Ok(Some(Self {
+            transport: builder.port(config.port).build(),
+            from: parse_mailbox(&config.from_address)?,
+        }))
+    }
+
+    /// Send a plain-text email, with an optional attachment
+    pub async fn send(
+        &self,
+        to: &[String],
+        subject: &str,
+        body: &str,
+        attachment: Option<EmailAttachment>,
+    ) -> Result<(), EmailError> {
+        if to.is_empty() {
+            return Err(EmailError::InvalidAddress("No recipients".to_string()));
+        }
+
+        let mut builder = Message::builder().from(self.from.clone()).subject(subject);
+        for address in to {
+            builder = builder.to(parse_mailbox(address)?);
+        }
+
+        let text = SinglePart::plain(body.to_string());
+        let message = match attachment {
+            Some(attachment) => {
+                let content_type =
+                    ContentType::parse(&attachment.content_type).unwrap_or(ContentType::TEXT_PLAIN);
+                builder.multipart(MultiPart::mixed().singlepart(text).singlepart(
+                    Attachment::new(attachment.filename).body(attachment.content, content_type),
+                ))?
+            }
+            None => builder.singlepart(text)?,
+        };
+
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+/// Parse an address such as `pi@lab.org` or `PI <pi@lab.org>`
+pub fn parse_mailbox(address: &str) -> Result<Mailbox, EmailError> {
+    address
+        .trim()
+        .parse()
+        .map_err(|_| EmailError::InvalidAddress(address.to_string()))
+}
+
+/// Email errors
+#[derive(Debug, thiserror::Error)]
+pub enum EmailError {
+    #[error("Invalid email address: {0}")]
+    InvalidAddress(String),
+
+    #[error("Could not build email: {0}")]
+    Message(#[from] lettre::error::Error),
+
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Accept one SMTP session and return the message data it received
+    pub(crate) async fn smtp_stand_in(listener: TcpListener) -> (Vec<String>, String) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut recipients = Vec::new();
+        let mut data = String::new();
+
+        writer.write_all(b"220 stand-in ESMTP\r\n").await.unwrap();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let command = line.to_ascii_uppercase();
+            let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                b"250 stand-in\r\n"
+            } else if command.starts_with("RCPT TO:") {
+                recipients.push(line[8..].trim().to_string());
+                b"250 OK\r\n"
+            } else if command.starts_with("DATA") {
+                writer.write_all(b"354 Go ahead\r\n").await.unwrap();
+                while let Some(line) = lines.next_line().await.unwrap() {
+                    if line == "." {
+                        break;
+                    }
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                b"250 Queued\r\n"
+            } else if command.starts_with("QUIT") {
+                writer.write_all(b"221 Bye\r\n").await.unwrap();
+                break;
+            } else {
+                b"250 OK\r\n"
+            };
+            writer.write_all(reply).await.unwrap();
+        }
+        (recipients, data)
+    }
+
+    #[tokio::test]
+    async fn test_sends_attachment_through_smtp_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(smtp_stand_in(listener));
+
+        let email = EmailService::from_config(&SmtpConfig {
+            host: Some("127.0.0.1".to_string()),
+            port,
+            tls: SmtpTls::None,
+            ..SmtpConfig::default()
+        })
+        .unwrap()
+        .unwrap();
+        email
+            .send(
+                &["PI <pi@lab.example>".to_string()],
+                "Weekly storage usage",
+                "Attached.",
+                Some(EmailAttachment {
+                    filename: "storage_usage.csv".to_string(),
+                    content_type: "text/csv".to_string(),
+                    content: b"location,samples\nFreezer A,12\n".to_vec(),
+                }),
+            )
+            .await
+            .unwrap();
+
+        let (recipients, data) = server.await.unwrap();
+        assert_eq!(recipients, ["<pi@lab.example>"]);
+        assert!(data.contains("Subject: Weekly storage usage"));
+        assert!(data.contains("filename=\"storage_usage.csv\""));
+    }
+
+    #[test]
+    fn test_no_host_disables_email() {
+        assert!(EmailService::from_config(&SmtpConfig::default())
+            .unwrap()
+            .is_none());
+        assert!(parse_mailbox("not an address").is_err());
+    }
+}
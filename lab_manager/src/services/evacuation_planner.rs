@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::models::storage::{normalize_position, SampleLocation, StorageLocation};
+
+/// Columns per row used when generating position labels (A1..A12, B1..B12, ...)
+const POSITIONS_PER_ROW: i32 = 12;
+
+/// A storage location considered as an evacuation target, with the positions already taken
+#[derive(Debug, Clone)]
+pub struct EvacuationTarget {
+    pub location: StorageLocation,
+    pub occupied_positions: HashSet<String>,
+}
+
+/// A single planned relocation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedMove {
+    pub sample_id: uuid::Uuid,
+    pub barcode: String,
+    pub from_position: Option<String>,
+    pub to_location_id: i32,
+    pub to_location_name: String,
+    pub to_position: String,
+}
+
+/// Move plan for emptying a storage location
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvacuationPlan {
+    pub source_location_id: i32,
+    pub source_location_name: String,
+    pub moves: Vec<PlannedMove>,
+    pub unplaced: Vec<String>,
+    pub target_location_ids: Vec<i32>,
+}
+
+impl EvacuationPlan {
+    pub fn is_complete(&self) -> bool {
+        self.unplaced.is_empty()
+    }
+
+    /// Printable pick list as CSV, ordered by source position
+    pub fn pick_list_csv(&self) -> String {
+        let mut moves: Vec<&PlannedMove> = self.moves.iter().collect();
+        moves.sort_by(|a, b| a.from_position.cmp(&b.from_position));
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let _ = writer.write_record([
+            "Source Location",
+            "Source Position",
+            "Barcode",
+            "Target Location",
+            "Target Position",
+        ]);
+        for planned in moves {
+            let _ = writer.write_record([
+                self.source_location_name.as_str(),
+                planned.from_position.as_deref().unwrap_or(""),
+                planned.barcode.as_str(),
+                planned.to_location_name.as_str(),
+                planned.to_position.as_str(),
+            ]);
+        }
+
+        writer
+            .into_inner()
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// Whether a location can receive samples evacuated from `source`
+pub fn is_compatible_target(source: &StorageLocation, target: &StorageLocation) -> bool {
+    target.id != source.id
+        && target.is_active
+        && target.temperature_zone == source.temperature_zone
+        && target.available_capacity() > 0
+}
+
+/// Position label for a zero-based slot index, e.g. 0 -> "A1", 12 -> "B1"
+pub fn position_label(index: i32) -> String {
+    let row = index / POSITIONS_PER_ROW;
+    let column = index % POSITIONS_PER_ROW + 1;
+
+    let mut row_label = String::new();
+    let mut remaining = row;
+    loop {
+        row_label.insert(0, (b'A' + (remaining % 26) as u8) as char);
+        if remaining < 26 {
+            break;
+        }
+        remaining = remaining / 26 - 1;
+    }
+
+    format!("{}{}", row_label, column)
+}
+
+/// Plan the relocation of every sample in `source` into compatible targets.
+///
+/// Targets with the most free space are filled first so samples end up in as
+/// few locations as possible. A sample keeps its original position label when
+/// that position is free in the target; otherwise it gets the next free slot.
+/// Positions are compared normalized, so "A01" is taken when "A1" is.
+pub fn plan_evacuation(
+    source: &StorageLocation,
+    samples: &[SampleLocation],
+    targets: Vec<EvacuationTarget>,
+) -> EvacuationPlan {
+    let mut targets: Vec<EvacuationTarget> = targets
+        .into_iter()
+        .filter(|t| is_compatible_target(source, &t.location))
+        .map(|t| EvacuationTarget {
+            occupied_positions: t
+                .occupied_positions
+                .iter()
+                .map(|position| normalize_position(position))
+                .collect(),
+            location: t.location,
+        })
+        .collect();
+    targets.sort_by_key(|t| std::cmp::Reverse(t.location.available_capacity()));
+
+    let mut samples: Vec<&SampleLocation> = samples.iter().collect();
+    samples.sort_by(|a, b| a.position.cmp(&b.position));
+
+    let mut plan = EvacuationPlan {
+        source_location_id: source.id,
+        source_location_name: source.name.clone(),
+        moves: Vec::new(),
+        unplaced: Vec::new(),
+        target_location_ids: Vec::new(),
+    };
+
+    let mut remaining: Vec<i32> = targets
+        .iter()
+        .map(|t| t.location.available_capacity())
+        .collect();
+    let mut next_slot = vec![0; targets.len()];
+    let mut current = 0;
+
+    for sample in samples {
+        while current < targets.len() && remaining[current] == 0 {
+            current += 1;
+        }
+        let Some(target) = targets.get_mut(current) else {
+            plan.unplaced.push(sample.barcode.clone());
+            continue;
+        };
+
+        let to_position = match sample.position.as_deref() {
+            Some(position)
+                if !target
+                    .occupied_positions
+                    .contains(&normalize_position(position)) =>
+            {
+                position.to_string()
+            }
+            _ => loop {
+                let candidate = position_label(next_slot[current]);
+                next_slot[current] += 1;
+                if !target.occupied_positions.contains(&candidate) {
+                    break candidate;
+                }
+            },
+        };
+
+        target
+            .occupied_positions
+            .insert(normalize_position(&to_position));
+        remaining[current] -= 1;
+        if !plan.target_location_ids.contains(&target.location.id) {
+            plan.target_location_ids.push(target.location.id);
+        }
+
+        plan.moves.push(PlannedMove {
+            sample_id: sample.sample_id,
+            barcode: sample.barcode.clone(),
+            from_position: sample.position.clone(),
+            to_location_id: target.location.id,
+            to_location_name: target.location.name.clone(),
+            to_position,
+        });
+    }
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::storage::{ContainerType, StorageState, TemperatureZone};
+    use chrono::Utc;
+
+    fn location(id: i32, zone: TemperatureZone, capacity: i32, usage: i32) -> StorageLocation {
+        StorageLocation {
+            id,
+            name: format!("Freezer {}", id),
+            description: None,
+            temperature_zone: zone,
+            capacity,
+            current_usage: usage,
+            container_type: ContainerType::Rack,
+            is_active: true,
+            location_path: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn sample(barcode: &str, position: &str) -> SampleLocation {
+        SampleLocation {
+            id: 1,
+            sample_id: uuid::Uuid::new_v4(),
+            location_id: 1,
+            barcode: barcode.to_string(),
+            position: Some(position.to_string()),
+            storage_state: StorageState::InStorage,
+            stored_at: Utc::now(),
+            stored_by: None,
+            moved_at: None,
+            moved_by: None,
+            notes: None,
+            temperature_log: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_position_label() {
+        assert_eq!(position_label(0), "A1");
+        assert_eq!(position_label(13), "B2");
+        assert_eq!(position_label(26 * 12), "AA1");
+    }
+
+    #[test]
+    fn test_plan_evacuation_respects_zone_and_capacity() {
+        let source = location(1, TemperatureZone::UltraLowFreezer, 10, 3);
+        let targets = vec![
+            EvacuationTarget {
+                location: location(2, TemperatureZone::Freezer, 100, 0),
+                occupied_positions: HashSet::new(),
+            },
+            EvacuationTarget {
+                location: location(3, TemperatureZone::UltraLowFreezer, 10, 9),
+                occupied_positions: HashSet::from(["A1".to_string()]),
+            },
+            EvacuationTarget {
+                location: location(4, TemperatureZone::UltraLowFreezer, 10, 9),
+                occupied_positions: HashSet::new(),
+            },
+        ];
+        let samples = vec![sample("S1", "A1"), sample("S2", "A2"), sample("S3", "A3")];
+
+        let plan = plan_evacuation(&source, &samples, targets);
+
+        assert_eq!(plan.moves.len(), 2);
+        assert_eq!(plan.unplaced, vec!["S3".to_string()]);
+        assert!(plan.moves.iter().all(|m| m.to_location_id != 2));
+        let s1 = plan.moves.iter().find(|m| m.barcode == "S1").unwrap();
+        assert!(!(s1.to_location_id == 3 && s1.to_position == "A1"));
+        assert_eq!(plan.pick_list_csv().lines().count(), 3);
+    }
+
+    #[test]
+    fn test_plan_evacuation_normalizes_positions() {
+        let source = location(1, TemperatureZone::Freezer, 10, 2);
+        let targets = vec![EvacuationTarget {
+            location: location(2, TemperatureZone::Freezer, 10, 2),
+            occupied_positions: HashSet::from(["a01".to_string(), "A2".to_string()]),
+        }];
+        let samples = vec![sample("S1", "A1"), sample("S2", "A03")];
+
+        let plan = plan_evacuation(&source, &samples, targets);
+
+        let positions: Vec<&str> = plan.moves.iter().map(|m| m.to_position.as_str()).collect();
+        assert_eq!(positions, vec!["A03", "A4"]);
+    }
+}
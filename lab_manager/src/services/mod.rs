@@ -1,12 +1,36 @@
+pub mod analytics_service;
 pub mod auth_service;
 pub mod barcode_service;
+pub mod column_inference;
+pub mod csv_dialect;
+pub mod dataset_series;
+pub mod dataset_validation;
+pub mod email_service;
+pub mod evacuation_planner;
 pub mod rag_integration_service;
+pub mod report_assistant;
+pub mod report_execution;
+pub mod report_export;
+pub mod report_query;
+pub mod report_schedule_service;
+pub mod report_values;
+pub mod sample_mapping;
+pub mod saved_report_service;
 pub mod sample_service;
 pub mod sequencing_service;
+pub mod shipment_service;
+pub mod spreadsheet_aggregate;
+pub mod spreadsheet_export;
+pub mod spreadsheet_ingest;
+pub mod spreadsheet_search;
 pub mod spreadsheet_service;
 pub mod storage_management_service;
+pub mod storage_reconciliation;
 pub mod storage_service;
 pub mod template_service;
+pub mod template_submission;
+pub mod template_workbook;
+pub mod xlsx_reader;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
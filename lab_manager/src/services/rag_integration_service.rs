@@ -8,6 +8,7 @@ use tokio::fs;
 
 use crate::{
     errors::api::ApiError,
+    models::report::{DatabaseSchema, ReportQuerySpec},
     sample_submission::CreateSample,
     services::{HealthCheck, HealthStatus, Service, ServiceConfig, ServiceHealth},
 };
@@ -224,6 +225,16 @@ pub struct RagEnhancedSampleResult {
     pub processing_time: f64,
 }
 
+/// A report drafted by the RAG system from a question, as a structured
+/// report or SQL
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RagReportDraft {
+    pub structured: Option<ReportQuerySpec>,
+    pub sql: Option<String>,
+    /// How the draft answers the question
+    pub explanation: Option<String>,
+}
+
 /// Service for integrating with the RAG LLM system
 pub struct RagIntegrationService {
     client: Client,
@@ -493,6 +504,45 @@ impl RagIntegrationService {
             .to_string())
     }
 
+    /// Ask the RAG system to draft a report answering a question. The schema
+    /// lists the tables and columns the draft may use; `formats` is the
+    /// kinds of draft accepted, `structured` and optionally `sql`.
+    pub async fn draft_report_query(
+        &self,
+        question: &str,
+        schema: &DatabaseSchema,
+        formats: &[&str],
+    ) -> Result<RagReportDraft, ApiError> {
+        let url = format!("{}/query/report", self.config.base_url);
+        let request_body = serde_json::json!({
+            "question": question,
+            "schema": schema,
+            "formats": formats
+        });
+
+        let response = self
+            .client
+            .post(&url)
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| {
+                ApiError::ServiceUnavailable(format!("RAG system is unavailable: {}", e))
+            })?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(ApiError::InternalServerError(format!(
+                "RAG report query error: {}",
+                error_text
+            )));
+        }
+
+        response.json().await.map_err(|e| {
+            ApiError::InternalServerError(format!("Failed to parse RAG report draft: {}", e))
+        })
+    }
+
     /// Convert RAG extraction result to lab manager sample format
     pub fn convert_to_samples(
         &self,
@@ -596,6 +646,7 @@ impl RagIntegrationService {
             barcode,
             location,
             metadata: Some(metadata),
+            template_version_id: None,
         })
     }
 
@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::api::ApiError;
+use crate::models::report::{DatabaseSchema, ReportQuerySpec};
+use crate::services::rag_integration_service::RagReportDraft;
+use crate::services::report_execution::ReportSource;
+use crate::services::report_query::{
+    compile_report, is_safe_query, ReportQueryError, DEFAULT_REPORT_LIMIT,
+};
+use crate::services::saved_report_service::PreparedReport;
+
+/// Longest question the report assistant accepts
+pub const MAX_QUESTION_CHARS: usize = 1000;
+
+/// A report drafted from a question. It is shown for confirmation and sent
+/// back to run, so it is checked again before running.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportDraft {
+    pub question: String,
+    /// Set for structured drafts
+    pub structured: Option<ReportQuerySpec>,
+    /// Set for SQL drafts, which only administrators may run
+    pub sql: Option<String>,
+    pub explanation: Option<String>,
+}
+
+/// The question, trimmed, if it is one the assistant takes
+pub fn check_question(question: &str) -> Result<String, ReportAssistantError> {
+    let question = question.trim();
+    if question.is_empty() {
+        return Err(ReportAssistantError::InvalidQuestion(
+            "Ask a question".to_string(),
+        ));
+    }
+    if question.chars().count() > MAX_QUESTION_CHARS {
+        return Err(ReportAssistantError::InvalidQuestion(format!(
+            "Questions are limited to {} characters",
+            MAX_QUESTION_CHARS
+        )));
+    }
+    Ok(question.to_string())
+}
+
+/// Kinds of draft to ask the RAG system for
+pub fn draft_formats(allow_sql: bool) -> &'static [&'static str] {
+    if allow_sql {
+        &["structured", "sql"]
+    } else {
+        &["structured"]
+    }
+}
+
+/// Take the RAG system's answer as a draft, preferring a structured report
+/// when it wrote both
+pub fn draft_from_rag(
+    question: String,
+    answer: RagReportDraft,
+) -> Result<ReportDraft, ReportAssistantError> {
+    let sql = answer
+        .sql
+        .map(|sql| sql.trim().to_string())
+        .filter(|sql| !sql.is_empty());
+    let (structured, sql) = match (answer.structured, sql) {
+        (Some(spec), _) => (Some(spec), None),
+        (None, Some(sql)) => (None, Some(sql)),
+        (None, None) => return Err(ReportAssistantError::NoDraft),
+    };
+    Ok(ReportDraft {
+        question,
+        structured,
+        sql,
+        explanation: answer.explanation,
+    })
+}
+
+/// Check a draft the way a hand-written report is checked: structured
+/// drafts are compiled against the schema, SQL drafts must be a single
+/// SELECT from an administrator
+pub fn prepare_draft(
+    draft: &ReportDraft,
+    schema: &DatabaseSchema,
+    allow_sql: bool,
+    max_rows: i64,
+) -> Result<PreparedReport, ReportAssistantError> {
+    match (&draft.structured, &draft.sql) {
+        (Some(spec), None) => {
+            let spec = ReportQuerySpec {
+                limit: Some(spec.limit.unwrap_or(DEFAULT_REPORT_LIMIT).min(max_rows)),
+                ..spec.clone()
+            };
+            let compiled = compile_report(&spec, schema)?;
+            Ok(PreparedReport {
+                source: ReportSource::Structured,
+                sql: compiled.sql,
+                params: compiled.params,
+                columns: Some(compiled.columns),
+                limit: compiled.limit,
+            })
+        }
+        (None, Some(sql)) => {
+            if !allow_sql {
+                return Err(ReportAssistantError::SqlNotAllowed);
+            }
+            if !is_safe_query(sql) {
+                return Err(ReportAssistantError::UnsafeSql);
+            }
+            Ok(PreparedReport {
+                source: ReportSource::Sql,
+                sql: sql.clone(),
+                params: Vec::new(),
+                columns: None,
+                limit: max_rows,
+            })
+        }
+        _ => Err(ReportAssistantError::InvalidDraft(
+            "A draft is either a structured report or SQL".to_string(),
+        )),
+    }
+}
+
+/// Report assistant errors
+#[derive(Debug, thiserror::Error)]
+pub enum ReportAssistantError {
+    #[error("Invalid question: {0}")]
+    InvalidQuestion(String),
+
+    #[error("Report assistant is disabled")]
+    Disabled,
+
+    #[error("{0}")]
+    Rag(#[from] ApiError),
+
+    #[error("The assistant could not draft a report for this question")]
+    NoDraft,
+
+    #[error("Invalid draft: {0}")]
+    InvalidDraft(String),
+
+    #[error("Only administrators can run SQL reports; ask for a structured report instead")]
+    SqlNotAllowed,
+
+    #[error("Only single SELECT queries are allowed for reports")]
+    UnsafeSql,
+
+    #[error("Drafted report is invalid: {0}")]
+    Query(#[from] ReportQueryError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::report::{ColumnInfo, TableInfo};
+    use serde_json::json;
+
+    fn schema() -> DatabaseSchema {
+        let column = |name: &str, udt_name: &str| ColumnInfo {
+            name: name.to_string(),
+            data_type: udt_name.to_string(),
+            udt_name: udt_name.to_string(),
+            is_nullable: true,
+            is_primary_key: name == "id",
+        };
+        DatabaseSchema {
+            tables: vec![TableInfo {
+                name: "samples".to_string(),
+                columns: vec![
+                    column("id", "uuid"),
+                    column("name", "varchar"),
+                    column("created_at", "timestamptz"),
+                ],
+            }],
+        }
+    }
+
+    fn draft(structured: Option<serde_json::Value>, sql: Option<&str>) -> ReportDraft {
+        ReportDraft {
+            question: "How many samples arrived last month?".to_string(),
+            structured: structured.map(|spec| serde_json::from_value(spec).unwrap()),
+            sql: sql.map(str::to_string),
+            explanation: None,
+        }
+    }
+
+    #[test]
+    fn test_questions_are_trimmed_and_bounded() {
+        assert_eq!(check_question("  how many?  ").unwrap(), "how many?");
+        assert!(check_question(" ").is_err());
+        assert!(check_question(&"?".repeat(MAX_QUESTION_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn test_structured_answers_win() {
+        let answer = RagReportDraft {
+            structured: Some(serde_json::from_value(json!({"entity": "samples"})).unwrap()),
+            sql: Some("SELECT 1".to_string()),
+            explanation: None,
+        };
+        let draft = draft_from_rag("q".to_string(), answer).unwrap();
+        assert!(draft.structured.is_some());
+        assert_eq!(draft.sql, None);
+
+        let answer = RagReportDraft {
+            sql: Some("  ".to_string()),
+            ..RagReportDraft::default()
+        };
+        assert!(matches!(
+            draft_from_rag("q".to_string(), answer),
+            Err(ReportAssistantError::NoDraft)
+        ));
+    }
+
+    #[test]
+    fn test_drafts_go_through_the_report_checks() {
+        let prepared = prepare_draft(
+            &draft(
+                Some(json!({
+                    "entity": "samples",
+                    "fields": ["name"],
+                    "filters": [{"field": "created_at", "op": "gte", "value": "2025-05-01"}],
+                    "limit": 50000
+                })),
+                None,
+            ),
+            &schema(),
+            false,
+            500,
+        )
+        .unwrap();
+        assert_eq!(prepared.source, ReportSource::Structured);
+        assert_eq!(prepared.limit, 500);
+        assert_eq!(prepared.columns, Some(vec!["name".to_string()]));
+
+        let unknown = draft(Some(json!({"entity": "users", "fields": ["email"]})), None);
+        assert!(matches!(
+            prepare_draft(&unknown, &schema(), true, 500),
+            Err(ReportAssistantError::Query(_))
+        ));
+
+        let sql = draft(None, Some("SELECT count(*) FROM samples"));
+        assert!(matches!(
+            prepare_draft(&sql, &schema(), false, 500),
+            Err(ReportAssistantError::SqlNotAllowed)
+        ));
+        assert_eq!(
+            prepare_draft(&sql, &schema(), true, 500).unwrap().source,
+            ReportSource::Sql
+        );
+
+        let unsafe_sql = draft(None, Some("DELETE FROM samples"));
+        assert!(matches!(
+            prepare_draft(&unsafe_sql, &schema(), true, 500),
+            Err(ReportAssistantError::UnsafeSql)
+        ));
+
+        assert!(matches!(
+            prepare_draft(&draft(None, None), &schema(), true, 500),
+            Err(ReportAssistantError::InvalidDraft(_))
+        ));
+    }
+}
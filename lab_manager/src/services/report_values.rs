@@ -0,0 +1,564 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde_json::{Number, Value};
+use sqlx::postgres::{PgRow, PgTypeInfo, PgTypeKind, PgValueFormat};
+use sqlx::{Column, Row, TypeInfo, ValueRef};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use uuid::Uuid;
+
+/// Microseconds per day
+const DAY_MICROS: i64 = 86_400_000_000;
+
+/// A report row keyed by column name
+pub fn row_to_json(row: &PgRow) -> HashMap<String, Value> {
+    row.columns()
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            (
+                column.name().to_string(),
+                convert_sql_value_to_json(row, i, column.type_info()),
+            )
+        })
+        .collect()
+}
+
+/// A report row's values in column order
+pub fn row_values(row: &PgRow) -> Vec<Value> {
+    row.columns()
+        .iter()
+        .enumerate()
+        .map(|(i, column)| convert_sql_value_to_json(row, i, column.type_info()))
+        .collect()
+}
+
+/// Column names of a SQL report, from its first row
+pub fn row_columns(rows: &[PgRow]) -> Vec<String> {
+    rows.first()
+        .map(|row| {
+            row.columns()
+                .iter()
+                .map(|col| col.name().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Convert SQL values to JSON values.
+///
+/// Values are decoded from Postgres' binary format. Integers, floats and
+/// numerics become numbers; numerics too large for an `i64` that have no
+/// fraction stay strings so no digits are lost. Dates and times use ISO
+/// 8601, enums their label, and arrays become JSON arrays.
+pub fn convert_sql_value_to_json(row: &PgRow, index: usize, type_info: &PgTypeInfo) -> Value {
+    let value = match row.try_get_raw(index) {
+        Ok(value) => value,
+        Err(_) => return Value::Null,
+    };
+    if value.is_null() {
+        return Value::Null;
+    }
+
+    match value.format() {
+        PgValueFormat::Text => value
+            .as_str()
+            .map(|text| Value::String(text.to_string()))
+            .unwrap_or(Value::Null),
+        PgValueFormat::Binary => value
+            .as_bytes()
+            .ok()
+            .and_then(|bytes| decode_binary(bytes, type_info))
+            .unwrap_or(Value::Null),
+    }
+}
+
+/// Decode one non-null value in binary format. `None` for malformed values
+/// and types reports don't support.
+pub fn decode_binary(bytes: &[u8], type_info: &PgTypeInfo) -> Option<Value> {
+    match type_info.name() {
+        "BOOL" => Some(Value::Bool(*bytes.first()? != 0)),
+        "INT2" => Some(Value::from(i16::from_be_bytes(fixed(bytes)?))),
+        "INT4" => Some(Value::from(i32::from_be_bytes(fixed(bytes)?))),
+        "INT8" => Some(Value::from(i64::from_be_bytes(fixed(bytes)?))),
+        "OID" => Some(Value::from(u32::from_be_bytes(fixed(bytes)?))),
+        "FLOAT4" => Some(float_value(f32::from_be_bytes(fixed(bytes)?) as f64)),
+        "FLOAT8" => Some(float_value(f64::from_be_bytes(fixed(bytes)?))),
+        "NUMERIC" => numeric_value(bytes),
+        "TEXT" | "VARCHAR" | "CHAR" | "NAME" | "UNKNOWN" | "\"CHAR\"" | "citext" | "xml" => {
+            text(bytes)
+        }
+        "UUID" => Uuid::from_slice(bytes)
+            .ok()
+            .map(|uuid| Value::String(uuid.to_string())),
+        "JSON" => serde_json::from_slice(bytes).ok(),
+        // JSONB starts with a format version
+        "JSONB" => serde_json::from_slice(bytes.get(1..)?).ok(),
+        "DATE" => Some(date_value(i32::from_be_bytes(fixed(bytes)?))),
+        "TIME" => time_value(i64::from_be_bytes(fixed(bytes)?)),
+        "TIMETZ" => {
+            let time = time_value(i64::from_be_bytes(fixed(bytes.get(..8)?)?))?;
+            // Stored as seconds west of UTC
+            let offset = -i32::from_be_bytes(fixed(bytes.get(8..12)?)?);
+            let sign = if offset < 0 { '-' } else { '+' };
+            let offset = offset.unsigned_abs();
+            Some(Value::String(format!(
+                "{}{}{:02}:{:02}",
+                time.as_str()?,
+                sign,
+                offset / 3600,
+                offset % 3600 / 60
+            )))
+        }
+        "TIMESTAMP" => Some(timestamp_value(i64::from_be_bytes(fixed(bytes)?), false)),
+        "TIMESTAMPTZ" => Some(timestamp_value(i64::from_be_bytes(fixed(bytes)?), true)),
+        "INTERVAL" => {
+            let micros = i64::from_be_bytes(fixed(bytes.get(..8)?)?);
+            let days = i32::from_be_bytes(fixed(bytes.get(8..12)?)?);
+            let months = i32::from_be_bytes(fixed(bytes.get(12..16)?)?);
+            Some(Value::String(interval_text(months, days, micros)))
+        }
+        "INET" | "CIDR" => inet_text(bytes, type_info.name() == "CIDR").map(Value::String),
+        "BYTEA" => {
+            let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            Some(Value::String(format!("\\x{}", hex)))
+        }
+        "tsvector" => tsvector_text(bytes).map(Value::String),
+        _ => match type_info.kind() {
+            PgTypeKind::Enum(_) => text(bytes),
+            PgTypeKind::Domain(base) => decode_binary(bytes, base),
+            PgTypeKind::Array(element) => decode_array(bytes, element),
+            _ => None,
+        },
+    }
+}
+
+/// Decode an array in binary format, nesting multi-dimensional arrays
+pub fn decode_array(bytes: &[u8], element: &PgTypeInfo) -> Option<Value> {
+    let dimensions = i32::from_be_bytes(fixed(bytes.get(..4)?)?) as usize;
+    // Skip the null flag and element OID
+    let mut offset = 12;
+    let mut lengths = Vec::with_capacity(dimensions);
+    for _ in 0..dimensions {
+        let length = i32::from_be_bytes(fixed(bytes.get(offset..offset + 4)?)?);
+        lengths.push(usize::try_from(length).ok()?);
+        // Skip the lower bound
+        offset += 8;
+    }
+    if lengths.is_empty() {
+        return Some(Value::Array(Vec::new()));
+    }
+
+    let count = lengths.iter().product::<usize>();
+    let mut elements = Vec::with_capacity(count.min(bytes.len()));
+    for _ in 0..count {
+        let length = i32::from_be_bytes(fixed(bytes.get(offset..offset + 4)?)?);
+        offset += 4;
+        if length < 0 {
+            elements.push(Value::Null);
+            continue;
+        }
+        let end = offset + length as usize;
+        elements.push(decode_binary(bytes.get(offset..end)?, element).unwrap_or(Value::Null));
+        offset = end;
+    }
+
+    // Group the flat elements from the innermost dimension outwards
+    for &length in lengths[1..].iter().rev() {
+        let mut grouped = Vec::with_capacity(elements.len() / length.max(1));
+        let mut rest = elements.into_iter();
+        loop {
+            let group: Vec<Value> = rest.by_ref().take(length).collect();
+            if group.is_empty() {
+                break;
+            }
+            grouped.push(Value::Array(group));
+        }
+        elements = grouped;
+    }
+    Some(Value::Array(elements))
+}
+
+fn fixed<const N: usize>(bytes: &[u8]) -> Option<[u8; N]> {
+    bytes.try_into().ok()
+}
+
+fn text(bytes: &[u8]) -> Option<Value> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .map(|text| Value::String(text.to_string()))
+}
+
+/// JSON has no NaN or infinity, so those become strings
+fn float_value(value: f64) -> Value {
+    match Number::from_f64(value) {
+        Some(number) => Value::Number(number),
+        None if value.is_nan() => Value::String("NaN".to_string()),
+        None if value > 0.0 => Value::String("Infinity".to_string()),
+        None => Value::String("-Infinity".to_string()),
+    }
+}
+
+fn numeric_value(bytes: &[u8]) -> Option<Value> {
+    let text = numeric_text(bytes)?;
+    if !text.contains('.') {
+        if let Ok(integer) = text.parse::<i64>() {
+            return Some(Value::from(integer));
+        }
+        return Some(Value::String(text));
+    }
+    Some(match text.parse::<f64>() {
+        Ok(value) if value.is_finite() => float_value(value),
+        _ => Value::String(text),
+    })
+}
+
+/// Decimal text of a NUMERIC: a sign, weight and display scale followed by
+/// base-10000 digits
+pub fn numeric_text(bytes: &[u8]) -> Option<String> {
+    let count = i16::from_be_bytes(fixed(bytes.get(0..2)?)?) as usize;
+    let weight = i16::from_be_bytes(fixed(bytes.get(2..4)?)?) as i32;
+    let sign = u16::from_be_bytes(fixed(bytes.get(4..6)?)?);
+    let scale = u16::from_be_bytes(fixed(bytes.get(6..8)?)?) as usize;
+    let digits = (0..count)
+        .map(|i| {
+            let start = 8 + i * 2;
+            bytes
+                .get(start..start + 2)
+                .and_then(fixed)
+                .map(i16::from_be_bytes)
+        })
+        .collect::<Option<Vec<i16>>>()?;
+
+    let negative = match sign {
+        0x0000 => false,
+        0x4000 => true,
+        0xC000 => return Some("NaN".to_string()),
+        0xD000 => return Some("Infinity".to_string()),
+        0xF000 => return Some("-Infinity".to_string()),
+        _ => return None,
+    };
+    let digit = |position: i32| -> i16 {
+        usize::try_from(position)
+            .ok()
+            .and_then(|position| digits.get(position).copied())
+            .unwrap_or(0)
+    };
+
+    let mut integer = String::new();
+    for position in 0..=weight {
+        if integer.is_empty() {
+            if digit(position) != 0 {
+                integer.push_str(&digit(position).to_string());
+            }
+        } else {
+            integer.push_str(&format!("{:04}", digit(position)));
+        }
+    }
+    if integer.is_empty() {
+        integer.push('0');
+    }
+
+    let mut fraction = String::new();
+    let mut position = weight + 1;
+    while fraction.len() < scale {
+        fraction.push_str(&format!("{:04}", digit(position)));
+        position += 1;
+    }
+    fraction.truncate(scale);
+
+    let sign = if negative { "-" } else { "" };
+    Some(if fraction.is_empty() {
+        format!("{}{}", sign, integer)
+    } else {
+        format!("{}{}.{}", sign, integer, fraction)
+    })
+}
+
+fn postgres_epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2000, 1, 1)
+        .unwrap_or_default()
+        .and_time(NaiveTime::MIN)
+}
+
+fn date_value(days: i32) -> Value {
+    match days {
+        i32::MAX => Value::String("infinity".to_string()),
+        i32::MIN => Value::String("-infinity".to_string()),
+        days => postgres_epoch()
+            .date()
+            .checked_add_signed(Duration::days(days as i64))
+            .map(|date| Value::String(date.format("%Y-%m-%d").to_string()))
+            .unwrap_or(Value::Null),
+    }
+}
+
+fn time_value(micros: i64) -> Option<Value> {
+    let seconds = u32::try_from(micros.div_euclid(1_000_000)).ok()?;
+    let nanos = (micros.rem_euclid(1_000_000) * 1000) as u32;
+    // 24:00:00 is a valid TIME
+    if micros == DAY_MICROS {
+        return Some(Value::String("24:00:00".to_string()));
+    }
+    NaiveTime::from_num_seconds_from_midnight_opt(seconds, nanos)
+        .map(|time| Value::String(time.format("%H:%M:%S%.f").to_string()))
+}
+
+fn timestamp_value(micros: i64, with_time_zone: bool) -> Value {
+    match micros {
+        i64::MAX => return Value::String("infinity".to_string()),
+        i64::MIN => return Value::String("-infinity".to_string()),
+        _ => {}
+    }
+    let Some(timestamp) = postgres_epoch().checked_add_signed(Duration::microseconds(micros))
+    else {
+        return Value::Null;
+    };
+    Value::String(if with_time_zone {
+        DateTime::<chrono::Utc>::from_naive_utc_and_offset(timestamp, chrono::Utc).to_rfc3339()
+    } else {
+        timestamp.format("%Y-%m-%dT%H:%M:%S%.f").to_string()
+    })
+}
+
+/// ISO 8601 duration, signed per field as Postgres' `iso_8601` style writes
+/// it, e.g. `P1Y2M3DT4H5M6.5S`
+pub fn interval_text(months: i32, days: i32, micros: i64) -> String {
+    let mut text = String::from("P");
+    if months / 12 != 0 {
+        text.push_str(&format!("{}Y", months / 12));
+    }
+    if months % 12 != 0 {
+        text.push_str(&format!("{}M", months % 12));
+    }
+    if days != 0 {
+        text.push_str(&format!("{}D", days));
+    }
+
+    if micros != 0 {
+        text.push('T');
+        let hours = micros / 3_600_000_000;
+        let minutes = (micros % 3_600_000_000) / 60_000_000;
+        let seconds = micros % 60_000_000;
+        if hours != 0 {
+            text.push_str(&format!("{}H", hours));
+        }
+        if minutes != 0 {
+            text.push_str(&format!("{}M", minutes));
+        }
+        if seconds != 0 {
+            let sign = if seconds < 0 { "-" } else { "" };
+            let seconds = seconds.unsigned_abs();
+            let fraction = format!("{:06}", seconds % 1_000_000);
+            let fraction = fraction.trim_end_matches('0');
+            if fraction.is_empty() {
+                text.push_str(&format!("{}{}S", sign, seconds / 1_000_000));
+            } else {
+                text.push_str(&format!("{}{}.{}S", sign, seconds / 1_000_000, fraction));
+            }
+        }
+    }
+
+    if text == "P" {
+        text.push_str("T0S");
+    }
+    text
+}
+
+/// Address text of an INET or CIDR; INET leaves out a full-length netmask
+fn inet_text(bytes: &[u8], cidr: bool) -> Option<String> {
+    let family = *bytes.first()?;
+    let bits = *bytes.get(1)?;
+    let address = bytes.get(4..)?;
+    let (address, max_bits): (IpAddr, u8) = match family {
+        2 => (Ipv4Addr::from(fixed::<4>(address)?).into(), 32),
+        3 => (Ipv6Addr::from(fixed::<16>(address)?).into(), 128),
+        _ => return None,
+    };
+    Some(if cidr || bits != max_bits {
+        format!("{}/{}", address, bits)
+    } else {
+        address.to_string()
+    })
+}
+
+/// Text of a TSVECTOR as Postgres writes it, e.g. `'plasma':1A,4 'sample'`
+fn tsvector_text(bytes: &[u8]) -> Option<String> {
+    let count = u32::from_be_bytes(fixed(bytes.get(..4)?)?);
+    let mut offset = 4;
+    let mut lexemes = Vec::new();
+    for _ in 0..count {
+        let length = bytes.get(offset..)?.iter().position(|&byte| byte == 0)?;
+        let lexeme = std::str::from_utf8(&bytes[offset..offset + length]).ok()?;
+        offset += length + 1;
+
+        let positions = u16::from_be_bytes(fixed(bytes.get(offset..offset + 2)?)?);
+        offset += 2;
+        let mut entry = format!("'{}'", lexeme.replace('\'', "''"));
+        for i in 0..positions {
+            let position = u16::from_be_bytes(fixed(bytes.get(offset..offset + 2)?)?);
+            offset += 2;
+            entry.push(if i == 0 { ':' } else { ',' });
+            entry.push_str(&(position & 0x3FFF).to_string());
+            match position >> 14 {
+                3 => entry.push('A'),
+                2 => entry.push('B'),
+                1 => entry.push('C'),
+                _ => {}
+            }
+        }
+        lexemes.push(entry);
+    }
+    Some(lexemes.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn numeric(weight: i16, sign: u16, scale: u16, digits: &[i16]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend((digits.len() as i16).to_be_bytes());
+        bytes.extend(weight.to_be_bytes());
+        bytes.extend(sign.to_be_bytes());
+        bytes.extend(scale.to_be_bytes());
+        for digit in digits {
+            bytes.extend(digit.to_be_bytes());
+        }
+        bytes
+    }
+
+    fn decode(name: &'static str, bytes: &[u8]) -> Option<Value> {
+        decode_binary(bytes, &PgTypeInfo::with_name(name))
+    }
+
+    #[test]
+    fn test_numerics_keep_their_digits() {
+        assert_eq!(
+            numeric_text(&numeric(1, 0, 3, &[1, 2345, 6780])).unwrap(),
+            "12345.678"
+        );
+        assert_eq!(
+            numeric_text(&numeric(-1, 0x4000, 5, &[12])).unwrap(),
+            "-0.00120"
+        );
+        assert_eq!(numeric_text(&numeric(2, 0, 0, &[1])).unwrap(), "100000000");
+        assert_eq!(numeric_text(&numeric(0, 0, 2, &[])).unwrap(), "0.00");
+        assert_eq!(numeric_text(&numeric(0, 0xC000, 0, &[])).unwrap(), "NaN");
+
+        assert_eq!(
+            decode("NUMERIC", &numeric(0, 0, 2, &[12, 5000])).unwrap(),
+            json!(12.5)
+        );
+        assert_eq!(
+            decode("NUMERIC", &numeric(1, 0, 0, &[4, 2])).unwrap(),
+            json!(40002)
+        );
+        // Integers beyond i64 stay exact as strings
+        assert_eq!(
+            decode("NUMERIC", &numeric(5, 0, 0, &[1, 0, 0, 0, 0, 0])).unwrap(),
+            json!("100000000000000000000")
+        );
+    }
+
+    #[test]
+    fn test_scalars_decode_from_binary() {
+        assert_eq!(decode("INT2", &7i16.to_be_bytes()).unwrap(), json!(7));
+        assert_eq!(decode("FLOAT4", &1.5f32.to_be_bytes()).unwrap(), json!(1.5));
+        assert_eq!(
+            decode("FLOAT8", &f64::NAN.to_be_bytes()).unwrap(),
+            json!("NaN")
+        );
+        assert_eq!(decode("BOOL", &[1]).unwrap(), json!(true));
+        assert_eq!(decode("JSONB", b"\x01{\"a\":1}").unwrap(), json!({"a": 1}));
+        assert_eq!(
+            decode("DATE", &(-1i32).to_be_bytes()).unwrap(),
+            json!("1999-12-31")
+        );
+        assert_eq!(
+            decode("TIME", &(DAY_MICROS / 2 + 500_000).to_be_bytes()).unwrap(),
+            json!("12:00:00.500")
+        );
+        let mut timetz = (9 * 3_600_000_000i64).to_be_bytes().to_vec();
+        timetz.extend((-3600i32).to_be_bytes());
+        assert_eq!(decode("TIMETZ", &timetz).unwrap(), json!("09:00:00+01:00"));
+        assert_eq!(
+            decode("TIMESTAMPTZ", &DAY_MICROS.to_be_bytes()).unwrap(),
+            json!("2000-01-02T00:00:00+00:00")
+        );
+        assert_eq!(
+            decode("TIMESTAMP", &i64::MAX.to_be_bytes()).unwrap(),
+            json!("infinity")
+        );
+        assert_eq!(decode("BYTEA", &[0xde, 0xad]).unwrap(), json!("\\xdead"));
+        assert_eq!(
+            decode("INET", &[2, 32, 0, 4, 10, 0, 0, 1]).unwrap(),
+            json!("10.0.0.1")
+        );
+        assert_eq!(
+            decode("CIDR", &[2, 24, 1, 4, 10, 0, 0, 0]).unwrap(),
+            json!("10.0.0.0/24")
+        );
+
+        let mut tsvector = 2u32.to_be_bytes().to_vec();
+        tsvector.extend(b"plasma\0");
+        tsvector.extend(2u16.to_be_bytes());
+        tsvector.extend((1u16 | 3 << 14).to_be_bytes());
+        tsvector.extend(4u16.to_be_bytes());
+        tsvector.extend(b"sample\0");
+        tsvector.extend(0u16.to_be_bytes());
+        assert_eq!(
+            decode("tsvector", &tsvector).unwrap(),
+            json!("'plasma':1A,4 'sample'")
+        );
+    }
+
+    #[test]
+    fn test_intervals_use_iso_8601() {
+        assert_eq!(
+            interval_text(14, 3, 4 * 3_600_000_000 + 5 * 60_000_000 + 6_500_000),
+            "P1Y2M3DT4H5M6.5S"
+        );
+        assert_eq!(interval_text(0, 0, 0), "PT0S");
+        assert_eq!(interval_text(0, -1, -30_000_000), "P-1DT-30S");
+    }
+
+    #[test]
+    fn test_arrays_nest_by_dimension() {
+        fn array(dimensions: &[i32], elements: &[Option<&[u8]>]) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            bytes.extend((dimensions.len() as i32).to_be_bytes());
+            bytes.extend(1i32.to_be_bytes());
+            bytes.extend(25u32.to_be_bytes());
+            for length in dimensions {
+                bytes.extend(length.to_be_bytes());
+                bytes.extend(1i32.to_be_bytes());
+            }
+            for element in elements {
+                match element {
+                    Some(value) => {
+                        bytes.extend((value.len() as i32).to_be_bytes());
+                        bytes.extend(*value);
+                    }
+                    None => bytes.extend((-1i32).to_be_bytes()),
+                }
+            }
+            bytes
+        }
+
+        let text = PgTypeInfo::with_name("TEXT");
+        assert_eq!(
+            decode_array(&array(&[3], &[Some(b"a"), None, Some(b"c")]), &text).unwrap(),
+            json!(["a", null, "c"])
+        );
+        assert_eq!(
+            decode_array(
+                &array(&[2, 2], &[Some(b"a"), Some(b"b"), Some(b"c"), Some(b"d")]),
+                &text
+            )
+            .unwrap(),
+            json!([["a", "b"], ["c", "d"]])
+        );
+        assert_eq!(decode_array(&array(&[], &[]), &text).unwrap(), json!([]));
+    }
+}
@@ -0,0 +1,664 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::models::spreadsheet::{FieldMapping, MappingTarget, ValueTransform};
+use crate::sample_submission::CreateSample;
+
+/// Rows returned by a preview unless the caller asks for more
+pub const DEFAULT_PREVIEW_LIMIT: usize = 100;
+
+/// Upper bound on rows returned by a preview
+pub const MAX_PREVIEW_LIMIT: usize = 5000;
+
+/// Column widths of the samples table
+const MAX_NAME_LENGTH: usize = 255;
+const MAX_BARCODE_LENGTH: usize = 50;
+const MAX_LOCATION_LENGTH: usize = 255;
+
+/// Dry run of converting a dataset into samples
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MappingPreview {
+    pub dataset_id: Uuid,
+    pub profile_id: Uuid,
+    pub total_rows: usize,
+    pub valid_rows: usize,
+    pub invalid_rows: usize,
+    /// Rows that already have a sample and would be left alone
+    pub converted_rows: usize,
+    pub rows: Vec<RowPreview>,
+    /// More rows matched than were returned
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    Valid,
+    Invalid,
+    Converted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowPreview {
+    pub record_id: Uuid,
+    pub row_number: i32,
+    pub status: RowStatus,
+    pub sample: Option<CreateSample>,
+    pub errors: Vec<RowIssue>,
+}
+
+/// Why a row cannot become a sample
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RowIssue {
+    pub column: Option<String>,
+    pub message: String,
+}
+
+impl RowIssue {
+    fn new(column: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            column: column.map(str::to_string),
+            message: message.into(),
+        }
+    }
+}
+
+/// Outcome of converting a dataset into samples
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SampleConversion {
+    pub dataset_id: Uuid,
+    pub profile_id: Uuid,
+    pub created: u64,
+    pub skipped_invalid: usize,
+    pub already_converted: usize,
+}
+
+/// Check that a profile fills every required sample field once and uses
+/// only known units
+pub fn validate_profile(mappings: &[FieldMapping]) -> Result<(), String> {
+    let mut seen: HashSet<&MappingTarget> = HashSet::new();
+
+    for mapping in mappings {
+        if mapping.column.trim().is_empty() {
+            return Err("Every mapping needs a source column".to_string());
+        }
+        if matches!(&mapping.target, MappingTarget::Metadata(key) if key.trim().is_empty()) {
+            return Err(format!(
+                "Metadata key for column '{}' is empty",
+                mapping.column
+            ));
+        }
+        if !seen.insert(&mapping.target) {
+            return Err(format!(
+                "{} is mapped more than once",
+                target_label(&mapping.target)
+            ));
+        }
+        for transform in &mapping.transforms {
+            if let ValueTransform::ConvertUnit { from, to } = transform {
+                let (from_unit, to_unit) = match (unit(from), unit(to)) {
+                    (Some(from_unit), Some(to_unit)) => (from_unit, to_unit),
+                    (None, _) => return Err(format!("Unknown unit '{}'", from)),
+                    (_, None) => return Err(format!("Unknown unit '{}'", to)),
+                };
+                if from_unit.dimension != to_unit.dimension {
+                    return Err(format!("Cannot convert '{}' to '{}'", from, to));
+                }
+            }
+        }
+    }
+
+    for required in [
+        MappingTarget::Name,
+        MappingTarget::Barcode,
+        MappingTarget::Location,
+    ] {
+        if !seen.contains(&required) {
+            return Err(format!(
+                "No column is mapped to {}",
+                target_label(&required)
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Build a sample from one dataset row, or every problem with the row
+pub fn map_row(row_data: &Value, mappings: &[FieldMapping]) -> Result<CreateSample, Vec<RowIssue>> {
+    let mut errors = Vec::new();
+    let mut name = None;
+    let mut barcode = None;
+    let mut location = None;
+    let mut metadata = Map::new();
+
+    for mapping in mappings {
+        let raw = row_data
+            .get(&mapping.column)
+            .map(cell_text)
+            .filter(|text| !text.trim().is_empty())
+            .or_else(|| mapping.default.clone());
+
+        let value = match raw {
+            Some(raw) => match apply_transforms(&raw, &mapping.transforms) {
+                Ok(value) => value,
+                Err(message) => {
+                    errors.push(RowIssue::new(Some(&mapping.column), message));
+                    continue;
+                }
+            },
+            None => {
+                if !matches!(mapping.target, MappingTarget::Metadata(_)) {
+                    errors.push(RowIssue::new(
+                        Some(&mapping.column),
+                        format!("{} is required", target_label(&mapping.target)),
+                    ));
+                }
+                continue;
+            }
+        };
+
+        let max_length = match &mapping.target {
+            MappingTarget::Name => MAX_NAME_LENGTH,
+            MappingTarget::Barcode => MAX_BARCODE_LENGTH,
+            MappingTarget::Location => MAX_LOCATION_LENGTH,
+            MappingTarget::Metadata(key) => {
+                metadata.insert(key.clone(), value.into_json());
+                continue;
+            }
+        };
+        let text = value.into_text();
+        if text.trim().is_empty() {
+            errors.push(RowIssue::new(
+                Some(&mapping.column),
+                format!("{} is required", target_label(&mapping.target)),
+            ));
+            continue;
+        }
+        if text.chars().count() > max_length {
+            errors.push(RowIssue::new(
+                Some(&mapping.column),
+                format!(
+                    "{} is longer than {} characters",
+                    target_label(&mapping.target),
+                    max_length
+                ),
+            ));
+            continue;
+        }
+        match mapping.target {
+            MappingTarget::Name => name = Some(text),
+            MappingTarget::Barcode => barcode = Some(text),
+            _ => location = Some(text),
+        }
+    }
+
+    match (name, barcode, location) {
+        (Some(name), Some(barcode), Some(location)) if errors.is_empty() => Ok(CreateSample {
+            name,
+            barcode,
+            location,
+            metadata: Some(Value::Object(metadata)),
+            template_version_id: None,
+        }),
+        _ => Err(errors),
+    }
+}
+
+/// Mark valid rows whose barcode repeats within the dataset or is already
+/// used by a sample
+pub fn mark_barcode_conflicts(rows: &mut [RowPreview], taken: &HashSet<String>) {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for row in rows.iter() {
+        if let Some(sample) = &row.sample {
+            *counts.entry(sample.barcode.clone()).or_default() += 1;
+        }
+    }
+
+    for row in rows.iter_mut() {
+        let Some(sample) = &row.sample else {
+            continue;
+        };
+        let message = if taken.contains(&sample.barcode) {
+            format!("Barcode '{}' is already used by a sample", sample.barcode)
+        } else if counts[&sample.barcode] > 1 {
+            format!("Barcode '{}' appears on more than one row", sample.barcode)
+        } else {
+            continue;
+        };
+        row.errors.push(RowIssue::new(None, message));
+        row.status = RowStatus::Invalid;
+        row.sample = None;
+    }
+}
+
+/// A cell value on its way through the transforms
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Text(String),
+    Number(f64),
+}
+
+impl Cell {
+    fn into_text(self) -> String {
+        match self {
+            Cell::Text(text) => text,
+            Cell::Number(number) => format_number(number),
+        }
+    }
+
+    fn into_json(self) -> Value {
+        match self {
+            Cell::Text(text) => Value::String(text),
+            Cell::Number(number) => serde_json::Number::from_f64(number)
+                .map(Value::Number)
+                .unwrap_or(Value::Null),
+        }
+    }
+}
+
+fn apply_transforms(raw: &str, transforms: &[ValueTransform]) -> Result<Cell, String> {
+    let mut cell = Cell::Text(raw.to_string());
+
+    for transform in transforms {
+        cell = match transform {
+            ValueTransform::Trim => Cell::Text(cell.into_text().trim().to_string()),
+            ValueTransform::Uppercase => Cell::Text(cell.into_text().to_uppercase()),
+            ValueTransform::Lowercase => Cell::Text(cell.into_text().to_lowercase()),
+            ValueTransform::Prefix { value } => {
+                Cell::Text(format!("{}{}", value, cell.into_text()))
+            }
+            ValueTransform::Replace { from, to } => {
+                Cell::Text(cell.into_text().replace(from.as_str(), to))
+            }
+            ValueTransform::Number => match cell {
+                Cell::Number(_) => cell,
+                Cell::Text(text) => Cell::Number(
+                    text.trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|number| number.is_finite())
+                        .ok_or_else(|| format!("'{}' is not a number", text.trim()))?,
+                ),
+            },
+            ValueTransform::ConvertUnit { from, to } => {
+                Cell::Number(convert_unit(&cell.into_text(), from, to)?)
+            }
+        };
+    }
+    Ok(cell)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Volume,
+    Mass,
+    MassConcentration,
+    MolarConcentration,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Unit {
+    dimension: Dimension,
+    /// Size relative to the base unit of the dimension
+    factor: f64,
+}
+
+fn unit(name: &str) -> Option<Unit> {
+    let normalized = name
+        .trim()
+        .replace(['µ', 'μ'], "u")
+        .replace(' ', "")
+        .to_lowercase();
+    let (dimension, factor) = match normalized.as_str() {
+        "l" => (Dimension::Volume, 1.0),
+        "ml" => (Dimension::Volume, 1e-3),
+        "ul" => (Dimension::Volume, 1e-6),
+        "nl" => (Dimension::Volume, 1e-9),
+        "g" => (Dimension::Mass, 1.0),
+        "mg" => (Dimension::Mass, 1e-3),
+        "ug" => (Dimension::Mass, 1e-6),
+        "ng" => (Dimension::Mass, 1e-9),
+        "pg" => (Dimension::Mass, 1e-12),
+        "g/l" | "mg/ml" | "ug/ul" => (Dimension::MassConcentration, 1.0),
+        "mg/l" | "ug/ml" | "ng/ul" => (Dimension::MassConcentration, 1e-3),
+        "ug/l" | "ng/ml" | "pg/ul" => (Dimension::MassConcentration, 1e-6),
+        "ng/l" | "pg/ml" => (Dimension::MassConcentration, 1e-9),
+        "m" => (Dimension::MolarConcentration, 1.0),
+        "mm" => (Dimension::MolarConcentration, 1e-3),
+        "um" => (Dimension::MolarConcentration, 1e-6),
+        "nm" => (Dimension::MolarConcentration, 1e-9),
+        "pm" => (Dimension::MolarConcentration, 1e-12),
+        _ => return None,
+    };
+    Some(Unit { dimension, factor })
+}
+
+/// Convert a quantity such as "1.5 mL" into `to`, reading it as `from`
+/// when the cell has no unit of its own
+fn convert_unit(text: &str, from: &str, to: &str) -> Result<f64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | '-' | '+')))
+        .unwrap_or(text.len());
+    let (number, cell_unit) = text.split_at(split);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is not a quantity", text))?;
+
+    let cell_unit = cell_unit.trim();
+    let from_name = if cell_unit.is_empty() {
+        from
+    } else {
+        cell_unit
+    };
+    let from_unit = unit(from_name).ok_or_else(|| format!("Unknown unit '{}'", from_name))?;
+    let to_unit = unit(to).ok_or_else(|| format!("Unknown unit '{}'", to))?;
+    if from_unit.dimension != to_unit.dimension {
+        return Err(format!("Cannot convert '{}' to '{}'", from_name, to));
+    }
+
+    // Round away the binary noise left by the unit factors
+    let converted = value * from_unit.factor / to_unit.factor;
+    Ok(format!("{:.12e}", converted).parse().unwrap_or(converted))
+}
+
+fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        format!("{}", number as i64)
+    } else {
+        number.to_string()
+    }
+}
+
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn target_label(target: &MappingTarget) -> String {
+    match target {
+        MappingTarget::Name => "Sample name".to_string(),
+        MappingTarget::Barcode => "Barcode".to_string(),
+        MappingTarget::Location => "Location".to_string(),
+        MappingTarget::Metadata(key) => format!("Metadata key '{}'", key),
+    }
+}
+
+/// Sample mapping errors
+#[derive(Debug, thiserror::Error)]
+pub enum MappingError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Mapping profile not found")]
+    ProfileNotFound,
+
+    #[error("Dataset not found")]
+    DatasetNotFound,
+
+    #[error("A mapping profile named '{0}' already exists")]
+    DuplicateProfileName(String),
+
+    #[error("Invalid mapping profile: {0}")]
+    InvalidProfile(String),
+
+    #[error("Dataset has no column(s) {0:?}")]
+    MissingColumns(Vec<String>),
+
+    #[error("{count} row(s) cannot be converted; preview the mapping to see why")]
+    InvalidRows { count: usize },
+
+    #[error("Samples changed while converting; preview the mapping again")]
+    Conflict,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn mapping(column: &str, target: MappingTarget) -> FieldMapping {
+        FieldMapping {
+            column: column.to_string(),
+            target,
+            transforms: Vec::new(),
+            default: None,
+        }
+    }
+
+    fn profile() -> Vec<FieldMapping> {
+        vec![
+            FieldMapping {
+                transforms: vec![ValueTransform::Trim],
+                ..mapping("Sample", MappingTarget::Name)
+            },
+            FieldMapping {
+                transforms: vec![
+                    ValueTransform::Trim,
+                    ValueTransform::Uppercase,
+                    ValueTransform::Prefix {
+                        value: "LAB-".to_string(),
+                    },
+                ],
+                ..mapping("Tube", MappingTarget::Barcode)
+            },
+            FieldMapping {
+                default: Some("Intake".to_string()),
+                ..mapping("Freezer", MappingTarget::Location)
+            },
+            FieldMapping {
+                transforms: vec![ValueTransform::ConvertUnit {
+                    from: "ml".to_string(),
+                    to: "ul".to_string(),
+                }],
+                ..mapping("Volume", MappingTarget::Metadata("volume_ul".to_string()))
+            },
+        ]
+    }
+
+    #[test]
+    fn test_map_row_applies_transforms_and_defaults() {
+        let row = json!({"Sample": " Liver 1 ", "Tube": " ab12", "Freezer": "", "Volume": "1.5"});
+        let sample = map_row(&row, &profile()).unwrap();
+
+        assert_eq!(sample.name, "Liver 1");
+        assert_eq!(sample.barcode, "LAB-AB12");
+        assert_eq!(sample.location, "Intake");
+        assert_eq!(sample.metadata, Some(json!({"volume_ul": 1500.0})));
+    }
+
+    #[test]
+    fn test_unit_in_cell_overrides_assumed_unit() {
+        assert_eq!(convert_unit("250 µL", "ml", "ul"), Ok(250.0));
+        assert_eq!(convert_unit("12.5ng/uL", "ng/ml", "ng/ml"), Ok(12500.0));
+        assert!(convert_unit("3 mg", "ml", "ul").is_err());
+        assert!(convert_unit("lots", "ml", "ul").is_err());
+    }
+
+    #[test]
+    fn test_map_row_collects_every_error() {
+        let row = json!({"Sample": "", "Tube": "T1", "Volume": "a lot"});
+        let errors = map_row(&row, &profile()).unwrap_err();
+
+        let columns: Vec<_> = errors.iter().map(|e| e.column.as_deref()).collect();
+        assert_eq!(columns, [Some("Sample"), Some("Volume")]);
+    }
+
+    #[test]
+    fn test_validate_profile_requires_sample_fields() {
+        assert!(validate_profile(&profile()).is_ok());
+        assert!(validate_profile(&profile()[..2]).is_err());
+
+        let mut bad_unit = profile();
+        bad_unit[3].transforms = vec![ValueTransform::ConvertUnit {
+            from: "ml".to_string(),
+            to: "mg".to_string(),
+        }];
+        assert!(validate_profile(&bad_unit).is_err());
+    }
+
+    #[test]
+    fn test_barcode_conflicts_invalidate_rows() {
+        let row = |barcode: &str| RowPreview {
+            record_id: Uuid::new_v4(),
+            row_number: 1,
+            status: RowStatus::Valid,
+            sample: Some(CreateSample {
+                name: "S".to_string(),
+                barcode: barcode.to_string(),
+                location: "F1".to_string(),
+                metadata: None,
+                template_version_id: None,
+            }),
+            errors: Vec::new(),
+        };
+        let mut rows = vec![row("A"), row("A"), row("B"), row("C")];
+        let taken = HashSet::from(["B".to_string()]);
+
+        mark_barcode_conflicts(&mut rows, &taken);
+        let statuses: Vec<_> = rows.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                RowStatus::Invalid,
+                RowStatus::Invalid,
+                RowStatus::Invalid,
+                RowStatus::Valid
+            ]
+        );
+    }
+
+    // Needs a migrated Postgres database:
+    // TEST_DATABASE_URL=... cargo test -- --ignored sample_mapping
+    #[tokio::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn test_convert_dataset_rows_to_samples() {
+        use crate::models::spreadsheet::CreateSampleMappingProfile;
+        use crate::services::spreadsheet_ingest::tests::{ingest_test_csv, setup_test_manager};
+        use crate::services::spreadsheet_service::SpreadsheetService;
+
+        let (manager, pool) = setup_test_manager().await;
+        let service = SpreadsheetService::new(manager.clone());
+        let tag = Uuid::new_v4().simple().to_string()[..8].to_uppercase();
+
+        let mapping = |column: &str, target| FieldMapping {
+            column: column.to_string(),
+            target,
+            transforms: vec![ValueTransform::Trim],
+            default: None,
+        };
+        let profile = service
+            .create_mapping_profile(CreateSampleMappingProfile {
+                name: format!("Manifest {}", tag),
+                description: None,
+                mappings: vec![
+                    mapping("Sample", MappingTarget::Name),
+                    FieldMapping {
+                        transforms: vec![
+                            ValueTransform::Trim,
+                            ValueTransform::Uppercase,
+                            ValueTransform::Prefix {
+                                value: format!("{}-", tag),
+                            },
+                        ],
+                        ..mapping("Tube", MappingTarget::Barcode)
+                    },
+                    mapping("Freezer", MappingTarget::Location),
+                    FieldMapping {
+                        transforms: vec![ValueTransform::ConvertUnit {
+                            from: "ml".to_string(),
+                            to: "ul".to_string(),
+                        }],
+                        ..mapping("Volume", MappingTarget::Metadata("volume_ul".to_string()))
+                    },
+                ],
+                created_by: None,
+            })
+            .await
+            .unwrap();
+
+        let csv = "Sample,Tube,Freezer,Volume\n\
+                   Liver 1,t1,F1,1.5\n\
+                   Liver 2,t2,F1,200 uL\n\
+                   ,t3,F2,1\n\
+                   Liver 4,t1,F2,2\n";
+        let (dataset, _) = ingest_test_csv(&manager, "manifest.csv", csv.as_bytes(), 100).await;
+
+        let preview = service
+            .preview_sample_mapping(dataset.id, profile.id, 10, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            (
+                preview.valid_rows,
+                preview.invalid_rows,
+                preview.converted_rows
+            ),
+            (1, 3, 0)
+        );
+        let statuses: Vec<_> = preview.rows.iter().map(|row| row.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                RowStatus::Invalid,
+                RowStatus::Valid,
+                RowStatus::Invalid,
+                RowStatus::Invalid
+            ]
+        );
+        assert!(preview.rows[0].errors[0]
+            .message
+            .contains("more than one row"));
+        assert_eq!(preview.rows[2].errors[0].column.as_deref(), Some("Sample"));
+        assert_eq!(
+            preview.rows[1].sample.as_ref().unwrap().metadata,
+            Some(serde_json::json!({"volume_ul": 200.0}))
+        );
+
+        assert!(matches!(
+            service
+                .convert_dataset_to_samples(dataset.id, profile.id, false)
+                .await,
+            Err(MappingError::InvalidRows { count: 3 })
+        ));
+        let conversion = service
+            .convert_dataset_to_samples(dataset.id, profile.id, true)
+            .await
+            .unwrap();
+        assert_eq!((conversion.created, conversion.skipped_invalid), (1, 3));
+
+        let linked = service.list_dataset_samples(dataset.id).await.unwrap();
+        assert_eq!(linked.len(), 1);
+        assert_eq!(linked[0].row_number, 2);
+        assert_eq!(linked[0].barcode, format!("{}-T2", tag));
+        let source: serde_json::Value =
+            sqlx::query_scalar("SELECT metadata->'spreadsheet_source' FROM samples WHERE id = $1")
+                .bind(linked[0].sample_id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(source["row_number"], 2);
+
+        // Converted rows are left alone on a second run
+        let again = service
+            .preview_sample_mapping(dataset.id, profile.id, 10, true)
+            .await
+            .unwrap();
+        assert_eq!((again.valid_rows, again.converted_rows), (0, 1));
+        assert_eq!(again.rows.len(), 3);
+
+        let sample_ids: Vec<Uuid> = linked.iter().map(|sample| sample.sample_id).collect();
+        sqlx::query("DELETE FROM samples WHERE id = ANY($1)")
+            .bind(&sample_ids)
+            .execute(&pool)
+            .await
+            .unwrap();
+        manager.delete_dataset(dataset.id).await.unwrap();
+        service.delete_mapping_profile(profile.id).await.unwrap();
+    }
+}
@@ -0,0 +1,856 @@
+use chrono::{Duration, NaiveDate};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::models::report::{
+    CreateSavedReport, DatabaseSchema, FilterOperator, ReportParameter, ReportParameterKind,
+    ReportQuerySpec, ReportVisibility, SavedReport, SavedReportQuery,
+};
+use crate::models::user::UserRole;
+use crate::repositories::saved_report_repository::SavedReportRepository;
+use crate::services::report_execution::ReportSource;
+use crate::services::report_query::{
+    compile_report_with_limit, is_safe_query, QueryParam, ReportQueryError, MAX_REPORT_LIMIT,
+};
+
+/// Longest relative date range a parameter may ask for
+const MAX_LAST_DAYS: i64 = 3660;
+
+/// The user saved reports are listed, changed or run for
+#[derive(Debug, Clone)]
+pub struct ReportViewer {
+    pub user_id: Uuid,
+    pub role: UserRole,
+    pub is_admin: bool,
+}
+
+/// A saved report with its parameters substituted, ready to run
+#[derive(Debug, Clone)]
+pub struct PreparedReport {
+    pub source: ReportSource,
+    pub sql: String,
+    pub params: Vec<QueryParam>,
+    /// Output columns, known before running for structured reports
+    pub columns: Option<Vec<String>>,
+    pub limit: i64,
+}
+
+/// A parameter's value once checked against its kind
+#[derive(Debug, Clone, PartialEq)]
+enum ParameterValue {
+    DateRange { start: NaiveDate, end: NaiveDate },
+    Text(String),
+    TextList(Vec<String>),
+}
+
+/// Saved reports with ownership and sharing
+#[derive(Debug)]
+pub struct SavedReportService<R: SavedReportRepository> {
+    report_repo: Arc<R>,
+}
+
+impl<R: SavedReportRepository> SavedReportService<R> {
+    pub fn new(report_repo: Arc<R>) -> Self {
+        Self { report_repo }
+    }
+
+    /// Reports the viewer owns or that are shared with them
+    pub async fn list_reports(
+        &self,
+        viewer: &ReportViewer,
+    ) -> Result<Vec<SavedReport>, SavedReportError> {
+        Ok(self
+            .report_repo
+            .list_visible_reports(viewer.user_id, &viewer.role, viewer.is_admin)
+            .await?)
+    }
+
+    /// Reports everyone can start from: system reports, then reports shared
+    /// with everyone
+    pub async fn list_templates(&self) -> Result<Vec<SavedReport>, SavedReportError> {
+        Ok(self.report_repo.list_public_reports().await?)
+    }
+
+    /// Get a report the viewer may see
+    pub async fn get_report(
+        &self,
+        id: Uuid,
+        viewer: &ReportViewer,
+    ) -> Result<SavedReport, SavedReportError> {
+        self.report_repo
+            .get_report(id)
+            .await?
+            .filter(|report| can_view(report, viewer))
+            .ok_or(SavedReportError::ReportNotFound(id))
+    }
+
+    /// Save a report owned by the viewer
+    pub async fn create_report(
+        &self,
+        viewer: &ReportViewer,
+        report: CreateSavedReport,
+        schema: &DatabaseSchema,
+        today: NaiveDate,
+    ) -> Result<SavedReport, SavedReportError> {
+        validate_report(&report, viewer, schema, today)?;
+        Ok(self
+            .report_repo
+            .create_report(viewer.user_id, &report)
+            .await?)
+    }
+
+    /// Replace the definition of a report the viewer owns. Administrators
+    /// may change any report except system reports.
+    pub async fn update_report(
+        &self,
+        id: Uuid,
+        viewer: &ReportViewer,
+        report: CreateSavedReport,
+        schema: &DatabaseSchema,
+        today: NaiveDate,
+    ) -> Result<SavedReport, SavedReportError> {
+        let current = self.get_report(id, viewer).await?;
+        check_editable(&current, viewer)?;
+        validate_report(&report, viewer, schema, today)?;
+
+        self.report_repo
+            .update_report(id, &report)
+            .await?
+            .ok_or(SavedReportError::ReportNotFound(id))
+    }
+
+    /// Delete a report the viewer owns
+    pub async fn delete_report(
+        &self,
+        id: Uuid,
+        viewer: &ReportViewer,
+    ) -> Result<(), SavedReportError> {
+        let current = self.get_report(id, viewer).await?;
+        check_editable(&current, viewer)?;
+
+        if !self.report_repo.delete_report(id).await? {
+            return Err(SavedReportError::ReportNotFound(id));
+        }
+        Ok(())
+    }
+}
+
+/// Whether a viewer may see and run a report
+pub fn can_view(report: &SavedReport, viewer: &ReportViewer) -> bool {
+    viewer.is_admin
+        || report.is_system
+        || report.owner_id == Some(viewer.user_id)
+        || match report.visibility {
+            ReportVisibility::Private => false,
+            ReportVisibility::Role => report.shared_role.as_ref() == Some(&viewer.role),
+            ReportVisibility::Everyone => true,
+        }
+}
+
+fn check_editable(report: &SavedReport, viewer: &ReportViewer) -> Result<(), SavedReportError> {
+    if report.is_system {
+        return Err(SavedReportError::ReadOnly(report.id));
+    }
+    if !viewer.is_admin && report.owner_id != Some(viewer.user_id) {
+        return Err(SavedReportError::Forbidden(
+            "Only the owner of a report can change it".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Check a report definition, compiling it with stand-in parameter values
+pub fn validate_report(
+    report: &CreateSavedReport,
+    viewer: &ReportViewer,
+    schema: &DatabaseSchema,
+    today: NaiveDate,
+) -> Result<(), SavedReportError> {
+    if report.name.trim().is_empty() || report.name.chars().count() > 255 {
+        return Err(SavedReportError::InvalidReport(
+            "Report names must be between 1 and 255 characters".to_string(),
+        ));
+    }
+    match (report.visibility, &report.shared_role) {
+        (ReportVisibility::Role, None) => {
+            return Err(SavedReportError::InvalidReport(
+                "Reports shared with a role need a shared_role".to_string(),
+            ))
+        }
+        (ReportVisibility::Private | ReportVisibility::Everyone, Some(_)) => {
+            return Err(SavedReportError::InvalidReport(
+                "shared_role is only used by reports shared with a role".to_string(),
+            ))
+        }
+        _ => {}
+    }
+    if let SavedReportQuery::Sql(sql) = &report.query {
+        if !viewer.is_admin {
+            return Err(SavedReportError::Forbidden(
+                "Only administrators can save SQL reports".to_string(),
+            ));
+        }
+        if !is_safe_query(sql) {
+            return Err(SavedReportError::InvalidReport(
+                "Only single SELECT queries are allowed for reports".to_string(),
+            ));
+        }
+    }
+
+    let mut names = HashSet::new();
+    let mut stand_ins = HashMap::new();
+    for parameter in &report.parameters {
+        let valid_name = parameter
+            .name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+            && parameter
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid_name {
+            return Err(SavedReportError::InvalidParameter(format!(
+                "Parameter name '{}' must be lowercase letters, digits and underscores",
+                parameter.name
+            )));
+        }
+        if !names.insert(parameter.name.as_str()) {
+            return Err(SavedReportError::InvalidParameter(format!(
+                "Parameter '{}' is declared more than once",
+                parameter.name
+            )));
+        }
+        if let Some(default) = &parameter.default {
+            resolve_value(parameter, default, today)?;
+        }
+        let stand_in = match parameter.kind {
+            ReportParameterKind::DateRange => json!({ "start": today, "end": today }),
+            ReportParameterKind::Project => json!("project"),
+            ReportParameterKind::Status => json!("status"),
+        };
+        stand_ins.insert(parameter.name.clone(), stand_in);
+    }
+
+    prepare_report(
+        &report.query,
+        &report.parameters,
+        &stand_ins,
+        schema,
+        None,
+        MAX_REPORT_LIMIT,
+        today,
+    )?;
+    Ok(())
+}
+
+/// Substitute parameter values into a saved report's query. Structured
+/// reports drop filters on optional parameters that have no value; SQL
+/// reports need a value for every parameter they reference.
+pub fn prepare_report(
+    query: &SavedReportQuery,
+    parameters: &[ReportParameter],
+    values: &HashMap<String, Value>,
+    schema: &DatabaseSchema,
+    limit: Option<i64>,
+    max_rows: i64,
+    today: NaiveDate,
+) -> Result<PreparedReport, SavedReportError> {
+    if let Some(name) = values
+        .keys()
+        .find(|name| !parameters.iter().any(|parameter| &parameter.name == *name))
+    {
+        return Err(SavedReportError::InvalidParameter(format!(
+            "Unknown parameter '{}'",
+            name
+        )));
+    }
+
+    let mut resolved = HashMap::new();
+    for parameter in parameters {
+        let value = match values.get(&parameter.name).or(parameter.default.as_ref()) {
+            Some(Value::Null) | None if parameter.required => {
+                return Err(SavedReportError::InvalidParameter(format!(
+                    "Parameter '{}' is required",
+                    parameter.name
+                )))
+            }
+            Some(Value::Null) | None => None,
+            Some(value) => Some(resolve_value(parameter, value, today)?),
+        };
+        resolved.insert(parameter.name.as_str(), (parameter, value));
+    }
+
+    match query {
+        SavedReportQuery::Structured(spec) => {
+            let mut spec = substitute_filters(spec, &resolved)?;
+            let requested = limit.or(spec.limit).unwrap_or(max_rows);
+            spec.limit = Some(requested.min(max_rows));
+            let compiled = compile_report_with_limit(&spec, schema, max_rows)?;
+            Ok(PreparedReport {
+                source: ReportSource::Structured,
+                sql: compiled.sql,
+                params: compiled.params,
+                columns: Some(compiled.columns),
+                limit: compiled.limit,
+            })
+        }
+        SavedReportQuery::Sql(sql) => {
+            let (sql, params) = substitute_placeholders(sql, &resolved)?;
+            Ok(PreparedReport {
+                source: ReportSource::Sql,
+                sql,
+                params,
+                columns: None,
+                limit: limit.unwrap_or(max_rows).clamp(1, max_rows),
+            })
+        }
+    }
+}
+
+type ResolvedParameters<'a> = HashMap<&'a str, (&'a ReportParameter, Option<ParameterValue>)>;
+
+fn resolve_value(
+    parameter: &ReportParameter,
+    value: &Value,
+    today: NaiveDate,
+) -> Result<ParameterValue, SavedReportError> {
+    let invalid = |message: &str| {
+        SavedReportError::InvalidParameter(format!("Parameter '{}' {}", parameter.name, message))
+    };
+
+    match parameter.kind {
+        ReportParameterKind::DateRange => {
+            if let Some(days) = value.get("last_days") {
+                let days = days
+                    .as_i64()
+                    .filter(|days| (1..=MAX_LAST_DAYS).contains(days))
+                    .ok_or_else(|| {
+                        invalid(&format!("needs last_days between 1 and {}", MAX_LAST_DAYS))
+                    })?;
+                return Ok(ParameterValue::DateRange {
+                    start: today - Duration::days(days),
+                    end: today,
+                });
+            }
+            let date = |key: &str| {
+                value
+                    .get(key)
+                    .and_then(Value::as_str)
+                    .and_then(|text| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok())
+            };
+            match (date("start"), date("end")) {
+                (Some(start), Some(end)) if start <= end => {
+                    Ok(ParameterValue::DateRange { start, end })
+                }
+                (Some(_), Some(_)) => Err(invalid("starts after it ends")),
+                _ => Err(invalid(
+                    "needs start and end dates (YYYY-MM-DD) or last_days",
+                )),
+            }
+        }
+        ReportParameterKind::Project => match value.as_str().map(str::trim) {
+            Some(project) if !project.is_empty() && project.chars().count() <= 255 => {
+                Ok(ParameterValue::Text(project.to_string()))
+            }
+            _ => Err(invalid("needs a project name")),
+        },
+        ReportParameterKind::Status => {
+            let status = |value: &Value| {
+                value
+                    .as_str()
+                    .map(str::trim)
+                    .filter(|status| {
+                        !status.is_empty()
+                            && status.len() <= 50
+                            && status
+                                .chars()
+                                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    })
+                    .map(str::to_string)
+            };
+            match value {
+                Value::Array(values) if !values.is_empty() => values
+                    .iter()
+                    .map(status)
+                    .collect::<Option<Vec<_>>>()
+                    .map(ParameterValue::TextList)
+                    .ok_or_else(|| invalid("needs a list of statuses")),
+                _ => status(value)
+                    .map(ParameterValue::Text)
+                    .ok_or_else(|| invalid("needs a status or a list of statuses")),
+            }
+        }
+    }
+}
+
+/// The parameter a filter takes its value from, written `{"param": "name"}`
+fn param_ref(value: &Value) -> Option<&str> {
+    value
+        .as_object()
+        .filter(|object| object.len() == 1)
+        .and_then(|object| object.get("param"))
+        .and_then(Value::as_str)
+}
+
+fn substitute_filters(
+    spec: &ReportQuerySpec,
+    resolved: &ResolvedParameters,
+) -> Result<ReportQuerySpec, SavedReportError> {
+    let mut spec = spec.clone();
+    let mut filters = Vec::with_capacity(spec.filters.len());
+    for mut filter in spec.filters {
+        let Some(name) = param_ref(&filter.value) else {
+            filters.push(filter);
+            continue;
+        };
+        let (_, value) = resolved.get(name).ok_or_else(|| {
+            SavedReportError::InvalidParameter(format!(
+                "Filter refers to undeclared parameter '{}'",
+                name
+            ))
+        })?;
+        let Some(value) = value else {
+            continue;
+        };
+
+        filter.value = match (value, filter.op) {
+            (ParameterValue::DateRange { start, end }, FilterOperator::Between) => {
+                json!([start.to_string(), end_of_day(*end)])
+            }
+            (ParameterValue::DateRange { start, .. }, FilterOperator::Gte) => {
+                json!(start.to_string())
+            }
+            (ParameterValue::DateRange { end, .. }, FilterOperator::Lte) => json!(end_of_day(*end)),
+            (ParameterValue::DateRange { .. }, _) => {
+                return Err(SavedReportError::InvalidParameter(format!(
+                    "Date range '{}' can only filter with between, gte or lte",
+                    name
+                )))
+            }
+            (ParameterValue::Text(text), FilterOperator::In | FilterOperator::NotIn) => {
+                json!([text])
+            }
+            (ParameterValue::Text(text), _) => json!(text),
+            (ParameterValue::TextList(values), FilterOperator::In | FilterOperator::NotIn) => {
+                json!(values)
+            }
+            (ParameterValue::TextList(values), _) if values.len() == 1 => json!(values[0]),
+            (ParameterValue::TextList(_), _) => {
+                return Err(SavedReportError::InvalidParameter(format!(
+                    "Parameter '{}' has several values; filter on it with in or not_in",
+                    name
+                )))
+            }
+        };
+        filters.push(filter);
+    }
+    spec.filters = filters;
+    Ok(spec)
+}
+
+/// The last moment of a day, so date ranges include their end date when
+/// compared with timestamps
+fn end_of_day(date: NaiveDate) -> String {
+    format!("{} 23:59:59.999999", date)
+}
+
+/// Replace `{{name}}` placeholders with bind parameters. Values are bound as
+/// text; SQL casts them where needed. A date range ends at the last moment of
+/// its end date, as in structured reports.
+fn substitute_placeholders(
+    sql: &str,
+    resolved: &ResolvedParameters,
+) -> Result<(String, Vec<QueryParam>), SavedReportError> {
+    let mut output = String::with_capacity(sql.len());
+    let mut params = Vec::new();
+    let mut placeholders: HashMap<String, usize> = HashMap::new();
+    let mut rest = sql;
+
+    while let Some(open) = rest.find("{{") {
+        output.push_str(&rest[..open]);
+        let after = &rest[open + 2..];
+        let close = after.find("}}").ok_or_else(|| {
+            SavedReportError::InvalidReport("Unclosed '{{' in report SQL".to_string())
+        })?;
+        let placeholder = after[..close].trim();
+        rest = &after[close + 2..];
+
+        if let Some(index) = placeholders.get(placeholder) {
+            output.push_str(&format!("${}", index));
+            continue;
+        }
+
+        let (name, part) = match placeholder.split_once('.') {
+            Some((name, part)) => (name, Some(part)),
+            None => (placeholder, None),
+        };
+        let (parameter, value) = resolved.get(name).ok_or_else(|| {
+            SavedReportError::InvalidParameter(format!(
+                "Report SQL refers to undeclared parameter '{}'",
+                name
+            ))
+        })?;
+        let value = value.as_ref().ok_or_else(|| {
+            SavedReportError::InvalidParameter(format!(
+                "Parameter '{}' needs a value for this report",
+                parameter.name
+            ))
+        })?;
+        let param = match (value, part) {
+            (ParameterValue::DateRange { start, .. }, Some("start")) => {
+                QueryParam::Text(start.to_string())
+            }
+            (ParameterValue::DateRange { end, .. }, Some("end")) => {
+                QueryParam::Text(end_of_day(*end))
+            }
+            (ParameterValue::DateRange { .. }, _) => {
+                return Err(SavedReportError::InvalidParameter(format!(
+                    "Refer to date range '{}' as {{{{{0}.start}}}} or {{{{{0}.end}}}}",
+                    name
+                )))
+            }
+            (ParameterValue::Text(text), None) => QueryParam::Text(text.clone()),
+            (ParameterValue::TextList(values), None) => QueryParam::TextList(values.clone()),
+            (_, Some(_)) => {
+                return Err(SavedReportError::InvalidParameter(format!(
+                    "Parameter '{}' has no parts",
+                    name
+                )))
+            }
+        };
+        params.push(param);
+        placeholders.insert(placeholder.to_string(), params.len());
+        output.push_str(&format!("${}", params.len()));
+    }
+    output.push_str(rest);
+
+    Ok((output, params))
+}
+
+/// Saved report errors
+#[derive(Debug, thiserror::Error)]
+pub enum SavedReportError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Report not found: {0}")]
+    ReportNotFound(Uuid),
+
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("System report {0} cannot be changed")]
+    ReadOnly(Uuid),
+
+    #[error("Invalid report: {0}")]
+    InvalidReport(String),
+
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
+
+    #[error(transparent)]
+    Query(#[from] ReportQueryError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::report::{ColumnInfo, TableInfo};
+    use chrono::Utc;
+
+    fn schema() -> DatabaseSchema {
+        let column = |name: &str, udt_name: &str| ColumnInfo {
+            name: name.to_string(),
+            data_type: udt_name.to_string(),
+            udt_name: udt_name.to_string(),
+            is_nullable: true,
+            is_primary_key: false,
+        };
+        DatabaseSchema {
+            tables: vec![TableInfo {
+                name: "samples".to_string(),
+                columns: vec![
+                    column("name", "varchar"),
+                    column("status", "sample_status"),
+                    column("location", "varchar"),
+                    column("created_at", "timestamptz"),
+                ],
+            }],
+        }
+    }
+
+    fn parameter(name: &str, kind: ReportParameterKind, required: bool) -> ReportParameter {
+        ReportParameter {
+            name: name.to_string(),
+            label: None,
+            kind,
+            required,
+            default: None,
+        }
+    }
+
+    fn viewer(is_admin: bool) -> ReportViewer {
+        ReportViewer {
+            user_id: Uuid::new_v4(),
+            role: if is_admin {
+                UserRole::LabAdministrator
+            } else {
+                UserRole::LabTechnician
+            },
+            is_admin,
+        }
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, 25).unwrap()
+    }
+
+    fn values(values: Value) -> HashMap<String, Value> {
+        serde_json::from_value(values).unwrap()
+    }
+
+    fn structured_query() -> SavedReportQuery {
+        SavedReportQuery::Structured(
+            serde_json::from_value(json!({
+                "entity": "samples",
+                "fields": ["name", "status"],
+                "filters": [
+                    {"field": "created_at", "op": "between", "value": {"param": "received"}},
+                    {"field": "status", "op": "in", "value": {"param": "status"}},
+                    {"field": "location", "op": "contains", "value": "-80"}
+                ]
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_structured_parameters_bind_as_values() {
+        let parameters = vec![
+            parameter("received", ReportParameterKind::DateRange, true),
+            parameter("status", ReportParameterKind::Status, false),
+        ];
+        let report = prepare_report(
+            &structured_query(),
+            &parameters,
+            &values(json!({
+                "received": {"start": "2025-05-01", "end": "2025-05-31"},
+                "status": ["pending", "validated"]
+            })),
+            &schema(),
+            Some(50),
+            1000,
+            today(),
+        )
+        .unwrap();
+
+        assert_eq!(report.source, ReportSource::Structured);
+        assert_eq!(report.limit, 50);
+        assert!(!report.sql.contains("2025-05"));
+        assert_eq!(
+            report.params[..3],
+            [
+                QueryParam::Text("2025-05-01".to_string()),
+                QueryParam::Text("2025-05-31 23:59:59.999999".to_string()),
+                QueryParam::TextList(vec!["pending".to_string(), "validated".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_optional_parameters_without_values_drop_their_filters() {
+        let parameters = vec![
+            ReportParameter {
+                default: Some(json!({"last_days": 30})),
+                ..parameter("received", ReportParameterKind::DateRange, true)
+            },
+            parameter("status", ReportParameterKind::Status, false),
+        ];
+        let report = prepare_report(
+            &structured_query(),
+            &parameters,
+            &HashMap::new(),
+            &schema(),
+            None,
+            1000,
+            today(),
+        )
+        .unwrap();
+
+        assert!(!report.sql.contains("ANY"));
+        assert_eq!(report.params[0], QueryParam::Text("2025-05-26".to_string()));
+        assert_eq!(report.params.len(), 3);
+    }
+
+    #[test]
+    fn test_parameter_values_are_checked() {
+        let parameters = vec![
+            parameter("received", ReportParameterKind::DateRange, true),
+            parameter("status", ReportParameterKind::Status, false),
+        ];
+        let prepare = |given: Value| {
+            prepare_report(
+                &structured_query(),
+                &parameters,
+                &values(given),
+                &schema(),
+                None,
+                1000,
+                today(),
+            )
+        };
+
+        for given in [
+            json!({}),
+            json!({"received": {"start": "2025-06-01", "end": "2025-05-01"}}),
+            json!({"received": {"last_days": 0}}),
+            json!({"received": {"last_days": 7}, "status": "pending'; DROP TABLE samples"}),
+            json!({"received": {"last_days": 7}, "project": "X"}),
+        ] {
+            assert!(matches!(
+                prepare(given),
+                Err(SavedReportError::InvalidParameter(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_sql_placeholders_become_bind_parameters() {
+        let parameters = vec![
+            parameter("received", ReportParameterKind::DateRange, true),
+            parameter("project", ReportParameterKind::Project, true),
+        ];
+        let sql = SavedReportQuery::Sql(
+            "SELECT name FROM samples WHERE metadata->>'project' = {{ project }} \
+             AND created_at >= {{received.start}}::date \
+             AND created_at <= {{received.end}}::timestamptz \
+             OR metadata->>'parent_project' = {{project}}"
+                .to_string(),
+        );
+        let report = prepare_report(
+            &sql,
+            &parameters,
+            &values(json!({
+                "received": {"start": "2025-05-01", "end": "2025-05-31"},
+                "project": "Project X'"
+            })),
+            &schema(),
+            None,
+            1000,
+            today(),
+        )
+        .unwrap();
+
+        assert_eq!(report.source, ReportSource::Sql);
+        assert_eq!(
+            report.sql,
+            "SELECT name FROM samples WHERE metadata->>'project' = $1 \
+             AND created_at >= $2::date \
+             AND created_at <= $3::timestamptz \
+             OR metadata->>'parent_project' = $1"
+        );
+        assert_eq!(
+            report.params,
+            [
+                QueryParam::Text("Project X'".to_string()),
+                QueryParam::Text("2025-05-01".to_string()),
+                QueryParam::Text("2025-05-31 23:59:59.999999".to_string()),
+            ]
+        );
+
+        let undeclared = SavedReportQuery::Sql("SELECT {{other}}".to_string());
+        assert!(prepare_report(
+            &undeclared,
+            &parameters,
+            &values(json!({"received": {"last_days": 1}, "project": "X"})),
+            &schema(),
+            None,
+            1000,
+            today(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_only_administrators_save_sql_reports() {
+        let report = CreateSavedReport {
+            name: "Freezer -80 samples".to_string(),
+            description: None,
+            category: None,
+            query: SavedReportQuery::Sql("SELECT name FROM samples".to_string()),
+            parameters: Vec::new(),
+            visibility: ReportVisibility::Private,
+            shared_role: None,
+        };
+
+        assert!(validate_report(&report, &viewer(true), &schema(), today()).is_ok());
+        assert!(matches!(
+            validate_report(&report, &viewer(false), &schema(), today()),
+            Err(SavedReportError::Forbidden(_))
+        ));
+
+        let shared = CreateSavedReport {
+            query: structured_query(),
+            visibility: ReportVisibility::Role,
+            ..report
+        };
+        assert!(matches!(
+            validate_report(&shared, &viewer(false), &schema(), today()),
+            Err(SavedReportError::InvalidReport(_))
+        ));
+        let shared = CreateSavedReport {
+            shared_role: Some(UserRole::PrincipalInvestigator),
+            parameters: vec![
+                parameter("received", ReportParameterKind::DateRange, true),
+                parameter("status", ReportParameterKind::Status, false),
+            ],
+            ..shared
+        };
+        assert!(validate_report(&shared, &viewer(false), &schema(), today()).is_ok());
+    }
+
+    #[test]
+    fn test_sharing_decides_who_sees_a_report() {
+        let owner = viewer(false);
+        let mut report = SavedReport {
+            id: Uuid::new_v4(),
+            name: "Weekly intake".to_string(),
+            description: None,
+            category: None,
+            query: structured_query(),
+            parameters: Vec::new(),
+            owner_id: Some(owner.user_id),
+            visibility: ReportVisibility::Private,
+            shared_role: None,
+            is_system: false,
+            system_key: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        let colleague = viewer(false);
+        let investigator = ReportViewer {
+            role: UserRole::PrincipalInvestigator,
+            ..viewer(false)
+        };
+
+        assert!(can_view(&report, &owner));
+        assert!(can_view(&report, &viewer(true)));
+        assert!(!can_view(&report, &colleague));
+
+        report.visibility = ReportVisibility::Role;
+        report.shared_role = Some(UserRole::PrincipalInvestigator);
+        assert!(can_view(&report, &investigator));
+        assert!(!can_view(&report, &colleague));
+        assert!(check_editable(&report, &investigator).is_err());
+        assert!(check_editable(&report, &owner).is_ok());
+
+        report.is_system = true;
+        assert!(matches!(
+            check_editable(&report, &viewer(true)),
+            Err(SavedReportError::ReadOnly(_))
+        ));
+    }
+}
@@ -0,0 +1,506 @@
+use chrono::Utc;
+use rand::Rng;
+use std::collections::HashSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::models::shipment::{
+    CreateShipment, CreateShipmentItem, ReceivedItem, Shipment, ShipmentDirection, ShipmentStatus,
+    ShipmentWithItems, ShippingCondition,
+};
+use crate::repositories::shipment_repository::{ReceiptError, ShipmentRepository};
+
+const MANIFEST_HEADERS: [&str; 11] = [
+    "Shipment Number",
+    "Origin",
+    "Destination",
+    "Carrier",
+    "Tracking Number",
+    "Shipping Condition",
+    "Barcode",
+    "Sample ID",
+    "Volume (uL)",
+    "Container",
+    "Notes",
+];
+
+/// Shipment service for sample transfers between sites and providers
+#[derive(Debug)]
+pub struct ShipmentService<R: ShipmentRepository> {
+    shipment_repo: Arc<R>,
+}
+
+impl<R: ShipmentRepository> ShipmentService<R> {
+    pub fn new(shipment_repo: Arc<R>) -> Self {
+        Self { shipment_repo }
+    }
+
+    /// Create a shipment in the prepared state
+    pub async fn create_shipment(
+        &self,
+        shipment: CreateShipment,
+    ) -> Result<ShipmentWithItems, ShipmentError> {
+        validate_items(&shipment.items)?;
+
+        let shipment_number = shipment
+            .shipment_number
+            .clone()
+            .unwrap_or_else(generate_shipment_number);
+
+        self.shipment_repo
+            .create_shipment(&shipment_number, shipment)
+            .await
+            .map_err(|e| unique_violation(e, &shipment_number))
+    }
+
+    /// Get a shipment with its manifest
+    pub async fn get_shipment(&self, id: Uuid) -> Result<ShipmentWithItems, ShipmentError> {
+        let shipment = self
+            .shipment_repo
+            .get_shipment(id)
+            .await?
+            .ok_or(ShipmentError::ShipmentNotFound(id))?;
+        let items = self.shipment_repo.get_shipment_items(id).await?;
+
+        Ok(ShipmentWithItems { shipment, items })
+    }
+
+    /// List shipments, optionally by status
+    pub async fn list_shipments(
+        &self,
+        status: Option<ShipmentStatus>,
+    ) -> Result<Vec<Shipment>, ShipmentError> {
+        Ok(self.shipment_repo.list_shipments(status).await?)
+    }
+
+    /// Dispatch a prepared shipment. Outbound samples still in storage are
+    /// released from their locations and marked in transit; a sample that is
+    /// checked out must be checked in before it can ship.
+    pub async fn dispatch_shipment(
+        &self,
+        id: Uuid,
+        shipped_by: &str,
+        tracking_number: Option<String>,
+    ) -> Result<Shipment, ShipmentError> {
+        let current = self.require_transition(id, ShipmentStatus::Shipped).await?;
+        let release = current.direction == ShipmentDirection::Outbound;
+
+        self.shipment_repo
+            .dispatch_shipment(id, shipped_by, tracking_number, release)
+            .await
+            .map_err(checked_out_violation)?
+            .ok_or(ShipmentError::InvalidStatusTransition {
+                current: current.status,
+                requested: ShipmentStatus::Shipped,
+            })
+    }
+
+    /// Receive a shipped shipment. Every received line must name a registered
+    /// sample; for inbound shipments, received samples are stored in
+    /// `store_in_location` when one is given.
+    pub async fn receive_shipment(
+        &self,
+        id: Uuid,
+        received_by: &str,
+        received_items: Vec<ReceivedItem>,
+        store_in_location: Option<i32>,
+    ) -> Result<Shipment, ShipmentError> {
+        let current = self
+            .require_transition(id, ShipmentStatus::Received)
+            .await?;
+        if store_in_location.is_some() && current.direction == ShipmentDirection::Outbound {
+            return Err(ShipmentError::InvalidManifest(
+                "Outbound shipments are received by the destination, not stored here".to_string(),
+            ));
+        }
+
+        self.shipment_repo
+            .receive_shipment(id, received_by, received_items, store_in_location)
+            .await
+            .map_err(receipt_error)?
+            .ok_or(ShipmentError::InvalidStatusTransition {
+                current: current.status,
+                requested: ShipmentStatus::Received,
+            })
+    }
+
+    /// Cancel a shipment that has not been dispatched
+    pub async fn cancel_shipment(&self, id: Uuid) -> Result<Shipment, ShipmentError> {
+        let current = self
+            .require_transition(id, ShipmentStatus::Cancelled)
+            .await?;
+
+        self.shipment_repo.cancel_shipment(id).await?.ok_or(
+            ShipmentError::InvalidStatusTransition {
+                current: current.status,
+                requested: ShipmentStatus::Cancelled,
+            },
+        )
+    }
+
+    /// Register an inbound shipment from a sender's manifest CSV. The shipment
+    /// is recorded as already shipped, ready to be received. It gets a local
+    /// shipment number and keeps the sender's as its external reference.
+    pub async fn import_manifest(
+        &self,
+        data: &[u8],
+        imported_by: &str,
+    ) -> Result<ShipmentWithItems, ShipmentError> {
+        let mut shipment = parse_manifest_csv(data)?;
+        shipment.direction = ShipmentDirection::Inbound;
+        shipment.created_by = imported_by.to_string();
+
+        let shipment_number = generate_shipment_number();
+        self.shipment_repo
+            .import_shipment(&shipment_number, shipment, imported_by)
+            .await
+            .map_err(|e| unique_violation(e, &shipment_number))
+    }
+
+    async fn require_transition(
+        &self,
+        id: Uuid,
+        requested: ShipmentStatus,
+    ) -> Result<Shipment, ShipmentError> {
+        let current = self
+            .shipment_repo
+            .get_shipment(id)
+            .await?
+            .ok_or(ShipmentError::ShipmentNotFound(id))?;
+
+        if !current.status.can_transition_to(requested) {
+            return Err(ShipmentError::InvalidStatusTransition {
+                current: current.status,
+                requested,
+            });
+        }
+
+        Ok(current)
+    }
+}
+
+/// Write a shipment manifest as CSV, one row per manifest line
+pub fn export_manifest_csv(shipment: &ShipmentWithItems) -> Result<String, ShipmentError> {
+    let header = &shipment.shipment;
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(MANIFEST_HEADERS)?;
+
+    for item in &shipment.items {
+        writer.write_record([
+            header.shipment_number.as_str(),
+            header.origin.as_str(),
+            header.destination.as_str(),
+            header.carrier.as_deref().unwrap_or(""),
+            header.tracking_number.as_deref().unwrap_or(""),
+            condition_code(header.shipping_condition),
+            item.barcode.as_str(),
+            &item.sample_id.map(|id| id.to_string()).unwrap_or_default(),
+            &item.volume_ul.map(|v| v.to_string()).unwrap_or_default(),
+            item.container_type.as_deref().unwrap_or(""),
+            item.notes.as_deref().unwrap_or(""),
+        ])?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| ShipmentError::InvalidManifest(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| ShipmentError::InvalidManifest(e.to_string()))
+}
+
+/// Read a manifest CSV produced by `export_manifest_csv`. The sender's
+/// shipment number is returned as the external reference.
+pub fn parse_manifest_csv(data: &[u8]) -> Result<CreateShipment, ShipmentError> {
+    let content = std::str::from_utf8(data)
+        .map_err(|e| ShipmentError::InvalidManifest(format!("Invalid UTF-8: {}", e)))?;
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| -> Result<usize, ShipmentError> {
+        headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+            .ok_or_else(|| ShipmentError::InvalidManifest(format!("Missing column '{}'", name)))
+    };
+    let columns: Vec<usize> = MANIFEST_HEADERS
+        .iter()
+        .map(|name| column(name))
+        .collect::<Result<_, _>>()?;
+    let optional = |value: &str| (!value.is_empty()).then(|| value.to_string());
+
+    let mut shipment: Option<CreateShipment> = None;
+    for (index, record) in reader.records().enumerate() {
+        let record = record?;
+        let field = |i: usize| record.get(columns[i]).unwrap_or("");
+        let row = index + 2;
+
+        let volume_ul = match field(8) {
+            "" => None,
+            value => Some(value.parse::<f64>().map_err(|_| {
+                ShipmentError::InvalidManifest(format!("Row {}: invalid volume '{}'", row, value))
+            })?),
+        };
+        let sample_id = match field(7) {
+            "" => None,
+            value => Some(Uuid::parse_str(value).map_err(|_| {
+                ShipmentError::InvalidManifest(format!(
+                    "Row {}: invalid sample ID '{}'",
+                    row, value
+                ))
+            })?),
+        };
+
+        let shipment = match &mut shipment {
+            Some(shipment) => {
+                if shipment.external_reference.as_deref() != optional(field(0)).as_deref() {
+                    return Err(ShipmentError::InvalidManifest(format!(
+                        "Row {}: manifest contains more than one shipment",
+                        row
+                    )));
+                }
+                shipment
+            }
+            None => shipment.insert(CreateShipment {
+                shipment_number: None,
+                external_reference: optional(field(0)),
+                direction: ShipmentDirection::Inbound,
+                origin: field(1).to_string(),
+                destination: field(2).to_string(),
+                carrier: optional(field(3)),
+                tracking_number: optional(field(4)),
+                shipping_condition: parse_condition(field(5)).ok_or_else(|| {
+                    ShipmentError::InvalidManifest(format!(
+                        "Row {}: unknown shipping condition '{}'",
+                        row,
+                        field(5)
+                    ))
+                })?,
+                notes: None,
+                created_by: String::new(),
+                items: Vec::new(),
+            }),
+        };
+
+        shipment.items.push(CreateShipmentItem {
+            sample_id,
+            barcode: field(6).to_string(),
+            volume_ul,
+            container_type: optional(field(9)),
+            notes: optional(field(10)),
+        });
+    }
+
+    let shipment = shipment
+        .ok_or_else(|| ShipmentError::InvalidManifest("Manifest has no lines".to_string()))?;
+    validate_items(&shipment.items)?;
+    Ok(shipment)
+}
+
+fn validate_items(items: &[CreateShipmentItem]) -> Result<(), ShipmentError> {
+    if items.is_empty() {
+        return Err(ShipmentError::InvalidManifest(
+            "A shipment needs at least one manifest line".to_string(),
+        ));
+    }
+
+    let mut barcodes = HashSet::new();
+    for item in items {
+        if item.barcode.trim().is_empty() {
+            return Err(ShipmentError::InvalidManifest(
+                "Manifest line without a barcode".to_string(),
+            ));
+        }
+        if !barcodes.insert(item.barcode.as_str()) {
+            return Err(ShipmentError::InvalidManifest(format!(
+                "Barcode {} appears more than once",
+                item.barcode
+            )));
+        }
+        if item.volume_ul.is_some_and(|v| v <= 0.0) {
+            return Err(ShipmentError::InvalidManifest(format!(
+                "Volume for {} must be positive",
+                item.barcode
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn condition_code(condition: ShippingCondition) -> &'static str {
+    match condition {
+        ShippingCondition::DryIce => "dry_ice",
+        ShippingCondition::WetIce => "wet_ice",
+        ShippingCondition::Ambient => "ambient",
+        ShippingCondition::LiquidNitrogen => "liquid_nitrogen",
+    }
+}
+
+fn parse_condition(value: &str) -> Option<ShippingCondition> {
+    match value.to_lowercase().replace([' ', '-'], "_").as_str() {
+        "dry_ice" => Some(ShippingCondition::DryIce),
+        "wet_ice" => Some(ShippingCondition::WetIce),
+        "ambient" | "room_temperature" => Some(ShippingCondition::Ambient),
+        "liquid_nitrogen" | "ln2" => Some(ShippingCondition::LiquidNitrogen),
+        _ => None,
+    }
+}
+
+fn generate_shipment_number() -> String {
+    let suffix: u16 = rand::rng().random_range(1000..=9999);
+    format!("SHP-{}-{}", Utc::now().format("%Y%m%d"), suffix)
+}
+
+fn unique_violation(error: sqlx::Error, shipment_number: &str) -> ShipmentError {
+    match error.as_database_error().and_then(|e| e.constraint()) {
+        Some("shipments_number_unique") => {
+            ShipmentError::DuplicateShipmentNumber(shipment_number.to_string())
+        }
+        _ => ShipmentError::DatabaseError(error),
+    }
+}
+
+/// Map the storage trigger refusing to release a checked out sample
+fn checked_out_violation(error: sqlx::Error) -> ShipmentError {
+    let barcode = error
+        .as_database_error()
+        .filter(|e| e.constraint() == Some("sample_checked_out"))
+        .and_then(|e| e.try_downcast_ref::<sqlx::postgres::PgDatabaseError>())
+        .and_then(|e| e.detail())
+        .map(str::to_string);
+
+    match barcode {
+        Some(barcode) => ShipmentError::SampleCheckedOut(barcode),
+        None => ShipmentError::DatabaseError(error),
+    }
+}
+
+/// Map a refused receipt to its error
+fn receipt_error(error: ReceiptError) -> ShipmentError {
+    match error {
+        ReceiptError::Database(e) => ShipmentError::DatabaseError(e),
+        ReceiptError::NotOnManifest(_) => ShipmentError::InvalidManifest(error.to_string()),
+        ReceiptError::UnknownSamples(barcodes) => ShipmentError::UnknownSamples(barcodes),
+        ReceiptError::AlreadyStored(barcode) => ShipmentError::SampleAlreadyStored(barcode),
+    }
+}
+
+/// Shipment error types
+#[derive(Debug, thiserror::Error)]
+pub enum ShipmentError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Shipment {0} not found")]
+    ShipmentNotFound(Uuid),
+
+    #[error("Shipment number {0} already exists")]
+    DuplicateShipmentNumber(String),
+
+    #[error("Sample {0} is checked out; check it in before shipping")]
+    SampleCheckedOut(String),
+
+    #[error("No registered sample for barcodes: {}", .0.join(", "))]
+    UnknownSamples(Vec<String>),
+
+    #[error("Sample {0} is already in storage")]
+    SampleAlreadyStored(String),
+
+    #[error("Invalid status transition from {current:?} to {requested:?}")]
+    InvalidStatusTransition {
+        current: ShipmentStatus,
+        requested: ShipmentStatus,
+    },
+
+    #[error("Invalid manifest: {0}")]
+    InvalidManifest(String),
+
+    #[error("Manifest CSV error: {0}")]
+    CsvError(#[from] csv::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::shipment::{ShipmentItem, ShipmentWithItems};
+
+    fn shipment_with_items() -> ShipmentWithItems {
+        let id = Uuid::new_v4();
+        let item = |barcode: &str, volume: Option<f64>| ShipmentItem {
+            id: 1,
+            shipment_id: id,
+            sample_id: Some(Uuid::new_v4()),
+            barcode: barcode.to_string(),
+            volume_ul: volume,
+            container_type: Some("tube".to_string()),
+            notes: None,
+            received: false,
+            received_condition: None,
+            created_at: Utc::now(),
+        };
+
+        ShipmentWithItems {
+            shipment: Shipment {
+                id,
+                shipment_number: "SHP-20250616-1234".to_string(),
+                external_reference: None,
+                direction: ShipmentDirection::Outbound,
+                origin: "Main Lab".to_string(),
+                destination: "Sequencing Provider, Inc.".to_string(),
+                carrier: Some("FedEx".to_string()),
+                tracking_number: Some("7712".to_string()),
+                shipping_condition: ShippingCondition::DryIce,
+                status: ShipmentStatus::Prepared,
+                notes: None,
+                created_by: "tech".to_string(),
+                shipped_by: None,
+                shipped_at: None,
+                received_by: None,
+                received_at: None,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            },
+            items: vec![item("LAB-001", Some(50.0)), item("LAB-002", None)],
+        }
+    }
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let shipment = shipment_with_items();
+        let csv = export_manifest_csv(&shipment).unwrap();
+        let parsed = parse_manifest_csv(csv.as_bytes()).unwrap();
+
+        assert_eq!(parsed.shipment_number, None);
+        assert_eq!(
+            parsed.external_reference.as_deref(),
+            Some("SHP-20250616-1234")
+        );
+        assert_eq!(parsed.destination, "Sequencing Provider, Inc.");
+        assert_eq!(parsed.shipping_condition, ShippingCondition::DryIce);
+        assert_eq!(parsed.items.len(), 2);
+        assert_eq!(parsed.items[0].volume_ul, Some(50.0));
+        assert_eq!(parsed.items[0].sample_id, shipment.items[0].sample_id);
+        assert_eq!(parsed.items[1].volume_ul, None);
+    }
+
+    #[test]
+    fn test_manifest_rejects_duplicate_barcodes() {
+        let mut shipment = shipment_with_items();
+        shipment.items[1].barcode = "LAB-001".to_string();
+        let csv = export_manifest_csv(&shipment).unwrap();
+
+        assert!(matches!(
+            parse_manifest_csv(csv.as_bytes()),
+            Err(ShipmentError::InvalidManifest(_))
+        ));
+    }
+
+    #[test]
+    fn test_shipment_status_transitions() {
+        assert!(ShipmentStatus::Prepared.can_transition_to(ShipmentStatus::Shipped));
+        assert!(ShipmentStatus::Shipped.can_transition_to(ShipmentStatus::Received));
+        assert!(!ShipmentStatus::Shipped.can_transition_to(ShipmentStatus::Cancelled));
+        assert!(!ShipmentStatus::Received.can_transition_to(ShipmentStatus::Prepared));
+    }
+}
@@ -0,0 +1,434 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+use crate::models::spreadsheet::{
+    AggregateFunction, AggregateQuery, AggregateSpec, ColumnRef, DatasetSide, JoinSpec,
+};
+
+/// Rows returned by an aggregation unless the caller asks for more
+pub const DEFAULT_RESULT_LIMIT: i64 = 1000;
+
+/// Upper bound on rows returned by an aggregation
+pub const MAX_RESULT_LIMIT: i64 = 10_000;
+
+/// Upper bound on distinct values a pivot turns into columns
+pub const MAX_PIVOT_VALUES: usize = 100;
+
+/// Upper bound on output columns, well under Postgres' select list limit
+pub const MAX_OUTPUT_COLUMNS: usize = 1000;
+
+/// Header of the pivot column for rows where the pivoted cell is empty
+const BLANK_PIVOT_LABEL: &str = "(blank)";
+
+/// Rows and columns in the shape the reports UI renders
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TabularResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<HashMap<String, Value>>,
+    pub row_count: usize,
+    /// More rows matched than were returned
+    pub truncated: bool,
+    pub execution_time_ms: u64,
+}
+
+/// Check an aggregation for references the SQL cannot satisfy
+pub fn validate_query(query: &AggregateQuery) -> Result<(), AggregateError> {
+    let empty_key =
+        |join: &JoinSpec| join.left_key.trim().is_empty() || join.right_key().trim().is_empty();
+    if query.join.as_ref().is_some_and(empty_key) {
+        return Err(AggregateError::ValidationError(
+            "Join keys cannot be empty".to_string(),
+        ));
+    }
+
+    let mut referenced: Vec<&ColumnRef> = query.group_by.iter().collect();
+    referenced.extend(
+        query
+            .aggregates
+            .iter()
+            .filter_map(|spec| spec.column.as_ref()),
+    );
+    referenced.extend(query.pivot.iter().map(|pivot| &pivot.column));
+    if !query.is_grouped() {
+        referenced.extend(&query.columns);
+    }
+    for column in referenced {
+        if column.column.is_empty() {
+            return Err(AggregateError::ValidationError(
+                "Column names cannot be empty".to_string(),
+            ));
+        }
+        if column.side == DatasetSide::Right && query.join.is_none() {
+            return Err(AggregateError::ValidationError(format!(
+                "Column '{}' reads the right dataset but there is no join",
+                column.column
+            )));
+        }
+    }
+
+    for spec in &query.aggregates {
+        if spec.column.is_none() && spec.function != AggregateFunction::Count {
+            return Err(AggregateError::ValidationError(format!(
+                "{} needs a column",
+                spec.function.name()
+            )));
+        }
+    }
+
+    if let Some(values) = query.pivot.as_ref().and_then(|pivot| pivot.values.as_ref()) {
+        if values.is_empty() {
+            return Err(AggregateError::ValidationError(
+                "Pivot values cannot be an empty list".to_string(),
+            ));
+        }
+        if values.len() > MAX_PIVOT_VALUES {
+            return Err(AggregateError::TooManyPivotValues {
+                max: MAX_PIVOT_VALUES,
+            });
+        }
+    }
+
+    if !query.is_grouped() && query.columns.is_empty() {
+        return Err(AggregateError::ValidationError(
+            "Select columns, group by a column or add an aggregate".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Output column names, in the order the manager returns cells
+pub fn output_columns(
+    query: &AggregateQuery,
+    pivot_values: &[Option<String>],
+) -> Result<Vec<String>, AggregateError> {
+    let mut columns: Vec<String> = Vec::new();
+    if query.is_grouped() {
+        columns.extend(query.group_by.iter().map(column_label));
+        match &query.pivot {
+            Some(_) => {
+                for value in pivot_values {
+                    let value = value.as_deref().unwrap_or(BLANK_PIVOT_LABEL);
+                    for spec in &query.aggregates {
+                        columns.push(if query.aggregates.len() == 1 {
+                            value.to_string()
+                        } else {
+                            format!("{} / {}", value, aggregate_label(spec))
+                        });
+                    }
+                }
+            }
+            None => columns.extend(query.aggregates.iter().map(aggregate_label)),
+        }
+    } else {
+        columns.extend(query.columns.iter().map(column_label));
+    }
+
+    if columns.len() > MAX_OUTPUT_COLUMNS {
+        return Err(AggregateError::ValidationError(format!(
+            "Result would have {} columns; the limit is {}",
+            columns.len(),
+            MAX_OUTPUT_COLUMNS
+        )));
+    }
+    let mut seen = HashSet::new();
+    if let Some(duplicate) = columns.iter().find(|column| !seen.insert(column.as_str())) {
+        return Err(AggregateError::ValidationError(format!(
+            "More than one output column is named '{}'; set an alias",
+            duplicate
+        )));
+    }
+    Ok(columns)
+}
+
+/// Key the cells of each row by column name. `rows` may hold one row past
+/// `limit`, which marks the result as truncated.
+pub fn build_table(
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    limit: usize,
+    execution_time_ms: u64,
+) -> TabularResult {
+    let truncated = rows.len() > limit;
+    let rows: Vec<HashMap<String, Value>> = rows
+        .into_iter()
+        .take(limit)
+        .map(|cells| columns.iter().cloned().zip(cells).collect())
+        .collect();
+
+    TabularResult {
+        row_count: rows.len(),
+        columns,
+        rows,
+        truncated,
+        execution_time_ms,
+    }
+}
+
+fn column_label(column: &ColumnRef) -> String {
+    match column.side {
+        DatasetSide::Left => column.column.clone(),
+        DatasetSide::Right => format!("right.{}", column.column),
+    }
+}
+
+fn aggregate_label(spec: &AggregateSpec) -> String {
+    if let Some(alias) = &spec.alias {
+        return alias.clone();
+    }
+    match &spec.column {
+        Some(column) => format!("{}({})", spec.function.name(), column_label(column)),
+        None => spec.function.name().to_string(),
+    }
+}
+
+/// Spreadsheet aggregation errors
+#[derive(Debug, thiserror::Error)]
+pub enum AggregateError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Dataset not found")]
+    DatasetNotFound,
+
+    #[error("Pivot column has more than {max} distinct values; list the values to pivot")]
+    TooManyPivotValues { max: usize },
+
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::spreadsheet::PivotSpec;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn column(name: &str) -> ColumnRef {
+        ColumnRef {
+            column: name.to_string(),
+            side: DatasetSide::Left,
+        }
+    }
+
+    fn aggregate(function: AggregateFunction, name: Option<&str>) -> AggregateSpec {
+        AggregateSpec {
+            function,
+            column: name.map(column),
+            alias: None,
+        }
+    }
+
+    #[test]
+    fn test_column_refs_accept_bare_names() {
+        let query: AggregateQuery = serde_json::from_value(json!({
+            "group_by": ["Project", {"column": "QC", "side": "right"}],
+            "aggregates": [{"function": "mean", "column": "Conc"}]
+        }))
+        .unwrap();
+
+        assert_eq!(query.group_by[0], column("Project"));
+        assert_eq!(query.group_by[1].side, DatasetSide::Right);
+        assert!(matches!(
+            validate_query(&query),
+            Err(AggregateError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn test_pivot_columns_are_named_after_values() {
+        let mut query = AggregateQuery {
+            group_by: vec![column("Project")],
+            aggregates: vec![aggregate(AggregateFunction::Sum, Some("Reads"))],
+            pivot: Some(PivotSpec {
+                column: column("Lane"),
+                values: None,
+            }),
+            ..Default::default()
+        };
+        let values = [Some("1".to_string()), None];
+
+        assert_eq!(
+            output_columns(&query, &values).unwrap(),
+            ["Project", "1", "(blank)"]
+        );
+
+        query
+            .aggregates
+            .push(aggregate(AggregateFunction::Count, None));
+        assert_eq!(
+            output_columns(&query, &values).unwrap(),
+            [
+                "Project",
+                "1 / sum(Reads)",
+                "1 / count",
+                "(blank) / sum(Reads)",
+                "(blank) / count"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validation_rejects_unusable_queries() {
+        let sum_without_column = AggregateQuery {
+            aggregates: vec![aggregate(AggregateFunction::Sum, None)],
+            ..Default::default()
+        };
+        assert!(validate_query(&sum_without_column).is_err());
+
+        assert!(validate_query(&AggregateQuery::default()).is_err());
+
+        let joined = AggregateQuery {
+            join: Some(JoinSpec {
+                dataset_id: Uuid::new_v4(),
+                left_key: "Sample".to_string(),
+                right_key: None,
+                kind: Default::default(),
+            }),
+            columns: vec![
+                column("Sample"),
+                ColumnRef {
+                    column: "Sample".to_string(),
+                    side: DatasetSide::Right,
+                },
+            ],
+            ..Default::default()
+        };
+        assert!(validate_query(&joined).is_ok());
+        assert_eq!(
+            output_columns(&joined, &[]).unwrap(),
+            ["Sample", "right.Sample"]
+        );
+
+        let duplicate = AggregateQuery {
+            aggregates: vec![
+                aggregate(AggregateFunction::Count, None),
+                aggregate(AggregateFunction::Count, None),
+            ],
+            ..Default::default()
+        };
+        assert!(output_columns(&duplicate, &[]).is_err());
+    }
+
+    #[test]
+    fn test_build_table_marks_truncation() {
+        let rows = vec![vec![json!("A"), json!(2)], vec![json!("B"), json!(3)]];
+        let table = build_table(vec!["Project".into(), "count".into()], rows, 1, 0);
+
+        assert!(table.truncated);
+        assert_eq!(table.row_count, 1);
+        assert_eq!(table.rows[0]["count"], json!(2));
+    }
+
+    // Needs a migrated Postgres database:
+    // TEST_DATABASE_URL=... cargo test -- --ignored spreadsheet_aggregate
+    #[tokio::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn test_aggregate_group_pivot_and_join() {
+        use crate::services::spreadsheet_ingest::tests::{ingest_test_csv, setup_test_manager};
+        use crate::services::spreadsheet_service::SpreadsheetService;
+
+        let (manager, _pool) = setup_test_manager().await;
+        let service = SpreadsheetService::new(manager.clone());
+
+        let mut dataset_ids = Vec::new();
+        for (name, csv) in [
+            (
+                "runs.csv",
+                &b"Sample,Project,Lane,Reads\nS1,P1,1,100\nS2,P1,2,50\nS3,P2,1,10\nS4,P2,,n/a\n"[..],
+            ),
+            ("qc.csv", &b"Sample,QC\nS1,pass\nS2,fail\nS3,pass\n"[..]),
+        ] {
+            let (dataset, _) = ingest_test_csv(&manager, name, csv, 100).await;
+            dataset_ids.push(dataset.id);
+        }
+        let (runs, qc) = (dataset_ids[0], dataset_ids[1]);
+
+        let query = |value: serde_json::Value| {
+            let mut query: AggregateQuery = serde_json::from_value(value).unwrap();
+            query.dataset_ids = vec![runs];
+            query
+        };
+
+        let grouped = service
+            .aggregate(query(json!({
+                "group_by": ["Project"],
+                "aggregates": [
+                    {"function": "count"},
+                    {"function": "sum", "column": "Reads"},
+                    {"function": "mean", "column": "Reads", "alias": "mean_reads"}
+                ]
+            })))
+            .await
+            .unwrap();
+        assert_eq!(
+            grouped.columns,
+            ["Project", "count", "sum(Reads)", "mean_reads"]
+        );
+        assert_eq!(grouped.rows[0]["sum(Reads)"], json!(150));
+        assert_eq!(grouped.rows[0]["mean_reads"], json!(75));
+        assert_eq!(grouped.rows[1]["count"], json!(2));
+
+        let pivoted = service
+            .aggregate(query(json!({
+                "group_by": ["Project"],
+                "pivot": {"column": "Lane"},
+                "aggregates": [{"function": "sum", "column": "Reads"}]
+            })))
+            .await
+            .unwrap();
+        assert_eq!(pivoted.columns, ["Project", "1", "2", "(blank)"]);
+        assert_eq!(pivoted.rows[1]["1"], json!(10));
+        assert_eq!(pivoted.rows[1]["2"], json!(null));
+
+        let joined = service
+            .aggregate(query(json!({
+                "join": {"dataset_id": qc, "left_key": "Sample", "kind": "left"},
+                "group_by": [{"column": "QC", "side": "right"}],
+                "aggregates": [{"function": "count"}]
+            })))
+            .await
+            .unwrap();
+        let counts: Vec<_> = joined
+            .rows
+            .iter()
+            .map(|row| (row["right.QC"].clone(), row["count"].clone()))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                (json!("fail"), json!(1)),
+                (json!("pass"), json!(2)),
+                (json!(null), json!(1))
+            ]
+        );
+
+        // Without aggregates the joined rows come back with every column
+        let rows = service
+            .aggregate(query(json!({
+                "join": {"dataset_id": qc, "left_key": "Sample"},
+                "limit": 2
+            })))
+            .await
+            .unwrap();
+        assert_eq!(
+            rows.columns,
+            [
+                "Sample",
+                "Project",
+                "Lane",
+                "Reads",
+                "right.Sample",
+                "right.QC"
+            ]
+        );
+        assert_eq!(rows.row_count, 2);
+        assert!(rows.truncated);
+        assert_eq!(rows.rows[1]["right.QC"], json!("fail"));
+
+        for dataset_id in dataset_ids {
+            manager.delete_dataset(dataset_id).await.unwrap();
+        }
+    }
+}
@@ -0,0 +1,515 @@
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::models::spreadsheet::{
+    DatasetColumns, SpreadsheetDataManager, SpreadsheetSearchQuery, SpreadsheetSearchRecord,
+};
+
+/// Records read from the database per export query
+pub const EXPORT_BATCH_SIZE: i64 = 1000;
+
+/// Data rows that fit on one worksheet below the header row
+pub const XLSX_MAX_ROWS: i64 = 1_048_575;
+
+/// Columns prepended when provenance is requested
+pub const PROVENANCE_COLUMNS: [&str; 3] = ["Dataset ID", "Source File", "Source Row"];
+
+/// Buffer between the CSV writer task and the response body
+const CSV_PIPE_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Xlsx,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Prepend the source dataset, file and row number of each record
+    pub include_provenance: bool,
+}
+
+/// An export ready to be sent, read incrementally from `body`
+pub struct SpreadsheetExport {
+    pub filename: String,
+    pub format: ExportFormat,
+    pub body: Pin<Box<dyn AsyncRead + Send>>,
+}
+
+/// Write the records matching `query` as CSV or XLSX.
+///
+/// CSV is produced while it is read: records are fetched a batch at a time
+/// by a background task that writes into the returned body. XLSX has to be
+/// complete before it can be sent, so the workbook is built in constant
+/// memory mode in `spool_dir` and the finished file is streamed from disk.
+pub async fn export_records(
+    manager: &SpreadsheetDataManager,
+    query: SpreadsheetSearchQuery,
+    options: ExportOptions,
+    spool_dir: &Path,
+    file_stem: &str,
+) -> Result<SpreadsheetExport, ExportError> {
+    let datasets = manager.search_dataset_columns(&query).await?;
+    let columns = export_columns(&datasets);
+
+    let body: Pin<Box<dyn AsyncRead + Send>> = match options.format {
+        ExportFormat::Csv => {
+            let (writer, reader) = tokio::io::duplex(CSV_PIPE_BYTES);
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                if let Err(e) = write_csv(
+                    &manager,
+                    &query,
+                    &columns,
+                    options.include_provenance,
+                    writer,
+                )
+                .await
+                {
+                    // The response has already started, so the client sees a
+                    // truncated file
+                    error!("CSV export stopped early: {}", e);
+                }
+            });
+            Box::pin(reader)
+        }
+        ExportFormat::Xlsx => {
+            let rows = manager.count_search_records(&query).await?;
+            if rows > XLSX_MAX_ROWS {
+                return Err(ExportError::TooManyRows {
+                    rows,
+                    max: XLSX_MAX_ROWS,
+                });
+            }
+
+            tokio::fs::create_dir_all(spool_dir).await?;
+            let path = spool_dir.join(format!("{}.xlsx", Uuid::new_v4()));
+            let result =
+                write_xlsx(manager, &query, columns, options.include_provenance, &path).await;
+            let file = match result {
+                Ok(()) => tokio::fs::File::open(&path)
+                    .await
+                    .map_err(ExportError::from),
+                Err(e) => Err(e),
+            };
+            // The open handle keeps the data readable after the file is unlinked
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                warn!("Failed to remove export spool file {:?}: {}", path, e);
+            }
+            Box::pin(file?)
+        }
+    };
+
+    Ok(SpreadsheetExport {
+        filename: format!(
+            "{}.{}",
+            file_stem.replace(['"', '\\', '/'], "_"),
+            options.format.extension()
+        ),
+        format: options.format,
+        body,
+    })
+}
+
+/// Columns of the export: each dataset's headers in their original order,
+/// with headers first seen in later datasets appended
+pub fn export_columns(datasets: &[DatasetColumns]) -> Vec<String> {
+    let mut columns: Vec<String> = Vec::new();
+    for header in datasets.iter().flat_map(|d| &d.column_headers) {
+        if !columns.contains(header) {
+            columns.push(header.clone());
+        }
+    }
+    columns
+}
+
+/// Header row of the export
+pub fn header_row(columns: &[String], include_provenance: bool) -> Vec<String> {
+    let provenance = PROVENANCE_COLUMNS
+        .iter()
+        .filter(|_| include_provenance)
+        .map(|column| column.to_string());
+    provenance.chain(columns.iter().cloned()).collect()
+}
+
+/// Text of each export column for one record; missing cells are empty
+pub fn record_row(
+    record: &SpreadsheetSearchRecord,
+    columns: &[String],
+    include_provenance: bool,
+) -> Vec<String> {
+    let mut row = Vec::with_capacity(columns.len() + PROVENANCE_COLUMNS.len());
+    if include_provenance {
+        row.push(record.dataset_id.to_string());
+        row.push(record.original_filename.clone());
+        row.push(record.row_number.to_string());
+    }
+    row.extend(columns.iter().map(|column| cell_text(record, column)));
+    row
+}
+
+fn cell_text(record: &SpreadsheetSearchRecord, column: &str) -> String {
+    match record.row_data.get(column) {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(serde_json::Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+async fn write_csv<W: AsyncWrite + Unpin>(
+    manager: &SpreadsheetDataManager,
+    query: &SpreadsheetSearchQuery,
+    columns: &[String],
+    include_provenance: bool,
+    mut out: W,
+) -> Result<(), ExportError> {
+    out.write_all(&csv_lines([header_row(columns, include_provenance)])?)
+        .await?;
+
+    let mut after = None;
+    loop {
+        let records = manager
+            .search_records_after(query, after, EXPORT_BATCH_SIZE)
+            .await?;
+        let rows = records
+            .iter()
+            .map(|record| record_row(record, columns, include_provenance));
+        out.write_all(&csv_lines(rows)?).await?;
+
+        match records.last() {
+            Some(last) if records.len() as i64 == EXPORT_BATCH_SIZE => {
+                after = Some((last.dataset_id, last.row_number));
+            }
+            _ => break,
+        }
+    }
+
+    out.shutdown().await?;
+    Ok(())
+}
+
+fn csv_lines(rows: impl IntoIterator<Item = Vec<String>>) -> Result<Vec<u8>, ExportError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+/// Fetch batches here and hand them to a blocking worker that owns the
+/// workbook, so building it never stalls the async runtime
+async fn write_xlsx(
+    manager: &SpreadsheetDataManager,
+    query: &SpreadsheetSearchQuery,
+    columns: Vec<String>,
+    include_provenance: bool,
+    path: &Path,
+) -> Result<(), ExportError> {
+    let (sender, receiver) = mpsc::channel::<Vec<SpreadsheetSearchRecord>>(2);
+    let path = path.to_path_buf();
+    let worker = tokio::task::spawn_blocking(move || {
+        build_workbook(receiver, &columns, include_provenance, path)
+    });
+
+    let mut after = None;
+    loop {
+        let records = manager
+            .search_records_after(query, after, EXPORT_BATCH_SIZE)
+            .await?;
+        let next = match records.last() {
+            Some(last) if records.len() as i64 == EXPORT_BATCH_SIZE => {
+                Some((last.dataset_id, last.row_number))
+            }
+            _ => None,
+        };
+        if sender.send(records).await.is_err() {
+            // The worker stopped early; its error is reported below
+            break;
+        }
+        match next {
+            Some(key) => after = Some(key),
+            None => break,
+        }
+    }
+    drop(sender);
+
+    worker
+        .await
+        .map_err(|e| ExportError::WorkerFailed(e.to_string()))?
+}
+
+fn build_workbook(
+    mut receiver: mpsc::Receiver<Vec<SpreadsheetSearchRecord>>,
+    columns: &[String],
+    include_provenance: bool,
+    path: PathBuf,
+) -> Result<(), ExportError> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet_with_constant_memory();
+    worksheet.set_name("Export")?;
+
+    let bold = Format::new().set_bold();
+    for (col, header) in header_row(columns, include_provenance).iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, header, &bold)?;
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+
+    let offset = if include_provenance {
+        PROVENANCE_COLUMNS.len() as u16
+    } else {
+        0
+    };
+    let mut row = 0u32;
+    while let Some(records) = receiver.blocking_recv() {
+        for record in &records {
+            row += 1;
+            if include_provenance {
+                worksheet.write_string(row, 0, record.dataset_id.to_string())?;
+                worksheet.write_string(row, 1, &record.original_filename)?;
+                worksheet.write_number(row, 2, record.row_number)?;
+            }
+            for (index, column) in columns.iter().enumerate() {
+                let col = offset + index as u16;
+                // Numbers and booleans inferred at upload keep their type
+                match record.typed_data.get(column) {
+                    Some(serde_json::Value::Number(number)) => {
+                        if let Some(value) = number.as_f64() {
+                            worksheet.write_number(row, col, value)?;
+                            continue;
+                        }
+                    }
+                    Some(serde_json::Value::Bool(value)) => {
+                        worksheet.write_boolean(row, col, *value)?;
+                        continue;
+                    }
+                    _ => {}
+                }
+                let text = cell_text(record, column);
+                if !text.is_empty() {
+                    worksheet.write_string(row, col, text)?;
+                }
+            }
+        }
+    }
+
+    workbook.save(&path)?;
+    Ok(())
+}
+
+/// Spreadsheet export errors
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
+
+    #[error("Dataset not found")]
+    DatasetNotFound,
+
+    #[error("{rows} rows exceed the XLSX limit of {max}; export as CSV instead")]
+    TooManyRows { rows: i64, max: i64 },
+
+    #[error("CSV error: {0}")]
+    CsvError(#[from] csv::Error),
+
+    #[error("XLSX error: {0}")]
+    XlsxError(#[from] XlsxError),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Export worker failed: {0}")]
+    WorkerFailed(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn record(row_data: serde_json::Value) -> SpreadsheetSearchRecord {
+        SpreadsheetSearchRecord {
+            id: Uuid::nil(),
+            dataset_id: Uuid::nil(),
+            row_number: 7,
+            row_data,
+            typed_data: json!({}),
+            search_text: None,
+            created_at: Utc::now(),
+            original_filename: "tracking.xlsx".to_string(),
+            file_type: "xlsx".to_string(),
+            dataset_filename: "stored.xlsx".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_columns_keep_dataset_order_and_append_new_headers() {
+        let datasets = [
+            DatasetColumns {
+                id: Uuid::nil(),
+                original_filename: "a.csv".to_string(),
+                column_headers: vec!["Sample".into(), "Volume".into(), "Type".into()],
+            },
+            DatasetColumns {
+                id: Uuid::nil(),
+                original_filename: "b.csv".to_string(),
+                column_headers: vec!["Sample".into(), "Freezer".into(), "Volume".into()],
+            },
+        ];
+
+        assert_eq!(
+            export_columns(&datasets),
+            ["Sample", "Volume", "Type", "Freezer"]
+        );
+    }
+
+    #[test]
+    fn test_record_row_with_provenance() {
+        let columns = vec![
+            "Sample".to_string(),
+            "Freezer".to_string(),
+            "QC".to_string(),
+        ];
+        let record = record(json!({"QC": true, "Sample": "S1", "_search_enhanced": "x"}));
+
+        assert_eq!(
+            header_row(&columns, true),
+            [
+                "Dataset ID",
+                "Source File",
+                "Source Row",
+                "Sample",
+                "Freezer",
+                "QC"
+            ]
+        );
+        assert_eq!(
+            record_row(&record, &columns, true),
+            [
+                &Uuid::nil().to_string(),
+                "tracking.xlsx",
+                "7",
+                "S1",
+                "",
+                "true"
+            ]
+        );
+        assert_eq!(record_row(&record, &columns, false), ["S1", "", "true"]);
+    }
+
+    // Needs a migrated Postgres database:
+    // TEST_DATABASE_URL=... cargo test -- --ignored spreadsheet_export
+    #[tokio::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn test_export_search_to_csv_and_xlsx() {
+        use crate::models::spreadsheet::SpreadsheetSearchQuery;
+        use crate::services::spreadsheet_ingest::tests::{ingest_test_csv, setup_test_manager};
+        use crate::services::spreadsheet_ingest::{open_row_stream, IngestOptions, IngestSource};
+        use std::sync::Arc;
+        use tokio::io::AsyncReadExt;
+
+        let (manager, _pool) = setup_test_manager().await;
+
+        // More rows than one export batch, in a column order that is not
+        // alphabetical
+        let rows = EXPORT_BATCH_SIZE as usize + 5;
+        let mut csv = String::from("Well,Sample,Conc\n");
+        for i in 1..=rows {
+            csv.push_str(&format!("A{},S{},{}\n", i, i, i % 7));
+        }
+        let (dataset, _) = ingest_test_csv(&manager, "export_test.csv", csv.as_bytes(), 500).await;
+
+        let spool_dir = std::env::temp_dir().join("lab_manager_export_test");
+        let query = || SpreadsheetSearchQuery {
+            search_term: None,
+            dataset_id: Some(dataset.id),
+            column_filters: None,
+            pool_filter: None,
+            sample_filter: None,
+            project_filter: None,
+            range_filters: None,
+            in_filters: None,
+            limit: Some(10),
+            offset: None,
+        };
+
+        let mut export = export_records(
+            &manager,
+            query(),
+            ExportOptions::default(),
+            &spool_dir,
+            "export_test",
+        )
+        .await
+        .unwrap();
+        let mut exported = String::new();
+        export.body.read_to_string(&mut exported).await.unwrap();
+        assert_eq!(export.filename, "export_test.csv");
+        assert!(exported == csv, "CSV export differs from the upload");
+
+        let mut export = export_records(
+            &manager,
+            query(),
+            ExportOptions {
+                format: ExportFormat::Xlsx,
+                include_provenance: true,
+            },
+            &spool_dir,
+            "export_test",
+        )
+        .await
+        .unwrap();
+        let mut xlsx = Vec::new();
+        export.body.read_to_end(&mut xlsx).await.unwrap();
+
+        let mut sheet = open_row_stream(
+            IngestSource::Bytes(Arc::from(xlsx)),
+            "xlsx",
+            &IngestOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            sheet.headers(),
+            [
+                "Dataset ID",
+                "Source File",
+                "Source Row",
+                "Well",
+                "Sample",
+                "Conc"
+            ]
+        );
+        let first = sheet.next_row().unwrap().unwrap();
+        assert_eq!(first[1..], ["export_test.csv", "1", "A1", "S1", "1"]);
+        assert_eq!(std::iter::from_fn(|| sheet.next_row()).count(), rows - 1);
+
+        manager.delete_dataset(dataset.id).await.unwrap();
+    }
+}
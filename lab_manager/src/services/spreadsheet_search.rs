@@ -0,0 +1,297 @@
+/// Turn a user search string into a Postgres `tsquery` expression.
+///
+/// Supported syntax:
+/// - `word` — must appear; words are ANDed together
+/// - `"two words"` — phrase, words adjacent and in order
+/// - `prefix*` — any word starting with `prefix`
+/// - `-word` — must not appear
+/// - `a OR b` — either side may match
+///
+/// Words are passed to Postgres as quoted operands, so identifiers such as
+/// `LAB-001` are split by the same parser that built the search vectors.
+/// Returns `None` when nothing searchable is left or every term is negated.
+pub fn build_tsquery(input: &str) -> Option<String> {
+    // Each group holds the alternatives of one `OR` chain
+    let mut groups: Vec<Vec<String>> = Vec::new();
+    let mut has_positive_term = false;
+    let mut pending_or = false;
+
+    for token in tokenize(input) {
+        if token.text == "OR" && !token.quoted {
+            pending_or = !groups.is_empty();
+            continue;
+        }
+
+        let (negated, text) = match token.text.strip_prefix('-') {
+            Some(rest) if !token.quoted && !rest.is_empty() => (true, rest),
+            _ => (false, token.text.as_str()),
+        };
+
+        let Some(term) = phrase(text) else {
+            continue;
+        };
+        has_positive_term |= !negated;
+
+        let operand = if negated { format!("!{}", term) } else { term };
+        match groups.last_mut() {
+            Some(group) if pending_or => group.push(operand),
+            _ => groups.push(vec![operand]),
+        }
+        pending_or = false;
+    }
+
+    if !has_positive_term {
+        return None;
+    }
+
+    // `&` binds tighter than `|`, so alternatives are parenthesized before
+    // being ANDed with the other terms
+    let query = groups
+        .into_iter()
+        .map(|mut group| match group.len() {
+            1 => group.pop().unwrap_or_default(),
+            _ => format!("({})", group.join(" | ")),
+        })
+        .collect::<Vec<_>>()
+        .join(" & ");
+    Some(query)
+}
+
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                if in_quotes || !current.is_empty() {
+                    tokens.push(Token {
+                        text: std::mem::take(&mut current),
+                        quoted: in_quotes,
+                    });
+                }
+                in_quotes = !in_quotes;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    tokens.push(Token {
+                        text: std::mem::take(&mut current),
+                        quoted: false,
+                    });
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        // An unterminated quote still counts as a phrase
+        tokens.push(Token {
+            text: current,
+            quoted: in_quotes,
+        });
+    }
+
+    tokens
+}
+
+/// Quoted operands for the words of `text`, adjacent and in order, with
+/// `:*` on any word that ends in `*`
+fn phrase(text: &str) -> Option<String> {
+    let mut operands: Vec<String> = text
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| {
+            let (word, prefix) = match word.strip_suffix('*') {
+                Some(stem) => (stem, ":*"),
+                None => (word, ""),
+            };
+            let escaped = word.replace('\\', "\\\\").replace('\'', "''");
+            format!("'{}'{}", escaped, prefix)
+        })
+        .collect();
+
+    match operands.len() {
+        0 => None,
+        1 => operands.pop(),
+        _ => Some(format!("({})", operands.join(" <-> "))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_words_are_anded() {
+        assert_eq!(
+            build_tsquery("plasma  Oncology").unwrap(),
+            "'plasma' & 'Oncology'"
+        );
+    }
+
+    #[test]
+    fn test_phrases_prefixes_and_identifiers() {
+        assert_eq!(
+            build_tsquery("\"whole blood\" LAB-00*").unwrap(),
+            "('whole' <-> 'blood') & 'LAB-00':*"
+        );
+        assert_eq!(
+            build_tsquery("\"unterminated phrase").unwrap(),
+            "('unterminated' <-> 'phrase')"
+        );
+    }
+
+    #[test]
+    fn test_or_and_negation() {
+        assert_eq!(
+            build_tsquery("dna OR rna -degraded").unwrap(),
+            "('dna' | 'rna') & !'degraded'"
+        );
+        assert_eq!(
+            build_tsquery("plasma dna OR rna OR \"cell free\"").unwrap(),
+            "'plasma' & ('dna' | 'rna' | ('cell' <-> 'free'))"
+        );
+        // OR with nothing before it is just a word boundary
+        assert_eq!(build_tsquery("OR dna").unwrap(), "'dna'");
+    }
+
+    #[test]
+    fn test_operands_are_escaped() {
+        assert_eq!(build_tsquery("o'neil").unwrap(), "'o''neil'");
+        assert_eq!(build_tsquery("a\\b").unwrap(), "'a\\\\b'");
+    }
+
+    #[test]
+    fn test_rejects_queries_without_positive_terms() {
+        assert_eq!(build_tsquery("  "), None);
+        assert_eq!(build_tsquery("&|!():*"), None);
+        assert_eq!(build_tsquery("-failed"), None);
+    }
+
+    // Needs a migrated Postgres database:
+    // TEST_DATABASE_URL=... cargo test -- --ignored spreadsheet_search
+    #[tokio::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn test_typed_range_and_in_filters() {
+        use crate::models::spreadsheet::{
+            RangeBound, RangeFilter, SpreadsheetSearchQuery, SpreadsheetSearchResult,
+        };
+        use crate::services::column_inference::ColumnType;
+        use crate::services::spreadsheet_ingest::tests::{ingest_test_csv, setup_test_manager};
+        use std::collections::HashMap;
+
+        let (manager, _pool) = setup_test_manager().await;
+        let (dataset, summary) = ingest_test_csv(
+            &manager,
+            "typed_test.csv",
+            b"Sample,Conc,Collected,Type\nS1,9.5,31/01/2024,DNA\nS2,12,01/02/2024,RNA\nS3,30,15/02/2024,DNA\nS4,n/a,20/02/2024,DNA\n",
+            2,
+        )
+        .await;
+        assert_eq!(summary.column_types[1].column_type, ColumnType::Decimal);
+        assert_eq!(summary.column_types[2].column_type, ColumnType::Date);
+
+        let search = |range_filters: HashMap<String, RangeFilter>,
+                      in_filters: HashMap<String, Vec<String>>| {
+            manager.search_records(SpreadsheetSearchQuery {
+                search_term: None,
+                dataset_id: Some(dataset.id),
+                column_filters: None,
+                pool_filter: None,
+                sample_filter: None,
+                project_filter: None,
+                range_filters: Some(range_filters),
+                in_filters: Some(in_filters),
+                limit: None,
+                offset: None,
+            })
+        };
+        let samples = |result: SpreadsheetSearchResult| {
+            result
+                .records
+                .iter()
+                .map(|r| r.row_data["Sample"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let conc = HashMap::from([(
+            "Conc".to_string(),
+            RangeFilter {
+                min: Some(RangeBound::Number(10.0)),
+                max: None,
+            },
+        )]);
+        let result = search(conc, HashMap::new()).await.unwrap();
+        assert_eq!(samples(result), ["S2", "S3"]);
+
+        let collected = HashMap::from([(
+            "Collected".to_string(),
+            RangeFilter {
+                min: RangeBound::parse("2024-02-01", false),
+                max: RangeBound::parse("2024-02-15", true),
+            },
+        )]);
+        let types = HashMap::from([("Type".to_string(), vec!["DNA".to_string()])]);
+        let result = search(collected, types).await.unwrap();
+        assert_eq!(result.total_count, 1);
+        assert_eq!(samples(result), ["S3"]);
+
+        manager.delete_dataset(dataset.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "requires TEST_DATABASE_URL"]
+    async fn test_ranked_search_highlights_cells_and_facets() {
+        use crate::models::spreadsheet::RankedSearchQuery;
+        use crate::services::spreadsheet_ingest::tests::{ingest_test_csv, setup_test_manager};
+
+        let (manager, _pool) = setup_test_manager().await;
+        let (dataset, _) = ingest_test_csv(
+            &manager,
+            "ranked_test.csv",
+            b"Sample,Type,Notes\nLAB-001,Whole blood,hemolysed whole blood sample\nLAB-002,Plasma,clear\nLAB-003,Whole blood,ok\n",
+            10,
+        )
+        .await;
+
+        let search = |q: &str| {
+            manager.ranked_search(RankedSearchQuery {
+                tsquery: build_tsquery(q).unwrap(),
+                dataset_ids: Some(vec![dataset.id]),
+                limit: None,
+                offset: None,
+            })
+        };
+
+        let result = search("\"whole blood\"").await.unwrap();
+        assert_eq!(result.total_count, 2);
+        // Matching in two cells ranks above matching in one
+        assert_eq!(result.records[0].row_data["Sample"], "LAB-001");
+        let columns: Vec<_> = result.records[0]
+            .matches
+            .iter()
+            .map(|m| m.column.as_str())
+            .collect();
+        assert_eq!(columns, ["Notes", "Type"]);
+        assert!(result.records[0].matches[1].snippet.contains("<mark>"));
+        assert_eq!(result.dataset_facets[0].count, 2);
+        assert_eq!(result.column_facets[0].column_name, "Type");
+        assert_eq!(result.column_facets[0].count, 2);
+
+        let result = search("lab-00* -plasma").await.unwrap();
+        assert_eq!(result.total_count, 2);
+
+        // A negated word excludes rows matching any of the alternatives
+        let result = search("plasma OR hemolysed -clear").await.unwrap();
+        assert_eq!(result.total_count, 1);
+        assert_eq!(result.records[0].row_data["Sample"], "LAB-001");
+
+        manager.delete_dataset(dataset.id).await.unwrap();
+    }
+}
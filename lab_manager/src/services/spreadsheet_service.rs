@@ -1,27 +1,67 @@
 use async_trait::async_trait;
 use calamine::{open_workbook_auto_from_rs, Reader};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
 use uuid::Uuid;
 
 use crate::{
     models::spreadsheet::{
-        CreateSpreadsheetDataset, CreateSpreadsheetRecord, ParsedSpreadsheetData,
+        AggregateFunction, AggregateQuery, AggregateSpec, ColumnRef, CreateDatasetRuleSet,
+        CreateDatasetSeries, CreateSampleMappingProfile, CreateSpreadsheetDataset, DatasetRuleSet,
+        DatasetSeries, DatasetSeriesWithVersions, DatasetSide, LinkedSample, ParsedSpreadsheetData,
+        RankedSearchQuery, RankedSearchResult, RecordSample, SampleMappingProfile, SeriesVersion,
         SpreadsheetDataManager, SpreadsheetDataset, SpreadsheetSearchQuery,
-        SpreadsheetSearchResult, UploadStatus,
+        SpreadsheetSearchResult, UploadStatus, ViolationAction,
     },
-    services::{HealthCheck, HealthStatus, Service, ServiceConfig, ServiceHealth},
+    services::{
+        csv_dialect::CsvDialectOverride,
+        dataset_series::{summarize_diff, DatasetDiff, SeriesError},
+        dataset_validation::{
+            split_rules_by_headers, validate_rule_set, QualityReport, QualityReportBuilder,
+            RuleSetError, UploadValidation,
+        },
+        sample_mapping::{
+            map_row, mark_barcode_conflicts, validate_profile, MappingError, MappingPreview,
+            RowPreview, RowStatus, SampleConversion,
+        },
+        spreadsheet_aggregate::{
+            build_table, output_columns, validate_query, AggregateError, TabularResult,
+            DEFAULT_RESULT_LIMIT, MAX_PIVOT_VALUES, MAX_RESULT_LIMIT,
+        },
+        spreadsheet_export::{export_records, ExportError, ExportOptions, SpreadsheetExport},
+        spreadsheet_ingest::{
+            check_workbook_size, excel_sheet_names, ingest_rows, open_row_stream, row_to_json,
+            CsvRowStream, IngestError, IngestOptions, IngestSource, IngestSummary, RowStream,
+            DEFAULT_BATCH_SIZE, DEFAULT_MAX_WORKBOOK_BYTES,
+        },
+        HealthCheck, HealthStatus, Service, ServiceConfig, ServiceHealth,
+    },
+    validation::dataset_rules::{build_row_chain, SheetRow},
 };
 
 #[derive(Debug, Clone)]
 pub struct SpreadsheetService {
     manager: SpreadsheetDataManager,
+    max_workbook_bytes: u64,
 }
 
 impl SpreadsheetService {
     pub fn new(manager: SpreadsheetDataManager) -> Self {
-        Self { manager }
+        Self {
+            manager,
+            max_workbook_bytes: DEFAULT_MAX_WORKBOOK_BYTES,
+        }
+    }
+
+    /// Size limit for xls and OpenDocument workbooks, which are decoded in
+    /// memory
+    pub fn with_max_workbook_bytes(mut self, max_workbook_bytes: u64) -> Self {
+        self.max_workbook_bytes = max_workbook_bytes;
+        self
     }
 
     /// Parse CSV data from bytes
@@ -29,28 +69,18 @@ impl SpreadsheetService {
         &self,
         data: &[u8],
     ) -> Result<ParsedSpreadsheetData, Box<dyn std::error::Error + Send + Sync>> {
-        let content = std::str::from_utf8(data)?;
-        let mut reader = csv::Reader::from_reader(content.as_bytes());
-
-        // Get headers
-        let headers = reader
-            .headers()?
-            .iter()
-            .map(|h| h.to_string())
-            .collect::<Vec<_>>();
+        let mut stream = CsvRowStream::new(
+            Cursor::new(data.to_vec()),
+            Some(data.len() as u64),
+            &CsvDialectOverride::default(),
+        )?;
+        let headers = stream.headers().to_vec();
         let total_columns = headers.len();
 
         // Parse rows
         let mut rows = Vec::new();
-        for result in reader.records() {
-            let record = result?;
-            let mut row_map = HashMap::new();
-
-            for (i, field) in record.iter().enumerate() {
-                if i < headers.len() {
-                    row_map.insert(headers[i].clone(), field.to_string());
-                }
-            }
+        while let Some(record) = stream.next_row() {
+            let row_map = headers.iter().cloned().zip(record?).collect();
             rows.push(row_map);
         }
 
@@ -70,6 +100,7 @@ impl SpreadsheetService {
         data: &[u8],
         sheet_name: Option<&str>,
     ) -> Result<ParsedSpreadsheetData, Box<dyn std::error::Error + Send + Sync>> {
+        check_workbook_size(data.len() as u64, self.max_workbook_bytes)?;
         let cursor = Cursor::new(data);
         let mut workbook = open_workbook_auto_from_rs(cursor)?;
 
@@ -158,7 +189,7 @@ impl SpreadsheetService {
             file_type: file_type.clone(),
             file_size: file_data.len() as i64,
             sheet_name: sheet_name.clone(),
-            column_headers: Vec::new(), // Will be updated after parsing
+            column_headers: Vec::new(), // Set when ingestion starts
             uploaded_by,
             metadata: Some(json!({
                 "processing_started_at": chrono::Utc::now(),
@@ -172,54 +203,180 @@ impl SpreadsheetService {
             .await
             .map_err(|e| format!("Failed to create dataset: {}", e))?;
 
-        // Parse the file data
-        let parsed_data = match file_type.to_lowercase().as_str() {
-            "csv" => self.parse_csv_data(&file_data),
-            "xlsx" | "xls" => self.parse_excel_data(&file_data, sheet_name.as_deref()),
-            _ => return Err(format!("Unsupported file type: {}", file_type).into()),
+        self.ingest_dataset(
+            initial_dataset.id,
+            IngestSource::Bytes(file_data.into()),
+            &file_type,
+            IngestOptions {
+                sheet_name,
+                ..Default::default()
+            },
+            row_to_json,
+        )
+        .await
+    }
+
+    /// Process an upload that has been spooled to disk. Rows are streamed from
+    /// the file, so memory use stays flat regardless of file size. With
+    /// `background` set, the dataset is returned as soon as it is queued and
+    /// progress can be followed on the dataset. The spooled file's name is
+    /// stored as the dataset filename, and the file is removed once ingestion
+    /// finishes either way.
+    pub async fn process_upload_file(
+        &self,
+        original_filename: String,
+        file_path: PathBuf,
+        file_type: String,
+        options: IngestOptions,
+        uploaded_by: Option<String>,
+        background: bool,
+    ) -> Result<SpreadsheetDataset, Box<dyn std::error::Error + Send + Sync>> {
+        let file_size = tokio::fs::metadata(&file_path).await?.len();
+        // xls and ods workbooks are decoded in memory, so refuse large ones up front
+        if matches!(file_type.as_str(), "xls" | "ods") {
+            let checked = check_workbook_size(file_size, self.max_workbook_bytes);
+            if checked.is_err() {
+                let _ = tokio::fs::remove_file(&file_path).await;
+            }
+            checked?;
+        }
+        let filename = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| original_filename.clone());
+
+        let create_dataset = CreateSpreadsheetDataset {
+            filename,
+            original_filename,
+            file_type: file_type.clone(),
+            file_size: file_size as i64,
+            sheet_name: options.sheet_name.clone(),
+            column_headers: Vec::new(), // Set when ingestion starts
+            uploaded_by,
+            metadata: Some(json!({
+                "processing_started_at": chrono::Utc::now(),
+                "file_size_bytes": file_size,
+                "background": background
+            })),
         };
 
-        match parsed_data {
-            Ok(data) => {
-                // Create records for each row
-                let mut records = Vec::new();
-                for (index, row) in data.rows.iter().enumerate() {
-                    records.push(CreateSpreadsheetRecord {
-                        dataset_id: initial_dataset.id,
-                        row_number: (index + 1) as i32, // 1-based indexing
-                        row_data: json!(row),
-                    });
-                }
+        let dataset = match self.manager.create_dataset(create_dataset).await {
+            Ok(dataset) => dataset,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&file_path).await;
+                return Err(format!("Failed to create dataset: {}", e).into());
+            }
+        };
 
-                // Bulk insert records
-                let _inserted_count = self
-                    .manager
-                    .bulk_create_records(records)
+        if !background {
+            let result = self
+                .ingest_dataset(
+                    dataset.id,
+                    IngestSource::File(file_path.clone()),
+                    &file_type,
+                    options,
+                    row_to_json,
+                )
+                .await;
+            remove_spooled_file(&file_path).await;
+            return result;
+        }
+
+        let queued = self
+            .manager
+            .update_dataset_status(dataset.id, UploadStatus::Queued, None, None, None)
+            .await
+            .map_err(|e| format!("Failed to queue dataset: {}", e))?;
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            match service
+                .ingest_dataset(
+                    dataset.id,
+                    IngestSource::File(file_path.clone()),
+                    &file_type,
+                    options,
+                    row_to_json,
+                )
+                .await
+            {
+                Ok(dataset) => tracing::info!(
+                    "Background ingestion of dataset {} finished with {} rows",
+                    dataset.id,
+                    dataset.total_rows
+                ),
+                Err(e) => tracing::error!(
+                    "Background ingestion of dataset {} failed: {}",
+                    dataset.id,
+                    e
+                ),
+            }
+            remove_spooled_file(&file_path).await;
+        });
+
+        Ok(queued)
+    }
+
+    /// Stream a source into an existing dataset and mark it completed, or
+    /// failed with any partially written rows removed. With validation rules
+    /// in the options, the rows are checked once written and a rejected
+    /// dataset fails the same way.
+    pub async fn ingest_dataset<F>(
+        &self,
+        dataset_id: Uuid,
+        source: IngestSource,
+        file_type: &str,
+        mut options: IngestOptions,
+        build_row: F,
+    ) -> Result<SpreadsheetDataset, Box<dyn std::error::Error + Send + Sync>>
+    where
+        F: Fn(&[String], Vec<String>) -> serde_json::Value + Send + 'static,
+    {
+        let validation = options.validation.take();
+        let result = match self
+            .run_ingest(dataset_id, source, file_type, options, build_row)
+            .await
+        {
+            Ok(summary) => match validation {
+                Some(validation) => self
+                    .check_upload(dataset_id, &validation)
+                    .await
+                    .map(|_| summary),
+                None => Ok(summary),
+            },
+            Err(e) => Err(format!("Failed to parse file: {}", e)),
+        };
+
+        match result {
+            Ok(summary) => {
+                self.manager
+                    .update_ingest_progress(dataset_id, summary.total_rows as i32, Some(100.0))
                     .await
-                    .map_err(|e| format!("Failed to insert records: {}", e))?;
+                    .map_err(|e| format!("Failed to update dataset progress: {}", e))?;
 
-                // Update dataset with final counts and status
                 let dataset = self
                     .manager
                     .update_dataset_status(
-                        initial_dataset.id,
+                        dataset_id,
                         UploadStatus::Completed,
                         None,
-                        Some(data.total_rows as i32),
-                        Some(data.total_columns as i32),
+                        Some(summary.total_rows as i32),
+                        Some(summary.total_columns as i32),
                     )
                     .await
                     .map_err(|e| format!("Failed to update dataset status: {}", e))?;
 
                 Ok(dataset)
             }
-            Err(e) => {
-                // Update dataset with error status
-                let error_message = format!("Failed to parse file: {}", e);
+            Err(error_message) => {
+                self.manager
+                    .delete_dataset_records(dataset_id)
+                    .await
+                    .map_err(|e| format!("Failed to remove partial records: {}", e))?;
                 let _dataset = self
                     .manager
                     .update_dataset_status(
-                        initial_dataset.id,
+                        dataset_id,
                         UploadStatus::Failed,
                         Some(error_message.clone()),
                         None,
@@ -233,6 +390,72 @@ impl SpreadsheetService {
         }
     }
 
+    /// Check a freshly ingested dataset, keeping the report with the dataset.
+    /// Errors with the rejection reason when the rules reject it.
+    async fn check_upload(
+        &self,
+        dataset_id: Uuid,
+        validation: &UploadValidation,
+    ) -> Result<QualityReport, String> {
+        let report = self
+            .check_dataset(dataset_id, &validation.rule_set, validation.action)
+            .await
+            .map_err(|e| format!("Failed to validate dataset: {}", e))?;
+        if report.accepted {
+            return Ok(report);
+        }
+        Err(format!(
+            "Rejected by rule set '{}': {} error(s) in {} row(s); see the dataset's quality report",
+            report.rule_set_name, report.error_count, report.rows_with_errors
+        ))
+    }
+
+    async fn run_ingest<F>(
+        &self,
+        dataset_id: Uuid,
+        source: IngestSource,
+        file_type: &str,
+        mut options: IngestOptions,
+        build_row: F,
+    ) -> Result<IngestSummary, IngestError>
+    where
+        F: Fn(&[String], Vec<String>) -> serde_json::Value + Send + 'static,
+    {
+        options
+            .max_workbook_bytes
+            .get_or_insert(self.max_workbook_bytes);
+        // Opening a workbook reads the sheet, so keep it off the async workers
+        let file_type = file_type.to_string();
+        let overrides = options.csv_dialect.clone();
+        let stream =
+            tokio::task::spawn_blocking(move || open_row_stream(source, &file_type, &options))
+                .await
+                .map_err(|e| IngestError::Worker(e.to_string()))??;
+
+        let total_rows = stream.total_rows().map(|rows| rows as i32);
+        self.manager
+            .start_ingest(dataset_id, stream.headers(), total_rows)
+            .await?;
+
+        // Keep the dialect with the dataset so a re-import can reproduce it
+        if let Some(dialect) = stream.csv_dialect() {
+            let mut dialect = json!(dialect);
+            dialect["overridden"] = json!(overrides.overridden_fields());
+            self.manager
+                .merge_dataset_metadata(dataset_id, json!({ "csv_dialect": dialect }))
+                .await?;
+        }
+
+        ingest_rows(
+            &self.manager,
+            dataset_id,
+            stream,
+            DEFAULT_BATCH_SIZE,
+            build_row,
+        )
+        .await
+    }
+
     pub async fn search_data(
         &self,
         query: SpreadsheetSearchQuery,
@@ -240,10 +463,717 @@ impl SpreadsheetService {
         self.manager.search_records(query).await
     }
 
+    pub async fn ranked_search(
+        &self,
+        query: RankedSearchQuery,
+    ) -> Result<RankedSearchResult, sqlx::Error> {
+        self.manager.ranked_search(query).await
+    }
+
     pub async fn get_dataset(&self, dataset_id: Uuid) -> Result<SpreadsheetDataset, sqlx::Error> {
         self.manager.get_dataset(dataset_id).await
     }
 
+    /// Export every record matching a search, ignoring its limit and offset
+    pub async fn export_search(
+        &self,
+        query: SpreadsheetSearchQuery,
+        options: ExportOptions,
+        spool_dir: &Path,
+    ) -> Result<SpreadsheetExport, ExportError> {
+        let file_stem = format!(
+            "spreadsheet_export_{}",
+            chrono::Utc::now().format("%Y%m%d_%H%M%S")
+        );
+        export_records(&self.manager, query, options, spool_dir, &file_stem).await
+    }
+
+    /// Export a whole dataset, named after its original file
+    pub async fn export_dataset(
+        &self,
+        dataset_id: Uuid,
+        options: ExportOptions,
+        spool_dir: &Path,
+    ) -> Result<SpreadsheetExport, ExportError> {
+        let dataset = match self.manager.get_dataset(dataset_id).await {
+            Ok(dataset) => dataset,
+            Err(sqlx::Error::RowNotFound) => return Err(ExportError::DatasetNotFound),
+            Err(e) => return Err(e.into()),
+        };
+        let file_stem = Path::new(&dataset.original_filename)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("dataset")
+            .to_string();
+
+        let query = SpreadsheetSearchQuery {
+            search_term: None,
+            dataset_id: Some(dataset_id),
+            column_filters: None,
+            pool_filter: None,
+            sample_filter: None,
+            project_filter: None,
+            range_filters: None,
+            in_filters: None,
+            limit: None,
+            offset: None,
+        };
+        export_records(&self.manager, query, options, spool_dir, &file_stem).await
+    }
+
+    /// Group, aggregate, pivot or join spreadsheet records into a table
+    pub async fn aggregate(
+        &self,
+        mut query: AggregateQuery,
+    ) -> Result<TabularResult, AggregateError> {
+        let started = Instant::now();
+
+        if query.pivot.is_some() && query.aggregates.is_empty() {
+            query.aggregates.push(AggregateSpec {
+                function: AggregateFunction::Count,
+                column: None,
+                alias: None,
+            });
+        }
+        if !query.is_grouped() && query.columns.is_empty() {
+            query.columns = self.default_aggregate_columns(&query).await?;
+        }
+        validate_query(&query)?;
+
+        if let Some(join) = &query.join {
+            match self.manager.get_dataset(join.dataset_id).await {
+                Ok(_) => {}
+                Err(sqlx::Error::RowNotFound) => return Err(AggregateError::DatasetNotFound),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let pivot_values = match &query.pivot {
+            Some(pivot) => match &pivot.values {
+                Some(values) => values.iter().cloned().map(Some).collect(),
+                None => {
+                    let values = self
+                        .manager
+                        .aggregate_pivot_values(&query, &pivot.column, MAX_PIVOT_VALUES as i64 + 1)
+                        .await?;
+                    if values.len() > MAX_PIVOT_VALUES {
+                        return Err(AggregateError::TooManyPivotValues {
+                            max: MAX_PIVOT_VALUES,
+                        });
+                    }
+                    values
+                }
+            },
+            None => Vec::new(),
+        };
+        let columns = output_columns(&query, &pivot_values)?;
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_RESULT_LIMIT)
+            .clamp(1, MAX_RESULT_LIMIT);
+        let rows = self
+            .manager
+            .aggregate_records(&query, &pivot_values, limit + 1)
+            .await?;
+
+        Ok(build_table(
+            columns,
+            rows,
+            limit as usize,
+            started.elapsed().as_millis() as u64,
+        ))
+    }
+
+    /// Every column of the queried datasets, then of the joined dataset
+    async fn default_aggregate_columns(
+        &self,
+        query: &AggregateQuery,
+    ) -> Result<Vec<ColumnRef>, AggregateError> {
+        let mut dataset_ids: Vec<(Uuid, DatasetSide)> = query
+            .dataset_ids
+            .iter()
+            .chain(&query.filter.dataset_id)
+            .map(|id| (*id, DatasetSide::Left))
+            .collect();
+        if dataset_ids.is_empty() {
+            return Err(AggregateError::ValidationError(
+                "Select columns when reading every dataset".to_string(),
+            ));
+        }
+        if let Some(join) = &query.join {
+            dataset_ids.push((join.dataset_id, DatasetSide::Right));
+        }
+
+        let mut columns: Vec<ColumnRef> = Vec::new();
+        for (dataset_id, side) in dataset_ids {
+            let dataset = match self.manager.get_dataset(dataset_id).await {
+                Ok(dataset) => dataset,
+                Err(sqlx::Error::RowNotFound) => return Err(AggregateError::DatasetNotFound),
+                Err(e) => return Err(e.into()),
+            };
+            for column in dataset.column_headers {
+                let column = ColumnRef { column, side };
+                if !columns.contains(&column) {
+                    columns.push(column);
+                }
+            }
+        }
+        Ok(columns)
+    }
+
+    pub async fn create_series(
+        &self,
+        series: CreateDatasetSeries,
+    ) -> Result<DatasetSeries, SeriesError> {
+        if series.name.trim().is_empty() {
+            return Err(SeriesError::ValidationError(
+                "Series name is required".to_string(),
+            ));
+        }
+        if series.identity_column.trim().is_empty() {
+            return Err(SeriesError::ValidationError(
+                "Identity column is required".to_string(),
+            ));
+        }
+
+        let name = series.name.clone();
+        self.manager.create_series(series).await.map_err(|e| {
+            match e.as_database_error().and_then(|db| db.constraint()) {
+                Some("spreadsheet_dataset_series_name_unique") => {
+                    SeriesError::DuplicateSeriesName(name)
+                }
+                _ => SeriesError::DatabaseError(e),
+            }
+        })
+    }
+
+    pub async fn list_series(&self) -> Result<Vec<DatasetSeries>, sqlx::Error> {
+        self.manager.list_series().await
+    }
+
+    pub async fn get_series_with_versions(
+        &self,
+        series_id: Uuid,
+    ) -> Result<DatasetSeriesWithVersions, SeriesError> {
+        let series = self.series(series_id).await?;
+        let versions = self.manager.list_series_versions(series_id).await?;
+        Ok(DatasetSeriesWithVersions { series, versions })
+    }
+
+    /// Append an uploaded dataset to a series as its next version
+    pub async fn add_dataset_to_series(
+        &self,
+        series_id: Uuid,
+        dataset_id: Uuid,
+    ) -> Result<SeriesVersion, SeriesError> {
+        let series = self.series(series_id).await?;
+        let dataset = match self.manager.get_dataset(dataset_id).await {
+            Ok(dataset) => dataset,
+            Err(sqlx::Error::RowNotFound) => return Err(SeriesError::DatasetNotFound),
+            Err(e) => return Err(e.into()),
+        };
+
+        // Headers are only known once ingestion has started
+        if !dataset.column_headers.is_empty()
+            && !dataset.column_headers.contains(&series.identity_column)
+        {
+            return Err(SeriesError::MissingIdentityColumn(series.identity_column));
+        }
+
+        self.manager
+            .add_series_version(series_id, dataset_id)
+            .await
+            .map_err(
+                |e| match e.as_database_error().and_then(|db| db.constraint()) {
+                    Some("spreadsheet_series_versions_dataset_unique") => {
+                        SeriesError::DatasetAlreadyInSeries
+                    }
+                    _ => SeriesError::DatabaseError(e),
+                },
+            )
+    }
+
+    /// The version to read: `version` if given, otherwise the latest version
+    /// added at or before `as_of`, otherwise the latest version
+    pub async fn resolve_version(
+        &self,
+        series_id: Uuid,
+        version: Option<i32>,
+        as_of: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<SeriesVersion, SeriesError> {
+        self.series(series_id).await?;
+        self.manager
+            .find_series_version(series_id, version, as_of)
+            .await?
+            .ok_or(SeriesError::VersionNotFound)
+    }
+
+    /// Added, removed and changed rows between two versions, matched on the
+    /// series identity column
+    pub async fn diff_versions(
+        &self,
+        series_id: Uuid,
+        from_version: i32,
+        to_version: i32,
+        limit: usize,
+    ) -> Result<DatasetDiff, SeriesError> {
+        let series = self.series(series_id).await?;
+        let from = self.diffable_version(&series, from_version).await?;
+        let to = self.diffable_version(&series, to_version).await?;
+
+        let pairs = self
+            .manager
+            .differing_rows(from.dataset_id, to.dataset_id, &series.identity_column)
+            .await?;
+        let counts = self
+            .manager
+            .version_match_counts(from.dataset_id, to.dataset_id, &series.identity_column)
+            .await?;
+        let (summary, rows, truncated) = summarize_diff(pairs, &counts, limit);
+
+        Ok(DatasetDiff {
+            series_id,
+            identity_column: series.identity_column,
+            from_version,
+            to_version,
+            summary,
+            rows,
+            truncated,
+        })
+    }
+
+    async fn series(&self, series_id: Uuid) -> Result<DatasetSeries, SeriesError> {
+        match self.manager.get_series(series_id).await {
+            Ok(series) => Ok(series),
+            Err(sqlx::Error::RowNotFound) => Err(SeriesError::SeriesNotFound),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// A completed version whose identity column is unique, so rows can be
+    /// matched one to one
+    async fn diffable_version(
+        &self,
+        series: &DatasetSeries,
+        version: i32,
+    ) -> Result<SeriesVersion, SeriesError> {
+        let found = self
+            .manager
+            .find_series_version(series.id, Some(version), None)
+            .await?
+            .ok_or(SeriesError::VersionNotFound)?;
+        if found.upload_status != UploadStatus::Completed {
+            return Err(SeriesError::VersionNotReady(version));
+        }
+
+        let keys = self
+            .manager
+            .duplicate_identities(found.dataset_id, &series.identity_column)
+            .await?;
+        if !keys.is_empty() {
+            return Err(SeriesError::DuplicateIdentities { version, keys });
+        }
+        Ok(found)
+    }
+
+    pub async fn create_rule_set(
+        &self,
+        rule_set: CreateDatasetRuleSet,
+    ) -> Result<DatasetRuleSet, RuleSetError> {
+        if rule_set.name.trim().is_empty() || rule_set.dataset_type.trim().is_empty() {
+            return Err(RuleSetError::InvalidRuleSet(
+                "Name and dataset type are required".to_string(),
+            ));
+        }
+        validate_rule_set(&rule_set.rules).map_err(RuleSetError::InvalidRuleSet)?;
+
+        let dataset_type = rule_set.dataset_type.clone();
+        self.manager.create_rule_set(rule_set).await.map_err(|e| {
+            match e.as_database_error().and_then(|db| db.constraint()) {
+                Some("spreadsheet_rule_sets_dataset_type_unique") => {
+                    RuleSetError::DuplicateDatasetType(dataset_type)
+                }
+                _ => RuleSetError::DatabaseError(e),
+            }
+        })
+    }
+
+    pub async fn list_rule_sets(&self) -> Result<Vec<DatasetRuleSet>, sqlx::Error> {
+        self.manager.list_rule_sets().await
+    }
+
+    pub async fn get_rule_set(&self, rule_set_id: Uuid) -> Result<DatasetRuleSet, RuleSetError> {
+        match self.manager.get_rule_set(rule_set_id).await {
+            Ok(rule_set) => Ok(rule_set),
+            Err(sqlx::Error::RowNotFound) => Err(RuleSetError::RuleSetNotFound),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn delete_rule_set(&self, rule_set_id: Uuid) -> Result<u64, sqlx::Error> {
+        self.manager.delete_rule_set(rule_set_id).await
+    }
+
+    /// Rules for an upload of the given dataset type, applying the rule
+    /// set's own action unless one is given
+    pub async fn upload_validation(
+        &self,
+        dataset_type: &str,
+        action: Option<ViolationAction>,
+    ) -> Result<UploadValidation, RuleSetError> {
+        let rule_set = self
+            .manager
+            .find_rule_set_for_type(dataset_type)
+            .await?
+            .ok_or_else(|| RuleSetError::UnknownDatasetType(dataset_type.to_string()))?;
+        Ok(UploadValidation {
+            action: action.unwrap_or(rule_set.on_violation),
+            rule_set,
+        })
+    }
+
+    /// Check an existing dataset against a rule set and keep the report with
+    /// the dataset. Nothing is removed, whatever the rule set's action.
+    pub async fn validate_dataset(
+        &self,
+        dataset_id: Uuid,
+        rule_set_id: Uuid,
+    ) -> Result<QualityReport, RuleSetError> {
+        let rule_set = self.get_rule_set(rule_set_id).await?;
+        self.check_dataset(dataset_id, &rule_set, rule_set.on_violation)
+            .await
+    }
+
+    /// The last quality report stored with a dataset
+    pub async fn get_quality_report(
+        &self,
+        dataset_id: Uuid,
+    ) -> Result<Option<QualityReport>, RuleSetError> {
+        let dataset = match self.manager.get_dataset(dataset_id).await {
+            Ok(dataset) => dataset,
+            Err(sqlx::Error::RowNotFound) => return Err(RuleSetError::DatasetNotFound),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(dataset
+            .metadata
+            .get("quality_report")
+            .and_then(|report| serde_json::from_value(report.clone()).ok()))
+    }
+
+    async fn check_dataset(
+        &self,
+        dataset_id: Uuid,
+        rule_set: &DatasetRuleSet,
+        action: ViolationAction,
+    ) -> Result<QualityReport, RuleSetError> {
+        let dataset = match self.manager.get_dataset(dataset_id).await {
+            Ok(dataset) => dataset,
+            Err(sqlx::Error::RowNotFound) => return Err(RuleSetError::DatasetNotFound),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut builder = QualityReportBuilder::new(rule_set, action);
+        let (rules, missing) = split_rules_by_headers(&rule_set.rules, &dataset.column_headers);
+        for violation in missing {
+            builder.add_violation(violation);
+        }
+        let chain = build_row_chain(&rules).map_err(RuleSetError::InvalidRuleSet)?;
+
+        let query = SpreadsheetSearchQuery {
+            dataset_id: Some(dataset_id),
+            ..Default::default()
+        };
+        let mut after = None;
+        loop {
+            let records = self
+                .manager
+                .search_records_after(&query, after, DEFAULT_BATCH_SIZE as i64)
+                .await?;
+            for record in &records {
+                let row = SheetRow {
+                    row_number: record.row_number,
+                    cells: record.row_data.clone(),
+                };
+                builder.add_row(record.row_number, &record.row_data, chain.validate(&row));
+            }
+            match records.last() {
+                Some(last) if records.len() == DEFAULT_BATCH_SIZE => {
+                    after = Some((last.dataset_id, last.row_number));
+                }
+                _ => break,
+            }
+        }
+
+        let report = builder.finish();
+        self.manager
+            .merge_dataset_metadata(
+                dataset_id,
+                json!({
+                    "dataset_type": rule_set.dataset_type,
+                    "quality_report": report,
+                }),
+            )
+            .await?;
+        Ok(report)
+    }
+
+    pub async fn create_mapping_profile(
+        &self,
+        profile: CreateSampleMappingProfile,
+    ) -> Result<SampleMappingProfile, MappingError> {
+        if profile.name.trim().is_empty() {
+            return Err(MappingError::InvalidProfile(
+                "Profile name is required".to_string(),
+            ));
+        }
+        validate_profile(&profile.mappings).map_err(MappingError::InvalidProfile)?;
+
+        let name = profile.name.clone();
+        self.manager
+            .create_mapping_profile(profile)
+            .await
+            .map_err(
+                |e| match e.as_database_error().and_then(|db| db.constraint()) {
+                    Some("spreadsheet_mapping_profiles_name_unique") => {
+                        MappingError::DuplicateProfileName(name)
+                    }
+                    _ => MappingError::DatabaseError(e),
+                },
+            )
+    }
+
+    pub async fn list_mapping_profiles(&self) -> Result<Vec<SampleMappingProfile>, sqlx::Error> {
+        self.manager.list_mapping_profiles().await
+    }
+
+    pub async fn get_mapping_profile(
+        &self,
+        profile_id: Uuid,
+    ) -> Result<SampleMappingProfile, MappingError> {
+        match self.manager.get_mapping_profile(profile_id).await {
+            Ok(profile) => Ok(profile),
+            Err(sqlx::Error::RowNotFound) => Err(MappingError::ProfileNotFound),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn delete_mapping_profile(&self, profile_id: Uuid) -> Result<u64, sqlx::Error> {
+        self.manager.delete_mapping_profile(profile_id).await
+    }
+
+    /// Show what converting a dataset with a profile would create, without
+    /// creating anything
+    pub async fn preview_sample_mapping(
+        &self,
+        dataset_id: Uuid,
+        profile_id: Uuid,
+        limit: usize,
+        only_invalid: bool,
+    ) -> Result<MappingPreview, MappingError> {
+        let profile = self.get_mapping_profile(profile_id).await?;
+        let rows = self.map_dataset_rows(dataset_id, &profile).await?;
+
+        let count = |status| rows.iter().filter(|row| row.status == status).count();
+        let (valid_rows, invalid_rows, converted_rows) = (
+            count(RowStatus::Valid),
+            count(RowStatus::Invalid),
+            count(RowStatus::Converted),
+        );
+        let total_rows = rows.len();
+        let shown: Vec<RowPreview> = rows
+            .into_iter()
+            .filter(|row| !only_invalid || row.status == RowStatus::Invalid)
+            .collect();
+        let truncated = shown.len() > limit;
+
+        Ok(MappingPreview {
+            dataset_id,
+            profile_id,
+            total_rows,
+            valid_rows,
+            invalid_rows,
+            converted_rows,
+            rows: shown.into_iter().take(limit).collect(),
+            truncated,
+        })
+    }
+
+    /// Create a sample for every dataset row not yet converted, linking
+    /// each back to its row. Nothing is created if any row is invalid
+    /// unless `skip_invalid` is set.
+    pub async fn convert_dataset_to_samples(
+        &self,
+        dataset_id: Uuid,
+        profile_id: Uuid,
+        skip_invalid: bool,
+    ) -> Result<SampleConversion, MappingError> {
+        let profile = self.get_mapping_profile(profile_id).await?;
+        let rows = self.map_dataset_rows(dataset_id, &profile).await?;
+
+        let mut samples = Vec::new();
+        let (mut skipped_invalid, mut already_converted) = (0, 0);
+        for row in rows {
+            match (row.status, row.sample) {
+                (RowStatus::Valid, Some(sample)) => {
+                    let mut metadata = match sample.metadata {
+                        Some(serde_json::Value::Object(metadata)) => metadata,
+                        _ => serde_json::Map::new(),
+                    };
+                    metadata.insert(
+                        "spreadsheet_source".to_string(),
+                        json!({
+                            "dataset_id": dataset_id,
+                            "record_id": row.record_id,
+                            "row_number": row.row_number,
+                            "profile_id": profile.id,
+                            "profile_name": profile.name,
+                        }),
+                    );
+                    samples.push(RecordSample {
+                        record_id: row.record_id,
+                        name: sample.name,
+                        barcode: sample.barcode,
+                        location: sample.location,
+                        metadata: serde_json::Value::Object(metadata),
+                    });
+                }
+                (RowStatus::Converted, _) => already_converted += 1,
+                _ => skipped_invalid += 1,
+            }
+        }
+
+        if skipped_invalid > 0 && !skip_invalid {
+            return Err(MappingError::InvalidRows {
+                count: skipped_invalid,
+            });
+        }
+
+        // A concurrent conversion or sample insert can still claim a row
+        // or barcode after the checks above
+        let created = self
+            .manager
+            .create_record_samples(dataset_id, profile_id, &samples)
+            .await
+            .map_err(
+                |e| match e.as_database_error().and_then(|db| db.constraint()) {
+                    Some("samples_barcode_key") | Some("spreadsheet_record_samples_pkey") => {
+                        MappingError::Conflict
+                    }
+                    _ => MappingError::DatabaseError(e),
+                },
+            )?;
+
+        Ok(SampleConversion {
+            dataset_id,
+            profile_id,
+            created,
+            skipped_invalid,
+            already_converted,
+        })
+    }
+
+    pub async fn list_dataset_samples(
+        &self,
+        dataset_id: Uuid,
+    ) -> Result<Vec<LinkedSample>, sqlx::Error> {
+        self.manager.list_linked_samples(dataset_id).await
+    }
+
+    /// Map every row of a dataset through a profile and check barcodes
+    /// against each other and existing samples
+    async fn map_dataset_rows(
+        &self,
+        dataset_id: Uuid,
+        profile: &SampleMappingProfile,
+    ) -> Result<Vec<RowPreview>, MappingError> {
+        let dataset = match self.manager.get_dataset(dataset_id).await {
+            Ok(dataset) => dataset,
+            Err(sqlx::Error::RowNotFound) => return Err(MappingError::DatasetNotFound),
+            Err(e) => return Err(e.into()),
+        };
+        if !dataset.column_headers.is_empty() {
+            let missing: Vec<String> = profile
+                .mappings
+                .iter()
+                .filter(|mapping| !dataset.column_headers.contains(&mapping.column))
+                .map(|mapping| mapping.column.clone())
+                .collect();
+            if !missing.is_empty() {
+                return Err(MappingError::MissingColumns(missing));
+            }
+        }
+
+        let converted: HashSet<Uuid> = self
+            .manager
+            .linked_record_ids(dataset_id)
+            .await?
+            .into_iter()
+            .collect();
+        let query = SpreadsheetSearchQuery {
+            search_term: None,
+            dataset_id: Some(dataset_id),
+            column_filters: None,
+            pool_filter: None,
+            sample_filter: None,
+            project_filter: None,
+            range_filters: None,
+            in_filters: None,
+            limit: None,
+            offset: None,
+        };
+
+        let mut rows = Vec::new();
+        let mut after = None;
+        loop {
+            let records = self
+                .manager
+                .search_records_after(&query, after, DEFAULT_BATCH_SIZE as i64)
+                .await?;
+            for record in &records {
+                let mut row = RowPreview {
+                    record_id: record.id,
+                    row_number: record.row_number,
+                    status: RowStatus::Converted,
+                    sample: None,
+                    errors: Vec::new(),
+                };
+                if !converted.contains(&record.id) {
+                    match map_row(&record.row_data, &profile.mappings) {
+                        Ok(sample) => {
+                            row.status = RowStatus::Valid;
+                            row.sample = Some(sample);
+                        }
+                        Err(errors) => {
+                            row.status = RowStatus::Invalid;
+                            row.errors = errors;
+                        }
+                    }
+                }
+                rows.push(row);
+            }
+
+            match records.last() {
+                Some(last) if records.len() == DEFAULT_BATCH_SIZE => {
+                    after = Some((last.dataset_id, last.row_number));
+                }
+                _ => break,
+            }
+        }
+
+        let barcodes: Vec<String> = rows
+            .iter()
+            .filter_map(|row| row.sample.as_ref().map(|sample| sample.barcode.clone()))
+            .collect();
+        let mut taken = HashSet::new();
+        for chunk in barcodes.chunks(DEFAULT_BATCH_SIZE) {
+            taken.extend(self.manager.existing_sample_barcodes(chunk).await?);
+        }
+        mark_barcode_conflicts(&mut rows, &taken);
+
+        Ok(rows)
+    }
+
     pub async fn list_datasets(
         &self,
         limit: Option<i64>,
@@ -325,76 +1255,31 @@ impl SpreadsheetService {
             .await
             .map_err(|e| format!("Failed to create dataset: {}", e))?;
 
-        // Parse the file data
-        let parsed_data = match file_type.to_lowercase().as_str() {
-            "csv" => self.parse_csv_data(&file_data),
-            "xlsx" | "xls" => self.parse_excel_data(&file_data, sheet_name.as_deref()),
-            _ => return Err(format!("Unsupported file type: {}", file_type).into()),
+        // Enhanced search text to include pool/sample/project data
+        let service = self.clone();
+        let build_row = move |headers: &[String], fields: Vec<String>| {
+            let mut row: HashMap<String, String> = headers.iter().cloned().zip(fields).collect();
+            let enhanced_search_text = service.generate_enhanced_search_text(
+                &row,
+                pool_column.as_deref(),
+                sample_column.as_deref(),
+                project_column.as_deref(),
+            );
+            row.insert("_search_enhanced".to_string(), enhanced_search_text);
+            json!(row)
         };
 
-        match parsed_data {
-            Ok(data) => {
-                // Create records for each row with enhanced search text
-                let mut records = Vec::new();
-                for (index, row) in data.rows.iter().enumerate() {
-                    // Enhanced search text to include pool/sample/project data
-                    let enhanced_search_text = self.generate_enhanced_search_text(
-                        row,
-                        pool_column.as_deref(),
-                        sample_column.as_deref(),
-                        project_column.as_deref(),
-                    );
-
-                    let mut enhanced_row_data = row.clone();
-                    enhanced_row_data.insert("_search_enhanced".to_string(), enhanced_search_text);
-
-                    records.push(CreateSpreadsheetRecord {
-                        dataset_id: initial_dataset.id,
-                        row_number: (index + 1) as i32, // 1-based indexing
-                        row_data: json!(enhanced_row_data),
-                    });
-                }
-
-                // Bulk insert records
-                let _inserted_count = self
-                    .manager
-                    .bulk_create_records(records)
-                    .await
-                    .map_err(|e| format!("Failed to insert records: {}", e))?;
-
-                // Update dataset with final counts and status
-                let dataset = self
-                    .manager
-                    .update_dataset_status(
-                        initial_dataset.id,
-                        UploadStatus::Completed,
-                        None,
-                        Some(data.total_rows as i32),
-                        Some(data.total_columns as i32),
-                    )
-                    .await
-                    .map_err(|e| format!("Failed to update dataset status: {}", e))?;
-
-                Ok(dataset)
-            }
-            Err(e) => {
-                // Update dataset with error status
-                let error_message = format!("Failed to parse file: {}", e);
-                let _dataset = self
-                    .manager
-                    .update_dataset_status(
-                        initial_dataset.id,
-                        UploadStatus::Failed,
-                        Some(error_message.clone()),
-                        None,
-                        None,
-                    )
-                    .await
-                    .map_err(|e| format!("Failed to update dataset error status: {}", e))?;
-
-                Err(error_message.into())
-            }
-        }
+        self.ingest_dataset(
+            initial_dataset.id,
+            IngestSource::Bytes(file_data.into()),
+            &file_type,
+            IngestOptions {
+                sheet_name,
+                ..Default::default()
+            },
+            build_row,
+        )
+        .await
     }
 
     fn generate_enhanced_search_text(
@@ -437,7 +1322,7 @@ impl SpreadsheetService {
 
     /// Get supported file types
     pub fn supported_file_types(&self) -> Vec<&'static str> {
-        vec!["csv", "xlsx", "xls"]
+        vec!["csv", "tsv", "xlsx", "xls", "ods", "jsonl"]
     }
 
     /// Validate file type
@@ -455,8 +1340,11 @@ impl SpreadsheetService {
 
         match extension.as_deref() {
             Some("csv") => Some("csv".to_string()),
+            Some("tsv" | "tab") => Some("tsv".to_string()),
             Some("xlsx") => Some("xlsx".to_string()),
             Some("xls") => Some("xls".to_string()),
+            Some("ods") => Some("ods".to_string()),
+            Some("jsonl" | "ndjson") => Some("jsonl".to_string()),
             _ => None,
         }
     }
@@ -465,10 +1353,13 @@ impl SpreadsheetService {
     pub fn get_excel_sheet_names(
         &self,
         data: &[u8],
+        file_type: &str,
     ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
-        let cursor = Cursor::new(data);
-        let workbook = open_workbook_auto_from_rs(cursor)?;
-        Ok(workbook.sheet_names().to_vec())
+        Ok(excel_sheet_names(
+            IngestSource::Bytes(data.into()),
+            file_type,
+            self.max_workbook_bytes,
+        )?)
     }
 
     /// Parse all sheets from Excel data
@@ -477,6 +1368,7 @@ impl SpreadsheetService {
         data: &[u8],
     ) -> Result<Vec<(String, ParsedSpreadsheetData)>, Box<dyn std::error::Error + Send + Sync>>
     {
+        check_workbook_size(data.len() as u64, self.max_workbook_bytes)?;
         let cursor = Cursor::new(data);
         let mut workbook = open_workbook_auto_from_rs(cursor)?;
         let sheet_names = workbook.sheet_names().to_vec();
@@ -557,8 +1449,8 @@ impl SpreadsheetService {
         uploaded_by: Option<String>,
     ) -> Result<Vec<SpreadsheetDataset>, Box<dyn std::error::Error + Send + Sync>> {
         match file_type.to_lowercase().as_str() {
-            "csv" => {
-                // For CSV, process as single sheet
+            "csv" | "tsv" | "jsonl" => {
+                // Text formats hold a single sheet
                 let dataset = self
                     .process_upload(
                         filename,
@@ -571,15 +1463,21 @@ impl SpreadsheetService {
                     .await?;
                 Ok(vec![dataset])
             }
-            "xlsx" | "xls" => {
-                let all_sheets = self.parse_all_excel_sheets(&file_data)?;
+            "xlsx" | "xls" | "ods" => {
+                let file_size = file_data.len();
+                let file_data: Arc<[u8]> = file_data.into();
+                let all_sheets = excel_sheet_names(
+                    IngestSource::Bytes(file_data.clone()),
+                    &file_type,
+                    self.max_workbook_bytes,
+                )?;
                 let mut datasets = Vec::new();
 
                 // Filter sheets based on selection
                 let sheets_to_process: Vec<_> = if let Some(selected) = selected_sheets {
                     all_sheets
                         .into_iter()
-                        .filter(|(name, _)| selected.contains(name))
+                        .filter(|name| selected.contains(name))
                         .collect()
                 } else {
                     all_sheets
@@ -587,7 +1485,7 @@ impl SpreadsheetService {
 
                 let process_multiple = sheets_to_process.len() > 1;
 
-                for (sheet_name, parsed_data) in sheets_to_process {
+                for sheet_name in sheets_to_process {
                     // Create dataset name with sheet suffix if multiple sheets
                     let dataset_filename = if process_multiple {
                         format!("{}_{}", filename, sheet_name.replace(" ", "_"))
@@ -599,16 +1497,14 @@ impl SpreadsheetService {
                         filename: dataset_filename,
                         original_filename: format!("{}_{}", original_filename, sheet_name),
                         file_type: file_type.clone(),
-                        file_size: file_data.len() as i64,
+                        file_size: file_size as i64,
                         sheet_name: Some(sheet_name.clone()),
-                        column_headers: parsed_data.headers.clone(),
+                        column_headers: Vec::new(), // Set when ingestion starts
                         uploaded_by: uploaded_by.clone(),
                         metadata: Some(json!({
                             "processing_started_at": chrono::Utc::now(),
-                            "file_size_bytes": file_data.len(),
+                            "file_size_bytes": file_size,
                             "sheet_name": sheet_name,
-                            "total_rows": parsed_data.total_rows,
-                            "total_columns": parsed_data.total_columns,
                         })),
                     };
 
@@ -623,45 +1519,19 @@ impl SpreadsheetService {
                                 )
                             })?;
 
-                    // Store the parsed records
-                    let mut records_to_insert = Vec::new();
-                    for (row_index, row_data) in parsed_data.rows.iter().enumerate() {
-                        let record = CreateSpreadsheetRecord {
-                            dataset_id: dataset.id,
-                            row_number: (row_index + 1) as i32, // 1-based indexing
-                            row_data: json!(row_data),
-                        };
-                        records_to_insert.push(record);
-                    }
-
-                    if !records_to_insert.is_empty() {
-                        self.manager
-                            .bulk_create_records(records_to_insert)
-                            .await
-                            .map_err(|e| {
-                                format!(
-                                    "Failed to insert records for sheet '{}': {}",
-                                    sheet_name, e
-                                )
-                            })?;
-                    }
-
-                    // Update dataset status
-                    self.manager
-                        .update_dataset_status(
+                    let dataset = self
+                        .ingest_dataset(
                             dataset.id,
-                            crate::models::spreadsheet::UploadStatus::Completed,
-                            None,
-                            Some(parsed_data.total_rows as i32),
-                            Some(parsed_data.total_columns as i32),
+                            IngestSource::Bytes(file_data.clone()),
+                            &file_type,
+                            IngestOptions {
+                                sheet_name: Some(sheet_name.clone()),
+                                ..Default::default()
+                            },
+                            row_to_json,
                         )
                         .await
-                        .map_err(|e| {
-                            format!(
-                                "Failed to update dataset status for sheet '{}': {}",
-                                sheet_name, e
-                            )
-                        })?;
+                        .map_err(|e| format!("Failed to process sheet '{}': {}", sheet_name, e))?;
 
                     datasets.push(dataset);
                 }
@@ -673,6 +1543,12 @@ impl SpreadsheetService {
     }
 }
 
+async fn remove_spooled_file(path: &Path) {
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        tracing::warn!("Failed to remove spooled upload {}: {}", path.display(), e);
+    }
+}
+
 #[async_trait]
 impl Service for SpreadsheetService {
     fn name(&self) -> &'static str {
@@ -738,7 +1614,10 @@ impl Service for SpreadsheetService {
             version: "1.0.0".to_string(),
             dependencies: vec!["database".to_string()],
             settings: HashMap::from([
-                ("supported_formats".to_string(), "csv,xlsx,xls".to_string()),
+                (
+                    "supported_formats".to_string(),
+                    "csv,tsv,xlsx,xls,ods,jsonl".to_string(),
+                ),
                 ("max_file_size".to_string(), "100MB".to_string()),
                 ("max_records_per_file".to_string(), "1000000".to_string()),
             ]),
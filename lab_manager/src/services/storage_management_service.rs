@@ -3,14 +3,21 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use crate::models::storage::{
-    SampleLocation, StorageCapacityStats, StorageLocation, StorageRequirement, StorageState,
-    StorageValidationError, TemperatureZone,
+    SampleCheckout, SampleLocation, StorageCapacityStats, StorageLocation, StorageRequirement,
+    StorageState, StorageValidationError, TemperatureZone, TimeOutOfStorage,
 };
 use crate::repositories::storage_repository::{
-    CreateMovementHistory, CreateSampleLocation, CreateStorageLocation, StorageRepository,
+    CreateMovementHistory, CreateSampleCheckout, CreateSampleLocation, CreateStorageLocation,
+    LocationUsageReconciliation, RelocationError, SampleRelocation, StorageRepository,
     UpdateStorageLocation,
 };
 use crate::services::barcode_service::BarcodeService;
+use crate::services::evacuation_planner::{
+    is_compatible_target, plan_evacuation, EvacuationPlan, EvacuationTarget,
+};
+use crate::services::storage_reconciliation::{
+    correction_conflicts, reconcile_box_scan, BoxScanEntry, BoxScanReconciliation,
+};
 
 /// Storage management service for biological sample storage operations
 #[derive(Debug)]
@@ -88,7 +95,7 @@ impl<R: StorageRepository> StorageManagementService<R> {
             .storage_repo
             .store_sample(sample_location)
             .await
-            .map_err(StorageManagementError::DatabaseError)?;
+            .map_err(|e| capacity_error(e, location_id))?;
 
         // Record movement history
         let movement = CreateMovementHistory {
@@ -147,12 +154,35 @@ impl<R: StorageRepository> StorageManagementService<R> {
             });
         }
 
+        // Shipped samples are moved back into storage by receiving the shipment
+        if let Some(current) = self
+            .storage_repo
+            .get_sample_location(sample_id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .filter(|current| matches!(current.storage_state, StorageState::InTransit))
+        {
+            return Err(StorageManagementError::SampleNotInStorage(current.barcode));
+        }
+
+        // A checked out sample keeps its position until it is checked in
+        if let Some(checkout) = self
+            .storage_repo
+            .get_open_checkout(sample_id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+        {
+            return Err(StorageManagementError::SampleAlreadyCheckedOut(
+                checkout.barcode,
+            ));
+        }
+
         // Move sample
         let moved_sample = self
             .storage_repo
             .move_sample(sample_id, new_location_id, moved_by, reason)
             .await
-            .map_err(StorageManagementError::DatabaseError)?;
+            .map_err(|e| capacity_error(e, new_location_id))?;
 
         Ok(moved_sample)
     }
@@ -210,12 +240,30 @@ impl<R: StorageRepository> StorageManagementService<R> {
                 sample_location.location_id,
             ))?;
 
+        if matches!(sample_location.storage_state, StorageState::InTransit) {
+            return Err(StorageManagementError::SampleNotInStorage(
+                barcode.to_string(),
+            ));
+        }
+
+        if self
+            .storage_repo
+            .get_open_checkout(sample_location.sample_id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .is_some()
+        {
+            return Err(StorageManagementError::SampleAlreadyCheckedOut(
+                barcode.to_string(),
+            ));
+        }
+
         // Remove the sample
         let removed_sample = self
             .storage_repo
             .remove_sample(sample_location.sample_id, removed_by, reason)
             .await
-            .map_err(StorageManagementError::DatabaseError)?;
+            .map_err(checkout_error)?;
 
         Ok(RemovedSampleResult {
             sample_location: removed_sample,
@@ -223,6 +271,139 @@ impl<R: StorageRepository> StorageManagementService<R> {
         })
     }
 
+    /// Check a sample out of storage temporarily, keeping its position reserved
+    pub async fn checkout_sample(
+        &self,
+        barcode: &str,
+        checked_out_by: &str,
+        due_back_at: chrono::DateTime<chrono::Utc>,
+        purpose: Option<String>,
+    ) -> Result<SampleCheckout, StorageManagementError> {
+        let sample_location = self
+            .storage_repo
+            .get_sample_by_barcode(barcode)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .ok_or(StorageManagementError::BarcodeNotFound(barcode.to_string()))?;
+
+        if !matches!(sample_location.storage_state, StorageState::InStorage) {
+            return Err(StorageManagementError::SampleNotInStorage(barcode.to_string()));
+        }
+
+        if due_back_at <= chrono::Utc::now() {
+            return Err(StorageManagementError::InvalidDueBackTime(due_back_at));
+        }
+
+        if self
+            .storage_repo
+            .get_open_checkout(sample_location.sample_id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .is_some()
+        {
+            return Err(StorageManagementError::SampleAlreadyCheckedOut(barcode.to_string()));
+        }
+
+        let checkout = CreateSampleCheckout {
+            sample_id: sample_location.sample_id,
+            barcode: sample_location.barcode,
+            location_id: sample_location.location_id,
+            position: sample_location.position,
+            checked_out_by: checked_out_by.to_string(),
+            due_back_at,
+            purpose,
+        };
+
+        self.storage_repo
+            .create_checkout(checkout)
+            .await
+            .map_err(|e| match e.as_database_error().and_then(|d| d.code()) {
+                // Unique index on open checkouts: another checkout won the race
+                Some(code) if code == "23505" => {
+                    StorageManagementError::SampleAlreadyCheckedOut(barcode.to_string())
+                }
+                _ => checkout_error(e),
+            })
+    }
+
+    /// Return a checked out sample to its reserved position
+    pub async fn check_in_sample(
+        &self,
+        barcode: &str,
+        checked_in_by: &str,
+        notes: Option<String>,
+    ) -> Result<CheckInResult, StorageManagementError> {
+        let sample_location = self
+            .storage_repo
+            .get_sample_by_barcode(barcode)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .ok_or(StorageManagementError::BarcodeNotFound(barcode.to_string()))?;
+
+        let checkout = self
+            .storage_repo
+            .check_in_sample(sample_location.sample_id, checked_in_by, notes)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .ok_or(StorageManagementError::SampleNotCheckedOut(barcode.to_string()))?;
+
+        let now = chrono::Utc::now();
+        let returned_late = checkout
+            .checked_in_at
+            .is_some_and(|checked_in_at| checked_in_at > checkout.due_back_at);
+
+        Ok(CheckInResult {
+            seconds_out: checkout.time_out_seconds(now),
+            returned_late,
+            checkout,
+        })
+    }
+
+    /// List open checkouts, optionally only those past their due-back time
+    pub async fn list_checkouts(
+        &self,
+        overdue_only: bool,
+    ) -> Result<Vec<SampleCheckout>, StorageManagementError> {
+        let checkouts = if overdue_only {
+            self.storage_repo.list_overdue_checkouts().await
+        } else {
+            self.storage_repo.list_open_checkouts().await
+        };
+
+        checkouts.map_err(StorageManagementError::DatabaseError)
+    }
+
+    /// Get the cumulative time a sample has spent checked out of storage
+    pub async fn get_time_out_of_storage(
+        &self,
+        barcode: &str,
+    ) -> Result<TimeOutOfStorage, StorageManagementError> {
+        let sample_location = self
+            .storage_repo
+            .get_sample_by_barcode(barcode)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .ok_or(StorageManagementError::BarcodeNotFound(barcode.to_string()))?;
+
+        let checkouts = self
+            .storage_repo
+            .get_sample_checkouts(sample_location.sample_id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?;
+
+        let now = chrono::Utc::now();
+        let open_checkout = checkouts.iter().find(|c| c.is_open()).cloned();
+
+        Ok(TimeOutOfStorage {
+            sample_id: sample_location.sample_id,
+            barcode: sample_location.barcode,
+            checkout_count: checkouts.len() as i32,
+            total_seconds_out: checkouts.iter().map(|c| c.time_out_seconds(now)).sum(),
+            currently_checked_out: open_checkout.is_some(),
+            open_checkout,
+        })
+    }
+
     /// Update sample storage state
     pub async fn update_sample_state(
         &self,
@@ -238,8 +419,11 @@ impl<R: StorageRepository> StorageManagementService<R> {
             .map_err(StorageManagementError::DatabaseError)?
             .ok_or(StorageManagementError::SampleNotFound(sample_id))?;
 
-        // Validate state transition
-        if !current_sample.storage_state.can_transition_to(new_state) {
+        // Validate state transition; shipments take samples in and out of
+        // transit so location usage stays in step
+        let shipping = matches!(current_sample.storage_state, StorageState::InTransit)
+            || matches!(new_state, StorageState::InTransit);
+        if shipping || !current_sample.storage_state.can_transition_to(new_state) {
             return Err(StorageManagementError::InvalidStateTransition {
                 current_state: current_sample.storage_state,
                 requested_state: new_state,
@@ -251,7 +435,7 @@ impl<R: StorageRepository> StorageManagementService<R> {
             .storage_repo
             .update_sample_state(sample_id, new_state, updated_by)
             .await
-            .map_err(StorageManagementError::DatabaseError)?;
+            .map_err(checkout_error)?;
 
         Ok(updated_sample)
     }
@@ -340,6 +524,285 @@ impl<R: StorageRepository> StorageManagementService<R> {
             .map_err(StorageManagementError::DatabaseError)
     }
 
+    /// Reconcile a rack scan against the samples recorded in a location.
+    ///
+    /// When `apply_corrections` is set, every misplaced tube is moved to the
+    /// scanned position in this location and the move is recorded in the
+    /// movement history. The moves are applied together in one transaction,
+    /// and none are applied if any tube conflicts with the location's
+    /// temperature zone or another tube's position. Missing and unknown tubes
+    /// are only reported.
+    pub async fn reconcile_location_scan(
+        &self,
+        location_id: i32,
+        scanned: &[BoxScanEntry],
+        apply_corrections: bool,
+        reconciled_by: &str,
+    ) -> Result<BoxScanReconciliation, StorageManagementError> {
+        let location = self
+            .storage_repo
+            .get_storage_location(location_id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .ok_or(StorageManagementError::LocationNotFound(location_id))?;
+
+        let expected = self
+            .storage_repo
+            .get_samples_in_location(location_id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?;
+
+        // Look up scanned tubes that are not recorded in this location
+        let mut elsewhere = std::collections::HashMap::new();
+        for entry in scanned {
+            if expected.iter().any(|s| s.barcode == entry.barcode)
+                || elsewhere.contains_key(&entry.barcode)
+            {
+                continue;
+            }
+            if let Some(sample) = self
+                .storage_repo
+                .get_sample_by_barcode(&entry.barcode)
+                .await
+                .map_err(StorageManagementError::DatabaseError)?
+            {
+                elsewhere.insert(entry.barcode.clone(), sample);
+            }
+        }
+
+        let mut result = reconcile_box_scan(location_id, &expected, &elsewhere, scanned);
+
+        if apply_corrections && !result.misplaced.is_empty() {
+            if !location.is_active {
+                return Err(StorageManagementError::LocationInactive(location_id));
+            }
+
+            let mut zones = std::collections::HashMap::new();
+            for sample in elsewhere.values() {
+                if zones.contains_key(&sample.location_id) {
+                    continue;
+                }
+                if let Some(recorded) = self
+                    .storage_repo
+                    .get_storage_location(sample.location_id)
+                    .await
+                    .map_err(StorageManagementError::DatabaseError)?
+                {
+                    zones.insert(recorded.id, recorded.temperature_zone);
+                }
+            }
+
+            let conflicts =
+                correction_conflicts(&result, &expected, location.temperature_zone, &zones);
+            if !conflicts.is_empty() {
+                result.correction_errors = conflicts;
+                return Ok(result);
+            }
+
+            let relocations = result
+                .misplaced
+                .iter()
+                .map(|tube| SampleRelocation {
+                    sample_id: tube.sample_id,
+                    location_id,
+                    position: Some(tube.scanned_position.clone()),
+                })
+                .collect();
+            let reason = format!("Box scan reconciliation of '{}'", location.name);
+
+            match self
+                .storage_repo
+                .move_samples_to_positions(relocations, reconciled_by, &reason)
+                .await
+            {
+                Ok(_) => {
+                    result.corrections_applied = result
+                        .misplaced
+                        .iter()
+                        .map(|tube| tube.barcode.clone())
+                        .collect()
+                }
+                Err(e) => result
+                    .correction_errors
+                    .push(format!("No corrections were applied: {}", e)),
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Plan the relocation of every sample in a location into compatible targets.
+    ///
+    /// Candidate targets are active locations in the same temperature zone with
+    /// free capacity, optionally restricted to `target_location_ids`.
+    pub async fn plan_freezer_evacuation(
+        &self,
+        source_location_id: i32,
+        target_location_ids: Option<Vec<i32>>,
+    ) -> Result<EvacuationPlan, StorageManagementError> {
+        let source = self
+            .storage_repo
+            .get_storage_location(source_location_id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .ok_or(StorageManagementError::LocationNotFound(source_location_id))?;
+
+        let checked_out = self.checked_out_samples().await?;
+        let (samples, checked_out): (Vec<_>, Vec<_>) = self
+            .storage_repo
+            .get_samples_in_location(source_location_id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .into_iter()
+            .partition(|s| !checked_out.contains(&s.sample_id));
+
+        let candidates = self
+            .storage_repo
+            .get_storage_locations_by_temperature(source.temperature_zone)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?;
+
+        let mut targets = Vec::new();
+        for location in candidates {
+            if target_location_ids
+                .as_ref()
+                .is_some_and(|ids| !ids.contains(&location.id))
+            {
+                continue;
+            }
+            if !is_compatible_target(&source, &location) {
+                continue;
+            }
+
+            let occupied_positions = self
+                .storage_repo
+                .get_samples_in_location(location.id)
+                .await
+                .map_err(StorageManagementError::DatabaseError)?
+                .into_iter()
+                .filter_map(|s| s.position)
+                .collect();
+
+            targets.push(EvacuationTarget {
+                location,
+                occupied_positions,
+            });
+        }
+
+        // Checked out samples keep their positions and cannot be planned
+        let mut plan = plan_evacuation(&source, &samples, targets);
+        plan.unplaced
+            .extend(checked_out.into_iter().map(|s| s.barcode));
+        Ok(plan)
+    }
+
+    /// Execute an evacuation plan as a single batch of moves.
+    ///
+    /// Every move shares one movement reason and runs in one transaction, so
+    /// if any move fails the whole batch is rolled back. Each target named by
+    /// the plan is checked under lock for its zone, active status, capacity
+    /// and free positions, since the plan may have been edited or gone stale.
+    pub async fn execute_freezer_evacuation(
+        &self,
+        plan: &EvacuationPlan,
+        moved_by: &str,
+        reason: &str,
+        deactivate_source: bool,
+    ) -> Result<EvacuationResult, StorageManagementError> {
+        let source = self
+            .storage_repo
+            .get_storage_location(plan.source_location_id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .ok_or(StorageManagementError::LocationNotFound(plan.source_location_id))?;
+
+        // The plan may have been reviewed for a while; make sure it still applies
+        let current_samples: std::collections::HashSet<uuid::Uuid> = self
+            .storage_repo
+            .get_samples_in_location(source.id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .into_iter()
+            .map(|s| s.sample_id)
+            .collect();
+
+        if let Some(stale) = plan
+            .moves
+            .iter()
+            .find(|m| !current_samples.contains(&m.sample_id))
+        {
+            return Err(StorageManagementError::EvacuationPlanStale(format!(
+                "sample {} is no longer in location {}",
+                stale.barcode, source.id
+            )));
+        }
+
+        let checked_out = self.checked_out_samples().await?;
+        if let Some(planned) = plan
+            .moves
+            .iter()
+            .find(|m| checked_out.contains(&m.sample_id))
+        {
+            return Err(StorageManagementError::SampleAlreadyCheckedOut(
+                planned.barcode.clone(),
+            ));
+        }
+
+        let relocations = plan
+            .moves
+            .iter()
+            .map(|m| SampleRelocation {
+                sample_id: m.sample_id,
+                location_id: m.to_location_id,
+                position: Some(m.to_position.clone()),
+            })
+            .collect();
+        let movement_reason = format!("Evacuation of '{}': {}", source.name, reason);
+
+        let moved = self
+            .storage_repo
+            .move_samples_to_positions(relocations, moved_by, &movement_reason)
+            .await
+            .map_err(evacuation_error)?;
+
+        let remaining_in_source = current_samples.len() - moved.len();
+        let source_deactivated = deactivate_source && remaining_in_source == 0;
+        if source_deactivated {
+            self.storage_repo
+                .update_storage_location(
+                    source.id,
+                    UpdateStorageLocation {
+                        name: None,
+                        description: None,
+                        capacity: None,
+                        is_active: Some(false),
+                        location_path: None,
+                    },
+                )
+                .await
+                .map_err(StorageManagementError::DatabaseError)?;
+        }
+
+        Ok(EvacuationResult {
+            source_location_id: source.id,
+            moved_count: moved.len(),
+            remaining_in_source,
+            source_deactivated,
+            movement_reason,
+        })
+    }
+
+    /// Recompute location usage from stored samples and correct any drift
+    pub async fn reconcile_location_usage(
+        &self,
+        location_id: Option<i32>,
+    ) -> Result<Vec<LocationUsageReconciliation>, StorageManagementError> {
+        self.storage_repo
+            .recalculate_location_usage(location_id)
+            .await
+            .map_err(StorageManagementError::DatabaseError)
+    }
+
     /// Create a new storage location
     pub async fn create_storage_location(
         &self,
@@ -372,6 +835,19 @@ impl<R: StorageRepository> StorageManagementService<R> {
     }
 
     /// Private helper methods
+    async fn checked_out_samples(
+        &self,
+    ) -> Result<std::collections::HashSet<uuid::Uuid>, StorageManagementError> {
+        Ok(self
+            .storage_repo
+            .list_open_checkouts()
+            .await
+            .map_err(StorageManagementError::DatabaseError)?
+            .into_iter()
+            .map(|c| c.sample_id)
+            .collect())
+    }
+
     fn validate_storage_requirements(
         &self,
         location: &StorageLocation,
@@ -498,6 +974,93 @@ impl<R: StorageRepository> StorageManagementService<R> {
     }
 }
 
+/// Map a violation of the location usage constraint to a capacity error.
+///
+/// Capacity is enforced by the database inside the same transaction that
+/// stores or moves the sample, so a full location surfaces here even when the
+/// pre-check passed under concurrent load.
+fn capacity_error(error: sqlx::Error, location_id: i32) -> StorageManagementError {
+    let is_capacity_violation = error
+        .as_database_error()
+        .and_then(|e| e.constraint())
+        .is_some_and(|constraint| constraint == "storage_locations_usage_valid");
+
+    if is_capacity_violation {
+        StorageManagementError::InsufficientCapacity {
+            location_id,
+            requested: 1,
+            available: 0,
+        }
+    } else {
+        checkout_error(error)
+    }
+}
+
+/// Map a refused evacuation batch to the matching error
+fn evacuation_error(error: RelocationError) -> StorageManagementError {
+    match error {
+        RelocationError::LocationNotFound(id) => StorageManagementError::LocationNotFound(id),
+        RelocationError::LocationInactive(id) => StorageManagementError::LocationInactive(id),
+        RelocationError::IncompatibleTemperature {
+            sample_temp,
+            location_temp,
+            ..
+        } => StorageManagementError::IncompatibleTemperature {
+            sample_temp_requirement: sample_temp,
+            location_temp,
+        },
+        RelocationError::InsufficientCapacity {
+            location_id,
+            requested,
+            available,
+        } => StorageManagementError::InsufficientCapacity {
+            location_id,
+            requested,
+            available,
+        },
+        RelocationError::SampleInTransit(barcode) => {
+            StorageManagementError::SampleNotInStorage(barcode)
+        }
+        RelocationError::PositionOccupied { .. } => {
+            StorageManagementError::EvacuationPlanStale(error.to_string())
+        }
+        RelocationError::Database(e) => match checkout_error(e) {
+            StorageManagementError::DatabaseError(e) => {
+                StorageManagementError::EvacuationFailed(e.to_string())
+            }
+            refused => refused,
+        },
+    }
+}
+
+/// Map a refusal from the sample checkout triggers to its error.
+///
+/// Open checkouts are enforced by the database in the transaction that
+/// moves, removes or re-stores a sample; the trigger names the barcode or
+/// position in the error detail.
+fn checkout_error(error: sqlx::Error) -> StorageManagementError {
+    let refusal = error.as_database_error().and_then(|e| {
+        let detail = e
+            .try_downcast_ref::<sqlx::postgres::PgDatabaseError>()?
+            .detail()?
+            .to_string();
+        Some((e.constraint()?.to_string(), detail))
+    });
+
+    match refusal {
+        Some((constraint, barcode)) if constraint == "sample_checked_out" => {
+            StorageManagementError::SampleAlreadyCheckedOut(barcode)
+        }
+        Some((constraint, position)) if constraint == "position_reserved_by_checkout" => {
+            StorageManagementError::PositionReserved(position)
+        }
+        Some((constraint, barcode)) if constraint == "sample_checkout_stale" => {
+            StorageManagementError::SampleNotInStorage(barcode)
+        }
+        _ => StorageManagementError::DatabaseError(error),
+    }
+}
+
 /// Result of storing a sample
 #[derive(Debug, Clone)]
 pub struct StoredSampleResult {
@@ -522,6 +1085,24 @@ pub struct RemovedSampleResult {
     pub location: StorageLocation,
 }
 
+/// Result of checking a sample back in
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckInResult {
+    pub checkout: SampleCheckout,
+    pub seconds_out: i64,
+    pub returned_late: bool,
+}
+
+/// Result of executing an evacuation plan
+#[derive(Debug, Clone, Serialize)]
+pub struct EvacuationResult {
+    pub source_location_id: i32,
+    pub moved_count: usize,
+    pub remaining_in_source: usize,
+    pub source_deactivated: bool,
+    pub movement_reason: String,
+}
+
 /// Capacity overview statistics
 #[derive(Debug, Clone, Serialize)]
 pub struct CapacityOverview {
@@ -580,6 +1161,30 @@ pub enum StorageManagementError {
     #[error("Location {0} is inactive")]
     LocationInactive(i32),
 
+    #[error("Invalid scan file: {0}")]
+    InvalidScanFile(String),
+
+    #[error("Sample {0} is not in storage")]
+    SampleNotInStorage(String),
+
+    #[error("Sample {0} is already checked out")]
+    SampleAlreadyCheckedOut(String),
+
+    #[error("Sample {0} is not checked out")]
+    SampleNotCheckedOut(String),
+
+    #[error("Position {0} is reserved for a checked out sample")]
+    PositionReserved(String),
+
+    #[error("Due-back time {0} must be in the future")]
+    InvalidDueBackTime(chrono::DateTime<chrono::Utc>),
+
+    #[error("Evacuation plan is out of date: {0}")]
+    EvacuationPlanStale(String),
+
+    #[error("Evacuation failed and was rolled back: {0}")]
+    EvacuationFailed(String),
+
     #[error("Invalid state transition from {current_state:?} to {requested_state:?}")]
     InvalidStateTransition {
         current_state: StorageState,
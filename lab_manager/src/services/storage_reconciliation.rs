@@ -0,0 +1,447 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::models::storage::{normalize_position, SampleLocation, TemperatureZone};
+use crate::services::storage_management_service::StorageManagementError;
+
+/// Values written by flatbed rack scanners for positions without a readable tube
+const EMPTY_TUBE_MARKERS: &[&str] = &["", "NO TUBE", "NOTUBE", "EMPTY", "NO READ", "NOREAD"];
+
+/// A single position→tube reading from a rack scan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoxScanEntry {
+    pub position: String,
+    pub barcode: String,
+}
+
+/// A tube found at the position recorded for it
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanMatch {
+    pub sample_id: uuid::Uuid,
+    pub barcode: String,
+    pub position: String,
+}
+
+/// A known tube found somewhere other than where storage records say it is
+#[derive(Debug, Clone, Serialize)]
+pub struct MisplacedTube {
+    pub sample_id: uuid::Uuid,
+    pub barcode: String,
+    pub scanned_position: String,
+    pub expected_location_id: i32,
+    pub expected_position: Option<String>,
+}
+
+/// A tube recorded in the location that was not present in the scan
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingTube {
+    pub sample_id: uuid::Uuid,
+    pub barcode: String,
+    pub expected_position: Option<String>,
+}
+
+/// A scanned tube with no storage record anywhere
+#[derive(Debug, Clone, Serialize)]
+pub struct UnknownTube {
+    pub barcode: String,
+    pub scanned_position: String,
+}
+
+/// Outcome of reconciling a rack scan against storage records
+#[derive(Debug, Clone, Serialize)]
+pub struct BoxScanReconciliation {
+    pub location_id: i32,
+    pub scanned_count: usize,
+    pub matched: Vec<ScanMatch>,
+    pub misplaced: Vec<MisplacedTube>,
+    pub missing: Vec<MissingTube>,
+    pub unknown: Vec<UnknownTube>,
+    pub corrections_applied: Vec<String>,
+    pub correction_errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl BoxScanReconciliation {
+    pub fn is_consistent(&self) -> bool {
+        self.misplaced.is_empty() && self.missing.is_empty() && self.unknown.is_empty()
+    }
+}
+
+/// Parse a rack scanner CSV export into position→tube entries.
+///
+/// Accepts files with or without a header row. When a header is present the
+/// position and tube columns are located by name, otherwise the first two
+/// columns are used. Positions without a readable tube are skipped.
+pub fn parse_box_scan_csv(data: &[u8]) -> Result<Vec<BoxScanEntry>, StorageManagementError> {
+    let content = std::str::from_utf8(data)
+        .map_err(|e| StorageManagementError::InvalidScanFile(format!("Invalid UTF-8: {}", e)))?;
+    let content = content.trim_start_matches('\u{feff}');
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+
+    let mut rows = reader.records();
+    let mut entries = Vec::new();
+    let (mut position_col, mut barcode_col) = (0, 1);
+
+    if let Some(first) = rows.next() {
+        let first = first.map_err(|e| StorageManagementError::InvalidScanFile(e.to_string()))?;
+        match header_columns(&first) {
+            Some((pos, code)) => {
+                position_col = pos;
+                barcode_col = code;
+            }
+            None => {
+                if let Some(entry) = scan_entry(&first, position_col, barcode_col) {
+                    entries.push(entry);
+                }
+            }
+        }
+    }
+
+    for (index, row) in rows.enumerate() {
+        let row = row.map_err(|e| {
+            StorageManagementError::InvalidScanFile(format!("Row {}: {}", index + 2, e))
+        })?;
+        if let Some(entry) = scan_entry(&row, position_col, barcode_col) {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Compare scanned entries with the samples recorded in a location.
+///
+/// `elsewhere` holds storage records for scanned barcodes that are not
+/// recorded in this location, keyed by barcode.
+pub fn reconcile_box_scan(
+    location_id: i32,
+    expected: &[SampleLocation],
+    elsewhere: &HashMap<String, SampleLocation>,
+    scanned: &[BoxScanEntry],
+) -> BoxScanReconciliation {
+    let expected_by_barcode: HashMap<&str, &SampleLocation> = expected
+        .iter()
+        .map(|sample| (sample.barcode.as_str(), sample))
+        .collect();
+
+    let mut result = BoxScanReconciliation {
+        location_id,
+        scanned_count: scanned.len(),
+        matched: Vec::new(),
+        misplaced: Vec::new(),
+        missing: Vec::new(),
+        unknown: Vec::new(),
+        corrections_applied: Vec::new(),
+        correction_errors: Vec::new(),
+        warnings: Vec::new(),
+    };
+
+    let mut seen_barcodes = HashSet::new();
+    let mut seen_positions = HashSet::new();
+
+    for entry in scanned {
+        let position = normalize_position(&entry.position);
+
+        if !seen_positions.insert(position.clone()) {
+            result.warnings.push(format!(
+                "Position {} appears more than once in the scan",
+                position
+            ));
+        }
+        if !seen_barcodes.insert(entry.barcode.as_str()) {
+            result.warnings.push(format!(
+                "Tube {} was scanned more than once; only the first reading is used",
+                entry.barcode
+            ));
+            continue;
+        }
+
+        if let Some(sample) = expected_by_barcode.get(entry.barcode.as_str()) {
+            let recorded = sample.position.as_deref().map(normalize_position);
+            if recorded.as_deref() == Some(position.as_str()) {
+                result.matched.push(ScanMatch {
+                    sample_id: sample.sample_id,
+                    barcode: entry.barcode.clone(),
+                    position,
+                });
+            } else {
+                result.misplaced.push(MisplacedTube {
+                    sample_id: sample.sample_id,
+                    barcode: entry.barcode.clone(),
+                    scanned_position: position,
+                    expected_location_id: sample.location_id,
+                    expected_position: sample.position.clone(),
+                });
+            }
+        } else if let Some(sample) = elsewhere.get(&entry.barcode) {
+            result.misplaced.push(MisplacedTube {
+                sample_id: sample.sample_id,
+                barcode: entry.barcode.clone(),
+                scanned_position: position,
+                expected_location_id: sample.location_id,
+                expected_position: sample.position.clone(),
+            });
+        } else {
+            result.unknown.push(UnknownTube {
+                barcode: entry.barcode.clone(),
+                scanned_position: position,
+            });
+        }
+    }
+
+    result.missing = expected
+        .iter()
+        .filter(|sample| !seen_barcodes.contains(sample.barcode.as_str()))
+        .map(|sample| MissingTube {
+            sample_id: sample.sample_id,
+            barcode: sample.barcode.clone(),
+            expected_position: sample.position.clone(),
+        })
+        .collect();
+
+    result
+}
+
+/// Reasons the misplaced tubes of a reconciliation cannot be moved to their
+/// scanned positions in the location, which is in `location_zone`.
+///
+/// `zones` holds the temperature zone of each location a misplaced tube is
+/// recorded in. Tubes recorded in the location that stay put keep their
+/// positions, so a scanned position recorded for one of them is taken.
+pub fn correction_conflicts(
+    result: &BoxScanReconciliation,
+    expected: &[SampleLocation],
+    location_zone: TemperatureZone,
+    zones: &HashMap<i32, TemperatureZone>,
+) -> Vec<String> {
+    let mut claims: HashMap<&str, usize> = HashMap::new();
+    let scanned_positions = result
+        .matched
+        .iter()
+        .map(|tube| tube.position.as_str())
+        .chain(
+            result
+                .misplaced
+                .iter()
+                .map(|tube| tube.scanned_position.as_str()),
+        )
+        .chain(
+            result
+                .unknown
+                .iter()
+                .map(|tube| tube.scanned_position.as_str()),
+        );
+    for position in scanned_positions {
+        *claims.entry(position).or_default() += 1;
+    }
+
+    let moving: HashSet<uuid::Uuid> = result.misplaced.iter().map(|tube| tube.sample_id).collect();
+    let held: HashMap<String, &str> = expected
+        .iter()
+        .filter(|sample| !moving.contains(&sample.sample_id))
+        .filter_map(|sample| {
+            let position = normalize_position(sample.position.as_deref()?);
+            Some((position, sample.barcode.as_str()))
+        })
+        .collect();
+
+    let mut conflicts = Vec::new();
+    for tube in &result.misplaced {
+        if tube.expected_location_id != result.location_id {
+            match zones.get(&tube.expected_location_id) {
+                Some(zone) if *zone == location_zone => {}
+                Some(zone) => conflicts.push(format!(
+                    "{}: recorded in a {} location, this location is {}",
+                    tube.barcode,
+                    zone.display_name(),
+                    location_zone.display_name()
+                )),
+                None => conflicts.push(format!(
+                    "{}: recorded location {} no longer exists",
+                    tube.barcode, tube.expected_location_id
+                )),
+            }
+        }
+
+        if claims
+            .get(tube.scanned_position.as_str())
+            .copied()
+            .unwrap_or(0)
+            > 1
+        {
+            conflicts.push(format!(
+                "{}: position {} was scanned for more than one tube",
+                tube.barcode, tube.scanned_position
+            ));
+        } else if let Some(holder) = held.get(&tube.scanned_position) {
+            conflicts.push(format!(
+                "{}: position {} is recorded for {}",
+                tube.barcode, tube.scanned_position, holder
+            ));
+        }
+    }
+
+    conflicts
+}
+
+fn header_columns(row: &csv::StringRecord) -> Option<(usize, usize)> {
+    let names: Vec<String> = row
+        .iter()
+        .map(|field| field.trim_start_matches('\u{feff}').to_lowercase())
+        .collect();
+
+    let position = names
+        .iter()
+        .position(|n| n.contains("position") || n.contains("well") || n == "pos")?;
+    let barcode = names.iter().enumerate().position(|(i, n)| {
+        i != position
+            && (n.contains("tube") || n.contains("barcode") || n.contains("sample") || n == "id")
+    })?;
+
+    Some((position, barcode))
+}
+
+fn scan_entry(
+    row: &csv::StringRecord,
+    position_col: usize,
+    barcode_col: usize,
+) -> Option<BoxScanEntry> {
+    let position = row.get(position_col)?.trim();
+    let barcode = row.get(barcode_col)?.trim();
+
+    if position.is_empty() || EMPTY_TUBE_MARKERS.contains(&barcode.to_uppercase().as_str()) {
+        return None;
+    }
+
+    Some(BoxScanEntry {
+        position: position.to_string(),
+        barcode: barcode.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::storage::StorageState;
+    use chrono::Utc;
+
+    fn sample(barcode: &str, location_id: i32, position: &str) -> SampleLocation {
+        SampleLocation {
+            id: 1,
+            sample_id: uuid::Uuid::new_v4(),
+            location_id,
+            barcode: barcode.to_string(),
+            position: Some(position.to_string()),
+            storage_state: StorageState::InStorage,
+            stored_at: Utc::now(),
+            stored_by: None,
+            moved_at: None,
+            moved_by: None,
+            notes: None,
+            temperature_log: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn entry(position: &str, barcode: &str) -> BoxScanEntry {
+        BoxScanEntry {
+            position: position.to_string(),
+            barcode: barcode.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_box_scan_csv_with_header() {
+        let csv = b"\xef\xbb\xbfTube ID,Position\nTUBE-001,A01\nNO TUBE,A02\nTUBE-003,A03\n";
+        let entries = parse_box_scan_csv(csv).unwrap();
+
+        assert_eq!(
+            entries,
+            vec![entry("A01", "TUBE-001"), entry("A03", "TUBE-003")]
+        );
+    }
+
+    #[test]
+    fn test_parse_box_scan_csv_without_header() {
+        let entries = parse_box_scan_csv(b"A1,TUBE-001\nB1,\n").unwrap();
+        assert_eq!(entries, vec![entry("A1", "TUBE-001")]);
+    }
+
+    #[test]
+    fn test_reconcile_box_scan_categories() {
+        let expected = vec![
+            sample("TUBE-001", 7, "A1"),
+            sample("TUBE-002", 7, "A2"),
+            sample("TUBE-003", 7, "A3"),
+        ];
+        let mut elsewhere = HashMap::new();
+        elsewhere.insert("TUBE-009".to_string(), sample("TUBE-009", 4, "C5"));
+
+        let scanned = vec![
+            entry("A01", "TUBE-001"),
+            entry("A03", "TUBE-002"),
+            entry("A04", "TUBE-009"),
+            entry("A05", "TUBE-404"),
+        ];
+
+        let result = reconcile_box_scan(7, &expected, &elsewhere, &scanned);
+
+        assert_eq!(result.matched.len(), 1);
+        assert_eq!(result.misplaced.len(), 2);
+        assert_eq!(result.misplaced[0].scanned_position, "A3");
+        assert_eq!(result.misplaced[1].expected_location_id, 4);
+        assert_eq!(result.missing.len(), 1);
+        assert_eq!(result.missing[0].barcode, "TUBE-003");
+        assert_eq!(result.unknown.len(), 1);
+        assert!(!result.is_consistent());
+    }
+
+    #[test]
+    fn test_correction_conflicts() {
+        let expected = vec![sample("TUBE-001", 7, "A1"), sample("TUBE-002", 7, "A2")];
+        let mut elsewhere = HashMap::new();
+        elsewhere.insert("TUBE-008".to_string(), sample("TUBE-008", 3, "B1"));
+        elsewhere.insert("TUBE-009".to_string(), sample("TUBE-009", 4, "C5"));
+        let zones = HashMap::from([
+            (3, TemperatureZone::Freezer),
+            (4, TemperatureZone::UltraLowFreezer),
+        ]);
+
+        // TUBE-002 vacates A2, so TUBE-008 may take it
+        let scanned = vec![
+            entry("A1", "TUBE-001"),
+            entry("A3", "TUBE-002"),
+            entry("A2", "TUBE-008"),
+        ];
+        let result = reconcile_box_scan(7, &expected, &elsewhere, &scanned);
+        assert!(
+            correction_conflicts(&result, &expected, TemperatureZone::Freezer, &zones).is_empty()
+        );
+
+        // Wrong zone, a position still recorded for a tube, a doubly scanned position
+        let scanned = vec![
+            entry("A3", "TUBE-009"),
+            entry("A1", "TUBE-008"),
+            entry("B4", "TUBE-002"),
+            entry("B4", "TUBE-404"),
+        ];
+        let result = reconcile_box_scan(7, &expected, &elsewhere, &scanned);
+        let conflicts = correction_conflicts(&result, &expected, TemperatureZone::Freezer, &zones);
+        assert_eq!(conflicts.len(), 3);
+        assert!(conflicts[0].starts_with("TUBE-009: recorded in"));
+        assert_eq!(
+            conflicts[1],
+            "TUBE-008: position A1 is recorded for TUBE-001"
+        );
+        assert_eq!(
+            conflicts[2],
+            "TUBE-002: position B4 was scanned for more than one tube"
+        );
+    }
+}
@@ -0,0 +1,550 @@
+use calamine::{open_workbook_auto_from_rs, DataType, Range, Reader};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+use uuid::Uuid;
+
+use crate::models::template::{FieldType, TemplateField};
+use crate::sample_submission::{CreateSample, Sample};
+use crate::services::template_workbook::{
+    SubmissionWorkbook, DATA_SHEET, MARKER_SHEET, MARKER_TEMPLATE_ID, MARKER_VERSION_ID,
+};
+
+/// Column widths of the samples table
+const MAX_NAME_LENGTH: usize = 255;
+const MAX_BARCODE_LENGTH: usize = 50;
+const MAX_LOCATION_LENGTH: usize = 255;
+
+/// A filled submission workbook, read back into text cells
+#[derive(Debug, Clone)]
+pub struct SubmittedSheet {
+    pub template_id: Uuid,
+    pub template_version_id: Uuid,
+    pub headers: Vec<String>,
+    /// Rows with at least one filled cell
+    pub rows: Vec<SubmittedRow>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubmittedRow {
+    /// Row number as shown in the spreadsheet, the header being row 1
+    pub row_number: u32,
+    pub cells: Vec<String>,
+}
+
+impl SubmittedSheet {
+    /// Trimmed text under a header; empty when the cell is missing
+    pub fn cell<'a>(&self, row: &'a SubmittedRow, column: Option<usize>) -> &'a str {
+        column
+            .and_then(|index| row.cells.get(index))
+            .map(|text| text.trim())
+            .unwrap_or("")
+    }
+
+    /// Index of the column holding a field
+    pub fn column(&self, field: &str) -> Option<usize> {
+        self.headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(field))
+    }
+}
+
+/// A problem that keeps a submission from being accepted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubmissionIssue {
+    /// Missing for problems with the sheet as a whole, such as a missing column
+    pub row_number: Option<u32>,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl SubmissionIssue {
+    fn new(row_number: Option<u32>, field: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            row_number,
+            field: field.map(str::to_string),
+            message: message.into(),
+        }
+    }
+}
+
+/// Samples built from the rows of a submission, and every problem found
+#[derive(Debug, Default)]
+pub struct SubmissionCheck {
+    /// Samples with the spreadsheet row each came from
+    pub samples: Vec<(u32, CreateSample)>,
+    pub issues: Vec<SubmissionIssue>,
+}
+
+/// Outcome of uploading a filled submission workbook
+pub enum SubmissionOutcome {
+    Accepted(SubmissionReceipt),
+    /// Nothing was created; the workbook points out what to fix
+    Rejected {
+        issues: Vec<SubmissionIssue>,
+        workbook: SubmissionWorkbook,
+    },
+}
+
+/// Samples created from an accepted submission
+#[derive(Debug, Serialize)]
+pub struct SubmissionReceipt {
+    pub template_id: Uuid,
+    pub template_version_id: Uuid,
+    pub version_number: i32,
+    pub samples: Vec<Sample>,
+}
+
+/// Sample columns filled from template fields of the same name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SampleColumn {
+    Name,
+    Barcode,
+    Location,
+}
+
+impl SampleColumn {
+    fn for_field(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace('_', " ").as_str() {
+            "name" | "sample" | "sample name" => Some(Self::Name),
+            "barcode" | "sample barcode" => Some(Self::Barcode),
+            "location" | "storage location" => Some(Self::Location),
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Name => "sample name",
+            Self::Barcode => "barcode",
+            Self::Location => "location",
+        }
+    }
+
+    fn max_length(&self) -> usize {
+        match self {
+            Self::Name => MAX_NAME_LENGTH,
+            Self::Barcode => MAX_BARCODE_LENGTH,
+            Self::Location => MAX_LOCATION_LENGTH,
+        }
+    }
+}
+
+/// Read the version marker and data rows of a workbook generated by
+/// `build_submission_workbook`
+pub fn read_submission(bytes: Vec<u8>) -> Result<SubmittedSheet, String> {
+    let mut workbook = open_workbook_auto_from_rs(Cursor::new(bytes))
+        .map_err(|e| format!("Not a readable spreadsheet: {}", e))?;
+
+    let marker = workbook
+        .worksheet_range(MARKER_SHEET)
+        .ok_or("The workbook was not generated from a template; download one first")?
+        .map_err(|e| format!("Error reading sheet '{}': {}", MARKER_SHEET, e))?;
+    let marker_id = |label: &str| {
+        marker
+            .rows()
+            .find(|row| row.first().is_some_and(|cell| cell_text(cell) == label))
+            .and_then(|row| row.get(1))
+            .and_then(|cell| Uuid::parse_str(cell_text(cell).trim()).ok())
+            .ok_or(format!("The template marker has no valid {}", label))
+    };
+    let template_id = marker_id(MARKER_TEMPLATE_ID)?;
+    let template_version_id = marker_id(MARKER_VERSION_ID)?;
+
+    let data = workbook
+        .worksheet_range(DATA_SHEET)
+        .ok_or(format!("The workbook has no '{}' sheet", DATA_SHEET))?
+        .map_err(|e| format!("Error reading sheet '{}': {}", DATA_SHEET, e))?;
+
+    let (headers, rows) = read_rows(&data);
+    Ok(SubmittedSheet {
+        template_id,
+        template_version_id,
+        headers,
+        rows,
+    })
+}
+
+fn read_rows(range: &Range<DataType>) -> (Vec<String>, Vec<SubmittedRow>) {
+    // Row numbers count from the top of the sheet, not of the used range
+    let first_row = range.start().map(|(row, _)| row).unwrap_or(0);
+    let mut rows = range.rows();
+    let headers = rows
+        .next()
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell_text(cell).trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let rows = rows
+        .enumerate()
+        .map(|(index, row)| SubmittedRow {
+            row_number: first_row + index as u32 + 2,
+            cells: row.iter().map(cell_text).collect(),
+        })
+        .filter(|row| row.cells.iter().any(|cell| !cell.trim().is_empty()))
+        .collect();
+    (headers, rows)
+}
+
+/// Cell text in the form the template field types accept
+fn cell_text(cell: &DataType) -> String {
+    match cell {
+        DataType::Empty => String::new(),
+        DataType::String(s) => s.clone(),
+        DataType::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", *f as i64),
+        DataType::Float(f) => f.to_string(),
+        DataType::Int(i) => i.to_string(),
+        DataType::Bool(b) => b.to_string(),
+        DataType::Error(e) => format!("Error: {:?}", e),
+        DataType::DateTime(serial) => excel_date(*serial),
+        DataType::Duration(d) => d.to_string(),
+        DataType::DateTimeIso(dt) => dt.chars().take(10).collect(),
+        DataType::DurationIso(d) => d.clone(),
+    }
+}
+
+/// Date of an Excel serial day number, in the form date fields accept
+fn excel_date(serial: f64) -> String {
+    let epoch = chrono::NaiveDate::from_ymd_opt(1899, 12, 30).expect("valid date");
+    epoch
+        .checked_add_signed(chrono::Duration::days(serial.floor() as i64))
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| serial.to_string())
+}
+
+/// Check every row against the fields of its template version and build a
+/// sample from each. Fields named like a sample column (name, barcode,
+/// location) fill it; the rest go into the sample's metadata.
+pub fn check_submission(fields: &[TemplateField], sheet: &SubmittedSheet) -> SubmissionCheck {
+    let mut check = SubmissionCheck::default();
+
+    let columns: Vec<Option<usize>> = fields
+        .iter()
+        .map(|field| sheet.column(&field.name))
+        .collect();
+    for (field, column) in fields.iter().zip(&columns) {
+        if column.is_none() && (field.required || SampleColumn::for_field(&field.name).is_some()) {
+            check.issues.push(SubmissionIssue::new(
+                None,
+                Some(&field.name),
+                format!("Column '{}' is missing", field.name),
+            ));
+        }
+    }
+    for sample_column in [
+        SampleColumn::Name,
+        SampleColumn::Barcode,
+        SampleColumn::Location,
+    ] {
+        if !fields
+            .iter()
+            .any(|field| SampleColumn::for_field(&field.name) == Some(sample_column))
+        {
+            check.issues.push(SubmissionIssue::new(
+                None,
+                None,
+                format!(
+                    "The template has no field for the {}; samples cannot be created",
+                    sample_column.label()
+                ),
+            ));
+        }
+    }
+
+    let mut barcodes: HashMap<String, u32> = HashMap::new();
+    for row in &sheet.rows {
+        let mut issues = Vec::new();
+        let mut sample: HashMap<SampleColumn, String> = HashMap::new();
+        let mut metadata = Map::new();
+
+        for (field, column) in fields.iter().zip(&columns) {
+            let issue = |message: String| {
+                SubmissionIssue::new(Some(row.row_number), Some(&field.name), message)
+            };
+            let sample_column = SampleColumn::for_field(&field.name);
+            let value = sheet.cell(row, *column);
+
+            if value.is_empty() {
+                if field.required || sample_column.is_some() {
+                    issues.push(issue(format!("{} is required", field.name)));
+                }
+                continue;
+            }
+            if !field.field_type.accepts(value) {
+                issues.push(issue(format!(
+                    "'{}' is not a valid {}",
+                    value,
+                    type_label(field.field_type)
+                )));
+                continue;
+            }
+            let value = match allowed_value(field, value) {
+                Some(value) => value,
+                None => {
+                    issues.push(issue(format!(
+                        "'{}' is not an allowed {} (expected one of: {})",
+                        value,
+                        field.name,
+                        field.allowed_values.join(", ")
+                    )));
+                    continue;
+                }
+            };
+
+            match sample_column {
+                Some(sample_column) if value.chars().count() > sample_column.max_length() => {
+                    issues.push(issue(format!(
+                        "{} is longer than {} characters",
+                        field.name,
+                        sample_column.max_length()
+                    )));
+                }
+                Some(SampleColumn::Barcode) => match barcodes.get(value) {
+                    Some(first_row) => issues.push(issue(format!(
+                        "Barcode '{}' repeats row {}",
+                        value, first_row
+                    ))),
+                    None => {
+                        barcodes.insert(value.to_string(), row.row_number);
+                        sample.insert(SampleColumn::Barcode, value.to_string());
+                    }
+                },
+                Some(sample_column) => {
+                    sample.insert(sample_column, value.to_string());
+                }
+                None => {
+                    metadata.insert(field.name.clone(), typed_value(field.field_type, value));
+                }
+            }
+        }
+
+        if !issues.is_empty() {
+            check.issues.extend(issues);
+            continue;
+        }
+        if let (Some(name), Some(barcode), Some(location)) = (
+            sample.remove(&SampleColumn::Name),
+            sample.remove(&SampleColumn::Barcode),
+            sample.remove(&SampleColumn::Location),
+        ) {
+            check.samples.push((
+                row.row_number,
+                CreateSample {
+                    name,
+                    barcode,
+                    location,
+                    metadata: Some(Value::Object(metadata)),
+                    template_version_id: None,
+                },
+            ));
+        }
+    }
+    check
+}
+
+/// Turn samples whose barcode is already used into issues on the barcode
+/// field
+pub fn mark_taken_barcodes(
+    check: &mut SubmissionCheck,
+    fields: &[TemplateField],
+    taken: &HashSet<String>,
+) {
+    let Some(field) = fields
+        .iter()
+        .find(|field| SampleColumn::for_field(&field.name) == Some(SampleColumn::Barcode))
+    else {
+        return;
+    };
+    check.samples.retain(|(row_number, sample)| {
+        if !taken.contains(&sample.barcode) {
+            return true;
+        }
+        check.issues.push(SubmissionIssue::new(
+            Some(*row_number),
+            Some(&field.name),
+            format!("Barcode '{}' is already used by a sample", sample.barcode),
+        ));
+        false
+    });
+    // Problems with the sheet first, then by row
+    check.issues.sort_by_key(|issue| issue.row_number);
+}
+
+/// The listed spelling of a value, matched without regard to case, or the
+/// value itself when the field takes any value
+fn allowed_value<'a>(field: &'a TemplateField, value: &'a str) -> Option<&'a str> {
+    if field.allowed_values.is_empty() {
+        return Some(value);
+    }
+    field
+        .allowed_values
+        .iter()
+        .find(|allowed| allowed.trim().eq_ignore_ascii_case(value))
+        .map(|allowed| allowed.trim())
+}
+
+fn typed_value(field_type: FieldType, value: &str) -> Value {
+    match field_type {
+        FieldType::Integer => value.parse::<i64>().map(Value::from).unwrap_or_default(),
+        FieldType::Number => value.parse::<f64>().map(Value::from).unwrap_or_default(),
+        FieldType::Boolean => Value::Bool(matches!(
+            value.to_lowercase().as_str(),
+            "true" | "yes" | "1"
+        )),
+        FieldType::Text | FieldType::Date => Value::String(value.to_string()),
+    }
+}
+
+fn type_label(field_type: FieldType) -> &'static str {
+    match field_type {
+        FieldType::Text => "text",
+        FieldType::Integer => "whole number",
+        FieldType::Number => "number",
+        FieldType::Boolean => "yes/no value",
+        FieldType::Date => "date (YYYY-MM-DD)",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::template::{Template, TemplateVersion};
+    use crate::services::template_workbook::build_submission_workbook;
+    use chrono::Utc;
+    use rust_xlsxwriter::Workbook;
+    use serde_json::json;
+
+    fn fields() -> Vec<TemplateField> {
+        serde_json::from_value(json!([
+            {"name": "Sample", "required": true},
+            {"name": "Barcode", "required": true},
+            {"name": "Location"},
+            {"name": "Volume", "type": "number"},
+            {"name": "Type", "allowed_values": ["DNA", "RNA"]},
+            {"name": "Collected", "type": "date"}
+        ]))
+        .unwrap()
+    }
+
+    fn sheet(rows: &[&[&str]]) -> SubmittedSheet {
+        SubmittedSheet {
+            template_id: Uuid::new_v4(),
+            template_version_id: Uuid::new_v4(),
+            headers: [
+                "Sample",
+                "Barcode",
+                "Location",
+                "Volume",
+                "Type",
+                "Collected",
+            ]
+            .map(String::from)
+            .to_vec(),
+            rows: rows
+                .iter()
+                .enumerate()
+                .map(|(i, cells)| SubmittedRow {
+                    row_number: i as u32 + 2,
+                    cells: cells.iter().map(|cell| cell.to_string()).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_rows_become_samples_with_typed_metadata() {
+        let check = check_submission(
+            &fields(),
+            &sheet(&[&["S1", "BC1", "Freezer A", "12.5", "rna", "2025-06-01"]]),
+        );
+
+        assert!(check.issues.is_empty());
+        let (row_number, sample) = &check.samples[0];
+        assert_eq!(*row_number, 2);
+        assert_eq!(
+            (sample.name.as_str(), sample.barcode.as_str()),
+            ("S1", "BC1")
+        );
+        assert_eq!(
+            sample.metadata,
+            Some(json!({"Volume": 12.5, "Type": "RNA", "Collected": "2025-06-01"}))
+        );
+    }
+
+    #[test]
+    fn test_issues_name_the_row_and_field() {
+        let check = check_submission(
+            &fields(),
+            &sheet(&[
+                &["S1", "BC1", "Freezer A", "lots", "DNA", ""],
+                &["S2", "BC1", "", "1", "Protein", "June"],
+            ]),
+        );
+
+        let located: Vec<_> = check
+            .issues
+            .iter()
+            .map(|issue| (issue.row_number, issue.field.as_deref()))
+            .collect();
+        assert_eq!(
+            located,
+            [
+                (Some(2), Some("Volume")),
+                (Some(3), Some("Barcode")),
+                (Some(3), Some("Location")),
+                (Some(3), Some("Type")),
+                (Some(3), Some("Collected")),
+            ]
+        );
+        assert!(check.samples.is_empty());
+
+        let mut missing = sheet(&[]);
+        missing.headers.remove(1);
+        let check = check_submission(&fields(), &missing);
+        assert_eq!(check.issues[0].message, "Column 'Barcode' is missing");
+    }
+
+    #[test]
+    fn test_generated_workbook_reads_back_with_its_marker() {
+        let template = Template {
+            id: Uuid::new_v4(),
+            name: "Intake".to_string(),
+            description: None,
+            file_path: String::new(),
+            file_type: "xlsx".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            metadata: json!({}),
+        };
+        let version = TemplateVersion {
+            id: Uuid::new_v4(),
+            template_id: template.id,
+            version_number: 1,
+            fields: fields(),
+            changes: Vec::new(),
+            changelog: None,
+            created_by: None,
+            created_at: Utc::now(),
+        };
+        let generated = build_submission_workbook(&template, &version, 10).unwrap();
+        let sheet = read_submission(generated.bytes).unwrap();
+        assert_eq!(
+            (sheet.template_id, sheet.template_version_id),
+            (template.id, version.id)
+        );
+        assert_eq!(sheet.headers.len(), 6);
+        assert!(sheet.rows.is_empty());
+
+        let mut workbook = Workbook::new();
+        workbook
+            .add_worksheet()
+            .write_string(0, 0, "Sample")
+            .unwrap();
+        let plain = workbook.save_to_buffer().unwrap();
+        assert!(read_submission(plain).is_err());
+    }
+}
@@ -0,0 +1,509 @@
+use rust_xlsxwriter::{
+    DataValidation, DataValidationRule, ExcelDateTime, Format, FormatBorder, Formula, Note,
+    ProtectionOptions, Workbook, Worksheet, XlsxError,
+};
+use std::collections::BTreeMap;
+
+use crate::models::template::{FieldType, Template, TemplateField, TemplateVersion};
+use crate::services::template_submission::{SubmissionIssue, SubmittedSheet};
+
+/// Sheet submitters fill in, one sample per row under the header
+pub const DATA_SHEET: &str = "Samples";
+
+/// Sheet describing every field of the template
+pub const INSTRUCTIONS_SHEET: &str = "Instructions";
+
+/// Sheet listing the problems of a rejected submission
+pub const PROBLEMS_SHEET: &str = "Problems";
+
+/// Hidden sheet naming the template version the workbook was generated from.
+/// It also holds the dropdown lists too long to inline in a validation.
+pub const MARKER_SHEET: &str = "_template";
+
+/// Marker labels in column A of the marker sheet; the values are in column B
+pub const MARKER_TEMPLATE_ID: &str = "template_id";
+pub const MARKER_VERSION_ID: &str = "template_version_id";
+pub const MARKER_VERSION_NUMBER: &str = "version_number";
+
+/// Data rows covered by validations unless the caller asks for more
+pub const DEFAULT_WORKBOOK_ROWS: u32 = 1000;
+
+/// Upper bound on data rows covered by validations
+pub const MAX_WORKBOOK_ROWS: u32 = 50_000;
+
+/// Column of the marker sheet holding the first dropdown list
+const FIRST_LIST_COLUMN: u16 = 3;
+
+/// Values a boolean field offers in its dropdown
+const BOOLEAN_VALUES: [&str; 2] = ["Yes", "No"];
+
+/// Excel's limits on the text of a validation prompt
+const INPUT_TITLE_MAX_CHARS: usize = 32;
+const INPUT_MESSAGE_MAX_CHARS: usize = 255;
+
+/// A generated workbook and the name to download it under
+pub struct SubmissionWorkbook {
+    pub filename: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Build a blank submission workbook for one version of a template. The
+/// data sheet has a locked header row named after the fields, with
+/// validations on `rows` rows below it.
+pub fn build_submission_workbook(
+    template: &Template,
+    version: &TemplateVersion,
+    rows: u32,
+) -> Result<SubmissionWorkbook, XlsxError> {
+    build_workbook(template, version, rows, None)
+}
+
+/// Write a rejected submission back into its template's workbook, with the
+/// cells at fault highlighted and noted and every problem listed on a sheet
+/// of its own. Fixing the cells and uploading the file again resubmits it.
+pub fn build_annotated_workbook(
+    template: &Template,
+    version: &TemplateVersion,
+    sheet: &SubmittedSheet,
+    issues: &[SubmissionIssue],
+) -> Result<SubmissionWorkbook, XlsxError> {
+    let last_row = sheet
+        .rows
+        .iter()
+        .map(|row| row.row_number)
+        .max()
+        .unwrap_or(0);
+    build_workbook(
+        template,
+        version,
+        last_row.max(DEFAULT_WORKBOOK_ROWS),
+        Some((sheet, issues)),
+    )
+}
+
+fn build_workbook(
+    template: &Template,
+    version: &TemplateVersion,
+    rows: u32,
+    submission: Option<(&SubmittedSheet, &[SubmissionIssue])>,
+) -> Result<SubmissionWorkbook, XlsxError> {
+    let mut workbook = Workbook::new();
+    let last_row = rows.clamp(1, MAX_WORKBOOK_ROWS);
+
+    let mut marker = Worksheet::new();
+    marker.set_name(MARKER_SHEET)?;
+    marker.write_string(0, 0, MARKER_TEMPLATE_ID)?;
+    marker.write_string(0, 1, template.id.to_string())?;
+    marker.write_string(1, 0, MARKER_VERSION_ID)?;
+    marker.write_string(1, 1, version.id.to_string())?;
+    marker.write_string(2, 0, MARKER_VERSION_NUMBER)?;
+    marker.write_number(2, 1, version.version_number)?;
+
+    let header = Format::new()
+        .set_bold()
+        .set_locked()
+        .set_background_color("#D9E1F2")
+        .set_border_bottom(FormatBorder::Thin);
+    let required_header = header.clone().set_font_color("#C00000");
+    let unlocked = Format::new().set_unlocked();
+    let date = unlocked.clone().set_num_format("yyyy-mm-dd");
+
+    let mut data = Worksheet::new();
+    data.set_name(DATA_SHEET)?;
+    let mut list_column = FIRST_LIST_COLUMN;
+    for (index, field) in version.fields.iter().enumerate() {
+        let col = index as u16;
+        let column_format = match field.field_type {
+            FieldType::Date => &date,
+            _ => &unlocked,
+        };
+        data.set_column_format(col, column_format)?;
+        data.set_column_width(col, (field.name.chars().count() + 4).clamp(12, 40) as f64)?;
+        data.write_string_with_format(
+            0,
+            col,
+            &field.name,
+            if field.required {
+                &required_header
+            } else {
+                &header
+            },
+        )?;
+
+        let validation = match field_validation(field)? {
+            Some(validation) => validation,
+            None => {
+                // Too long to inline: list the values on the marker sheet
+                let values = dropdown_values(field);
+                for (row, value) in values.iter().enumerate() {
+                    marker.write_string(row as u32, list_column, *value)?;
+                }
+                let letter = column_letter(list_column);
+                list_column += 1;
+                DataValidation::new().allow_list_formula(Formula::new(format!(
+                    "='{}'!${}$1:${}${}",
+                    MARKER_SHEET,
+                    letter,
+                    letter,
+                    values.len()
+                )))
+            }
+        };
+        let validation = with_prompt(validation, field)?;
+        data.add_data_validation(1, col, last_row, col, &validation)?;
+    }
+    if let Some((sheet, issues)) = submission {
+        fill_submission(&mut data, version, sheet, issues)?;
+    }
+    data.set_freeze_panes(1, 0)?;
+    data.protect_with_options(&ProtectionOptions {
+        format_columns: true,
+        ..ProtectionOptions::new()
+    });
+    data.set_active(true);
+
+    let instructions = instructions_sheet(template, version)?;
+    marker.set_very_hidden(true);
+
+    workbook.push_worksheet(data);
+    if let Some((_, issues)) = submission {
+        workbook.push_worksheet(problems_sheet(issues)?);
+    }
+    workbook.push_worksheet(instructions);
+    workbook.push_worksheet(marker);
+
+    Ok(SubmissionWorkbook {
+        filename: format!(
+            "{}_v{}{}.xlsx",
+            safe_filename(&template.name),
+            version.version_number,
+            if submission.is_some() {
+                "_problems"
+            } else {
+                ""
+            }
+        ),
+        bytes: workbook.save_to_buffer()?,
+    })
+}
+
+/// Write submitted cells under the fields they were given for, keeping
+/// their row numbers so the problems still point at them
+fn fill_submission(
+    data: &mut Worksheet,
+    version: &TemplateVersion,
+    sheet: &SubmittedSheet,
+    issues: &[SubmissionIssue],
+) -> Result<(), XlsxError> {
+    let unlocked = Format::new().set_unlocked();
+    let date = unlocked.clone().set_num_format("yyyy-mm-dd");
+    let flagged = unlocked
+        .clone()
+        .set_background_color("#FFC7CE")
+        .set_font_color("#9C0006");
+    let flagged_date = flagged.clone().set_num_format("yyyy-mm-dd");
+
+    let mut messages: BTreeMap<(u32, &str), Vec<&str>> = BTreeMap::new();
+    for issue in issues {
+        if let (Some(row_number), Some(field)) = (issue.row_number, &issue.field) {
+            messages
+                .entry((row_number, field.as_str()))
+                .or_default()
+                .push(&issue.message);
+        }
+    }
+
+    for (index, field) in version.fields.iter().enumerate() {
+        let col = index as u16;
+        let column = sheet.column(&field.name);
+        for row in &sheet.rows {
+            let row_index = row.row_number - 1;
+            let value = sheet.cell(row, column);
+            let problems = messages.get(&(row.row_number, field.name.as_str()));
+            let format = match (problems.is_some(), field.field_type) {
+                (false, FieldType::Date) => &date,
+                (false, _) => &unlocked,
+                (true, FieldType::Date) => &flagged_date,
+                (true, _) => &flagged,
+            };
+
+            match field.field_type {
+                FieldType::Integer | FieldType::Number if field.field_type.accepts(value) => {
+                    data.write_number_with_format(
+                        row_index,
+                        col,
+                        value.parse::<f64>().unwrap_or_default(),
+                        format,
+                    )?;
+                }
+                FieldType::Date if field.field_type.accepts(value) => {
+                    data.write_date_with_format(
+                        row_index,
+                        col,
+                        &ExcelDateTime::parse_from_str(value)?,
+                        format,
+                    )?;
+                }
+                _ => {
+                    data.write_string_with_format(row_index, col, value, format)?;
+                }
+            }
+            if let Some(problems) = problems {
+                data.insert_note(row_index, col, &Note::new(problems.join("\n")))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn problems_sheet(issues: &[SubmissionIssue]) -> Result<Worksheet, XlsxError> {
+    let bold = Format::new().set_bold();
+    let mut sheet = Worksheet::new();
+    sheet.set_name(PROBLEMS_SHEET)?;
+    for (col, name) in ["Row", "Field", "Problem"].iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *name, &bold)?;
+    }
+    for (index, issue) in issues.iter().enumerate() {
+        let row = index as u32 + 1;
+        if let Some(row_number) = issue.row_number {
+            sheet.write_number(row, 0, row_number)?;
+        }
+        sheet.write_string(row, 1, issue.field.as_deref().unwrap_or(""))?;
+        sheet.write_string(row, 2, &issue.message)?;
+    }
+    sheet.set_column_width(1, 24)?;
+    sheet.set_column_width(2, 80)?;
+    Ok(sheet)
+}
+
+/// Validation for a field's type, or `None` for a dropdown whose values are
+/// too long to inline and must be listed on a sheet
+fn field_validation(field: &TemplateField) -> Result<Option<DataValidation>, XlsxError> {
+    let values = dropdown_values(field);
+    if !values.is_empty() {
+        let inline = values.iter().map(|value| value.len() + 1).sum::<usize>() <= 256;
+        return Ok(match inline {
+            true => Some(DataValidation::new().allow_list_strings(&values)?),
+            false => None,
+        });
+    }
+
+    Ok(Some(match field.field_type {
+        FieldType::Text => DataValidation::new().allow_any_value(),
+        FieldType::Integer => DataValidation::new()
+            .allow_whole_number(DataValidationRule::Between(i32::MIN, i32::MAX)),
+        FieldType::Number => DataValidation::new()
+            .allow_decimal_number(DataValidationRule::Between(f64::MIN, f64::MAX)),
+        FieldType::Date => DataValidation::new().allow_date(DataValidationRule::Between(
+            ExcelDateTime::from_ymd(1900, 1, 1)?,
+            ExcelDateTime::from_ymd(9999, 12, 31)?,
+        )),
+        // Boolean fields always have dropdown values
+        FieldType::Boolean => unreachable!(),
+    }))
+}
+
+fn dropdown_values(field: &TemplateField) -> Vec<&str> {
+    if !field.allowed_values.is_empty() {
+        field.allowed_values.iter().map(String::as_str).collect()
+    } else if field.field_type == FieldType::Boolean {
+        BOOLEAN_VALUES.to_vec()
+    } else {
+        Vec::new()
+    }
+}
+
+/// Show the field's help, unit and whether it is required when its cells
+/// are selected
+fn with_prompt(
+    validation: DataValidation,
+    field: &TemplateField,
+) -> Result<DataValidation, XlsxError> {
+    let mut lines = Vec::new();
+    if field.required {
+        lines.push("Required.".to_string());
+    }
+    if let Some(unit) = &field.unit {
+        lines.push(format!("Unit: {}", unit));
+    }
+    if let Some(help) = &field.help_text {
+        lines.push(help.clone());
+    }
+    if lines.is_empty() {
+        return Ok(validation);
+    }
+
+    validation
+        .set_input_title(truncate(&field.name, INPUT_TITLE_MAX_CHARS))?
+        .set_input_message(truncate(&lines.join("\n"), INPUT_MESSAGE_MAX_CHARS))
+}
+
+fn instructions_sheet(
+    template: &Template,
+    version: &TemplateVersion,
+) -> Result<Worksheet, XlsxError> {
+    let title = Format::new().set_bold().set_font_size(14);
+    let bold = Format::new().set_bold();
+    let wrap = Format::new().set_text_wrap();
+
+    let mut sheet = Worksheet::new();
+    sheet.set_name(INSTRUCTIONS_SHEET)?;
+    sheet.write_string_with_format(0, 0, &template.name, &title)?;
+    sheet.write_string(
+        1,
+        0,
+        format!(
+            "Template version {}. Fill in one sample per row on the '{}' sheet and keep \
+             the header row as it is; red columns are required.",
+            version.version_number, DATA_SHEET
+        ),
+    )?;
+    if let Some(description) = &template.description {
+        sheet.write_string(2, 0, description)?;
+    }
+
+    let headers = [
+        "Field",
+        "Type",
+        "Required",
+        "Unit",
+        "Allowed values",
+        "Help",
+    ];
+    for (col, name) in headers.iter().enumerate() {
+        sheet.write_string_with_format(4, col as u16, *name, &bold)?;
+    }
+    for (index, field) in version.fields.iter().enumerate() {
+        let row = 5 + index as u32;
+        sheet.write_string(row, 0, &field.name)?;
+        sheet.write_string(row, 1, format!("{:?}", field.field_type).to_lowercase())?;
+        sheet.write_string(row, 2, if field.required { "Yes" } else { "No" })?;
+        sheet.write_string(row, 3, field.unit.as_deref().unwrap_or(""))?;
+        sheet.write_string_with_format(row, 4, dropdown_values(field).join(", "), &wrap)?;
+        sheet.write_string_with_format(row, 5, field.help_text.as_deref().unwrap_or(""), &wrap)?;
+    }
+    for (col, width) in [24, 10, 10, 10, 40, 60].into_iter().enumerate() {
+        sheet.set_column_width(col as u16, width)?;
+    }
+    Ok(sheet)
+}
+
+fn column_letter(col: u16) -> String {
+    let mut col = col as u32 + 1;
+    let mut letters = Vec::new();
+    while col > 0 {
+        let rem = (col - 1) % 26;
+        letters.push((b'A' + rem as u8) as char);
+        col = (col - 1) / 26;
+    }
+    letters.iter().rev().collect()
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    text.chars().take(max_chars).collect()
+}
+
+fn safe_filename(name: &str) -> String {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || c == '-' || c == '_' => c,
+            _ => '_',
+        })
+        .collect();
+    if name.is_empty() {
+        "template".to_string()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{open_workbook_auto_from_rs, Reader};
+    use chrono::Utc;
+    use serde_json::json;
+    use std::io::Cursor;
+    use uuid::Uuid;
+
+    fn template_version(fields: serde_json::Value) -> (Template, TemplateVersion) {
+        let template = Template {
+            id: Uuid::new_v4(),
+            name: "RNA submission".to_string(),
+            description: Some("Samples for RNA-seq".to_string()),
+            file_path: String::new(),
+            file_type: "xlsx".to_string(),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            metadata: json!({}),
+        };
+        let version = TemplateVersion {
+            id: Uuid::new_v4(),
+            template_id: template.id,
+            version_number: 3,
+            fields: serde_json::from_value(fields).unwrap(),
+            changes: Vec::new(),
+            changelog: None,
+            created_by: None,
+            created_at: Utc::now(),
+        };
+        (template, version)
+    }
+
+    #[test]
+    fn test_workbook_has_header_instructions_and_version_marker() {
+        let long_values: Vec<String> = (0..40).map(|i| format!("Buffer number {}", i)).collect();
+        let (template, version) = template_version(json!([
+            {"name": "Sample", "required": true, "help_text": "Your sample name"},
+            {"name": "Volume", "type": "number", "unit": "uL"},
+            {"name": "Type", "allowed_values": ["DNA", "RNA"]},
+            {"name": "Buffer", "allowed_values": long_values},
+            {"name": "Collected", "type": "date"},
+            {"name": "Pooled", "type": "boolean"}
+        ]));
+
+        let workbook = build_submission_workbook(&template, &version, 100).unwrap();
+        assert_eq!(workbook.filename, "RNA_submission_v3.xlsx");
+
+        let mut sheets = open_workbook_auto_from_rs(Cursor::new(workbook.bytes)).unwrap();
+        assert_eq!(
+            sheets.sheet_names(),
+            [DATA_SHEET, INSTRUCTIONS_SHEET, MARKER_SHEET]
+        );
+
+        let data = sheets.worksheet_range(DATA_SHEET).unwrap().unwrap();
+        let headers: Vec<String> = data
+            .rows()
+            .next()
+            .unwrap()
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(
+            headers,
+            ["Sample", "Volume", "Type", "Buffer", "Collected", "Pooled"]
+        );
+
+        let marker = sheets.worksheet_range(MARKER_SHEET).unwrap().unwrap();
+        let value = |row: u32, col: u32| marker.get_value((row, col)).unwrap().to_string();
+        assert_eq!(value(0, 1), template.id.to_string());
+        assert_eq!(value(1, 1), version.id.to_string());
+        assert_eq!(value(2, 1), "3");
+        // The long dropdown is listed on the marker sheet
+        assert_eq!(value(39, FIRST_LIST_COLUMN as u32), "Buffer number 39");
+
+        let instructions = sheets.worksheet_range(INSTRUCTIONS_SHEET).unwrap().unwrap();
+        assert_eq!(instructions.get_value((8, 4)).unwrap().to_string(), "Buffer number 0, Buffer number 1, Buffer number 2, Buffer number 3, Buffer number 4, Buffer number 5, Buffer number 6, Buffer number 7, Buffer number 8, Buffer number 9, Buffer number 10, Buffer number 11, Buffer number 12, Buffer number 13, Buffer number 14, Buffer number 15, Buffer number 16, Buffer number 17, Buffer number 18, Buffer number 19, Buffer number 20, Buffer number 21, Buffer number 22, Buffer number 23, Buffer number 24, Buffer number 25, Buffer number 26, Buffer number 27, Buffer number 28, Buffer number 29, Buffer number 30, Buffer number 31, Buffer number 32, Buffer number 33, Buffer number 34, Buffer number 35, Buffer number 36, Buffer number 37, Buffer number 38, Buffer number 39");
+        assert_eq!(instructions.get_value((6, 3)).unwrap().to_string(), "uL");
+    }
+
+    #[test]
+    fn test_column_letters() {
+        assert_eq!(column_letter(0), "A");
+        assert_eq!(column_letter(25), "Z");
+        assert_eq!(column_letter(26), "AA");
+        assert_eq!(column_letter(701), "ZZ");
+    }
+}
@@ -0,0 +1,656 @@
+use flate2::read::DeflateDecoder;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader as XmlReader;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use zip::result::ZipError;
+use zip::{CompressionMethod, ZipArchive};
+
+use crate::services::spreadsheet_ingest::{IngestError, RowStream};
+
+/// A workbook source that can be opened more than once
+pub trait WorkbookInput: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> WorkbookInput for T {}
+
+/// A worksheet row: its zero-based index and the cells holding a value,
+/// keyed by zero-based column
+type SheetRow = (u32, Vec<(u32, String)>);
+
+/// XLSX worksheet rows parsed straight from the compressed sheet, so memory
+/// use does not grow with the number of rows. The sheet is read twice: once
+/// to find the used range, which is the one calamine reports, and once for
+/// the rows. Shared strings are loaded up front.
+pub struct XlsxRowStream {
+    rows: SheetRows<BufReader<Box<dyn Read + Send>>>,
+    strings: Vec<String>,
+    headers: Vec<String>,
+    first_column: u32,
+    /// Index of the first data row, just below the header
+    first_row: u32,
+    pending: Option<SheetRow>,
+    total_rows: usize,
+    rows_read: usize,
+}
+
+impl XlsxRowStream {
+    pub fn new<F>(open: F, sheet_name: Option<&str>) -> Result<Self, IngestError>
+    where
+        F: Fn() -> std::io::Result<Box<dyn WorkbookInput>>,
+    {
+        let mut package = XlsxPackage::open(open()?)?;
+        let path = package.sheet_path(sheet_name)?;
+        let strings = package.shared_strings()?;
+
+        let mut used: Option<UsedRange> = None;
+        let part = {
+            let file = package.archive.by_name(&path)?;
+            let part = PartLocation {
+                data_start: file.data_start(),
+                compressed_size: file.compressed_size(),
+                compression: file.compression(),
+            };
+            let mut rows = SheetRows::new(BufReader::new(file));
+            while let Some((row, cells)) = rows.next(&strings)? {
+                let used = used.get_or_insert(UsedRange {
+                    first_row: row,
+                    last_row: row,
+                    first_column: u32::MAX,
+                    last_column: 0,
+                });
+                used.last_row = row;
+                for (column, _) in &cells {
+                    used.first_column = used.first_column.min(*column);
+                    used.last_column = used.last_column.max(*column);
+                }
+            }
+            part
+        };
+
+        let mut stream = Self {
+            rows: SheetRows::new(open_part(open()?, &part)?),
+            strings,
+            headers: Vec::new(),
+            first_column: 0,
+            first_row: 0,
+            pending: None,
+            total_rows: 0,
+            rows_read: 0,
+        };
+        if let Some(used) = used {
+            stream.first_column = used.first_column;
+            stream.first_row = used.first_row + 1;
+            stream.total_rows = (used.last_row - used.first_row) as usize;
+            let width = (used.last_column - used.first_column + 1) as usize;
+            stream.headers = vec![String::new(); width];
+            let header = stream.row_at(used.first_row)?;
+            stream.headers = stream.spread(header);
+        }
+        Ok(stream)
+    }
+
+    /// Cells of the row at `index`, empty when the sheet has no such row
+    fn row_at(&mut self, index: u32) -> Result<Vec<(u32, String)>, IngestError> {
+        loop {
+            match &self.pending {
+                Some((row, _)) if *row >= index => break,
+                _ => match self.rows.next(&self.strings)? {
+                    Some(row) => self.pending = Some(row),
+                    None => {
+                        self.pending = None;
+                        break;
+                    }
+                },
+            }
+        }
+
+        Ok(match self.pending.take() {
+            Some((row, cells)) if row == index => cells,
+            other => {
+                self.pending = other;
+                Vec::new()
+            }
+        })
+    }
+
+    /// Lay a row's cells out across the used columns
+    fn spread(&self, cells: Vec<(u32, String)>) -> Vec<String> {
+        let mut row = vec![String::new(); self.headers.len()];
+        for (column, text) in cells {
+            if let Some(slot) = column
+                .checked_sub(self.first_column)
+                .and_then(|offset| row.get_mut(offset as usize))
+            {
+                *slot = text;
+            }
+        }
+        row
+    }
+}
+
+impl RowStream for XlsxRowStream {
+    fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    fn total_rows(&self) -> Option<usize> {
+        Some(self.total_rows)
+    }
+
+    fn progress_percent(&self) -> Option<f32> {
+        let total = self.total_rows().filter(|total| *total > 0)?;
+        Some((self.rows_read as f64 / total as f64 * 100.0).min(100.0) as f32)
+    }
+
+    fn next_row(&mut self) -> Option<Result<Vec<String>, IngestError>> {
+        if self.rows_read >= self.total_rows {
+            return None;
+        }
+        let index = self.first_row + self.rows_read as u32;
+        self.rows_read += 1;
+        Some(self.row_at(index).map(|cells| self.spread(cells)))
+    }
+}
+
+/// Sheet names of an XLSX workbook, in workbook order
+pub fn xlsx_sheet_names<RS: Read + Seek>(input: RS) -> Result<Vec<String>, IngestError> {
+    Ok(XlsxPackage::open(input)?
+        .sheets
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect())
+}
+
+/// Rows and columns spanned by the cells holding a value
+struct UsedRange {
+    first_row: u32,
+    last_row: u32,
+    first_column: u32,
+    last_column: u32,
+}
+
+/// Where a part's compressed bytes sit in the archive
+struct PartLocation {
+    data_start: u64,
+    compressed_size: u64,
+    compression: CompressionMethod,
+}
+
+/// The parts of an XLSX archive needed to find a worksheet
+struct XlsxPackage<RS: Read + Seek> {
+    archive: ZipArchive<RS>,
+    /// Sheet names with the archive path of each sheet
+    sheets: Vec<(String, String)>,
+}
+
+impl<RS: Read + Seek> XlsxPackage<RS> {
+    fn open(input: RS) -> Result<Self, IngestError> {
+        let mut archive = ZipArchive::new(input)?;
+        let relationships = read_relationships(&mut archive)?;
+        let sheets = read_sheets(&mut archive, &relationships)?;
+        Ok(Self { archive, sheets })
+    }
+
+    /// Archive path of the named sheet, or of the first one
+    fn sheet_path(&self, sheet_name: Option<&str>) -> Result<String, IngestError> {
+        match sheet_name {
+            Some(name) => self
+                .sheets
+                .iter()
+                .find(|(sheet, _)| sheet == name)
+                .map(|(_, path)| path.clone())
+                .ok_or_else(|| IngestError::SheetNotFound(name.to_string())),
+            None => self
+                .sheets
+                .first()
+                .map(|(_, path)| path.clone())
+                .ok_or(IngestError::NoSheets),
+        }
+    }
+
+    fn shared_strings(&mut self) -> Result<Vec<String>, IngestError> {
+        let mut strings = Vec::new();
+        let Some(mut xml) = read_part(&mut self.archive, "xl/sharedStrings.xml")? else {
+            return Ok(strings);
+        };
+        let mut buf = Vec::new();
+        loop {
+            match xml.read_event_into(&mut buf)? {
+                Event::Start(e) if e.local_name().as_ref() == b"si" => {
+                    strings.push(read_string(&mut xml, b"si")?.unwrap_or_default());
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+            buf.clear();
+        }
+        Ok(strings)
+    }
+}
+
+/// Workbook relationship targets by id
+fn read_relationships<RS: Read + Seek>(
+    archive: &mut ZipArchive<RS>,
+) -> Result<HashMap<String, String>, IngestError> {
+    let mut buf = Vec::new();
+    let mut relationships = HashMap::new();
+    let mut xml = read_part(archive, "xl/_rels/workbook.xml.rels")?
+        .ok_or_else(|| IngestError::Workbook("Workbook relationships are missing".to_string()))?;
+    loop {
+        match xml.read_event_into(&mut buf)? {
+            Event::Start(e) if e.local_name().as_ref() == b"Relationship" => {
+                let id = attribute(&xml, &e, b"Id")?;
+                if let (Some(id), Some(target)) = (id, attribute(&xml, &e, b"Target")?) {
+                    relationships.insert(id, target);
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(relationships)
+}
+
+/// Sheet names with the archive path of each sheet, in workbook order
+fn read_sheets<RS: Read + Seek>(
+    archive: &mut ZipArchive<RS>,
+    relationships: &HashMap<String, String>,
+) -> Result<Vec<(String, String)>, IngestError> {
+    let mut buf = Vec::new();
+    let mut sheets = Vec::new();
+    let mut xml = read_part(archive, "xl/workbook.xml")?
+        .ok_or_else(|| IngestError::Workbook("Workbook part is missing".to_string()))?;
+    loop {
+        match xml.read_event_into(&mut buf)? {
+            Event::Start(e) if e.local_name().as_ref() == b"sheet" => {
+                let name = attribute(&xml, &e, b"name")?.unwrap_or_default();
+                let target = attribute(&xml, &e, b"id")?
+                    .and_then(|id| relationships.get(&id))
+                    .ok_or_else(|| {
+                        IngestError::Workbook(format!("Sheet '{}' has no part", name))
+                    })?;
+                // Targets are relative to xl/ unless they are absolute
+                let path = match target.strip_prefix('/') {
+                    Some(absolute) => absolute.to_string(),
+                    None if target.starts_with("xl/") => target.clone(),
+                    None => format!("xl/{}", target),
+                };
+                sheets.push((name, path));
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(sheets)
+}
+
+/// Worksheet cells read one row at a time from the `sheetData` element
+struct SheetRows<R: BufRead> {
+    xml: XmlReader<R>,
+    buf: Vec<u8>,
+    in_sheet_data: bool,
+    finished: bool,
+    /// Index given to a row that does not state its own
+    next_index: u32,
+}
+
+impl<R: BufRead> SheetRows<R> {
+    fn new(input: R) -> Self {
+        Self {
+            xml: xml_reader(input),
+            buf: Vec::new(),
+            in_sheet_data: false,
+            finished: false,
+            next_index: 0,
+        }
+    }
+
+    /// The next row holding at least one value
+    fn next(&mut self, strings: &[String]) -> Result<Option<SheetRow>, IngestError> {
+        let mut index = self.next_index;
+        let mut column = 0;
+        let mut cells = Vec::new();
+        while !self.finished {
+            self.buf.clear();
+            match self.xml.read_event_into(&mut self.buf)? {
+                Event::Start(e) if !self.in_sheet_data => {
+                    self.in_sheet_data = e.local_name().as_ref() == b"sheetData";
+                }
+                Event::Start(e) if e.local_name().as_ref() == b"row" => {
+                    if let Some(r) = attribute(&self.xml, &e, b"r")? {
+                        index = cell_position(r.as_bytes())?.0;
+                    }
+                    column = 0;
+                    cells.clear();
+                }
+                Event::End(e) if e.local_name().as_ref() == b"row" => {
+                    self.next_index = index + 1;
+                    if !cells.is_empty() {
+                        return Ok(Some((index, cells)));
+                    }
+                }
+                Event::Start(e) if e.local_name().as_ref() == b"c" => {
+                    if let Some(r) = attribute(&self.xml, &e, b"r")? {
+                        (index, column) = cell_position(r.as_bytes())?;
+                    }
+                    let kind = attribute(&self.xml, &e, b"t")?;
+                    if let Some(text) = read_cell(&mut self.xml, kind.as_deref(), strings)? {
+                        cells.push((column, text));
+                    }
+                    column += 1;
+                }
+                Event::End(e) if e.local_name().as_ref() == b"sheetData" => {
+                    self.finished = true;
+                }
+                Event::Eof if self.in_sheet_data => {
+                    return Err(IngestError::Workbook(
+                        "Worksheet ended inside its data".to_string(),
+                    ));
+                }
+                Event::Eof => self.finished = true,
+                _ => (),
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Text of a cell, or `None` when it holds no value
+fn read_cell<R: BufRead>(
+    xml: &mut XmlReader<R>,
+    kind: Option<&str>,
+    strings: &[String],
+) -> Result<Option<String>, IngestError> {
+    let mut buf = Vec::new();
+    let mut text = None;
+    loop {
+        match xml.read_event_into(&mut buf)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"is" => text = read_string(xml, b"is")?,
+                b"v" => text = cell_value(kind, read_text(xml, b"v")?, strings)?,
+                b"f" => {
+                    xml.read_to_end_into(e.name(), &mut Vec::new())?;
+                }
+                other => {
+                    return Err(IngestError::Workbook(format!(
+                        "Unexpected <{}> in a worksheet cell",
+                        String::from_utf8_lossy(other)
+                    )))
+                }
+            },
+            Event::End(e) if e.local_name().as_ref() == b"c" => return Ok(text),
+            Event::Eof => {
+                return Err(IngestError::Workbook(
+                    "Worksheet ended inside a cell".to_string(),
+                ))
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+}
+
+/// Cell text for a `<v>` value, formatted the way calamine displays it
+fn cell_value(
+    kind: Option<&str>,
+    value: String,
+    strings: &[String],
+) -> Result<Option<String>, IngestError> {
+    Ok(Some(match kind {
+        Some("s") => value
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| strings.get(index).cloned())
+            .ok_or_else(|| IngestError::Workbook(format!("Unknown shared string '{}'", value)))?,
+        Some("b") => (value != "0").to_string(),
+        Some("n") if value.is_empty() => return Ok(None),
+        Some("n") => value
+            .parse::<f64>()
+            .map_err(|_| IngestError::Workbook(format!("Invalid number '{}'", value)))?
+            .to_string(),
+        Some("e" | "d" | "inlineStr") => value,
+        // Formula results and untyped cells are numbers when they parse as one
+        _ => value.parse::<f64>().map(|n| n.to_string()).unwrap_or(value),
+    }))
+}
+
+/// Text of a shared or inline string: its rich text runs joined, without
+/// phonetic hints. `None` when it has no text element.
+fn read_string<R: BufRead>(
+    xml: &mut XmlReader<R>,
+    closing: &[u8],
+) -> Result<Option<String>, IngestError> {
+    let mut buf = Vec::new();
+    let mut text: Option<String> = None;
+    let mut phonetic = false;
+    loop {
+        match xml.read_event_into(&mut buf)? {
+            Event::Start(e) if e.local_name().as_ref() == b"rPh" => phonetic = true,
+            Event::End(e) if e.local_name().as_ref() == b"rPh" => phonetic = false,
+            Event::Start(e) if e.local_name().as_ref() == b"t" && !phonetic => {
+                let run = read_text(xml, b"t")?;
+                text.get_or_insert_with(String::new).push_str(&run);
+            }
+            Event::End(e) if e.local_name().as_ref() == closing => return Ok(text),
+            Event::Eof => {
+                return Err(IngestError::Workbook(format!(
+                    "Workbook ended inside <{}>",
+                    String::from_utf8_lossy(closing)
+                )))
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+}
+
+/// Unescaped text up to the end of the current element
+fn read_text<R: BufRead>(xml: &mut XmlReader<R>, closing: &[u8]) -> Result<String, IngestError> {
+    let mut buf = Vec::new();
+    let mut text = String::new();
+    loop {
+        match xml.read_event_into(&mut buf)? {
+            Event::Text(t) => text.push_str(&t.unescape()?),
+            Event::End(e) if e.local_name().as_ref() == closing => return Ok(text),
+            Event::Eof => {
+                return Err(IngestError::Workbook(format!(
+                    "Workbook ended inside <{}>",
+                    String::from_utf8_lossy(closing)
+                )))
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+}
+
+/// Value of the attribute with the given local name
+fn attribute<R>(
+    xml: &XmlReader<R>,
+    element: &BytesStart<'_>,
+    name: &[u8],
+) -> Result<Option<String>, IngestError> {
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(quick_xml::Error::from)?;
+        if attribute.key.local_name().as_ref() == name {
+            return Ok(Some(attribute.decode_and_unescape_value(xml)?.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+/// Zero-based row and column of a reference such as "B12". A row reference
+/// such as "12" has column 0.
+fn cell_position(reference: &[u8]) -> Result<(u32, u32), IngestError> {
+    let invalid = || {
+        IngestError::Workbook(format!(
+            "Invalid cell reference '{}'",
+            String::from_utf8_lossy(reference)
+        ))
+    };
+    let split = reference
+        .iter()
+        .position(|c| c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (letters, digits) = reference.split_at(split);
+
+    let mut column: u32 = 0;
+    for letter in letters {
+        if !letter.is_ascii_alphabetic() {
+            return Err(invalid());
+        }
+        column = column * 26 + (letter.to_ascii_uppercase() - b'A') as u32 + 1;
+    }
+    let row: u32 = std::str::from_utf8(digits)
+        .ok()
+        .and_then(|digits| digits.parse().ok())
+        .ok_or_else(invalid)?;
+
+    Ok((
+        row.checked_sub(1).ok_or_else(invalid)?,
+        column.saturating_sub(1),
+    ))
+}
+
+fn xml_reader<R: BufRead>(input: R) -> XmlReader<R> {
+    let mut xml = XmlReader::from_reader(input);
+    xml.trim_text(false)
+        .check_end_names(false)
+        .expand_empty_elements(true);
+    xml
+}
+
+/// A part of the archive as XML, or `None` when the archive lacks it
+fn read_part<'a, RS: Read + Seek>(
+    archive: &'a mut ZipArchive<RS>,
+    path: &str,
+) -> Result<Option<XmlReader<BufReader<zip::read::ZipFile<'a>>>>, IngestError> {
+    match archive.by_name(path) {
+        Ok(file) => Ok(Some(xml_reader(BufReader::new(file)))),
+        Err(ZipError::FileNotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A part's contents read through its own handle on the workbook, so the
+/// reader does not borrow the archive
+fn open_part(
+    mut input: Box<dyn WorkbookInput>,
+    part: &PartLocation,
+) -> Result<BufReader<Box<dyn Read + Send>>, IngestError> {
+    input.seek(SeekFrom::Start(part.data_start))?;
+    let compressed = input.take(part.compressed_size);
+    let data: Box<dyn Read + Send> = match part.compression {
+        CompressionMethod::Stored => Box::new(compressed),
+        CompressionMethod::Deflated => Box::new(DeflateDecoder::new(compressed)),
+        other => {
+            return Err(IngestError::Workbook(format!(
+                "Unsupported compression in workbook: {}",
+                other
+            )))
+        }
+    };
+    Ok(BufReader::new(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::spreadsheet_ingest::ExcelRowStream;
+    use calamine::{open_workbook_auto_from_rs, Reader};
+    use rust_xlsxwriter::Workbook;
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    fn stream(data: &Arc<[u8]>, sheet_name: Option<&str>) -> XlsxRowStream {
+        XlsxRowStream::new(
+            || Ok(Box::new(Cursor::new(data.clone())) as Box<dyn WorkbookInput>),
+            sheet_name,
+        )
+        .unwrap()
+    }
+
+    fn rows(mut stream: impl RowStream) -> Vec<Vec<String>> {
+        let mut rows = vec![stream.headers().to_vec()];
+        while let Some(row) = stream.next_row() {
+            rows.push(row.unwrap());
+        }
+        rows
+    }
+
+    #[test]
+    fn test_rows_match_calamine() {
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("Samples").unwrap();
+        sheet.write_string(1, 1, "Barcode").unwrap();
+        sheet.write_string(1, 2, "Volume").unwrap();
+        sheet.write_string(1, 3, "Passed").unwrap();
+        sheet.write_string(2, 1, "LAB-001").unwrap();
+        sheet.write_number(2, 2, 12.5).unwrap();
+        sheet.write_boolean(2, 3, true).unwrap();
+        // Row 4 is blank and row 5 leaves a gap before its last cell
+        sheet.write_string(4, 1, "LAB-<&>").unwrap();
+        sheet.write_number(4, 4, 3.0).unwrap();
+        sheet.write_formula(5, 2, "=C3*2").unwrap();
+        let data: Arc<[u8]> = workbook.save_to_buffer().unwrap().into();
+
+        let mut calamine = open_workbook_auto_from_rs(Cursor::new(data.clone())).unwrap();
+        let range = calamine.worksheet_range("Samples").unwrap().unwrap();
+        let expected = rows(ExcelRowStream::new(range));
+
+        let streamed = stream(&data, Some("Samples"));
+        assert_eq!(streamed.total_rows(), Some(4));
+        let streamed = rows(streamed);
+        assert_eq!(streamed, expected);
+        assert_eq!(streamed[0], vec!["Barcode", "Volume", "Passed", ""]);
+        assert_eq!(streamed[1], vec!["LAB-001", "12.5", "true", ""]);
+        assert_eq!(streamed[2], vec!["", "", "", ""]);
+        assert_eq!(streamed[3], vec!["LAB-<&>", "", "", "3"]);
+    }
+
+    #[test]
+    fn test_inline_strings_and_sheet_selection() {
+        let mut workbook = Workbook::new();
+        workbook.add_worksheet().set_name("Empty").unwrap();
+        let sheet = workbook.add_worksheet_with_constant_memory();
+        sheet.set_name("Data").unwrap();
+        sheet.write_string(0, 0, "Name").unwrap();
+        sheet.write_string(1, 0, "Plasma").unwrap();
+        let data: Arc<[u8]> = workbook.save_to_buffer().unwrap().into();
+
+        assert_eq!(
+            xlsx_sheet_names(Cursor::new(data.clone())).unwrap(),
+            vec!["Empty", "Data"]
+        );
+        assert_eq!(
+            rows(stream(&data, Some("Data"))),
+            vec![vec!["Name"], vec!["Plasma"]]
+        );
+
+        let empty = stream(&data, None);
+        assert_eq!(empty.total_rows(), Some(0));
+        assert_eq!(rows(empty), vec![Vec::<String>::new()]);
+
+        assert!(matches!(
+            XlsxRowStream::new(
+                || Ok(Box::new(Cursor::new(data.clone())) as Box<dyn WorkbookInput>),
+                Some("Missing"),
+            ),
+            Err(IngestError::SheetNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_cell_position() {
+        assert_eq!(cell_position(b"A1").unwrap(), (0, 0));
+        assert_eq!(cell_position(b"C107").unwrap(), (106, 2));
+        assert_eq!(cell_position(b"AA10").unwrap(), (9, 26));
+        assert_eq!(cell_position(b"12").unwrap(), (11, 0));
+        assert!(cell_position(b"A0").is_err());
+        assert!(cell_position(b"B").is_err());
+    }
+}
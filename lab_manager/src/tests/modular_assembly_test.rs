@@ -51,6 +51,7 @@ mod tests {
                 base_path: "/tmp/custom".into(),
                 max_file_size: 50 * 1024 * 1024,
                 allowed_extensions: vec!["csv".to_string()],
+                max_workbook_bytes: 20 * 1024 * 1024,
             },
             server: crate::config::ServerConfig {
                 host: "127.0.0.1".to_string(),
@@ -59,6 +60,8 @@ mod tests {
             },
             rag: crate::config::RagIntegrationConfig::default(),
             shibboleth: crate::config::ShibbolethConfig::default(),
+            reports: crate::config::ReportConfig::default(),
+            smtp: crate::config::SmtpConfig::default(),
         };
 
         assert_eq!(custom_config.server.port, 8080);
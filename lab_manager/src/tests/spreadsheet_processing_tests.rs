@@ -127,6 +127,8 @@ mod spreadsheet_processing_tests {
             sample_filter: None,
             project_filter: None,
             column_filters: None,
+            range_filters: None,
+            in_filters: None,
             limit: Some(10),
             offset: None,
         };
@@ -236,6 +238,8 @@ mod spreadsheet_processing_tests {
             sample_filter: None,
             project_filter: None,
             column_filters: None,
+            range_filters: None,
+            in_filters: None,
             limit: None,
             offset: None,
         };
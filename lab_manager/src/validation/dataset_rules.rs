@@ -0,0 +1,326 @@
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use super::{
+    ErrorSeverity, RuleMetadata, RuleResult, ValidationChain, ValidationError, ValidationRule,
+    ValidationWarning,
+};
+use crate::models::spreadsheet::{DatasetRule, DatasetRuleSpec, RuleSeverity};
+
+/// A spreadsheet row under validation, with its cells keyed by header
+#[derive(Debug, Clone)]
+pub struct SheetRow {
+    pub row_number: i32,
+    pub cells: serde_json::Value,
+}
+
+impl SheetRow {
+    /// Trimmed cell text; empty when the cell is missing
+    pub fn cell(&self, column: &str) -> &str {
+        self.cells
+            .get(column)
+            .and_then(|value| value.as_str())
+            .unwrap_or("")
+            .trim()
+    }
+}
+
+/// Build a chain checking every row against the given rules. Fails on a
+/// rule that cannot be compiled, such as a bad pattern.
+pub fn build_row_chain(specs: &[DatasetRuleSpec]) -> Result<ValidationChain<SheetRow>, String> {
+    let mut chain = ValidationChain::new();
+    for spec in specs {
+        let target = RuleTarget {
+            column: spec.rule.column().to_string(),
+            severity: spec.severity,
+        };
+        let rule: Box<dyn ValidationRule<SheetRow>> = match &spec.rule {
+            DatasetRule::RequiredColumn { .. } => Box::new(RequiredValueRule { target }),
+            DatasetRule::AllowedValues {
+                values,
+                ignore_case,
+                ..
+            } => {
+                if values.is_empty() {
+                    return Err(format!("No allowed values listed for '{}'", target.column));
+                }
+                let values = values
+                    .iter()
+                    .map(|value| fold_case(value.trim(), *ignore_case))
+                    .collect();
+                Box::new(AllowedValuesRule {
+                    target,
+                    values,
+                    ignore_case: *ignore_case,
+                })
+            }
+            DatasetRule::Pattern { pattern, .. } => {
+                let regex = Regex::new(&format!("^(?:{})$", pattern))
+                    .map_err(|e| format!("Invalid pattern for '{}': {}", target.column, e))?;
+                Box::new(PatternRule {
+                    target,
+                    pattern: pattern.clone(),
+                    regex,
+                })
+            }
+            DatasetRule::Unique { .. } => Box::new(UniqueValueRule {
+                target,
+                seen: Mutex::new(HashMap::new()),
+            }),
+            DatasetRule::NumericRange { min, max, .. } => {
+                if matches!((min, max), (Some(min), Some(max)) if min > max) {
+                    return Err(format!("Minimum is above maximum for '{}'", target.column));
+                }
+                Box::new(NumericRangeRule {
+                    target,
+                    min: *min,
+                    max: *max,
+                })
+            }
+        };
+        chain = chain.add_rule(rule);
+    }
+    Ok(chain)
+}
+
+fn fold_case(value: &str, ignore_case: bool) -> String {
+    if ignore_case {
+        value.to_lowercase()
+    } else {
+        value.to_string()
+    }
+}
+
+/// The column a rule reads and how a failure is reported
+struct RuleTarget {
+    column: String,
+    severity: RuleSeverity,
+}
+
+impl RuleTarget {
+    fn pass(&self) -> RuleResult {
+        RuleResult {
+            passed: true,
+            error: None,
+            warning: None,
+        }
+    }
+
+    fn fail(&self, code: &str, message: String) -> RuleResult {
+        let (error, warning) = match self.severity {
+            RuleSeverity::Error => (
+                Some(
+                    ValidationError::new(code.to_string(), message)
+                        .with_field(self.column.clone())
+                        .with_severity(ErrorSeverity::High),
+                ),
+                None,
+            ),
+            RuleSeverity::Warning => (
+                None,
+                Some(
+                    ValidationWarning::new(code.to_string(), message)
+                        .with_field(self.column.clone()),
+                ),
+            ),
+        };
+        RuleResult {
+            passed: false,
+            error,
+            warning,
+        }
+    }
+
+    fn metadata(&self, kind: &str, description: String) -> RuleMetadata {
+        RuleMetadata {
+            name: format!("{}_{}", kind, self.column),
+            description,
+            category: "dataset".to_string(),
+            severity: match self.severity {
+                RuleSeverity::Error => ErrorSeverity::High,
+                RuleSeverity::Warning => ErrorSeverity::Low,
+            },
+        }
+    }
+}
+
+/// Cell must be filled
+struct RequiredValueRule {
+    target: RuleTarget,
+}
+
+impl ValidationRule<SheetRow> for RequiredValueRule {
+    fn applies_to(&self, _row: &SheetRow) -> bool {
+        true
+    }
+
+    fn validate(&self, row: &SheetRow) -> RuleResult {
+        if row.cell(&self.target.column).is_empty() {
+            self.target.fail(
+                "REQUIRED_VALUE",
+                format!("{} is required", self.target.column),
+            )
+        } else {
+            self.target.pass()
+        }
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        self.target.metadata(
+            "required",
+            format!("Validates that {} is filled", self.target.column),
+        )
+    }
+}
+
+/// Filled cell must be one of a fixed list
+struct AllowedValuesRule {
+    target: RuleTarget,
+    values: HashSet<String>,
+    ignore_case: bool,
+}
+
+impl ValidationRule<SheetRow> for AllowedValuesRule {
+    fn applies_to(&self, row: &SheetRow) -> bool {
+        !row.cell(&self.target.column).is_empty()
+    }
+
+    fn validate(&self, row: &SheetRow) -> RuleResult {
+        let value = row.cell(&self.target.column);
+        if self.values.contains(&fold_case(value, self.ignore_case)) {
+            return self.target.pass();
+        }
+        let mut allowed: Vec<&str> = self.values.iter().map(String::as_str).collect();
+        allowed.sort_unstable();
+        self.target.fail(
+            "VALUE_NOT_ALLOWED",
+            format!(
+                "'{}' is not an allowed {} (expected one of: {})",
+                value,
+                self.target.column,
+                allowed.join(", ")
+            ),
+        )
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        self.target.metadata(
+            "allowed_values",
+            format!("Validates {} against a list of values", self.target.column),
+        )
+    }
+}
+
+/// Filled cell must match a pattern in full
+struct PatternRule {
+    target: RuleTarget,
+    pattern: String,
+    regex: Regex,
+}
+
+impl ValidationRule<SheetRow> for PatternRule {
+    fn applies_to(&self, row: &SheetRow) -> bool {
+        !row.cell(&self.target.column).is_empty()
+    }
+
+    fn validate(&self, row: &SheetRow) -> RuleResult {
+        let value = row.cell(&self.target.column);
+        if self.regex.is_match(value) {
+            self.target.pass()
+        } else {
+            self.target.fail(
+                "PATTERN_MISMATCH",
+                format!("'{}' does not match the pattern {}", value, self.pattern),
+            )
+        }
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        self.target.metadata(
+            "pattern",
+            format!("Validates the format of {}", self.target.column),
+        )
+    }
+}
+
+/// Filled cell must not repeat an earlier row. Rows must be validated in
+/// order for the reported first occurrence to be right.
+struct UniqueValueRule {
+    target: RuleTarget,
+    seen: Mutex<HashMap<String, i32>>,
+}
+
+impl ValidationRule<SheetRow> for UniqueValueRule {
+    fn applies_to(&self, row: &SheetRow) -> bool {
+        !row.cell(&self.target.column).is_empty()
+    }
+
+    fn validate(&self, row: &SheetRow) -> RuleResult {
+        let value = row.cell(&self.target.column);
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        match seen.get(value) {
+            Some(first_row) => self.target.fail(
+                "DUPLICATE_VALUE",
+                format!(
+                    "'{}' in {} repeats row {}",
+                    value, self.target.column, first_row
+                ),
+            ),
+            None => {
+                seen.insert(value.to_string(), row.row_number);
+                self.target.pass()
+            }
+        }
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        self.target.metadata(
+            "unique",
+            format!("Validates that {} does not repeat", self.target.column),
+        )
+    }
+}
+
+/// Filled cell must be a number within optional bounds
+struct NumericRangeRule {
+    target: RuleTarget,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl ValidationRule<SheetRow> for NumericRangeRule {
+    fn applies_to(&self, row: &SheetRow) -> bool {
+        !row.cell(&self.target.column).is_empty()
+    }
+
+    fn validate(&self, row: &SheetRow) -> RuleResult {
+        let value = row.cell(&self.target.column);
+        let Some(number) = value.parse::<f64>().ok().filter(|n| n.is_finite()) else {
+            return self.target.fail(
+                "NOT_A_NUMBER",
+                format!("'{}' in {} is not a number", value, self.target.column),
+            );
+        };
+        if self.min.is_some_and(|min| number < min) || self.max.is_some_and(|max| number > max) {
+            let bounds = match (self.min, self.max) {
+                (Some(min), Some(max)) => format!("between {} and {}", min, max),
+                (Some(min), None) => format!("at least {}", min),
+                (None, Some(max)) => format!("at most {}", max),
+                (None, None) => unreachable!(),
+            };
+            return self.target.fail(
+                "OUT_OF_RANGE",
+                format!("{} must be {}, got {}", self.target.column, bounds, value),
+            );
+        }
+        self.target.pass()
+    }
+
+    fn metadata(&self) -> RuleMetadata {
+        self.target.metadata(
+            "range",
+            format!("Validates the numeric range of {}", self.target.column),
+        )
+    }
+}
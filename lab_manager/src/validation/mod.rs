@@ -1,3 +1,4 @@
+pub mod dataset_rules;
 pub mod rules;
 pub mod validators;
 